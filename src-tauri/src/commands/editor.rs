@@ -0,0 +1,56 @@
+use tauri::{AppHandle, State};
+
+use super::paths::stamp_dir;
+use crate::{
+    pdf::editor::{DocumentMetadata, EditHistory, EditOperation, SaveMode},
+    service::editor_service,
+    state::AppState,
+};
+
+#[tauri::command]
+pub fn apply_edit(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    operation: EditOperation,
+) -> Result<EditHistory, String> {
+    editor_service::apply_edit(&state, id, operation, stamp_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn undo_edit(state: State<AppState>, id: String) -> Result<EditHistory, String> {
+    editor_service::undo_edit(&state, id)
+}
+
+#[tauri::command]
+pub fn redo_edit(state: State<AppState>, id: String) -> Result<EditHistory, String> {
+    editor_service::redo_edit(&state, id)
+}
+
+#[tauri::command]
+pub fn get_edit_history(state: State<AppState>, id: String) -> Result<EditHistory, String> {
+    editor_service::get_edit_history(&state, id)
+}
+
+#[tauri::command]
+pub fn save_pdf(state: State<AppState>, id: String) -> Result<SaveMode, String> {
+    editor_service::save_pdf(&state, id, None)
+}
+
+#[tauri::command]
+pub fn save_pdf_as(state: State<AppState>, id: String, dest: String) -> Result<SaveMode, String> {
+    editor_service::save_pdf(&state, id, Some(dest))
+}
+
+#[tauri::command]
+pub fn discard_edits(state: State<AppState>, id: String) -> Result<(), String> {
+    editor_service::discard_edits(&state, id)
+}
+
+#[tauri::command]
+pub fn get_document_metadata(
+    state: State<AppState>,
+    id: String,
+) -> Result<DocumentMetadata, String> {
+    editor_service::get_document_metadata(&state, id)
+}
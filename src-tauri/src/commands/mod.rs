@@ -1,2 +1,4 @@
+pub mod editor;
+mod paths;
 pub mod reader;
 pub mod tools;
@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use tauri::{AppHandle, Manager};
+
+/// Directory of the annotation sidecar files, in the app data dir.
+pub(super) fn sidecar_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app_data_dir(app)?.join("annotations"))
+}
+
+/// Directory of the custom stamp library, in the app data dir.
+pub(super) fn stamp_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app_data_dir(app)?.join("stamps"))
+}
+
+fn app_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {e}"))
+}
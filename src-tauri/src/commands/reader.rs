@@ -1,8 +1,15 @@
 use tauri::{AppHandle, Manager, State};
 
+use super::paths::{sidecar_dir, stamp_dir};
 use crate::{
     pdf::{
-        reader::{Annotation, PageText, SearchHit},
+        reader::{
+            Annotation, AnnotationExchangeFormat, AnnotationImportSummary, AnnotationQuery,
+            AnnotationQueryResult, AnnotationStorage, DocumentSecurity, FormField, FormFieldUpdate,
+            HighlightGrouping, HighlightSummaryFormat, PageText, RedactionPattern,
+            RedactionSummary, RenderOptions, ReviewStatus, RiskReport, SearchHit, SignatureInfo,
+            StampDefinition,
+        },
         Bookmarks, PdfInfo,
     },
     service::reader_service,
@@ -25,11 +32,13 @@ pub async fn render_page(
     id: String,
     page_index: u16,
     target_width: i32,
+    options: Option<RenderOptions>,
 ) -> Result<tauri::ipc::Response, String> {
     let app_state = state.inner().clone();
+    let options = options.unwrap_or_default();
 
     let page = tauri::async_runtime::spawn_blocking(move || {
-        reader_service::render_page(&app_state, id, page_index, target_width)
+        reader_service::render_page(&app_state, id, page_index, target_width, options)
     })
     .await
     .map_err(|e| e.to_string())??;
@@ -52,8 +61,10 @@ pub async fn render_tile(
     tile_y: i32,
     tile_width: i32,
     tile_height: i32,
+    options: Option<RenderOptions>,
 ) -> Result<tauri::ipc::Response, String> {
     let app_state = state.inner().clone();
+    let options = options.unwrap_or_default();
 
     let tile = tauri::async_runtime::spawn_blocking(move || {
         reader_service::render_tile(
@@ -65,6 +76,7 @@ pub async fn render_tile(
             tile_y,
             tile_width,
             tile_height,
+            options,
         )
     })
     .await
@@ -130,25 +142,212 @@ pub fn generate_preview(
 }
 
 #[tauri::command]
-pub fn get_annotations(state: State<AppState>, id: String) -> Result<Vec<Annotation>, String> {
-    reader_service::get_annotations(&state, id)
+pub fn get_annotations(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+) -> Result<Vec<Annotation>, String> {
+    reader_service::get_annotations(&state, id, sidecar_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn query_annotations(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    query: AnnotationQuery,
+) -> Result<AnnotationQueryResult, String> {
+    reader_service::query_annotations(&state, id, query, sidecar_dir(&app)?)
 }
 
 #[tauri::command]
 pub fn add_annotation(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    annotation: Annotation,
+) -> Result<(), String> {
+    reader_service::add_annotation(&state, id, annotation, stamp_dir(&app)?, sidecar_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn add_stamp(
+    app: AppHandle,
     state: State<AppState>,
     id: String,
     annotation: Annotation,
 ) -> Result<(), String> {
-    reader_service::add_annotation(&state, id, annotation)
+    reader_service::add_stamp(&state, id, annotation, stamp_dir(&app)?, sidecar_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn list_stamps(app: AppHandle) -> Result<Vec<StampDefinition>, String> {
+    reader_service::list_stamps(stamp_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn add_custom_stamp(
+    app: AppHandle,
+    path: String,
+    name: String,
+    page_index: Option<u16>,
+) -> Result<StampDefinition, String> {
+    reader_service::add_custom_stamp(stamp_dir(&app)?, path, name, page_index)
+}
+
+#[tauri::command]
+pub fn remove_custom_stamp(app: AppHandle, stamp_id: String) -> Result<(), String> {
+    reader_service::remove_custom_stamp(stamp_dir(&app)?, stamp_id)
 }
 
 #[tauri::command]
 pub fn remove_annotation(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    page_index: u16,
+    annotation_id: String,
+) -> Result<(), String> {
+    reader_service::remove_annotation(
+        &state,
+        id,
+        page_index,
+        annotation_id,
+        stamp_dir(&app)?,
+        sidecar_dir(&app)?,
+    )
+}
+
+#[tauri::command]
+pub fn set_review_state(
+    app: AppHandle,
     state: State<AppState>,
     id: String,
     page_index: u16,
     annotation_id: String,
+    status: ReviewStatus,
 ) -> Result<(), String> {
-    reader_service::remove_annotation(&state, id, page_index, annotation_id)
+    reader_service::set_review_state(
+        &state,
+        id,
+        page_index,
+        annotation_id,
+        status,
+        stamp_dir(&app)?,
+        sidecar_dir(&app)?,
+    )
+}
+
+#[tauri::command]
+pub fn get_annotation_storage(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+) -> Result<AnnotationStorage, String> {
+    reader_service::get_annotation_storage(&state, id, sidecar_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn use_sidecar_annotations(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+) -> Result<(), String> {
+    reader_service::use_sidecar_annotations(&state, id, sidecar_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn bake_sidecar_annotations(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    dest: Option<String>,
+) -> Result<AnnotationImportSummary, String> {
+    reader_service::bake_sidecar_annotations(&state, id, stamp_dir(&app)?, sidecar_dir(&app)?, dest)
+}
+
+#[tauri::command]
+pub fn export_annotations(
+    state: State<AppState>,
+    id: String,
+    format: AnnotationExchangeFormat,
+    dest: String,
+) -> Result<(), String> {
+    reader_service::export_annotations(&state, id, format, dest)
+}
+
+#[tauri::command]
+pub fn import_annotations(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    source: String,
+) -> Result<AnnotationImportSummary, String> {
+    reader_service::import_annotations(&state, id, source, stamp_dir(&app)?, sidecar_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn export_highlight_summary(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    format: HighlightSummaryFormat,
+    group_by: HighlightGrouping,
+    dest: String,
+) -> Result<(), String> {
+    reader_service::export_highlight_summary(&state, id, format, group_by, dest, sidecar_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn get_form_fields(state: State<AppState>, id: String) -> Result<Vec<FormField>, String> {
+    reader_service::get_form_fields(&state, id)
+}
+
+#[tauri::command]
+pub fn fill_form_fields(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    updates: Vec<FormFieldUpdate>,
+) -> Result<(), String> {
+    reader_service::fill_form_fields(&state, id, updates, stamp_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn get_signatures(state: State<AppState>, id: String) -> Result<Vec<SignatureInfo>, String> {
+    reader_service::get_signatures(&state, id)
+}
+
+#[tauri::command]
+pub fn get_document_security(
+    state: State<AppState>,
+    id: String,
+    owner_password: Option<String>,
+) -> Result<DocumentSecurity, String> {
+    reader_service::get_document_security(&state, id, owner_password)
+}
+
+#[tauri::command]
+pub fn mark_redactions(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    patterns: Vec<RedactionPattern>,
+) -> Result<Vec<Annotation>, String> {
+    reader_service::mark_redactions(&state, id, patterns, stamp_dir(&app)?, sidecar_dir(&app)?)
+}
+
+#[tauri::command]
+pub fn apply_redactions(
+    app: AppHandle,
+    state: State<AppState>,
+    id: String,
+    dest: Option<String>,
+) -> Result<RedactionSummary, String> {
+    reader_service::apply_redactions(&state, id, stamp_dir(&app)?, sidecar_dir(&app)?, dest)
+}
+
+#[tauri::command]
+pub fn scan_document(state: State<AppState>, path: String) -> Result<RiskReport, String> {
+    reader_service::scan_document(&state, path)
 }
@@ -96,11 +96,19 @@ pub async fn protect_pdf(
     tools_service::protect_pdf(&state, input).await
 }
 
+#[tauri::command]
+pub async fn change_security_pdf(
+    state: State<'_, AppState>,
+    input: tools::ChangeSecurityInput,
+) -> Result<(), String> {
+    tools_service::change_security_pdf(&state, input).await
+}
+
 #[tauri::command]
 pub async fn unlock_pdf(
     state: State<'_, AppState>,
     input: tools::UnlockInput,
-) -> Result<(), String> {
+) -> Result<tools::UnlockReport, String> {
     tools_service::unlock_pdf(&state, input).await
 }
 
@@ -111,3 +119,67 @@ pub async fn watermark_pdf(
 ) -> Result<(), String> {
     tools_service::watermark_pdf(&state, input).await
 }
+
+#[tauri::command]
+pub async fn flatten_pdf(
+    state: State<'_, AppState>,
+    input: tools::FlattenInput,
+) -> Result<tools::FlattenSummary, String> {
+    tools_service::flatten_pdf(&state, input).await
+}
+
+#[tauri::command]
+pub async fn merge_annotations(
+    state: State<'_, AppState>,
+    input: tools::AnnotationMergeInput,
+) -> Result<tools::AnnotationMergeReport, String> {
+    tools_service::merge_annotations(&state, input).await
+}
+
+#[tauri::command]
+pub async fn export_form_data(
+    state: State<'_, AppState>,
+    input: tools::FormExportInput,
+) -> Result<usize, String> {
+    tools_service::export_form_data(&state, input).await
+}
+
+#[tauri::command]
+pub async fn import_form_data(
+    state: State<'_, AppState>,
+    input: tools::FormImportInput,
+) -> Result<crate::pdf::reader::FormImportSummary, String> {
+    tools_service::import_form_data(&state, input).await
+}
+
+#[tauri::command]
+pub async fn fill_form_batch(
+    state: State<'_, AppState>,
+    input: tools::FormBatchInput,
+) -> Result<tools::FormBatchSummary, String> {
+    tools_service::fill_form_batch(&state, input).await
+}
+
+#[tauri::command]
+pub async fn flatten_form_pdf(
+    state: State<'_, AppState>,
+    input: tools::FlattenFormInput,
+) -> Result<tools::FlattenSummary, String> {
+    tools_service::flatten_form_pdf(&state, input).await
+}
+
+#[tauri::command]
+pub async fn sign_pdf(
+    state: State<'_, AppState>,
+    input: tools::SignInput,
+) -> Result<crate::pdf::reader::SignatureInfo, String> {
+    tools_service::sign_pdf(&state, input).await
+}
+
+#[tauri::command]
+pub async fn sanitize_pdf(
+    state: State<'_, AppState>,
+    input: tools::SanitizeInput,
+) -> Result<tools::SanitizeReport, String> {
+    tools_service::sanitize_pdf(&state, input).await
+}
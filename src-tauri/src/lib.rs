@@ -35,9 +35,37 @@ pub fn run() {
             commands::reader::search_document,
             commands::reader::generate_preview,
             commands::reader::get_annotations,
+            commands::reader::query_annotations,
             commands::reader::add_annotation,
+            commands::reader::add_stamp,
+            commands::reader::list_stamps,
+            commands::reader::add_custom_stamp,
+            commands::reader::remove_custom_stamp,
             commands::reader::remove_annotation,
+            commands::reader::set_review_state,
+            commands::reader::get_annotation_storage,
+            commands::reader::use_sidecar_annotations,
+            commands::reader::bake_sidecar_annotations,
+            commands::reader::export_annotations,
+            commands::reader::import_annotations,
+            commands::reader::export_highlight_summary,
+            commands::reader::get_form_fields,
+            commands::reader::fill_form_fields,
+            commands::reader::get_signatures,
+            commands::reader::get_document_security,
+            commands::reader::mark_redactions,
+            commands::reader::apply_redactions,
+            commands::reader::scan_document,
             commands::reader::render_tile,
+            // editor
+            commands::editor::apply_edit,
+            commands::editor::undo_edit,
+            commands::editor::redo_edit,
+            commands::editor::get_edit_history,
+            commands::editor::save_pdf,
+            commands::editor::save_pdf_as,
+            commands::editor::discard_edits,
+            commands::editor::get_document_metadata,
             commands::tools::extract_tar_gz,
             commands::tools::merge_pdfs,
             commands::tools::split_pdf,
@@ -47,8 +75,17 @@ pub fn run() {
             commands::tools::image_to_pdf,
             commands::tools::rotate_pdf,
             commands::tools::protect_pdf,
+            commands::tools::change_security_pdf,
             commands::tools::unlock_pdf,
             commands::tools::watermark_pdf,
+            commands::tools::flatten_pdf,
+            commands::tools::merge_annotations,
+            commands::tools::export_form_data,
+            commands::tools::import_form_data,
+            commands::tools::fill_form_batch,
+            commands::tools::flatten_form_pdf,
+            commands::tools::sign_pdf,
+            commands::tools::sanitize_pdf,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,7 @@
+pub mod operation;
+pub mod save;
+pub mod session;
+
+pub use operation::*;
+pub use save::*;
+pub use session::*;
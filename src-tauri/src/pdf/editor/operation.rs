@@ -0,0 +1,459 @@
+use std::collections::BTreeSet;
+
+use lopdf::{Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::annotation::create::{
+    create_annotation, page_id, remove_annotation, update_annotation,
+};
+use crate::pdf::reader::annotation::dictionary::find_annotation;
+use crate::pdf::reader::{Annotation, RestrictedAction, StampLibrary};
+
+/// Page attributes a page inherits from its `/Pages` ancestors.
+const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Guards against cycles when walking `/Parent` links of malformed page trees.
+const MAX_TREE_DEPTH: usize = 64;
+
+/// Default page height (US Letter) when a page has no usable `/MediaBox`.
+const DEFAULT_PAGE_HEIGHT: f32 = 792.0;
+
+/// Document information dictionary (`/Info`) entries. `None` removes the entry.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+}
+
+impl DocumentMetadata {
+    fn entries(&self) -> [(&'static str, &Option<String>); 6] {
+        [
+            ("Title", &self.title),
+            ("Author", &self.author),
+            ("Subject", &self.subject),
+            ("Keywords", &self.keywords),
+            ("Creator", &self.creator),
+            ("Producer", &self.producer),
+        ]
+    }
+}
+
+/// A change to an open document that can be undone.
+///
+/// Page indices are zero-based and refer to the page order before the operation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum EditOperation {
+    AddAnnotation(Annotation),
+
+    /// Rewrites the annotation with the same id, keeping its replies
+    UpdateAnnotation(Annotation),
+
+    /// Removes an annotation with its popup, replies and review states
+    RemoveAnnotation {
+        page_index: u16,
+        annotation_id: String,
+    },
+
+    /// Rotates pages clockwise by a multiple of 90 degrees
+    RotatePages {
+        pages: Vec<u16>,
+        angle: i32,
+    },
+
+    DeletePages {
+        pages: Vec<u16>,
+    },
+
+    /// New page order, listing every current page index exactly once
+    ReorderPages {
+        order: Vec<u16>,
+    },
+
+    SetMetadata(DocumentMetadata),
+}
+
+impl EditOperation {
+    /// Short description shown in the edit history.
+    pub fn label(&self) -> String {
+        match self {
+            EditOperation::AddAnnotation(annotation) => {
+                format!("Add {:?} annotation", annotation.subtype)
+            }
+            EditOperation::UpdateAnnotation(annotation) => {
+                format!("Edit {:?} annotation", annotation.subtype)
+            }
+            EditOperation::RemoveAnnotation { .. } => "Remove annotation".to_string(),
+            EditOperation::RotatePages { pages, angle } => {
+                format!("Rotate {} by {angle}°", pages_label(pages.len()))
+            }
+            EditOperation::DeletePages { pages } => {
+                format!("Delete {}", pages_label(pages.len()))
+            }
+            EditOperation::ReorderPages { .. } => "Reorder pages".to_string(),
+            EditOperation::SetMetadata(_) => "Edit document properties".to_string(),
+        }
+    }
+
+    /// Permission the document must grant for the operation.
+    pub fn restricted_action(&self) -> RestrictedAction {
+        match self {
+            EditOperation::AddAnnotation(_)
+            | EditOperation::UpdateAnnotation(_)
+            | EditOperation::RemoveAnnotation { .. } => RestrictedAction::Annotate,
+            EditOperation::RotatePages { .. }
+            | EditOperation::DeletePages { .. }
+            | EditOperation::ReorderPages { .. }
+            | EditOperation::SetMetadata(_) => RestrictedAction::Modify,
+        }
+    }
+
+    /// Existing objects the operation may change or remove. The edit session copies
+    /// only these before applying it; objects it adds are told apart by their ids.
+    pub fn affected_objects(&self, document: &Document) -> BTreeSet<ObjectId> {
+        match self {
+            EditOperation::AddAnnotation(annotation)
+            | EditOperation::UpdateAnnotation(annotation) => {
+                annotation_objects(document, annotation.page_index)
+            }
+            EditOperation::RemoveAnnotation { page_index, .. } => {
+                annotation_objects(document, *page_index)
+            }
+            EditOperation::RotatePages { .. }
+            | EditOperation::DeletePages { .. }
+            | EditOperation::ReorderPages { .. } => page_tree(document),
+            EditOperation::SetMetadata(_) => document
+                .trailer
+                .get(b"Info")
+                .and_then(Object::as_reference)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Applies the operation to `document`, drawing custom stamps from `stamps`. On
+    /// error the document may be partially modified; the edit session restores it.
+    pub fn apply(
+        &self,
+        document: &mut Document,
+        stamps: Option<&StampLibrary>,
+    ) -> Result<(), String> {
+        match self {
+            EditOperation::AddAnnotation(annotation) => {
+                let page_id = page_id(document, annotation.page_index)?;
+                let page_height = page_height(document, page_id);
+                create_annotation(document, annotation, page_height, stamps).map(|_| ())
+            }
+            EditOperation::UpdateAnnotation(annotation) => {
+                let page_id = page_id(document, annotation.page_index)?;
+                let annotation_id =
+                    find_annotation(document, page_id, annotation.page_index, &annotation.id)
+                        .ok_or_else(|| format!("Annotation {} not found", annotation.id))?;
+                let page_height = page_height(document, page_id);
+                update_annotation(document, annotation_id, annotation, page_height, stamps)
+            }
+            EditOperation::RemoveAnnotation {
+                page_index,
+                annotation_id,
+            } => remove_annotation(document, *page_index, annotation_id),
+            EditOperation::RotatePages { pages, angle } => rotate_pages(document, pages, *angle),
+            EditOperation::DeletePages { pages } => delete_pages(document, pages),
+            EditOperation::ReorderPages { order } => reorder_pages(document, order),
+            EditOperation::SetMetadata(metadata) => set_metadata(document, metadata),
+        }
+    }
+}
+
+fn pages_label(count: usize) -> String {
+    if count == 1 {
+        "1 page".to_string()
+    } else {
+        format!("{count} pages")
+    }
+}
+
+fn rotate_pages(document: &mut Document, pages: &[u16], angle: i32) -> Result<(), String> {
+    if angle % 90 != 0 {
+        return Err(format!(
+            "Rotation must be a multiple of 90 degrees, got {angle}"
+        ));
+    }
+
+    for page_index in pages.iter().collect::<BTreeSet<_>>() {
+        let page_id = page_id(document, *page_index)?;
+        let current = inherited(document, page_id, b"Rotate")
+            .and_then(|rotate| rotate.as_i64().ok())
+            .unwrap_or(0);
+        let rotation = (current + angle as i64).rem_euclid(360);
+
+        document
+            .get_dictionary_mut(page_id)
+            .map_err(|e| e.to_string())?
+            .set("Rotate", rotation);
+    }
+
+    Ok(())
+}
+
+fn delete_pages(document: &mut Document, pages: &[u16]) -> Result<(), String> {
+    let pages = pages.iter().collect::<BTreeSet<_>>();
+    if pages.len() >= document.get_pages().len() {
+        return Err("Cannot delete every page of a document".to_string());
+    }
+
+    // Resolve every page first; indices shift as pages are removed.
+    let page_ids = pages
+        .into_iter()
+        .map(|page_index| page_id(document, *page_index))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for page_id in page_ids {
+        let parent_id = parent(document, page_id).ok_or("Page has no parent")?;
+
+        let kids = document
+            .get_dictionary_mut(parent_id)
+            .and_then(|parent| parent.get_mut(b"Kids"))
+            .and_then(|kids| kids.as_array_mut())
+            .map_err(|e| format!("Failed to read page tree: {e}"))?;
+        kids.retain(|kid| kid.as_reference().ok() != Some(page_id));
+
+        // Every ancestor counts the page among its leaves.
+        let mut node = Some(parent_id);
+        for _ in 0..MAX_TREE_DEPTH {
+            let Some(node_id) = node else {
+                break;
+            };
+            let dict = document
+                .get_dictionary_mut(node_id)
+                .map_err(|e| e.to_string())?;
+            let count = dict.get(b"Count").and_then(|c| c.as_i64()).unwrap_or(1);
+            dict.set("Count", (count - 1).max(0));
+            node = parent(document, node_id);
+        }
+
+        document.objects.remove(&page_id);
+    }
+
+    Ok(())
+}
+
+fn reorder_pages(document: &mut Document, order: &[u16]) -> Result<(), String> {
+    let pages = document.get_pages().into_values().collect::<Vec<_>>();
+
+    let unique = order.iter().collect::<BTreeSet<_>>();
+    if order.len() != pages.len()
+        || unique.len() != pages.len()
+        || order.iter().any(|index| *index as usize >= pages.len())
+    {
+        return Err("Page order must list every page exactly once".to_string());
+    }
+
+    let root_id = document
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(|pages| pages.as_reference())
+        .map_err(|e| format!("Failed to read page tree: {e}"))?;
+
+    // The tree is flattened into the root node: attributes inherited from
+    // intermediate nodes are copied onto the pages before those nodes are removed.
+    let mut intermediate = BTreeSet::new();
+    for page_id in &pages {
+        let inherited_values = INHERITABLE
+            .iter()
+            .filter_map(|key| Some((*key, inherited(document, *page_id, key)?.clone())))
+            .collect::<Vec<_>>();
+
+        let mut node = parent(document, *page_id);
+        for _ in 0..MAX_TREE_DEPTH {
+            match node {
+                Some(node_id) if node_id != root_id => {
+                    intermediate.insert(node_id);
+                    node = parent(document, node_id);
+                }
+                _ => break,
+            }
+        }
+
+        let page = document
+            .get_dictionary_mut(*page_id)
+            .map_err(|e| e.to_string())?;
+        for (key, value) in inherited_values {
+            if !page.has(key) {
+                page.set(key, value);
+            }
+        }
+        page.set("Parent", Object::Reference(root_id));
+    }
+
+    for node_id in intermediate {
+        document.objects.remove(&node_id);
+    }
+
+    let kids = order
+        .iter()
+        .map(|index| Object::Reference(pages[*index as usize]))
+        .collect::<Vec<_>>();
+
+    let root = document
+        .get_dictionary_mut(root_id)
+        .map_err(|e| e.to_string())?;
+    root.set("Kids", kids);
+    root.set("Count", pages.len() as i64);
+
+    Ok(())
+}
+
+fn set_metadata(document: &mut Document, metadata: &DocumentMetadata) -> Result<(), String> {
+    let info_ref = document
+        .trailer
+        .get(b"Info")
+        .and_then(|info| info.as_reference())
+        .ok();
+
+    let info = match info_ref {
+        Some(info_id) => document
+            .get_dictionary_mut(info_id)
+            .map_err(|e| format!("Failed to read document info: {e}"))?,
+        None => {
+            if !matches!(document.trailer.get(b"Info"), Ok(Object::Dictionary(_))) {
+                document
+                    .trailer
+                    .set("Info", Object::Dictionary(Default::default()));
+            }
+            document
+                .trailer
+                .get_mut(b"Info")
+                .and_then(|info| info.as_dict_mut())
+                .map_err(|e| format!("Failed to read document info: {e}"))?
+        }
+    };
+
+    for (key, value) in metadata.entries() {
+        match value {
+            Some(value) => info.set(key, lopdf::text_string(value)),
+            None => {
+                info.remove(key.as_bytes());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the document information dictionary.
+pub fn document_metadata(document: &Document) -> DocumentMetadata {
+    let info = document
+        .trailer
+        .get_deref(b"Info", document)
+        .and_then(|info| info.as_dict())
+        .ok();
+
+    let read = |key: &str| {
+        info?
+            .get(key.as_bytes())
+            .ok()
+            .and_then(|value| lopdf::decode_text_string(value).ok())
+    };
+
+    DocumentMetadata {
+        title: read("Title"),
+        author: read("Author"),
+        subject: read("Subject"),
+        keywords: read("Keywords"),
+        creator: read("Creator"),
+        producer: read("Producer"),
+    }
+}
+
+/// Height of the page's crop box (or media box), used to flip annotation
+/// coordinates between the frontend and PDF space.
+pub fn page_height(document: &Document, page_id: ObjectId) -> f32 {
+    [b"CropBox".as_slice(), b"MediaBox"]
+        .iter()
+        .find_map(|key| {
+            let values = inherited(document, page_id, key)?;
+            let values = document.dereference(values).ok()?.1.as_array().ok()?;
+            let [_, y1, _, y2] = values.as_slice() else {
+                return None;
+            };
+            Some((y2.as_float().ok()? - y1.as_float().ok()?).abs())
+        })
+        .unwrap_or(DEFAULT_PAGE_HEIGHT)
+}
+
+/// Looks up a page attribute on the page or, if missing, its `/Pages` ancestors.
+pub fn inherited<'a>(document: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node_id = page_id;
+    for _ in 0..MAX_TREE_DEPTH {
+        let node = document.get_dictionary(node_id).ok()?;
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+        node_id = node.get(b"Parent").ok()?.as_reference().ok()?;
+    }
+    None
+}
+
+/// A page with its `/Annots` array and the annotations and popups listed there.
+fn annotation_objects(document: &Document, page_index: u16) -> BTreeSet<ObjectId> {
+    let Ok(page_id) = page_id(document, page_index) else {
+        return BTreeSet::new();
+    };
+    let mut objects = BTreeSet::from([page_id]);
+
+    let annots = document
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"Annots"))
+        .ok();
+    if let Some(Object::Reference(annots_id)) = annots {
+        objects.insert(*annots_id);
+    }
+
+    let annotation_ids = annots
+        .and_then(|annots| document.dereference(annots).ok())
+        .and_then(|(_, annots)| annots.as_array().ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|annotation| annotation.as_reference().ok());
+    for annotation_id in annotation_ids {
+        objects.insert(annotation_id);
+        let popup = document
+            .get_dictionary(annotation_id)
+            .and_then(|annotation| annotation.get(b"Popup"))
+            .and_then(Object::as_reference);
+        if let Ok(popup_id) = popup {
+            objects.insert(popup_id);
+        }
+    }
+
+    objects
+}
+
+/// Every page with the `/Pages` nodes above it.
+fn page_tree(document: &Document) -> BTreeSet<ObjectId> {
+    let mut objects = BTreeSet::new();
+    for page_id in document.get_pages().into_values() {
+        let mut node = Some(page_id);
+        for _ in 0..MAX_TREE_DEPTH {
+            match node {
+                Some(node_id) if objects.insert(node_id) => node = parent(document, node_id),
+                _ => break,
+            }
+        }
+    }
+    objects
+}
+
+fn parent(document: &Document, node_id: ObjectId) -> Option<ObjectId> {
+    document
+        .get_dictionary(node_id)
+        .ok()?
+        .get(b"Parent")
+        .ok()?
+        .as_reference()
+        .ok()
+}
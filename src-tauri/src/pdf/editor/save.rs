@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use lopdf::{Document, IncrementalDocument};
+use pdfium_render::prelude::PdfDocument;
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::document_to_bytes;
+use crate::pdf::DocumentId;
+use crate::utils::fs::write_atomic;
+
+/// Trailer entries an edit can change.
+const TRAILER_KEYS: [&str; 2] = ["Root", "Info"];
+
+/// How a save wrote the document.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveMode {
+    /// Nothing changed; the file was written as it was
+    Unchanged,
+
+    /// Changed objects were appended to the original bytes, so existing digital
+    /// signatures stay valid
+    Incremental,
+
+    /// The document was rewritten, which invalidates existing signatures
+    Full,
+}
+
+pub struct SaveOutput {
+    pub bytes: Vec<u8>,
+    pub mode: SaveMode,
+}
+
+/// Serializes `edited` for saving over `original`, the bytes it was loaded from.
+///
+/// Changes are written as an incremental update when possible. Encrypted documents
+/// and files lopdf cannot update incrementally are rewritten in full.
+pub fn serialize_edits(original: Vec<u8>, edited: &Document) -> Result<SaveOutput, String> {
+    // lopdf drops `/Encrypt` from the trailer of documents it decrypted on load.
+    if !edited.trailer.has(b"Encrypt") && edited.encryption_state.is_none() {
+        if let Ok(output) = incremental_update(original, edited) {
+            return Ok(output);
+        }
+    }
+
+    let mut document = edited.clone();
+    Ok(SaveOutput {
+        bytes: document_to_bytes(&mut document)?,
+        mode: SaveMode::Full,
+    })
+}
+
+fn incremental_update(original: Vec<u8>, edited: &Document) -> Result<SaveOutput, String> {
+    let mut incremental = IncrementalDocument::load_from(original.as_slice())
+        .map_err(|e| format!("Failed to load PDF: {e}"))?;
+    let previous = incremental.get_prev_documents();
+
+    let changed = edited
+        .objects
+        .iter()
+        .filter(|(object_id, object)| previous.objects.get(object_id) != Some(*object))
+        .map(|(object_id, object)| (*object_id, object.clone()))
+        .collect::<Vec<_>>();
+
+    let trailer_changed = TRAILER_KEYS.iter().any(|key| {
+        previous.trailer.get(key.as_bytes()).ok() != edited.trailer.get(key.as_bytes()).ok()
+    });
+
+    if changed.is_empty() && !trailer_changed {
+        return Ok(SaveOutput {
+            bytes: original,
+            mode: SaveMode::Unchanged,
+        });
+    }
+
+    // Objects removed by the edits stay in the earlier revision; they are no longer
+    // referenced, so readers ignore them.
+    let version = previous.version.clone();
+    let update = &mut incremental.new_document;
+    update.version = version;
+    update.max_id = update.max_id.max(edited.max_id);
+    update.objects.extend(changed);
+
+    for key in TRAILER_KEYS {
+        match edited.trailer.get(key.as_bytes()) {
+            Ok(value) => update.trailer.set(key, value.clone()),
+            Err(_) => {
+                update.trailer.remove(key.as_bytes());
+            }
+        }
+    }
+
+    let mut bytes = Vec::new();
+    incremental
+        .save_to(&mut bytes)
+        .map_err(|e| format!("Failed to save PDF: {e}"))?;
+
+    Ok(SaveOutput {
+        bytes,
+        mode: SaveMode::Incremental,
+    })
+}
+
+/// Writes serialized document bytes to `dest`, closing the worker's copy of the
+/// document first so it is reloaded from the new file.
+pub fn write_document(
+    documents: &mut HashMap<DocumentId, PdfDocument>,
+    id: &DocumentId,
+    dest: &Path,
+    bytes: &[u8],
+) -> Result<(), String> {
+    documents.remove(id);
+
+    write_atomic(dest, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::editor::{DocumentMetadata, EditOperation, EditSession};
+    use lopdf::{dictionary, Object};
+    use std::path::PathBuf;
+
+    fn original() -> Vec<u8> {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 600.into(), 800.into()],
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn session(bytes: &[u8]) -> EditSession {
+        EditSession::new(
+            PathBuf::from("test.pdf"),
+            Document::load_mem(bytes).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_incremental_update_appends_changes() {
+        let original = original();
+        let mut session = session(&original);
+        session
+            .apply(
+                EditOperation::RotatePages {
+                    pages: vec![0],
+                    angle: 90,
+                },
+                None,
+            )
+            .unwrap();
+        session
+            .apply(
+                EditOperation::SetMetadata(DocumentMetadata {
+                    title: Some("Signed".into()),
+                    ..Default::default()
+                }),
+                None,
+            )
+            .unwrap();
+
+        let output = serialize_edits(original.clone(), session.document()).unwrap();
+        assert_eq!(output.mode, SaveMode::Incremental);
+        assert!(output.bytes.starts_with(&original));
+
+        let saved = Document::load_mem(&output.bytes).unwrap();
+        let page_id = saved.get_pages()[&1];
+        let rotate = saved.get_dictionary(page_id).unwrap().get(b"Rotate");
+        assert_eq!(rotate.unwrap().as_i64().unwrap(), 90);
+
+        let info = saved.trailer.get_deref(b"Info", &saved).unwrap();
+        let title = info.as_dict().unwrap().get(b"Title").unwrap();
+        assert_eq!(lopdf::decode_text_string(title).unwrap(), "Signed");
+    }
+
+    #[test]
+    fn test_unchanged_document_keeps_bytes() {
+        let original = original();
+        let session = session(&original);
+
+        let output = serialize_edits(original.clone(), session.document()).unwrap();
+        assert_eq!(output.mode, SaveMode::Unchanged);
+        assert_eq!(output.bytes, original);
+    }
+}
@@ -0,0 +1,885 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::pdf::editor::operation::{document_metadata, DocumentMetadata, EditOperation};
+use crate::pdf::reader::{file_fingerprint, StampLibrary};
+
+/// Number of operations kept for undo; older ones are dropped.
+const MAX_HISTORY: usize = 100;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EditHistory {
+    /// Applied operations, oldest first, followed by undone operations in redo order
+    pub entries: Vec<EditHistoryEntry>,
+    pub can_undo: bool,
+    pub can_redo: bool,
+
+    /// Whether the session has changes that are not written to disk
+    pub dirty: bool,
+
+    /// Labels of edits dropped when the file was changed outside the editor and
+    /// they no longer applied to it
+    pub dropped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EditHistoryEntry {
+    pub revision: u64,
+    pub label: String,
+
+    /// `false` for operations that were undone and can be redone
+    pub applied: bool,
+}
+
+/// Objects changed by one operation, as they were before and after it.
+///
+/// `None` means the object did not exist, so undoing an addition removes it.
+#[derive(Clone)]
+struct Change {
+    before: BTreeMap<ObjectId, Option<Object>>,
+    after: BTreeMap<ObjectId, Option<Object>>,
+    trailer_before: Dictionary,
+    trailer_after: Dictionary,
+}
+
+impl Change {
+    /// Compares the document with the snapshot taken before an operation.
+    fn between(snapshot: Snapshot, document: &Document) -> Self {
+        let mut before = BTreeMap::new();
+        let mut after = BTreeMap::new();
+
+        for (object_id, object) in snapshot.added(document) {
+            before.insert(*object_id, None);
+            after.insert(*object_id, Some(object.clone()));
+        }
+
+        for (object_id, object) in snapshot.objects {
+            let current = document.objects.get(&object_id);
+            if current != Some(&object) {
+                after.insert(object_id, current.cloned());
+                before.insert(object_id, Some(object));
+            }
+        }
+
+        Self {
+            before,
+            after,
+            trailer_before: snapshot.trailer,
+            trailer_after: document.trailer.clone(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.before.is_empty() && self.trailer_before == self.trailer_after
+    }
+
+    fn undo(&self, document: &mut Document) {
+        restore(document, &self.before, &self.trailer_before);
+    }
+
+    fn redo(&self, document: &mut Document) {
+        restore(document, &self.after, &self.trailer_after);
+    }
+
+    /// Undoes the change on a document reloaded from a file that holds it. Returns
+    /// `false`, leaving the document as it is, when the file changed any of the same
+    /// objects since.
+    fn revert(&self, document: &mut Document) -> bool {
+        let keys = self
+            .trailer_before
+            .iter()
+            .chain(self.trailer_after.iter())
+            .map(|(key, _)| key.clone())
+            .filter(|key| self.trailer_before.get(key).ok() != self.trailer_after.get(key).ok())
+            .collect::<Vec<_>>();
+
+        let unchanged = self.after.iter().all(|(object_id, object)| {
+            same_object(document.objects.get(object_id), object.as_ref())
+        }) && keys
+            .iter()
+            .all(|key| document.trailer.get(key).ok() == self.trailer_after.get(key).ok());
+        if !unchanged {
+            return false;
+        }
+
+        for (object_id, object) in &self.before {
+            match object {
+                Some(object) => {
+                    document.objects.insert(*object_id, object.clone());
+                }
+                None => {
+                    document.objects.remove(object_id);
+                }
+            }
+        }
+        for key in keys {
+            match self.trailer_before.get(&key) {
+                Ok(value) => document.trailer.set(key, value.clone()),
+                Err(_) => {
+                    document.trailer.remove(&key);
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// Compares objects by value, as they would read back from a file: numbers
+/// written as reals may come back as integers, and streams read from a file also
+/// record where they were found.
+fn same_object(a: Option<&Object>, b: Option<&Object>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => same_value(a, b),
+        (a, b) => a == b,
+    }
+}
+
+fn same_value(a: &Object, b: &Object) -> bool {
+    let same_dictionary = |a: &Dictionary, b: &Dictionary| {
+        a.len() == b.len()
+            && a.iter()
+                .all(|(key, value)| b.get(key).is_ok_and(|other| same_value(value, other)))
+    };
+
+    match (a, b) {
+        (Object::Integer(_) | Object::Real(_), Object::Integer(_) | Object::Real(_)) => {
+            a.as_float().ok() == b.as_float().ok()
+        }
+        (Object::String(a, _), Object::String(b, _)) => a == b,
+        (Object::Array(a), Object::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b))
+        }
+        (Object::Dictionary(a), Object::Dictionary(b)) => same_dictionary(a, b),
+        (Object::Stream(a), Object::Stream(b)) => {
+            same_dictionary(&a.dict, &b.dict) && a.content == b.content
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn restore(
+    document: &mut Document,
+    objects: &BTreeMap<ObjectId, Option<Object>>,
+    trailer: &Dictionary,
+) {
+    for (object_id, object) in objects {
+        match object {
+            Some(object) => {
+                document.objects.insert(*object_id, object.clone());
+            }
+            None => {
+                document.objects.remove(object_id);
+            }
+        }
+    }
+    document.trailer = trailer.clone();
+}
+
+/// Copies of the objects an operation may change, taken before it runs.
+struct Snapshot {
+    objects: BTreeMap<ObjectId, Object>,
+    trailer: Dictionary,
+
+    /// Objects added by the operation get higher ids
+    max_id: u32,
+}
+
+impl Snapshot {
+    fn take(document: &Document, object_ids: BTreeSet<ObjectId>) -> Self {
+        let objects = object_ids
+            .into_iter()
+            .filter_map(|object_id| Some((object_id, document.objects.get(&object_id)?.clone())))
+            .collect();
+
+        Self {
+            objects,
+            trailer: document.trailer.clone(),
+            max_id: document.max_id,
+        }
+    }
+
+    /// Objects added to the document since the snapshot was taken.
+    fn added<'a>(
+        &self,
+        document: &'a Document,
+    ) -> impl Iterator<Item = (&'a ObjectId, &'a Object)> {
+        document.objects.range((self.max_id + 1, 0)..)
+    }
+
+    /// Puts the document back the way it was, undoing a failed operation.
+    fn restore(self, document: &mut Document) {
+        let added = self
+            .added(document)
+            .map(|(object_id, _)| *object_id)
+            .collect::<Vec<_>>();
+        for object_id in added {
+            document.objects.remove(&object_id);
+        }
+
+        document.objects.extend(self.objects);
+        document.trailer = self.trailer;
+        document.max_id = self.max_id;
+    }
+}
+
+#[derive(Clone)]
+struct EditRecord {
+    revision: u64,
+    operation: EditOperation,
+    change: Change,
+}
+
+/// In-memory edits of one open document.
+///
+/// Operations are applied to a copy of the document loaded with lopdf and recorded
+/// with the objects they changed, so they can be undone and redone in any number
+/// of steps. Nothing is written to the file until the document is saved.
+pub struct EditSession {
+    path: PathBuf,
+    document: Document,
+    undo: Vec<EditRecord>,
+    redo: Vec<EditRecord>,
+    next_revision: u64,
+
+    /// Revision of the state before the oldest kept operation
+    base_revision: u64,
+    saved_revision: u64,
+
+    /// Saved operations that were undone and then cleared from redo by a new
+    /// operation; the file still holds them
+    reverted: Vec<EditRecord>,
+
+    /// Edits dropped by the last rebase
+    dropped: Vec<String>,
+
+    /// SHA-256 of the file when it was loaded or last saved. Modification times
+    /// are too coarse to notice a rewrite in the same second.
+    fingerprint: Option<String>,
+}
+
+impl EditSession {
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let (document, fingerprint) = load(&path)?;
+
+        Ok(Self {
+            fingerprint: Some(fingerprint),
+            ..Self::new(path, document)
+        })
+    }
+
+    pub fn new(path: PathBuf, document: Document) -> Self {
+        Self {
+            document,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            next_revision: 1,
+            base_revision: 0,
+            saved_revision: 0,
+            reverted: Vec::new(),
+            dropped: Vec::new(),
+            fingerprint: file_fingerprint(&path).ok(),
+            path,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    pub fn metadata(&self) -> DocumentMetadata {
+        document_metadata(&self.document)
+    }
+
+    /// Applies an operation and records it for undo. A failed operation leaves the
+    /// document unchanged; one that changes nothing is not recorded.
+    pub fn apply(
+        &mut self,
+        mut operation: EditOperation,
+        stamps: Option<&StampLibrary>,
+    ) -> Result<(), String> {
+        // Name new annotations up front so later operations can refer to them.
+        if let EditOperation::AddAnnotation(annotation) = &mut operation {
+            if annotation.id.is_empty() {
+                annotation.id = uuid::Uuid::new_v4().to_string();
+            }
+        }
+
+        let change = apply_operation(&mut self.document, &operation, stamps)?;
+        if change.is_empty() {
+            return Ok(());
+        }
+
+        self.undo.push(EditRecord {
+            revision: self.next_revision,
+            operation,
+            change,
+        });
+        self.next_revision += 1;
+        self.dropped.clear();
+
+        let saved = self.saved_revision;
+        self.reverted.extend(
+            self.redo
+                .drain(..)
+                .filter(|record| record.revision <= saved),
+        );
+
+        if self.undo.len() > MAX_HISTORY {
+            let dropped = self.undo.remove(0);
+            self.base_revision = dropped.revision;
+        }
+
+        Ok(())
+    }
+
+    pub fn undo(&mut self) -> Result<(), String> {
+        let record = self.undo.pop().ok_or("Nothing to undo")?;
+        record.change.undo(&mut self.document);
+        self.redo.push(record);
+        self.dropped.clear();
+
+        Ok(())
+    }
+
+    pub fn redo(&mut self) -> Result<(), String> {
+        let record = self.redo.pop().ok_or("Nothing to redo")?;
+        record.change.redo(&mut self.document);
+        self.undo.push(record);
+        self.dropped.clear();
+
+        Ok(())
+    }
+
+    /// Operations applied since the document was loaded, in order.
+    pub fn operations(&self) -> impl Iterator<Item = &EditOperation> {
+        self.undo.iter().map(|record| &record.operation)
+    }
+
+    fn revision(&self) -> u64 {
+        self.undo
+            .last()
+            .map_or(self.base_revision, |record| record.revision)
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.revision() != self.saved_revision
+    }
+
+    pub fn history(&self) -> EditHistory {
+        let entry = |record: &EditRecord, applied| EditHistoryEntry {
+            revision: record.revision,
+            label: record.operation.label(),
+            applied,
+        };
+
+        let entries = self
+            .undo
+            .iter()
+            .map(|record| entry(record, true))
+            .chain(self.redo.iter().rev().map(|record| entry(record, false)))
+            .collect();
+
+        EditHistory {
+            entries,
+            can_undo: !self.undo.is_empty(),
+            can_redo: !self.redo.is_empty(),
+            dirty: self.is_dirty(),
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Whether the file changed on disk since the session loaded or saved it.
+    pub fn is_stale(&self) -> bool {
+        file_fingerprint(&self.path).ok() != self.fingerprint
+    }
+
+    /// Reloads the file after it was rewritten outside the session, such as by the
+    /// reader adding an annotation, and brings it to the state of the session again:
+    /// saved operations that were undone are taken out of the file, unsaved ones are
+    /// applied again and undone ones are kept for redo.
+    ///
+    /// Operations that no longer apply are dropped and listed in the history.
+    /// Applied operations the file already holds leave the undo history, since the
+    /// file may have changed the objects they would restore.
+    pub fn rebase(&mut self, stamps: Option<&StampLibrary>) -> Result<(), String> {
+        let (mut document, fingerprint) = load(&self.path)?;
+        let saved = self.saved_revision;
+        let mut dropped = Vec::new();
+
+        let (held, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.undo)
+            .into_iter()
+            .partition(|record| record.revision <= saved);
+        let mut redo = std::mem::take(&mut self.redo);
+
+        // The file holds the saved operations the session undid; take them out
+        // again, latest first.
+        let mut undone = std::mem::take(&mut self.reverted);
+        undone.extend(
+            redo.iter()
+                .filter(|record| record.revision <= saved)
+                .cloned(),
+        );
+        undone.sort_by_key(|record| std::cmp::Reverse(record.revision));
+
+        let mut kept = Vec::new();
+        for record in &undone {
+            if record.change.revert(&mut document) {
+                kept.push(record.revision);
+            } else {
+                dropped.push(format!("Undo {}", record.operation.label()));
+            }
+        }
+        redo.retain(|record| record.revision > saved || kept.contains(&record.revision));
+
+        // Below the pending operations is the file, unless saved operations were
+        // taken out of it.
+        self.base_revision = if kept.is_empty() {
+            saved
+        } else {
+            held.last()
+                .map_or(self.base_revision, |record| record.revision)
+        };
+
+        for record in pending {
+            match apply_operation(&mut document, &record.operation, stamps) {
+                Ok(change) if change.is_empty() => {}
+                Ok(change) => self.undo.push(EditRecord { change, ..record }),
+                Err(_) => dropped.push(record.operation.label()),
+            }
+        }
+
+        // Undone operations are redone to record what they change in the new file,
+        // then undone again.
+        let mut redone = Vec::new();
+        for record in redo.into_iter().rev() {
+            if record.revision <= saved {
+                record.change.redo(&mut document);
+                redone.push(record);
+                continue;
+            }
+            match apply_operation(&mut document, &record.operation, stamps) {
+                Ok(change) => redone.push(EditRecord { change, ..record }),
+                Err(_) => dropped.push(record.operation.label()),
+            }
+        }
+        for record in redone.into_iter().rev() {
+            record.change.undo(&mut document);
+            self.redo.push(record);
+        }
+
+        self.document = document;
+        self.fingerprint = Some(fingerprint);
+        self.dropped = dropped;
+
+        Ok(())
+    }
+
+    /// Records that the document was written to `path`, which becomes the file the
+    /// session edits. Undo history is kept, so edits can still be undone and saved.
+    pub fn mark_saved(&mut self, path: PathBuf) {
+        self.reverted.clear();
+        self.dropped.clear();
+        self.fingerprint = file_fingerprint(&path).ok();
+        self.path = path;
+        self.saved_revision = self.revision();
+    }
+}
+
+/// Applies `operation` and returns what it changed. A failed operation leaves the
+/// document unchanged.
+fn apply_operation(
+    document: &mut Document,
+    operation: &EditOperation,
+    stamps: Option<&StampLibrary>,
+) -> Result<Change, String> {
+    let snapshot = Snapshot::take(document, operation.affected_objects(document));
+
+    if let Err(e) = operation.apply(document, stamps) {
+        snapshot.restore(document);
+        return Err(e);
+    }
+
+    Ok(Change::between(snapshot, document))
+}
+
+/// Loads a file with the fingerprint of its contents.
+fn load(path: &Path) -> Result<(Document, String), String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read PDF: {e}"))?;
+    let document = Document::load_mem(&bytes).map_err(|e| format!("Failed to load PDF: {e}"))?;
+
+    Ok((document, format!("{:x}", Sha256::digest(&bytes))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::annotation::create::page_id;
+    use crate::pdf::reader::annotation::dictionary::page_annotations;
+    use crate::pdf::reader::{
+        Annotation, AnnotationAppearance, AnnotationFlags, AnnotationGeometry, AnnotationMetadata,
+        AnnotationType, PdfRect,
+    };
+    use lopdf::dictionary;
+
+    /// Three pages split over two `/Pages` nodes; the second node sets `/Rotate 90`.
+    fn document() -> Document {
+        let mut document = Document::with_version("1.7");
+        let root_id = document.new_object_id();
+        let node_id = document.new_object_id();
+
+        let page = |document: &mut Document, parent: ObjectId, label: &str| {
+            document.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => parent,
+                "MediaBox" => vec![0.into(), 0.into(), 600.into(), 800.into()],
+                "Label" => Object::string_literal(label),
+            })
+        };
+        let first = page(&mut document, root_id, "a");
+        let second = page(&mut document, node_id, "b");
+        let third = page(&mut document, node_id, "c");
+
+        document.objects.insert(
+            node_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Parent" => root_id,
+                "Kids" => vec![second.into(), third.into()],
+                "Count" => 2,
+                "Rotate" => 90,
+            }),
+        );
+        document.objects.insert(
+            root_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![first.into(), node_id.into()],
+                "Count" => 3,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => root_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    fn labels(document: &Document) -> Vec<String> {
+        document
+            .get_pages()
+            .into_values()
+            .map(|page_id| {
+                let label = document.get_dictionary(page_id).unwrap().get(b"Label");
+                lopdf::decode_text_string(label.unwrap()).unwrap()
+            })
+            .collect()
+    }
+
+    fn note() -> Annotation {
+        let rect = PdfRect {
+            left: 10.0,
+            top: 10.0,
+            right: 30.0,
+            bottom: 30.0,
+        };
+
+        Annotation {
+            id: String::new(),
+            page_index: 0,
+            subtype: AnnotationType::Text,
+            rect: rect.clone(),
+            geometry: AnnotationGeometry::Rect(rect),
+            appearance: AnnotationAppearance {
+                color: "#FFFF00".into(),
+                interior_color: None,
+                opacity: 1.0,
+                border_width: None,
+                border_style: None,
+                dash_pattern: None,
+                line_endings: None,
+                font_size: None,
+                icon: None,
+            },
+            metadata: AnnotationMetadata {
+                author: None,
+                contents: Some("Check".into()),
+                rich_contents: None,
+                creation_date: None,
+                modified_date: None,
+            },
+            flags: AnnotationFlags::default(),
+            in_reply_to: None,
+            replies: Vec::new(),
+            review_states: Vec::new(),
+            popup: None,
+        }
+    }
+
+    fn session() -> EditSession {
+        EditSession::new(PathBuf::from("test.pdf"), document())
+    }
+
+    #[test]
+    fn test_undo_redo_annotations() {
+        let mut session = session();
+        let original = session.document().objects.clone();
+
+        session
+            .apply(EditOperation::AddAnnotation(note()), None)
+            .unwrap();
+        let page = page_id(session.document(), 0).unwrap();
+        let name = page_annotations(session.document(), page)[0]
+            .unwrap()
+            .name()
+            .unwrap();
+
+        session
+            .apply(
+                EditOperation::RemoveAnnotation {
+                    page_index: 0,
+                    annotation_id: name,
+                },
+                None,
+            )
+            .unwrap();
+        assert!(page_annotations(session.document(), page).is_empty());
+
+        session.undo().unwrap();
+        assert_eq!(page_annotations(session.document(), page).len(), 1);
+
+        session.undo().unwrap();
+        assert!(page_annotations(session.document(), page).is_empty());
+        assert!(session.document().objects == original);
+        assert!(session.undo().is_err());
+
+        session.redo().unwrap();
+        assert_eq!(page_annotations(session.document(), page).len(), 1);
+
+        // A failed operation leaves the document as it was.
+        let objects = session.document().objects.clone();
+        let mut reply = note();
+        reply.in_reply_to = Some("missing".into());
+        assert!(session
+            .apply(EditOperation::AddAnnotation(reply), None)
+            .is_err());
+        assert!(session.document().objects == objects);
+
+        let history = session.history();
+        assert_eq!(history.entries.len(), 2);
+        assert!(history.entries[0].applied);
+        assert!(!history.entries[1].applied);
+        assert!(history.can_undo && history.can_redo && history.dirty);
+    }
+
+    #[test]
+    fn test_page_operations_round_trip() {
+        let mut session = session();
+        let original = session.document().objects.clone();
+
+        session
+            .apply(
+                EditOperation::ReorderPages {
+                    order: vec![2, 0, 1],
+                },
+                None,
+            )
+            .unwrap();
+        assert_eq!(labels(session.document()), ["c", "a", "b"]);
+
+        // Rotation inherited from the removed intermediate node is kept.
+        let pages = session.document().get_pages();
+        let rotate = |document: &Document, page_id| {
+            document
+                .get_dictionary(page_id)
+                .unwrap()
+                .get(b"Rotate")
+                .and_then(|rotate| rotate.as_i64())
+                .ok()
+        };
+        assert_eq!(rotate(session.document(), pages[&1]), Some(90));
+        assert_eq!(rotate(session.document(), pages[&2]), None);
+
+        session
+            .apply(
+                EditOperation::RotatePages {
+                    pages: vec![0, 1],
+                    angle: -90,
+                },
+                None,
+            )
+            .unwrap();
+        let pages = session.document().get_pages();
+        assert_eq!(rotate(session.document(), pages[&1]), Some(0));
+        assert_eq!(rotate(session.document(), pages[&2]), Some(270));
+
+        session
+            .apply(EditOperation::DeletePages { pages: vec![0, 2] }, None)
+            .unwrap();
+        assert_eq!(labels(session.document()), ["a"]);
+
+        assert!(session
+            .apply(EditOperation::DeletePages { pages: vec![0] }, None)
+            .is_err());
+        assert!(session
+            .apply(
+                EditOperation::RotatePages {
+                    pages: vec![0],
+                    angle: 45,
+                },
+                None
+            )
+            .is_err());
+        assert_eq!(session.history().entries.len(), 3);
+
+        while session.undo().is_ok() {}
+        assert_eq!(labels(session.document()), ["a", "b", "c"]);
+        assert!(session.document().objects == original);
+        assert!(!session.is_dirty());
+    }
+
+    #[test]
+    fn test_metadata_and_unchanged_operations() {
+        let mut session = session();
+        let metadata = DocumentMetadata {
+            title: Some("Report".into()),
+            author: Some("Ana".into()),
+            ..Default::default()
+        };
+
+        session
+            .apply(EditOperation::SetMetadata(metadata.clone()), None)
+            .unwrap();
+        assert_eq!(session.metadata(), metadata);
+
+        // Setting the same values again changes nothing and is not recorded.
+        session
+            .apply(EditOperation::SetMetadata(metadata), None)
+            .unwrap();
+        assert_eq!(session.history().entries.len(), 1);
+
+        session.undo().unwrap();
+        assert_eq!(session.metadata(), DocumentMetadata::default());
+    }
+
+    #[test]
+    fn test_mark_saved_keeps_history() {
+        let mut session = session();
+
+        session
+            .apply(EditOperation::DeletePages { pages: vec![1] }, None)
+            .unwrap();
+        session.mark_saved(PathBuf::from("copy.pdf"));
+        assert!(!session.is_dirty());
+        assert_eq!(session.path(), Path::new("copy.pdf"));
+
+        session.undo().unwrap();
+        assert!(session.is_dirty());
+        assert!(session.history().can_redo);
+    }
+
+    #[test]
+    fn test_rebase_keeps_unsaved_edits() {
+        let path = std::env::temp_dir().join(format!("velin-{}.pdf", uuid::Uuid::new_v4()));
+        document().save(&path).unwrap();
+
+        let mut session = EditSession::open(path.clone()).unwrap();
+        session
+            .apply(EditOperation::AddAnnotation(note()), None)
+            .unwrap();
+
+        rotate_last_page(&path);
+        assert!(session.is_stale());
+
+        session.rebase(None).unwrap();
+        assert!(!session.is_stale());
+        assert!(session.is_dirty());
+        assert_eq!(session.history().entries.len(), 1);
+
+        let document = session.document();
+        let page = page_id(document, 0).unwrap();
+        assert_eq!(page_annotations(document, page).len(), 1);
+        let last = document.get_dictionary(document.get_pages()[&3]).unwrap();
+        assert_eq!(last.get(b"Rotate").unwrap().as_i64().unwrap(), 180);
+
+        session.undo().unwrap();
+        assert!(page_annotations(session.document(), page).is_empty());
+        assert!(!session.is_dirty());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    /// Rotates the last page of the file, as another writer would.
+    fn rotate_last_page(path: &std::path::Path) {
+        let mut rewritten = Document::load(path).unwrap();
+        let last = rewritten.get_pages()[&3];
+        rewritten
+            .get_dictionary_mut(last)
+            .unwrap()
+            .set("Rotate", 180);
+        rewritten.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_rebase_keeps_saved_edit_undone() {
+        let path = std::env::temp_dir().join(format!("velin-{}.pdf", uuid::Uuid::new_v4()));
+        document().save(&path).unwrap();
+
+        let mut session = EditSession::open(path.clone()).unwrap();
+        session
+            .apply(EditOperation::AddAnnotation(note()), None)
+            .unwrap();
+        session.document.clone().save(&path).unwrap();
+        session.mark_saved(path.clone());
+        session.undo().unwrap();
+
+        rotate_last_page(&path);
+        session.rebase(None).unwrap();
+
+        let page = page_id(session.document(), 0).unwrap();
+        assert!(page_annotations(session.document(), page).is_empty());
+        assert!(session.is_dirty());
+        assert!(session.history().can_redo);
+        assert!(session.history().dropped.is_empty());
+
+        session.redo().unwrap();
+        assert_eq!(page_annotations(session.document(), page).len(), 1);
+        assert!(!session.is_dirty());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_rebase_reports_dropped_edits() {
+        let path = std::env::temp_dir().join(format!("velin-{}.pdf", uuid::Uuid::new_v4()));
+        document().save(&path).unwrap();
+
+        let mut session = EditSession::open(path.clone()).unwrap();
+        session
+            .apply(EditOperation::DeletePages { pages: vec![2] }, None)
+            .unwrap();
+        let label = session.history().entries[0].label.clone();
+
+        // Another writer deletes the same page.
+        let mut rewritten = Document::load(&path).unwrap();
+        rewritten.delete_pages(&[3]);
+        rewritten.save(&path).unwrap();
+
+        session.rebase(None).unwrap();
+        assert_eq!(session.document().get_pages().len(), 2);
+        assert!(session.history().entries.is_empty());
+        assert_eq!(session.history().dropped, vec![label]);
+        assert!(!session.is_dirty());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,20 +1,29 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use uuid::Uuid;
 
 use crate::pdf::{
+    editor::EditSession,
     worker::{PdfEvent, PdfWorker},
     DocumentId,
 };
 
 pub struct DocumentManager {
     worker: PdfWorker,
+    paths: HashMap<DocumentId, PathBuf>,
+
+    /// Unsaved edits, created on the first edit of a document
+    sessions: HashMap<DocumentId, EditSession>,
 }
 
 impl DocumentManager {
     pub fn new() -> Self {
         Self {
             worker: PdfWorker::spawn(),
+            paths: HashMap::new(),
+            sessions: HashMap::new(),
         }
     }
 
@@ -27,7 +36,7 @@ impl DocumentManager {
             .sender()
             .send(PdfEvent::Open {
                 id: id.clone(),
-                path,
+                path: path.clone(),
                 reply: tx,
             })
             .map_err(|e| format!("Error sending open command: {e}"))?;
@@ -35,9 +44,49 @@ impl DocumentManager {
         rx.recv()
             .map_err(|e| format!("Error receiving open result: {e}"))??;
 
+        self.paths.insert(id.clone(), path);
+
         Ok(id)
     }
 
+    /// Forgets a closed document, discarding its unsaved edits.
+    pub fn close(&mut self, id: &DocumentId) {
+        self.paths.remove(id);
+        self.sessions.remove(id);
+    }
+
+    /// Returns the edit session of a document, loading the document on first use.
+    pub fn session(&mut self, id: &DocumentId) -> Result<&mut EditSession, String> {
+        match self.sessions.entry(id.clone()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let path = self.paths.get(id).ok_or("Document not found")?;
+                Ok(entry.insert(EditSession::open(path.clone())?))
+            }
+        }
+    }
+
+    pub fn existing_session(&self, id: &DocumentId) -> Option<&EditSession> {
+        self.sessions.get(id)
+    }
+
+    pub fn existing_session_mut(&mut self, id: &DocumentId) -> Option<&mut EditSession> {
+        self.sessions.get_mut(id)
+    }
+
+    pub fn path(&self, id: &DocumentId) -> Option<&PathBuf> {
+        self.paths.get(id)
+    }
+
+    /// Points an open document at a new file after Save As.
+    pub fn set_path(&mut self, id: &DocumentId, path: PathBuf) {
+        self.paths.insert(id.clone(), path);
+    }
+
+    pub fn discard_session(&mut self, id: &DocumentId) {
+        self.sessions.remove(id);
+    }
+
     pub fn worker(&self) -> &PdfWorker {
         &self.worker
     }
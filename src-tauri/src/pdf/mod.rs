@@ -1,5 +1,6 @@
 use serde::Serialize;
 
+pub mod editor;
 pub mod manager;
 pub mod reader;
 pub mod tools;
@@ -1,43 +1,186 @@
-use crate::pdf::reader::annotation::{Annotation, AnnotationGeometry, AnnotationType, PdfRect};
+use crate::pdf::editor::serialize_edits;
+use crate::pdf::reader::annotation::create;
+use crate::pdf::reader::annotation::dictionary::{self, AnnotationDictionary};
+use crate::pdf::reader::annotation::exchange::{merge_annotations, page_heights};
+use crate::pdf::reader::annotation::sidecar::{
+    merge_sidecar, AnnotationStorage, SidecarFile, SidecarStore,
+};
+use crate::pdf::reader::annotation::source::SourceFile;
+use crate::pdf::reader::annotation::stamp::{StampKind, StampLibrary};
+use crate::pdf::reader::annotation::{
+    Annotation, AnnotationGeometry, AnnotationImportSummary, AnnotationPopup, AnnotationType,
+    PdfRect, ReviewState, ReviewStateRecord, ReviewStatus,
+};
 use crate::pdf::reader::{AnnotationAppearance, AnnotationFlags, AnnotationMetadata, Point, Quad};
 use crate::pdf::DocumentId;
+use crate::utils::fs::write_atomic;
+use lopdf::ObjectId;
 use pdfium_render::prelude::*;
 use std::collections::HashMap;
+use std::path::Path;
 
+/// Reads the annotations of a document as threads. When a sidecar store is given,
+/// annotations kept in the document's sidecar file are included.
 pub fn get_annotations(
     documents: &HashMap<DocumentId, PdfDocument>,
+    file: &SourceFile,
     id: &DocumentId,
+    sidecar: Option<&SidecarStore>,
 ) -> Result<Vec<Annotation>, String> {
     let document = documents.get(id).ok_or("Document not found")?;
-    let mut annotations = Vec::new();
+    let mut entries = Vec::new();
+
+    // Geometry that pdfium does not expose is read from the raw dictionaries. If lopdf
+    // cannot parse the file, annotations are still returned with their bounding boxes.
+    let source = file.document().ok();
+    let source_pages = source.map(|source| source.get_pages()).unwrap_or_default();
 
     for (page_idx, page) in document.pages().iter().enumerate() {
+        let dictionaries = match (source, source_pages.get(&(page_idx as u32 + 1))) {
+            (Some(source), Some(page_id)) => dictionary::page_annotations(source, *page_id),
+            _ => Vec::new(),
+        };
+
         for (annot_idx, annotation) in page.annotations().iter().enumerate() {
-            let annot_type = annotation.annotation_type();
-            match annot_type {
-                PdfPageAnnotationType::Highlight
-                | PdfPageAnnotationType::Underline
-                | PdfPageAnnotationType::Squiggly
-                | PdfPageAnnotationType::Strikeout => {
-                    process_markup_annotation(
-                        &mut annotations,
-                        &annotation,
-                        page.height().value,
-                        page_idx,
-                        annot_idx,
-                    )?;
+            let annotation_type = get_annotation_type(&annotation);
+            if annotation_type == AnnotationType::Unknown {
+                continue;
+            }
+
+            let dictionary = dictionaries.get(annot_idx).copied().flatten();
+
+            // Popups belonging to a markup annotation are reported on their parent.
+            if annotation_type == AnnotationType::Popup
+                && dictionary.and_then(|d| d.parent()).is_some()
+            {
+                continue;
+            }
+
+            process_annotation(
+                &mut entries,
+                &annotation,
+                dictionary,
+                page.height().value,
+                page_idx,
+                annot_idx,
+            )?;
+        }
+    }
+
+    let mut threads = build_threads(entries);
+
+    if let Some(store) = sidecar {
+        if let Some(sidecar) = store.load(&file.fingerprint)? {
+            merge_sidecar(&mut threads, &sidecar);
+        }
+    }
+
+    Ok(threads)
+}
+
+/// An annotation together with the references needed to place it in a thread.
+struct ThreadEntry {
+    annotation: Annotation,
+    object_id: Option<ObjectId>,
+    in_reply_to: Option<ObjectId>,
+    review_state: Option<ReviewState>,
+}
+
+/// Nests replies (`/IRT`) under the annotation they reply to and folds state
+/// annotations into the review states of their target.
+///
+/// Replies whose target cannot be found, and annotations that are part of a reply
+/// cycle, are returned as top-level annotations.
+fn build_threads(entries: Vec<ThreadEntry>) -> Vec<Annotation> {
+    let index_of = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| entry.object_id.map(|object_id| (object_id, index)))
+        .collect::<HashMap<_, _>>();
+
+    let mut parents = entries
+        .iter()
+        .map(|entry| {
+            entry
+                .in_reply_to
+                .and_then(|target| index_of.get(&target).copied())
+        })
+        .collect::<Vec<_>>();
+
+    let in_cycle = (0..entries.len())
+        .map(|index| {
+            let mut current = parents[index];
+            for _ in 0..entries.len() {
+                match current {
+                    Some(parent) if parent == index => return true,
+                    Some(parent) => current = parents[parent],
+                    None => return false,
                 }
-                _ => {}
             }
+            false
+        })
+        .collect::<Vec<_>>();
+
+    for (parent, in_cycle) in parents.iter_mut().zip(in_cycle) {
+        if in_cycle {
+            *parent = None;
         }
     }
 
-    Ok(annotations)
+    let mut children = vec![Vec::new(); entries.len()];
+    let mut roots = Vec::new();
+    for (index, parent) in parents.iter().enumerate() {
+        match parent {
+            Some(parent) => children[*parent].push(index),
+            None => roots.push(index),
+        }
+    }
+
+    let mut slots = entries.into_iter().map(Some).collect::<Vec<_>>();
+
+    roots
+        .into_iter()
+        .filter_map(|index| assemble_thread(index, &children, &mut slots))
+        .map(|(annotation, _)| annotation)
+        .collect()
+}
+
+fn assemble_thread(
+    index: usize,
+    children: &[Vec<usize>],
+    slots: &mut [Option<ThreadEntry>],
+) -> Option<(Annotation, Option<ReviewState>)> {
+    let entry = slots[index].take()?;
+    let mut annotation = entry.annotation;
+
+    for child in &children[index] {
+        let Some((mut reply, review_state)) = assemble_thread(*child, children, slots) else {
+            continue;
+        };
+
+        match review_state {
+            Some(state) => annotation.review_states.push(ReviewStatus {
+                state,
+                author: reply.metadata.author,
+                date: reply
+                    .metadata
+                    .modified_date
+                    .or(reply.metadata.creation_date),
+            }),
+            None => {
+                reply.in_reply_to = Some(annotation.id.clone());
+                annotation.replies.push(reply);
+            }
+        }
+    }
+
+    Some((annotation, entry.review_state))
 }
 
-fn process_markup_annotation<'a>(
-    annotations: &mut Vec<Annotation>,
+fn process_annotation<'a>(
+    entries: &mut Vec<ThreadEntry>,
     annotation: &PdfPageAnnotation<'a>,
+    dictionary: Option<AnnotationDictionary>,
     page_height: f32,
     page_index: usize,
     annot_index: usize,
@@ -53,64 +196,70 @@ fn process_markup_annotation<'a>(
         bottom: page_height - bounds.bottom().value,
     };
 
-    let attachment_points = annotation.attachment_points();
+    let geometry = match annotation_type {
+        AnnotationType::Highlight
+        | AnnotationType::Underline
+        | AnnotationType::Squiggly
+        | AnnotationType::Strikeout => {
+            let quads = read_quads(annotation, page_height);
 
-    let quads = attachment_points
-        .iter()
-        .map(|qp| Quad {
-            p1: Point {
-                x: qp.x1.value,
-                y: page_height - qp.y1.value,
-            },
-            p2: Point {
-                x: qp.x2.value,
-                y: page_height - qp.y2.value,
-            },
-            p3: Point {
-                x: qp.x3.value,
-                y: page_height - qp.y3.value,
-            },
-            p4: Point {
-                x: qp.x4.value,
-                y: page_height - qp.y4.value,
-            },
-        })
-        .collect::<Vec<_>>();
-
-    if quads.is_empty() {
-        return Ok(());
-    }
+            if quads.is_empty() {
+                return Ok(());
+            }
 
-    // Safety: fill_color() and stroke_color() in pdfium-render 0.8.x can crash (ACCESS_VIOLATION)
-    // because they try to cast an annotation handle to a page object handle
-    // if FPDFAnnot_GetColor() fails.
-    /*
-    let color_result = annotation.stroke_color();
-    let (r, g, b, a) = match color_result {
-        Ok(c) => (c.red(), c.green(), c.blue(), c.alpha()),
-        Err(_) => (255, 255, 0, 255), // Default to yellow on error
+            AnnotationGeometry::QuadPoints(quads)
+        }
+        AnnotationType::Line => dictionary
+            .and_then(|d| d.line(page_height))
+            .map(|(start, end)| AnnotationGeometry::Line { start, end })
+            .unwrap_or_else(|| AnnotationGeometry::Rect(rect.clone())),
+        AnnotationType::Polygon | AnnotationType::Polyline => dictionary
+            .and_then(|d| d.vertices(page_height))
+            .map(AnnotationGeometry::Points)
+            .unwrap_or_else(|| AnnotationGeometry::Rect(rect.clone())),
+        AnnotationType::Ink => dictionary
+            .and_then(|d| d.ink_paths(page_height))
+            .map(AnnotationGeometry::InkPaths)
+            .unwrap_or_else(|| AnnotationGeometry::Rect(rect.clone())),
+        AnnotationType::Redacted => dictionary
+            .and_then(|d| d.quads(page_height))
+            .map(AnnotationGeometry::QuadPoints)
+            .unwrap_or_else(|| AnnotationGeometry::Rect(rect.clone())),
+        _ => AnnotationGeometry::Rect(rect.clone()),
     };
-    */
-    // let (r, g, b, a) = match color_result {
-    //     Ok(c) => (c.red(), c.green(), c.blue(), c.alpha()),
-    //     Err(_) => (255, 255, 0, 255), // Default to yellow on error
-    // };
-    let (r, g, b, a) = (255, 0, 0, 150);
+
+    // Colours are read from the dictionary rather than through stroke_color() and
+    // fill_color(): in pdfium-render 0.8.x those can crash (ACCESS_VIOLATION) because
+    // they cast the annotation handle to a page object handle if FPDFAnnot_GetColor() fails.
+    let (border_style, dash_pattern) = dictionary
+        .and_then(|d| d.border_style())
+        .map(|(style, dashes)| (Some(style), dashes))
+        .unwrap_or((None, None));
 
     let appearance = AnnotationAppearance {
-        color: format!("#{:02X}{:02X}{:02X}", r, g, b),
-        opacity: a as f32 / 255.0,
-        border_width: None,
+        color: dictionary
+            .and_then(|d| d.color())
+            .unwrap_or_else(|| default_color(&annotation_type).to_string()),
+        interior_color: dictionary.and_then(|d| d.interior_color()),
+        opacity: dictionary.and_then(|d| d.opacity()).unwrap_or(1.0),
+        border_width: dictionary.and_then(|d| d.border_width()),
+        border_style,
+        dash_pattern,
+        line_endings: dictionary.and_then(|d| d.line_endings()),
+        font_size: dictionary.and_then(|d| d.font_size()),
+        icon: dictionary.and_then(|d| d.icon()),
     };
 
     let author = annotation.creator();
     let contents = annotation.contents();
+    let rich_contents = dictionary.and_then(|d| d.rich_contents());
     let creation_date = annotation.creation_date();
     let modified_date = annotation.modification_date();
 
     let metadata = AnnotationMetadata {
         author,
         contents,
+        rich_contents,
         creation_date,
         modified_date,
     };
@@ -127,60 +276,576 @@ fn process_markup_annotation<'a>(
         read_only,
     };
 
-    let prefix = match annotation_type {
+    // Prefer the annotation name so replies keep pointing at their target when
+    // other annotations are added or removed.
+    let stable_id = dictionary.and_then(|d| d.name()).unwrap_or_else(|| {
+        format!(
+            "{}-{}-{}",
+            id_prefix(&annotation_type),
+            page_index,
+            annot_index
+        )
+    });
+
+    let popup = dictionary.and_then(|d| d.popup()).and_then(|popup| {
+        Some(AnnotationPopup {
+            rect: popup.rect(page_height)?,
+            open: popup.is_open(),
+        })
+    });
+
+    entries.push(ThreadEntry {
+        annotation: Annotation {
+            id: stable_id,
+            page_index: page_index as u16,
+            subtype: annotation_type,
+            rect,
+            geometry,
+            appearance,
+            metadata,
+            flags,
+            in_reply_to: None,
+            replies: Vec::new(),
+            review_states: Vec::new(),
+            popup,
+        },
+        object_id: dictionary.and_then(|d| d.object_id),
+        in_reply_to: dictionary.and_then(|d| d.in_reply_to()),
+        review_state: dictionary.and_then(|d| d.review_state()),
+    });
+
+    Ok(())
+}
+
+fn read_quads(annotation: &PdfPageAnnotation, page_height: f32) -> Vec<Quad> {
+    annotation
+        .attachment_points()
+        .iter()
+        .map(|qp| Quad {
+            p1: Point {
+                x: qp.x1.value,
+                y: page_height - qp.y1.value,
+            },
+            p2: Point {
+                x: qp.x2.value,
+                y: page_height - qp.y2.value,
+            },
+            p3: Point {
+                x: qp.x3.value,
+                y: page_height - qp.y3.value,
+            },
+            p4: Point {
+                x: qp.x4.value,
+                y: page_height - qp.y4.value,
+            },
+        })
+        .collect()
+}
+
+/// Colour used when the dictionary has no (or a transparent) `/C` entry.
+fn default_color(annotation_type: &AnnotationType) -> &'static str {
+    match annotation_type {
+        AnnotationType::Highlight | AnnotationType::Text => "#FFFF00",
+        AnnotationType::Underline | AnnotationType::Squiggly => "#00A000",
+        AnnotationType::Strikeout | AnnotationType::Redacted => "#FF0000",
+        _ => "#000000",
+    }
+}
+
+fn id_prefix(annotation_type: &AnnotationType) -> &'static str {
+    match annotation_type {
         AnnotationType::Highlight => "hl",
         AnnotationType::Underline => "ul",
         AnnotationType::Squiggly => "sq",
         AnnotationType::Strikeout => "so",
+        AnnotationType::Text => "tx",
+        AnnotationType::FreeText => "ft",
+        AnnotationType::Square => "sr",
+        AnnotationType::Circle => "ci",
+        AnnotationType::Line => "ln",
+        AnnotationType::Polygon => "pg",
+        AnnotationType::Polyline => "pl",
+        AnnotationType::Ink => "ik",
+        AnnotationType::Stamp => "st",
+        AnnotationType::Caret => "ca",
+        AnnotationType::Link => "lk",
+        AnnotationType::Popup => "pp",
+        AnnotationType::Redacted => "rd",
         _ => "ann",
-    };
-
-    let stable_id = format!("{}-{}-{}", prefix, page_index, annot_index);
-
-    annotations.push(Annotation {
-        id: stable_id,
-        page_index: page_index as u16,
-        subtype: annotation_type,
-        rect,
-        geometry: AnnotationGeometry::QuadPoints(quads),
-        appearance,
-        metadata,
-        flags,
-    });
-
-    Ok(())
+    }
 }
 
 fn get_annotation_type(annotation: &PdfPageAnnotation) -> AnnotationType {
     match annotation.annotation_type() {
+        PdfPageAnnotationType::Text => AnnotationType::Text,
+        PdfPageAnnotationType::Link => AnnotationType::Link,
+        PdfPageAnnotationType::FreeText => AnnotationType::FreeText,
+        PdfPageAnnotationType::Line => AnnotationType::Line,
+        PdfPageAnnotationType::Square => AnnotationType::Square,
+        PdfPageAnnotationType::Circle => AnnotationType::Circle,
+        PdfPageAnnotationType::Polygon => AnnotationType::Polygon,
+        PdfPageAnnotationType::Polyline => AnnotationType::Polyline,
         PdfPageAnnotationType::Highlight => AnnotationType::Highlight,
         PdfPageAnnotationType::Underline => AnnotationType::Underline,
         PdfPageAnnotationType::Squiggly => AnnotationType::Squiggly,
         PdfPageAnnotationType::Strikeout => AnnotationType::Strikeout,
+        PdfPageAnnotationType::Stamp => AnnotationType::Stamp,
+        PdfPageAnnotationType::Caret => AnnotationType::Caret,
+        PdfPageAnnotationType::Ink => AnnotationType::Ink,
+        PdfPageAnnotationType::Popup => AnnotationType::Popup,
+        PdfPageAnnotationType::Redacted => AnnotationType::Redacted,
         _ => AnnotationType::Unknown,
     }
 }
 
-#[allow(unused_variables)]
-pub fn add_annotation(
-    documents: &HashMap<DocumentId, PdfDocument>,
+pub fn add_annotation<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
     id: &DocumentId,
     annotation: Annotation,
+    sidecar: Option<&SidecarStore>,
 ) -> Result<(), String> {
-    eprintln!("Mock adding annotation: {:?}", annotation);
+    add_annotations(documents, file, id, vec![annotation], sidecar)
+}
 
-    Ok(())
+/// Adds several annotations, writing the PDF or its sidecar file once.
+pub fn add_annotations<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
+    id: &DocumentId,
+    annotations: Vec<Annotation>,
+    sidecar: Option<&SidecarStore>,
+) -> Result<(), String> {
+    let document = documents.get(id).ok_or("Document not found")?;
+
+    if let Some((store, mut sidecar)) = writable_sidecar(sidecar, file)? {
+        let now = create::pdf_date_now();
+        for mut annotation in annotations {
+            if annotation.id.is_empty() {
+                annotation.id = uuid::Uuid::new_v4().to_string();
+            }
+            let metadata = &mut annotation.metadata;
+            metadata.creation_date.get_or_insert_with(|| now.clone());
+            metadata.modified_date.get_or_insert_with(|| now.clone());
+
+            sidecar.annotations.push(annotation);
+        }
+        return store.save(&file.fingerprint, &sidecar);
+    }
+
+    let mut source = file.document()?.clone();
+
+    for annotation in &annotations {
+        let page_height = document
+            .pages()
+            .get(annotation.page_index)
+            .map_err(|e| format!("Failed to get page: {e}"))?
+            .height()
+            .value;
+
+        create::create_annotation(&mut source, annotation, page_height, None)?;
+    }
+
+    // Drop the worker's copy of the document before replacing the file; it is
+    // reopened with the new annotations on the next request.
+    documents.remove(id);
+
+    save_document(file, &source, &file.path)
 }
 
-#[allow(unused_variables)]
-pub fn delete_annotation(
-    documents: &HashMap<DocumentId, PdfDocument>,
+/// Adds a Stamp annotation. Built-in stamps are drawn like any other annotation;
+/// custom stamps from the library embed their image or PDF page in the appearance.
+/// `annotation.appearance.icon` holds the stamp id.
+pub fn add_stamp<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
     id: &DocumentId,
-    _page_index: u16,
-    annot_id: String,
+    annotation: Annotation,
+    library: &StampLibrary,
+    sidecar: Option<&SidecarStore>,
 ) -> Result<(), String> {
-    // Mock implementation
-    eprintln!("Mock deleting annotation: {}", annot_id);
+    if annotation.subtype != AnnotationType::Stamp {
+        return Err("Annotation is not a stamp".into());
+    }
 
-    Ok(())
+    let stamp_id = annotation
+        .appearance
+        .icon
+        .as_deref()
+        .ok_or("Stamp id missing")?;
+    let stamp = library.get(stamp_id)?;
+
+    let document = documents.get(id).ok_or("Document not found")?;
+
+    // Sidecar annotations keep only the stamp id; the frontend draws them from the
+    // library.
+    if stamp.kind == StampKind::Builtin || writable_sidecar(sidecar, file)?.is_some() {
+        return add_annotation(documents, file, id, annotation, sidecar);
+    }
+
+    let page_height = document
+        .pages()
+        .get(annotation.page_index)
+        .map_err(|e| format!("Failed to get page: {e}"))?
+        .height()
+        .value;
+
+    let mut source = file.document()?.clone();
+
+    create::create_annotation(&mut source, &annotation, page_height, Some(library))?;
+
+    documents.remove(id);
+
+    save_document(file, &source, &file.path)
+}
+
+/// Records a review state (Accepted, Rejected, Completed, ...) on an annotation.
+pub fn set_review_state<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
+    id: &DocumentId,
+    page_index: u16,
+    annotation_id: &str,
+    status: &ReviewStatus,
+    sidecar: Option<&SidecarStore>,
+) -> Result<(), String> {
+    if let Some((store, mut sidecar)) = writable_sidecar(sidecar, file)? {
+        let mut status = status.clone();
+        status.date.get_or_insert_with(create::pdf_date_now);
+
+        sidecar.review_states.push(ReviewStateRecord {
+            page_index,
+            annotation_id: annotation_id.to_string(),
+            status,
+        });
+        return store.save(&file.fingerprint, &sidecar);
+    }
+
+    let mut source = file.document()?.clone();
+
+    create::create_review_state(&mut source, page_index, annotation_id, status)?;
+
+    documents.remove(id);
+
+    save_document(file, &source, &file.path)
+}
+
+/// Returns the sidecar that annotations of `file` are written to.
+///
+/// That is the existing sidecar, or a new one when the file is read-only. `None`
+/// means annotations are written into the PDF.
+pub(crate) fn writable_sidecar<'s>(
+    store: Option<&'s SidecarStore>,
+    file: &SourceFile,
+) -> Result<Option<(&'s SidecarStore, SidecarFile)>, String> {
+    let Some(store) = store else {
+        return Ok(None);
+    };
+
+    let sidecar = match store.load(&file.fingerprint)? {
+        Some(sidecar) => sidecar,
+        None if is_read_only(&file.path) => SidecarFile::new(file.file_name()),
+        None => return Ok(None),
+    };
+
+    Ok(Some((store, sidecar)))
+}
+
+/// Reports whether new annotations of a document go to the PDF or to a sidecar.
+pub fn get_annotation_storage(
+    file: &SourceFile,
+    store: &SidecarStore,
+) -> Result<AnnotationStorage, String> {
+    if store.exists(&file.fingerprint) || is_read_only(&file.path) {
+        Ok(AnnotationStorage::Sidecar)
+    } else {
+        Ok(AnnotationStorage::Document)
+    }
+}
+
+/// Switches a document to sidecar storage so the PDF is no longer modified.
+pub fn use_sidecar_annotations(file: &SourceFile, store: &SidecarStore) -> Result<(), String> {
+    if store.exists(&file.fingerprint) {
+        return Ok(());
+    }
+
+    store.save(&file.fingerprint, &SidecarFile::new(file.file_name()))
+}
+
+/// Writes the sidecar annotations into the PDF ("bake").
+///
+/// With `dest` the annotated copy is written there and the sidecar stays attached
+/// to the untouched original. Without it the PDF is updated in place and the
+/// sidecar is removed, returning the document to document storage.
+pub fn bake_sidecar_annotations<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
+    id: &DocumentId,
+    store: &SidecarStore,
+    stamps: &StampLibrary,
+    dest: Option<&Path>,
+) -> Result<AnnotationImportSummary, String> {
+    let document = documents.get(id).ok_or("Document not found")?;
+    let page_heights = page_heights(document);
+
+    let sidecar = store
+        .load(&file.fingerprint)?
+        .ok_or("Document has no sidecar annotations")?;
+
+    let mut source = file.document()?.clone();
+    let summary = merge_annotations(
+        &mut source,
+        sidecar.annotations,
+        sidecar.review_states,
+        &page_heights,
+        Some(stamps),
+    );
+
+    match dest {
+        Some(dest) if dest != file.path => save_document(file, &source, dest)?,
+        _ => {
+            documents.remove(id);
+            save_document(file, &source, &file.path)?;
+            store.remove(&file.fingerprint)?;
+        }
+    }
+
+    Ok(summary)
+}
+
+fn is_read_only(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.permissions().readonly())
+        .unwrap_or(false)
+}
+
+/// Writes `document`, an edited copy of `file`, to `dest`. The changes are appended
+/// to the file's bytes as an incremental update so existing signatures stay valid,
+/// and the file is replaced atomically so a failed save never truncates it.
+pub fn save_document(
+    file: &SourceFile,
+    document: &lopdf::Document,
+    dest: &Path,
+) -> Result<(), String> {
+    let original = std::fs::read(&file.path).map_err(|e| format!("Failed to read PDF: {e}"))?;
+
+    write_atomic(dest, &serialize_edits(original, document)?.bytes)
+}
+
+/// Serializes a document loaded with lopdf, encrypting it again when it was
+/// decrypted on load.
+pub fn document_to_bytes(document: &mut lopdf::Document) -> Result<Vec<u8>, String> {
+    // lopdf decrypts on load; encrypt again with the original key so the saved file
+    // keeps its protection.
+    if let Some(state) = document.encryption_state.take() {
+        document
+            .encrypt(&state)
+            .map_err(|e| format!("Failed to encrypt PDF: {e}"))?;
+    }
+
+    let mut buffer = Vec::new();
+    document
+        .save_to(&mut buffer)
+        .map_err(|e| format!("Failed to save PDF: {e}"))?;
+
+    Ok(buffer)
+}
+
+/// Removes an annotation and its replies from the document's sidecar. Returns
+/// `false` when the sidecar does not hold the annotation.
+pub fn delete_sidecar_annotation(
+    file: &SourceFile,
+    annotation_id: &str,
+    store: &SidecarStore,
+) -> Result<bool, String> {
+    let Some(mut sidecar) = store.load(&file.fingerprint)? else {
+        return Ok(false);
+    };
+    if !sidecar.remove(annotation_id) {
+        return Ok(false);
+    }
+
+    store.save(&file.fingerprint, &sidecar)?;
+    Ok(true)
+}
+
+/// Removes an annotation stored in the PDF with its popup, replies and review states.
+pub fn delete_annotation<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
+    id: &DocumentId,
+    page_index: u16,
+    annotation_id: &str,
+) -> Result<(), String> {
+    let mut source = file.document()?.clone();
+
+    create::remove_annotation(&mut source, page_index, annotation_id)?;
+
+    documents.remove(id);
+
+    save_document(file, &source, &file.path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::{read_signatures, SignatureStatus};
+    use crate::pdf::tools::sign::{sign_document, SignInput};
+    use crate::utils::pkcs12::tests::test_identity;
+    use lopdf::{dictionary, Document, Object};
+
+    fn entry(id: &str, object: u32, reply_to: Option<u32>) -> ThreadEntry {
+        ThreadEntry {
+            annotation: Annotation {
+                id: id.into(),
+                page_index: 0,
+                subtype: AnnotationType::Text,
+                rect: PdfRect {
+                    left: 0.0,
+                    top: 0.0,
+                    right: 20.0,
+                    bottom: 20.0,
+                },
+                geometry: AnnotationGeometry::Rect(PdfRect {
+                    left: 0.0,
+                    top: 0.0,
+                    right: 20.0,
+                    bottom: 20.0,
+                }),
+                appearance: AnnotationAppearance {
+                    color: "#FFFF00".into(),
+                    interior_color: None,
+                    opacity: 1.0,
+                    border_width: None,
+                    border_style: None,
+                    dash_pattern: None,
+                    line_endings: None,
+                    font_size: None,
+                    icon: None,
+                },
+                metadata: AnnotationMetadata {
+                    author: Some(format!("author of {id}")),
+                    contents: None,
+                    rich_contents: None,
+                    creation_date: None,
+                    modified_date: Some("D:20240101000000Z".into()),
+                },
+                flags: AnnotationFlags::default(),
+                in_reply_to: None,
+                replies: Vec::new(),
+                review_states: Vec::new(),
+                popup: None,
+            },
+            object_id: Some((object, 0)),
+            in_reply_to: reply_to.map(|object| (object, 0)),
+            review_state: None,
+        }
+    }
+
+    #[test]
+    fn test_build_threads_nests_replies_and_states() {
+        let mut state = entry("state", 4, Some(1));
+        state.review_state = Some(ReviewState::Completed);
+
+        let threads = build_threads(vec![
+            entry("root", 1, None),
+            entry("reply", 2, Some(1)),
+            entry("nested", 3, Some(2)),
+            state,
+            entry("orphan", 5, Some(99)),
+        ]);
+
+        assert_eq!(threads.len(), 2);
+        let root = &threads[0];
+        assert_eq!(root.replies.len(), 1);
+        assert_eq!(root.replies[0].in_reply_to.as_deref(), Some("root"));
+        assert_eq!(root.replies[0].replies[0].id, "nested");
+        assert_eq!(root.review_states.len(), 1);
+        assert_eq!(root.review_states[0].state, ReviewState::Completed);
+        assert_eq!(
+            root.review_states[0].author.as_deref(),
+            Some("author of state")
+        );
+        assert_eq!(threads[1].id, "orphan");
+        assert_eq!(threads[1].in_reply_to, None);
+    }
+
+    #[test]
+    fn test_build_threads_breaks_cycles() {
+        let threads = build_threads(vec![
+            entry("a", 1, Some(2)),
+            entry("b", 2, Some(1)),
+            entry("c", 3, Some(1)),
+        ]);
+
+        let ids = threads.iter().map(|a| a.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(threads[0].replies[0].id, "c");
+    }
+
+    fn signed_document() -> Vec<u8> {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes).unwrap();
+
+        let input = SignInput {
+            input_path: String::new(),
+            output_path: String::new(),
+            certificate_path: String::new(),
+            password: String::new(),
+            field_name: None,
+            appearance: None,
+            reason: None,
+            location: None,
+            contact_info: None,
+        };
+        sign_document(&bytes, &test_identity(), &input).unwrap().0
+    }
+
+    #[test]
+    fn test_annotating_keeps_signatures_valid() {
+        let path = std::env::temp_dir().join(format!("velin-{}.pdf", uuid::Uuid::new_v4()));
+        let signed = signed_document();
+        std::fs::write(&path, &signed).unwrap();
+
+        let file = SourceFile::load(&path).unwrap();
+        let mut document = file.document().unwrap().clone();
+        let note = entry("note", 1, None).annotation;
+        create::create_annotation(&mut document, &note, 792.0, None).unwrap();
+        save_document(&file, &document, &file.path).unwrap();
+
+        let saved = std::fs::read(&path).unwrap();
+        assert!(saved.starts_with(&signed));
+
+        let signatures = read_signatures(&saved).unwrap();
+        assert_eq!(signatures[0].status, SignatureStatus::Valid);
+        assert!(signatures[0].modified_after_signing);
+
+        let saved = Document::load_mem(&saved).unwrap();
+        let page_id = saved.get_pages()[&1];
+        // The signature widget and the note
+        assert_eq!(dictionary::page_annotations(&saved, page_id).len(), 2);
+
+        let _ = std::fs::remove_file(path);
+    }
 }
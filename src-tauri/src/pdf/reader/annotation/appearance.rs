@@ -0,0 +1,644 @@
+use lopdf::{dictionary, Dictionary, Object, Stream};
+
+use crate::pdf::reader::annotation::{LineEnding, LineEndings};
+
+/// Bezier control point factor used to approximate a quarter ellipse.
+const KAPPA: f32 = 0.552_284_8;
+
+/// Average advance of Helvetica-Bold capitals, in em.
+const BOLD_CAPS_WIDTH: f32 = 0.72;
+
+/// Average advance of Helvetica text, in em.
+const TEXT_WIDTH: f32 = 0.5;
+
+/// Height of Helvetica capitals, in em.
+const CAP_HEIGHT: f32 = 0.72;
+
+/// A point in PDF user space (bottom-left origin).
+pub type PdfPoint = (f32, f32);
+
+/// Stroke and fill settings shared by every appearance stream.
+pub struct AppearanceStyle {
+    pub stroke: (f32, f32, f32),
+    pub fill: Option<(f32, f32, f32)>,
+    pub width: f32,
+    pub opacity: f32,
+    pub dashes: Option<Vec<f32>>,
+    pub line_endings: Option<LineEndings>,
+}
+
+/// Builds the normal appearance (`/AP /N`) form XObject for an annotation.
+///
+/// The content is drawn directly in page coordinates, so the form's `/BBox` is
+/// the annotation rectangle and its `/Matrix` stays the identity.
+pub struct AppearanceBuilder {
+    content: String,
+    resources: Dictionary,
+    bbox: [f32; 4],
+}
+
+impl AppearanceBuilder {
+    pub fn new(bbox: [f32; 4], style: &AppearanceStyle, blend_multiply: bool) -> Self {
+        let mut builder = Self {
+            content: String::from("q\n"),
+            resources: Dictionary::new(),
+            bbox,
+        };
+
+        if style.opacity < 1.0 || blend_multiply {
+            let mut state = dictionary! {
+                "Type" => "ExtGState",
+                "CA" => style.opacity,
+                "ca" => style.opacity,
+            };
+            if blend_multiply {
+                state.set("BM", Object::Name(b"Multiply".to_vec()));
+            }
+
+            builder
+                .resources
+                .set("ExtGState", dictionary! { "GS0" => state });
+            builder.content.push_str("/GS0 gs\n");
+        }
+
+        let (r, g, b) = style.stroke;
+        builder.op(&format!("{} {} {} RG", num(r), num(g), num(b)));
+
+        let (r, g, b) = style.fill.unwrap_or(style.stroke);
+        builder.op(&format!("{} {} {} rg", num(r), num(g), num(b)));
+
+        builder.op(&format!("{} w", num(style.width)));
+
+        if let Some(dashes) = &style.dashes {
+            let dashes = dashes.iter().map(|d| num(*d)).collect::<Vec<_>>();
+            builder.op(&format!("[{}] 0 d", dashes.join(" ")));
+        }
+
+        builder
+    }
+
+    fn op(&mut self, operator: &str) {
+        self.content.push_str(operator);
+        self.content.push('\n');
+    }
+
+    fn path(&mut self, points: &[PdfPoint], close: bool) {
+        for (index, (x, y)) in points.iter().enumerate() {
+            let operator = if index == 0 { "m" } else { "l" };
+            self.op(&format!("{} {} {}", num(*x), num(*y), operator));
+        }
+        if close {
+            self.op("h");
+        }
+    }
+
+    pub fn rectangle(&mut self, rect: [f32; 4], inset: f32, filled: bool) {
+        let [left, bottom, right, top] = rect;
+        self.op(&format!(
+            "{} {} {} {} re",
+            num(left + inset),
+            num(bottom + inset),
+            num((right - left - inset * 2.0).max(0.0)),
+            num((top - bottom - inset * 2.0).max(0.0)),
+        ));
+        self.op(if filled { "B" } else { "S" });
+    }
+
+    pub fn ellipse(&mut self, rect: [f32; 4], inset: f32, filled: bool) {
+        let [left, bottom, right, top] = rect;
+        let cx = (left + right) / 2.0;
+        let cy = (bottom + top) / 2.0;
+        let rx = ((right - left) / 2.0 - inset).max(0.0);
+        let ry = ((top - bottom) / 2.0 - inset).max(0.0);
+        let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+
+        self.op(&format!("{} {} m", num(cx + rx), num(cy)));
+        self.curve((cx + rx, cy + ky), (cx + kx, cy + ry), (cx, cy + ry));
+        self.curve((cx - kx, cy + ry), (cx - rx, cy + ky), (cx - rx, cy));
+        self.curve((cx - rx, cy - ky), (cx - kx, cy - ry), (cx, cy - ry));
+        self.curve((cx + kx, cy - ry), (cx + rx, cy - ky), (cx + rx, cy));
+        self.op(if filled { "b" } else { "s" });
+    }
+
+    fn curve(&mut self, c1: PdfPoint, c2: PdfPoint, end: PdfPoint) {
+        self.op(&format!(
+            "{} {} {} {} {} {} c",
+            num(c1.0),
+            num(c1.1),
+            num(c2.0),
+            num(c2.1),
+            num(end.0),
+            num(end.1)
+        ));
+    }
+
+    /// Draws an open or closed polyline and the line endings of its first and last segment.
+    pub fn polyline(&mut self, points: &[PdfPoint], style: &AppearanceStyle, closed: bool) {
+        if points.len() < 2 {
+            return;
+        }
+
+        self.path(points, closed);
+        self.op(if closed && style.fill.is_some() {
+            "B"
+        } else {
+            "S"
+        });
+
+        if closed {
+            return;
+        }
+
+        if let Some(endings) = &style.line_endings {
+            let filled = style.fill.is_some();
+            let last = points.len() - 1;
+            self.line_ending(points[0], points[1], endings.start, style.width, filled);
+            self.line_ending(
+                points[last],
+                points[last - 1],
+                endings.end,
+                style.width,
+                filled,
+            );
+        }
+    }
+
+    /// Draws a line ending at `tip`, pointing away from `from`.
+    fn line_ending(
+        &mut self,
+        tip: PdfPoint,
+        from: PdfPoint,
+        ending: LineEnding,
+        width: f32,
+        filled: bool,
+    ) {
+        let (dx, dy) = (tip.0 - from.0, tip.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+
+        // Unit vector along the line towards the tip, and its normal.
+        let (ux, uy) = (dx / length, dy / length);
+        let (nx, ny) = (-uy, ux);
+        let size = (width * 3.0).max(6.0);
+        let at = |along: f32, across: f32| {
+            (
+                tip.0 + ux * along + nx * across,
+                tip.1 + uy * along + ny * across,
+            )
+        };
+        let paint = if filled { "b" } else { "s" };
+
+        match ending {
+            LineEnding::None => {}
+            LineEnding::OpenArrow | LineEnding::ClosedArrow => {
+                let wing = [at(-size, size / 2.0), tip, at(-size, -size / 2.0)];
+                if ending == LineEnding::OpenArrow {
+                    self.path(&wing, false);
+                    self.op("S");
+                } else {
+                    self.path(&wing, true);
+                    self.op(paint);
+                }
+            }
+            LineEnding::ROpenArrow | LineEnding::RClosedArrow => {
+                let wing = [at(size, size / 2.0), tip, at(size, -size / 2.0)];
+                if ending == LineEnding::ROpenArrow {
+                    self.path(&wing, false);
+                    self.op("S");
+                } else {
+                    self.path(&wing, true);
+                    self.op(paint);
+                }
+            }
+            LineEnding::Square => {
+                let half = size / 2.0;
+                let corners = [
+                    at(-half, -half),
+                    at(half, -half),
+                    at(half, half),
+                    at(-half, half),
+                ];
+                self.path(&corners, true);
+                self.op(paint);
+            }
+            LineEnding::Diamond => {
+                let half = size / 2.0;
+                let corners = [at(-half, 0.0), at(0.0, -half), at(half, 0.0), at(0.0, half)];
+                self.path(&corners, true);
+                self.op(paint);
+            }
+            LineEnding::Circle => {
+                let half = size / 2.0;
+                self.ellipse(
+                    [tip.0 - half, tip.1 - half, tip.0 + half, tip.1 + half],
+                    0.0,
+                    filled,
+                );
+            }
+            LineEnding::Butt => {
+                self.path(&[at(0.0, size / 2.0), at(0.0, -size / 2.0)], false);
+                self.op("S");
+            }
+            LineEnding::Slash => {
+                self.path(
+                    &[at(-size / 4.0, -size / 2.0), at(size / 4.0, size / 2.0)],
+                    false,
+                );
+                self.op("S");
+            }
+        }
+    }
+
+    /// Draws ink strokes as Catmull-Rom splines so the smoothed points join without corners.
+    pub fn ink(&mut self, strokes: &[Vec<PdfPoint>]) {
+        self.op("1 J");
+        self.op("1 j");
+
+        for stroke in strokes {
+            match stroke.len() {
+                0 => continue,
+                1 => {
+                    let (x, y) = stroke[0];
+                    self.op(&format!("{} {} m", num(x), num(y)));
+                    self.op(&format!("{} {} l", num(x), num(y)));
+                }
+                _ => {
+                    let (x, y) = stroke[0];
+                    self.op(&format!("{} {} m", num(x), num(y)));
+
+                    for i in 0..stroke.len() - 1 {
+                        let p0 = stroke[i.saturating_sub(1)];
+                        let p1 = stroke[i];
+                        let p2 = stroke[i + 1];
+                        let p3 = stroke[(i + 2).min(stroke.len() - 1)];
+
+                        let c1 = (p1.0 + (p2.0 - p0.0) / 6.0, p1.1 + (p2.1 - p0.1) / 6.0);
+                        let c2 = (p2.0 - (p3.0 - p1.0) / 6.0, p2.1 - (p3.1 - p1.1) / 6.0);
+                        self.curve(c1, c2, p2);
+                    }
+                }
+            }
+            self.op("S");
+        }
+    }
+
+    /// Fills each quad of a Highlight annotation.
+    pub fn highlight(&mut self, quads: &[[PdfPoint; 4]]) {
+        for [p1, p2, p3, p4] in quads {
+            self.path(&[*p3, *p4, *p2, *p1], true);
+            self.op("f");
+        }
+    }
+
+    /// Outlines each quad of a Redact annotation that has not been applied yet.
+    pub fn outline(&mut self, quads: &[[PdfPoint; 4]]) {
+        for [p1, p2, p3, p4] in quads {
+            self.path(&[*p3, *p4, *p2, *p1], true);
+            self.op("S");
+        }
+    }
+
+    /// Draws Underline, Strikeout or Squiggly marks under (or through) each quad.
+    pub fn text_decoration(&mut self, quads: &[[PdfPoint; 4]], position: f32, squiggly: bool) {
+        for [p1, p2, p3, p4] in quads {
+            let height = ((p1.0 - p3.0).powi(2) + (p1.1 - p3.1).powi(2)).sqrt();
+            let width = (height / 14.0).max(0.5);
+            let lerp =
+                |a: PdfPoint, b: PdfPoint, t: f32| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            let start = lerp(*p3, *p1, position);
+            let end = lerp(*p4, *p2, position);
+
+            self.op(&format!("{} w", num(width)));
+
+            if !squiggly {
+                self.path(&[start, end], false);
+                self.op("S");
+                continue;
+            }
+
+            let length = ((end.0 - start.0).powi(2) + (end.1 - start.1).powi(2)).sqrt();
+            let (nx, ny) = if height > 0.0 {
+                ((p1.0 - p3.0) / height, (p1.1 - p3.1) / height)
+            } else {
+                (0.0, 1.0)
+            };
+            let amplitude = (height / 12.0).max(1.0);
+            let steps = ((length / (amplitude * 2.0)).ceil() as usize).max(1);
+            let wave = (0..=steps)
+                .map(|step| {
+                    let (x, y) = lerp(start, end, step as f32 / steps as f32);
+                    let offset = if step % 2 == 0 { 0.0 } else { amplitude };
+                    (x + nx * offset, y + ny * offset)
+                })
+                .collect::<Vec<_>>();
+
+            self.path(&wave, false);
+            self.op("S");
+        }
+    }
+
+    /// Draws a sticky note icon filling the rectangle.
+    pub fn note_icon(&mut self, rect: [f32; 4]) {
+        let [left, bottom, right, top] = rect;
+        let (width, height) = (right - left, top - bottom);
+
+        self.op("0 0 0 RG");
+        self.op("1 w");
+        self.rectangle(rect, 0.5, true);
+
+        for line in 1..=3 {
+            let y = top - height * line as f32 / 4.0;
+            self.path(&[(left + width * 0.2, y), (right - width * 0.2, y)], false);
+            self.op("S");
+        }
+    }
+
+    /// Draws wrapped Helvetica text inside the rectangle, optionally framed by a border.
+    pub fn free_text(
+        &mut self,
+        rect: [f32; 4],
+        text: &str,
+        font_size: f32,
+        text_color: (f32, f32, f32),
+        border: bool,
+    ) {
+        let [left, bottom, right, top] = rect;
+
+        if border {
+            self.rectangle(rect, 0.5, false);
+        }
+
+        self.resources.set(
+            "Font",
+            dictionary! {
+                "Helv" => dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => "Helvetica",
+                    "Encoding" => "WinAnsiEncoding",
+                },
+            },
+        );
+
+        let padding = 2.0;
+        let leading = font_size * 1.2;
+        let max_chars = (((right - left - padding * 2.0) / (font_size * 0.5)) as usize).max(1);
+        let (r, g, b) = text_color;
+
+        self.op(&format!(
+            "{} {} {} {} re W n",
+            num(left),
+            num(bottom),
+            num(right - left),
+            num(top - bottom)
+        ));
+        self.op("BT");
+        self.op(&format!("/Helv {} Tf", num(font_size)));
+        self.op(&format!("{} {} {} rg", num(r), num(g), num(b)));
+        self.op(&format!(
+            "{} {} Td",
+            num(left + padding),
+            num(top - padding - font_size)
+        ));
+        self.op(&format!("{} TL", num(leading)));
+
+        for (index, line) in wrap_text(text, max_chars).iter().enumerate() {
+            if index > 0 {
+                self.op("T*");
+            }
+            self.op(&format!("<{}> Tj", win_ansi_hex(line)));
+        }
+
+        self.op("ET");
+    }
+
+    /// Draws a rubber stamp filling the rectangle: a double frame with the label
+    /// centred in bold capitals and an optional detail line (author and date) below.
+    pub fn stamp(
+        &mut self,
+        rect: [f32; 4],
+        label: &str,
+        detail: Option<&str>,
+        color: (f32, f32, f32),
+    ) {
+        let [left, bottom, right, top] = rect;
+        let (width, height) = (right - left, top - bottom);
+        let (r, g, b) = color;
+
+        self.op(&format!("{} {} {} RG", num(r), num(g), num(b)));
+        self.op(&format!("{} {} {} rg", num(r), num(g), num(b)));
+
+        let frame = (height * 0.06).clamp(1.0, 4.0);
+        self.op(&format!("{} w", num(frame)));
+        self.rectangle(rect, frame / 2.0, false);
+        self.op(&format!("{} w", num(frame / 3.0)));
+        self.rectangle(rect, frame * 2.0, false);
+
+        self.resources.set(
+            "Font",
+            dictionary! {
+                "HeBo" => dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => "Helvetica-Bold",
+                    "Encoding" => "WinAnsiEncoding",
+                },
+            },
+        );
+
+        let inner_width = (width - frame * 6.0).max(1.0);
+        let label = label.to_uppercase();
+        let label_height = if detail.is_some() { 0.45 } else { 0.6 };
+        let size = (height * label_height)
+            .min(inner_width / (label.chars().count().max(1) as f32 * BOLD_CAPS_WIDTH))
+            .max(1.0);
+        let baseline = match detail {
+            Some(_) => bottom + height * 0.42,
+            None => bottom + (height - size * CAP_HEIGHT) / 2.0,
+        };
+        self.centered_text(&label, size * BOLD_CAPS_WIDTH, size, (left + right) / 2.0, baseline);
+
+        if let Some(detail) = detail {
+            let size = (size * 0.35)
+                .min(inner_width / (detail.chars().count().max(1) as f32 * TEXT_WIDTH))
+                .max(1.0);
+            self.centered_text(
+                detail,
+                size * TEXT_WIDTH,
+                size,
+                (left + right) / 2.0,
+                bottom + height * 0.18,
+            );
+        }
+    }
+
+    /// Draws one line of bold text centred on `center`, estimating its width from
+    /// the average character advance.
+    fn centered_text(&mut self, text: &str, advance: f32, size: f32, center: f32, baseline: f32) {
+        let x = center - text.chars().count() as f32 * advance / 2.0;
+
+        self.op("BT");
+        self.op(&format!("/HeBo {} Tf", num(size)));
+        self.op(&format!("{} {} Td", num(x), num(baseline)));
+        self.op(&format!("<{}> Tj", win_ansi_hex(text)));
+        self.op("ET");
+    }
+
+    pub fn finish(mut self) -> Stream {
+        self.op("Q");
+
+        let [left, bottom, right, top] = self.bbox;
+
+        Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "FormType" => 1,
+                "BBox" => vec![left.into(), bottom.into(), right.into(), top.into()],
+                "Resources" => self.resources,
+            },
+            self.content.into_bytes(),
+        )
+    }
+}
+
+/// Removes jitter from a freehand stroke.
+///
+/// Points closer than half a point to their predecessor are dropped, then each
+/// interior point is averaged with its neighbours. The end points are kept so the
+/// stroke still starts and ends where the user drew it.
+pub fn smooth_stroke(points: &[PdfPoint]) -> Vec<PdfPoint> {
+    let mut filtered: Vec<PdfPoint> = Vec::with_capacity(points.len());
+    for point in points {
+        let keep = filtered.last().is_none_or(|last: &PdfPoint| {
+            let (dx, dy) = (point.0 - last.0, point.1 - last.1);
+            dx * dx + dy * dy >= 0.25
+        });
+        if keep {
+            filtered.push(*point);
+        }
+    }
+
+    if filtered.len() < 3 {
+        return filtered;
+    }
+
+    let last = filtered.len() - 1;
+    (0..filtered.len())
+        .map(|i| {
+            if i == 0 || i == last {
+                return filtered[i];
+            }
+            let (a, b, c) = (filtered[i - 1], filtered[i], filtered[i + 1]);
+            ((a.0 + b.0 * 2.0 + c.0) / 4.0, (a.1 + b.1 * 2.0 + c.1) / 4.0)
+        })
+        .collect()
+}
+
+/// Parses `#RRGGBB` into PDF colour components.
+pub fn parse_color(hex: &str) -> Result<(f32, f32, f32), String> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("Invalid annotation color: #{hex}"));
+    }
+
+    let channel = |range: std::ops::Range<usize>| {
+        u8::from_str_radix(&hex[range], 16)
+            .map(|value| value as f32 / 255.0)
+            .map_err(|_| format!("Invalid annotation color: #{hex}"))
+    };
+
+    Ok((channel(0..2)?, channel(2..4)?, channel(4..6)?))
+}
+
+pub fn wrap_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Encodes text as a hex string for the WinAnsi-encoded standard font. Characters
+/// outside Latin-1 are replaced by `?`.
+pub fn win_ansi_hex(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            let code = c as u32;
+            let byte = if code < 0x100 { code as u8 } else { b'?' };
+            format!("{:02X}", byte)
+        })
+        .collect()
+}
+
+/// Formats a number for a content stream without exponent notation.
+pub fn num(value: f32) -> String {
+    if value == value.trunc() {
+        format!("{}", value as i64)
+    } else {
+        let formatted = format!("{:.3}", value);
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smooth_stroke_keeps_end_points() {
+        let stroke = vec![
+            (0.0, 0.0),
+            (0.1, 0.1),
+            (10.0, 4.0),
+            (20.0, 0.0),
+            (30.0, 4.0),
+        ];
+        let smoothed = smooth_stroke(&stroke);
+
+        assert_eq!(smoothed.len(), 4);
+        assert_eq!(smoothed[0], (0.0, 0.0));
+        assert_eq!(smoothed[3], (30.0, 4.0));
+        assert_eq!(smoothed[1], (10.0, 2.0));
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#FF0000").unwrap(), (1.0, 0.0, 0.0));
+        assert!(parse_color("#F00").is_err());
+        assert!(parse_color("#GG0000").is_err());
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(
+            wrap_text("Please review this\nclause", 10),
+            vec!["Please", "review", "this", "clause"]
+        );
+        assert_eq!(wrap_text("a b c", 10), vec!["a b c"]);
+    }
+
+    #[test]
+    fn test_num_formatting() {
+        assert_eq!(num(2.0), "2");
+        assert_eq!(num(0.5), "0.5");
+        assert_eq!(num(1.23456), "1.235");
+    }
+}
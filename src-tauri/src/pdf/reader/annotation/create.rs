@@ -0,0 +1,993 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::pdf::reader::annotation::appearance::{
+    parse_color, smooth_stroke, AppearanceBuilder, AppearanceStyle, PdfPoint,
+};
+use crate::pdf::reader::annotation::dictionary::{find_annotation, page_annotations};
+use crate::pdf::reader::annotation::stamp::{
+    custom_stamp, stamp_appearance, stamp_detail, stamp_label, StampLibrary,
+};
+use crate::pdf::reader::annotation::{
+    Annotation, AnnotationGeometry, AnnotationPopup, AnnotationType, BorderStyle, LineEnding,
+    PdfRect, Point, ReviewStatus,
+};
+
+const FLAG_HIDDEN: i64 = 1 << 1;
+const FLAG_PRINT: i64 = 1 << 2;
+const FLAG_READ_ONLY: i64 = 1 << 6;
+const FLAG_LOCKED: i64 = 1 << 7;
+
+/// Default FreeText font size when the annotation does not specify one.
+const DEFAULT_FONT_SIZE: f32 = 12.0;
+
+/// Creates an annotation dictionary with a generated appearance stream and appends it
+/// to the page's `/Annots` array.
+///
+/// Coordinates in `annotation` use the top-left origin of the frontend and are flipped
+/// with `page_height`, mirroring how `get_annotations` reads them.
+///
+/// When `in_reply_to` is set the annotation is written as a reply (`/IRT`, `/RT /R`)
+/// to that annotation on the same page, and `popup` adds an associated Popup
+/// annotation. Nested `replies` and `review_states` are not written; they are
+/// created separately once the parent exists.
+///
+/// Stamps named after a custom stamp are drawn from `stamps`, and fail when it does
+/// not have them.
+pub fn create_annotation(
+    document: &mut Document,
+    annotation: &Annotation,
+    page_height: f32,
+    stamps: Option<&StampLibrary>,
+) -> Result<ObjectId, String> {
+    let page_id = page_id(document, annotation.page_index)?;
+    let dict = prepare_dictionary(document, page_id, annotation, page_height, stamps)?;
+
+    let annotation_id = document.add_object(dict);
+    append_to_page(document, page_id, annotation_id)?;
+
+    if let Some(popup) = &annotation.popup {
+        create_popup(document, page_id, annotation_id, popup, page_height)?;
+    }
+
+    Ok(annotation_id)
+}
+
+/// Rewrites an existing annotation from `annotation`, keeping its object id so
+/// replies and review states that point at it through `/IRT` stay attached.
+pub fn update_annotation(
+    document: &mut Document,
+    annotation_id: ObjectId,
+    annotation: &Annotation,
+    page_height: f32,
+    stamps: Option<&StampLibrary>,
+) -> Result<(), String> {
+    let page_id = page_id(document, annotation.page_index)?;
+    let mut dict = prepare_dictionary(document, page_id, annotation, page_height, stamps)?;
+
+    let existing_popup = document
+        .get_dictionary(annotation_id)
+        .and_then(|existing| existing.get(b"Popup"))
+        .and_then(|popup| popup.as_reference())
+        .ok();
+
+    match (existing_popup, &annotation.popup) {
+        (Some(popup_id), Some(popup)) => {
+            let mut popup = popup_dictionary(popup, annotation_id, page_height);
+            popup.set("P", Object::Reference(page_id));
+            document.objects.insert(popup_id, Object::Dictionary(popup));
+            dict.set("Popup", Object::Reference(popup_id));
+        }
+        (Some(popup_id), None) => dict.set("Popup", Object::Reference(popup_id)),
+        (None, _) => {}
+    }
+
+    document
+        .objects
+        .insert(annotation_id, Object::Dictionary(dict));
+
+    if let (None, Some(popup)) = (existing_popup, &annotation.popup) {
+        create_popup(document, page_id, annotation_id, popup, page_height)?;
+    }
+
+    Ok(())
+}
+
+/// Removes an annotation from its page together with its popup and the replies and
+/// review states that point at it, directly or through other replies.
+pub fn remove_annotation(
+    document: &mut Document,
+    page_index: u16,
+    annotation_id: &str,
+) -> Result<(), String> {
+    let page_id = page_id(document, page_index)?;
+    let target_id = find_annotation(document, page_id, page_index, annotation_id)
+        .ok_or_else(|| format!("Annotation {annotation_id} not found"))?;
+
+    let links = page_annotations(document, page_id)
+        .into_iter()
+        .flatten()
+        .filter_map(|annotation| {
+            let linked = annotation.in_reply_to().or_else(|| annotation.parent());
+            Some((annotation.object_id?, linked?))
+        })
+        .collect::<Vec<_>>();
+
+    let mut removed = vec![target_id];
+    let mut index = 0;
+    while index < removed.len() {
+        let current = removed[index];
+        for (object_id, linked) in &links {
+            if *linked == current && !removed.contains(object_id) {
+                removed.push(*object_id);
+            }
+        }
+        index += 1;
+    }
+
+    let keep = |object: &Object| {
+        object
+            .as_reference()
+            .map_or(true, |reference| !removed.contains(&reference))
+    };
+
+    let annots_ref = document
+        .get_dictionary(page_id)
+        .map_err(|e| e.to_string())?
+        .get(b"Annots")
+        .and_then(|annots| annots.as_reference())
+        .ok();
+
+    let annots = match annots_ref {
+        Some(annots_id) => document.get_object_mut(annots_id).ok(),
+        None => document
+            .get_dictionary_mut(page_id)
+            .map_err(|e| e.to_string())?
+            .get_mut(b"Annots")
+            .ok(),
+    };
+    if let Some(Object::Array(annots)) = annots {
+        annots.retain(keep);
+    }
+
+    for object_id in removed {
+        document.objects.remove(&object_id);
+    }
+
+    Ok(())
+}
+
+/// Builds the dictionary for `annotation`, adds its appearance stream and resolves
+/// the reply target on the page.
+fn prepare_dictionary(
+    document: &mut Document,
+    page_id: ObjectId,
+    annotation: &Annotation,
+    page_height: f32,
+    stamps: Option<&StampLibrary>,
+) -> Result<Dictionary, String> {
+    let (mut dict, mut appearance) = build_dictionary(annotation, page_height)?;
+
+    if let Some((stamp, path)) = custom_stamp(annotation, stamps)? {
+        let rect = to_pdf_rect(&annotation.rect, page_height);
+        appearance = stamp_appearance(document, rect, &stamp, &path)?;
+    }
+
+    if let Some(target) = &annotation.in_reply_to {
+        let target_id = find_annotation(document, page_id, annotation.page_index, target)
+            .ok_or_else(|| format!("Annotation {target} not found"))?;
+        dict.set("IRT", Object::Reference(target_id));
+        dict.set("RT", "R");
+    }
+
+    let appearance_id = document.add_object(appearance);
+    dict.set("AP", dictionary! { "N" => appearance_id });
+    dict.set("P", Object::Reference(page_id));
+
+    Ok(dict)
+}
+
+/// Sets a review state on an annotation by adding a hidden state annotation that
+/// replies to it, the way Acrobat records Accepted/Rejected/Completed reviews.
+pub fn create_review_state(
+    document: &mut Document,
+    page_index: u16,
+    annotation_id: &str,
+    status: &ReviewStatus,
+) -> Result<ObjectId, String> {
+    let page_id = page_id(document, page_index)?;
+    let target_id = find_annotation(document, page_id, page_index, annotation_id)
+        .ok_or_else(|| format!("Annotation {annotation_id} not found"))?;
+
+    let rect = document
+        .get_dictionary(target_id)
+        .and_then(|target| target.get(b"Rect"))
+        .cloned()
+        .map_err(|e| format!("Failed to read annotation rect: {e}"))?;
+
+    let mut dict = review_state_dictionary(rect, target_id, status);
+    dict.set("P", Object::Reference(page_id));
+
+    let state_id = document.add_object(dict);
+    append_to_page(document, page_id, state_id)?;
+
+    Ok(state_id)
+}
+
+/// Adds a Popup annotation for `parent_id` and links the two (`/Popup`, `/Parent`).
+fn create_popup(
+    document: &mut Document,
+    page_id: ObjectId,
+    parent_id: ObjectId,
+    popup: &AnnotationPopup,
+    page_height: f32,
+) -> Result<ObjectId, String> {
+    let mut dict = popup_dictionary(popup, parent_id, page_height);
+    dict.set("P", Object::Reference(page_id));
+
+    let popup_id = document.add_object(dict);
+    append_to_page(document, page_id, popup_id)?;
+
+    document
+        .get_dictionary_mut(parent_id)
+        .map_err(|e| e.to_string())?
+        .set("Popup", Object::Reference(popup_id));
+
+    Ok(popup_id)
+}
+
+/// Builds the hidden Text annotation that records `status` on `target_id`.
+pub fn review_state_dictionary(
+    rect: Object,
+    target_id: ObjectId,
+    status: &ReviewStatus,
+) -> Dictionary {
+    let date = status.date.clone().unwrap_or_else(pdf_date_now);
+    let author = status.author.clone().unwrap_or_default();
+    let contents = if author.is_empty() {
+        status.state.name().to_string()
+    } else {
+        format!("{} set by {author}", status.state.name())
+    };
+
+    let mut dict = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Text",
+        "Rect" => rect,
+        "F" => FLAG_HIDDEN | FLAG_PRINT,
+        "IRT" => Object::Reference(target_id),
+        "State" => status.state.name(),
+        "StateModel" => status.state.model(),
+        "Name" => "Comment",
+        "Open" => false,
+        "NM" => Object::string_literal(uuid::Uuid::new_v4().to_string()),
+        "M" => Object::string_literal(date.clone()),
+        "CreationDate" => Object::string_literal(date),
+        "Contents" => lopdf::text_string(&contents),
+    };
+    if !author.is_empty() {
+        dict.set("T", lopdf::text_string(&author));
+    }
+
+    dict
+}
+
+/// Builds a Popup annotation dictionary for `parent_id`.
+pub fn popup_dictionary(
+    popup: &AnnotationPopup,
+    parent_id: ObjectId,
+    page_height: f32,
+) -> Dictionary {
+    dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Popup",
+        "Rect" => rect_array(to_pdf_rect(&popup.rect, page_height)),
+        "Parent" => Object::Reference(parent_id),
+        "Open" => popup.open,
+    }
+}
+
+pub fn page_id(document: &Document, page_index: u16) -> Result<ObjectId, String> {
+    document
+        .get_pages()
+        .get(&(page_index as u32 + 1))
+        .copied()
+        .ok_or_else(|| format!("Page {} not found", page_index + 1))
+}
+
+/// Builds the annotation dictionary and its normal appearance stream without adding
+/// either to a document. The caller links them through `/AP /N`.
+pub fn build_dictionary(
+    annotation: &Annotation,
+    page_height: f32,
+) -> Result<(Dictionary, Stream), String> {
+    let subtype = subtype_name(&annotation.subtype).ok_or_else(|| {
+        format!(
+            "Creating {:?} annotations is not supported",
+            annotation.subtype
+        )
+    })?;
+
+    let appearance = &annotation.appearance;
+    let stroke = parse_color(&appearance.color)?;
+    let fill = appearance
+        .interior_color
+        .as_deref()
+        .map(parse_color)
+        .transpose()?;
+    let width = appearance.border_width.unwrap_or(1.0).max(0.0);
+    let opacity = appearance.opacity.clamp(0.0, 1.0);
+
+    let style = AppearanceStyle {
+        stroke,
+        fill,
+        width,
+        opacity,
+        dashes: match appearance.border_style {
+            Some(BorderStyle::Dashed) => {
+                Some(appearance.dash_pattern.clone().unwrap_or_else(|| vec![3.0]))
+            }
+            _ => None,
+        },
+        line_endings: appearance.line_endings.clone(),
+    };
+
+    let mut dict = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => subtype,
+        "F" => flags(annotation),
+        "C" => color_array(stroke),
+        "CA" => opacity,
+    };
+
+    let name = if annotation.id.is_empty() {
+        uuid::Uuid::new_v4().to_string()
+    } else {
+        annotation.id.clone()
+    };
+    dict.set("NM", Object::string_literal(name));
+
+    // Dates supplied with the annotation (e.g. from an import) are kept so merges
+    // can compare them; new annotations are stamped with the current time.
+    let metadata = &annotation.metadata;
+    let now = pdf_date_now();
+    let modified = metadata
+        .modified_date
+        .clone()
+        .unwrap_or_else(|| now.clone());
+    let created = metadata.creation_date.clone().unwrap_or(now);
+    dict.set("M", Object::string_literal(modified));
+    dict.set("CreationDate", Object::string_literal(created.clone()));
+
+    if let Some(author) = &annotation.metadata.author {
+        dict.set("T", lopdf::text_string(author));
+    }
+    if let Some(contents) = &annotation.metadata.contents {
+        dict.set("Contents", lopdf::text_string(contents));
+    }
+    if let Some(fill) = fill {
+        dict.set("IC", color_array(fill));
+    }
+
+    // Expand the bounding box so strokes and line endings are not clipped.
+    let margin = width / 2.0
+        + if appearance.line_endings.is_some() {
+            (width * 3.0).max(6.0)
+        } else {
+            0.0
+        };
+
+    let rect;
+    let mut builder;
+
+    match &annotation.subtype {
+        AnnotationType::Text => {
+            rect = to_pdf_rect(&annotation.rect, page_height);
+            builder = AppearanceBuilder::new(rect, &style, false);
+            builder.note_icon(rect);
+
+            let icon = appearance.icon.clone().unwrap_or_else(|| "Comment".into());
+            dict.set("Name", Object::Name(icon.into_bytes()));
+            dict.set("Open", false);
+        }
+        AnnotationType::Stamp => {
+            rect = to_pdf_rect(&annotation.rect, page_height);
+            let name = appearance.icon.clone().unwrap_or_else(|| "Draft".into());
+            let (label, dynamic) = stamp_label(&name);
+            let detail = dynamic.then(|| stamp_detail(metadata.author.as_deref(), &created));
+
+            builder = AppearanceBuilder::new(rect, &style, false);
+            builder.stamp(rect, &label, detail.as_deref(), stroke);
+
+            dict.set("Name", Object::Name(name.into_bytes()));
+        }
+        AnnotationType::FreeText => {
+            rect = to_pdf_rect(&annotation.rect, page_height);
+            let font_size = appearance.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+            let text = annotation.metadata.contents.as_deref().unwrap_or_default();
+
+            builder = AppearanceBuilder::new(rect, &style, false);
+            builder.free_text(
+                rect,
+                text,
+                font_size,
+                stroke,
+                appearance.border_width.is_some_and(|w| w > 0.0),
+            );
+
+            let (r, g, b) = stroke;
+            dict.set(
+                "DA",
+                Object::string_literal(format!("{r} {g} {b} rg /Helv {font_size} Tf")),
+            );
+        }
+        AnnotationType::Square | AnnotationType::Circle => {
+            rect = to_pdf_rect(&annotation.rect, page_height);
+            builder = AppearanceBuilder::new(rect, &style, false);
+
+            if annotation.subtype == AnnotationType::Square {
+                builder.rectangle(rect, width / 2.0, fill.is_some());
+            } else {
+                builder.ellipse(rect, width / 2.0, fill.is_some());
+            }
+        }
+        AnnotationType::Line => {
+            let AnnotationGeometry::Line { start, end } = &annotation.geometry else {
+                return Err("Line annotations require line geometry".into());
+            };
+            let points = [
+                to_pdf_point(start, page_height),
+                to_pdf_point(end, page_height),
+            ];
+
+            rect = bounds(&points, margin);
+            builder = AppearanceBuilder::new(rect, &style, false);
+            builder.polyline(&points, &style, false);
+
+            dict.set("L", flatten(&points));
+        }
+        AnnotationType::Polygon | AnnotationType::Polyline => {
+            let AnnotationGeometry::Points(points) = &annotation.geometry else {
+                return Err("Polygon and polyline annotations require point geometry".into());
+            };
+            let points = points
+                .iter()
+                .map(|point| to_pdf_point(point, page_height))
+                .collect::<Vec<_>>();
+            if points.len() < 2 {
+                return Err("Polygon and polyline annotations need at least two points".into());
+            }
+
+            let closed = annotation.subtype == AnnotationType::Polygon;
+            rect = bounds(&points, margin);
+            builder = AppearanceBuilder::new(rect, &style, false);
+            builder.polyline(&points, &style, closed);
+
+            dict.set("Vertices", flatten(&points));
+        }
+        AnnotationType::Ink => {
+            let AnnotationGeometry::InkPaths(paths) = &annotation.geometry else {
+                return Err("Ink annotations require ink path geometry".into());
+            };
+            let strokes = paths
+                .iter()
+                .map(|path| {
+                    let points = path
+                        .iter()
+                        .map(|point| to_pdf_point(point, page_height))
+                        .collect::<Vec<_>>();
+                    smooth_stroke(&points)
+                })
+                .filter(|stroke| !stroke.is_empty())
+                .collect::<Vec<_>>();
+            if strokes.is_empty() {
+                return Err("Ink annotations need at least one stroke".into());
+            }
+
+            rect = bounds(&strokes.concat(), margin);
+            builder = AppearanceBuilder::new(rect, &style, false);
+            builder.ink(&strokes);
+
+            dict.set(
+                "InkList",
+                strokes
+                    .iter()
+                    .map(|stroke| Object::Array(flatten(stroke)))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        AnnotationType::Highlight
+        | AnnotationType::Underline
+        | AnnotationType::Squiggly
+        | AnnotationType::Strikeout => {
+            let AnnotationGeometry::QuadPoints(quads) = &annotation.geometry else {
+                return Err("Text markup annotations require quad point geometry".into());
+            };
+            let quads = quads
+                .iter()
+                .map(|quad| {
+                    [
+                        to_pdf_point(&quad.p1, page_height),
+                        to_pdf_point(&quad.p2, page_height),
+                        to_pdf_point(&quad.p3, page_height),
+                        to_pdf_point(&quad.p4, page_height),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            if quads.is_empty() {
+                return Err("Text markup annotations need at least one quad".into());
+            }
+
+            rect = bounds(&quads.concat(), 1.0);
+            let highlight = annotation.subtype == AnnotationType::Highlight;
+            builder = AppearanceBuilder::new(rect, &style, highlight);
+
+            match annotation.subtype {
+                AnnotationType::Highlight => builder.highlight(&quads),
+                AnnotationType::Underline => builder.text_decoration(&quads, 0.05, false),
+                AnnotationType::Strikeout => builder.text_decoration(&quads, 0.5, false),
+                _ => builder.text_decoration(&quads, 0.0, true),
+            }
+
+            dict.set("QuadPoints", flatten(&quads.concat()));
+        }
+        AnnotationType::Redacted => {
+            // Marked areas are outlined until the redaction is applied; `/IC` is the
+            // colour of the box that replaces the removed content.
+            let quads = match &annotation.geometry {
+                AnnotationGeometry::QuadPoints(quads) => quads
+                    .iter()
+                    .map(|quad| {
+                        [
+                            to_pdf_point(&quad.p1, page_height),
+                            to_pdf_point(&quad.p2, page_height),
+                            to_pdf_point(&quad.p3, page_height),
+                            to_pdf_point(&quad.p4, page_height),
+                        ]
+                    })
+                    .collect::<Vec<_>>(),
+                _ => Vec::new(),
+            };
+
+            if quads.is_empty() {
+                rect = to_pdf_rect(&annotation.rect, page_height);
+                builder = AppearanceBuilder::new(rect, &style, false);
+                builder.rectangle(rect, width / 2.0, false);
+            } else {
+                rect = bounds(&quads.concat(), width / 2.0);
+                builder = AppearanceBuilder::new(rect, &style, false);
+                builder.outline(&quads);
+                dict.set("QuadPoints", flatten(&quads.concat()));
+            }
+
+            if fill.is_none() {
+                dict.set("IC", color_array((0.0, 0.0, 0.0)));
+            }
+        }
+        _ => unreachable!("subtype_name() filters unsupported subtypes"),
+    }
+
+    dict.set("Rect", rect_array(rect));
+
+    if uses_border_style(&annotation.subtype) {
+        let mut border = dictionary! {
+            "W" => width,
+            "S" => border_style_name(appearance.border_style.unwrap_or_default()),
+        };
+        if let Some(dashes) = &style.dashes {
+            border.set(
+                "D",
+                dashes.iter().map(|d| Object::Real(*d)).collect::<Vec<_>>(),
+            );
+        }
+        dict.set("BS", border);
+    }
+
+    if let Some(endings) = &appearance.line_endings {
+        if matches!(
+            annotation.subtype,
+            AnnotationType::Line | AnnotationType::Polyline
+        ) {
+            dict.set(
+                "LE",
+                vec![
+                    Object::Name(line_ending_name(endings.start).to_vec()),
+                    Object::Name(line_ending_name(endings.end).to_vec()),
+                ],
+            );
+        }
+    }
+
+    Ok((dict, builder.finish()))
+}
+
+/// Whether the subtype is drawn with a border style dictionary (`/BS`).
+fn uses_border_style(subtype: &AnnotationType) -> bool {
+    matches!(
+        subtype,
+        AnnotationType::FreeText
+            | AnnotationType::Square
+            | AnnotationType::Circle
+            | AnnotationType::Line
+            | AnnotationType::Polygon
+            | AnnotationType::Polyline
+            | AnnotationType::Ink
+    )
+}
+
+pub fn append_to_page(
+    document: &mut Document,
+    page_id: ObjectId,
+    annotation_id: ObjectId,
+) -> Result<(), String> {
+    // `/Annots` may be an indirect array shared by reference; update it in place.
+    let annots_ref = document
+        .get_dictionary(page_id)
+        .map_err(|e| e.to_string())?
+        .get(b"Annots")
+        .and_then(|annots| annots.as_reference())
+        .ok();
+
+    if let Some(annots_id) = annots_ref {
+        if let Ok(Object::Array(annots)) = document.get_object_mut(annots_id) {
+            annots.push(Object::Reference(annotation_id));
+            return Ok(());
+        }
+    }
+
+    let page = document
+        .get_dictionary_mut(page_id)
+        .map_err(|e| e.to_string())?;
+
+    match page.get_mut(b"Annots") {
+        Ok(Object::Array(annots)) => annots.push(Object::Reference(annotation_id)),
+        _ => page.set("Annots", vec![Object::Reference(annotation_id)]),
+    }
+
+    Ok(())
+}
+
+fn subtype_name(subtype: &AnnotationType) -> Option<&'static str> {
+    match subtype {
+        AnnotationType::Text => Some("Text"),
+        AnnotationType::FreeText => Some("FreeText"),
+        AnnotationType::Square => Some("Square"),
+        AnnotationType::Circle => Some("Circle"),
+        AnnotationType::Line => Some("Line"),
+        AnnotationType::Polygon => Some("Polygon"),
+        AnnotationType::Polyline => Some("PolyLine"),
+        AnnotationType::Ink => Some("Ink"),
+        AnnotationType::Highlight => Some("Highlight"),
+        AnnotationType::Underline => Some("Underline"),
+        AnnotationType::Squiggly => Some("Squiggly"),
+        AnnotationType::Strikeout => Some("StrikeOut"),
+        AnnotationType::Stamp => Some("Stamp"),
+        AnnotationType::Redacted => Some("Redact"),
+        _ => None,
+    }
+}
+
+pub fn line_ending_name(ending: LineEnding) -> &'static [u8] {
+    match ending {
+        LineEnding::None => b"None",
+        LineEnding::Square => b"Square",
+        LineEnding::Circle => b"Circle",
+        LineEnding::Diamond => b"Diamond",
+        LineEnding::OpenArrow => b"OpenArrow",
+        LineEnding::ClosedArrow => b"ClosedArrow",
+        LineEnding::Butt => b"Butt",
+        LineEnding::ROpenArrow => b"ROpenArrow",
+        LineEnding::RClosedArrow => b"RClosedArrow",
+        LineEnding::Slash => b"Slash",
+    }
+}
+
+fn border_style_name(style: BorderStyle) -> &'static str {
+    match style {
+        BorderStyle::Solid => "S",
+        BorderStyle::Dashed => "D",
+        BorderStyle::Beveled => "B",
+        BorderStyle::Inset => "I",
+        BorderStyle::Underline => "U",
+    }
+}
+
+fn flags(annotation: &Annotation) -> i64 {
+    let mut flags = 0;
+    if annotation.flags.hidden {
+        flags |= FLAG_HIDDEN;
+    }
+    if annotation.flags.printable {
+        flags |= FLAG_PRINT;
+    }
+    if annotation.flags.read_only {
+        flags |= FLAG_READ_ONLY;
+    }
+    if annotation.flags.locked {
+        flags |= FLAG_LOCKED;
+    }
+    flags
+}
+
+fn color_array((r, g, b): (f32, f32, f32)) -> Vec<Object> {
+    vec![r.into(), g.into(), b.into()]
+}
+
+fn rect_array([left, bottom, right, top]: [f32; 4]) -> Vec<Object> {
+    vec![left.into(), bottom.into(), right.into(), top.into()]
+}
+
+fn flatten(points: &[PdfPoint]) -> Vec<Object> {
+    points
+        .iter()
+        .flat_map(|(x, y)| [Object::Real(*x), Object::Real(*y)])
+        .collect()
+}
+
+fn to_pdf_point(point: &Point, page_height: f32) -> PdfPoint {
+    (point.x, page_height - point.y)
+}
+
+fn to_pdf_rect(rect: &PdfRect, page_height: f32) -> [f32; 4] {
+    let (top, bottom) = (page_height - rect.top, page_height - rect.bottom);
+    [
+        rect.left.min(rect.right),
+        top.min(bottom),
+        rect.left.max(rect.right),
+        top.max(bottom),
+    ]
+}
+
+fn bounds(points: &[PdfPoint], margin: f32) -> [f32; 4] {
+    let mut rect = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
+    for (x, y) in points {
+        rect[0] = rect[0].min(*x);
+        rect[1] = rect[1].min(*y);
+        rect[2] = rect[2].max(*x);
+        rect[3] = rect[3].max(*y);
+    }
+    [
+        rect[0] - margin,
+        rect[1] - margin,
+        rect[2] + margin,
+        rect[3] + margin,
+    ]
+}
+
+/// Current UTC time as a PDF date string (`D:YYYYMMDDHHmmSSZ`).
+pub fn pdf_date_now() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+
+    let (days, rest) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+
+    // Civil-from-days conversion (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3_600,
+        rest % 3_600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::{
+        AnnotationAppearance, AnnotationFlags, AnnotationMetadata, LineEndings, ReviewState,
+    };
+
+    fn annotation(subtype: AnnotationType, geometry: AnnotationGeometry) -> Annotation {
+        Annotation {
+            id: "test-annotation".into(),
+            page_index: 0,
+            subtype,
+            rect: PdfRect {
+                left: 100.0,
+                top: 100.0,
+                right: 200.0,
+                bottom: 150.0,
+            },
+            geometry,
+            appearance: AnnotationAppearance {
+                color: "#0000FF".into(),
+                interior_color: None,
+                opacity: 1.0,
+                border_width: Some(2.0),
+                border_style: None,
+                dash_pattern: None,
+                line_endings: None,
+                font_size: None,
+                icon: None,
+            },
+            metadata: AnnotationMetadata {
+                author: Some("Reviewer".into()),
+                contents: Some("Check this".into()),
+                rich_contents: None,
+                creation_date: None,
+                modified_date: None,
+            },
+            flags: AnnotationFlags {
+                printable: true,
+                ..Default::default()
+            },
+            in_reply_to: None,
+            replies: Vec::new(),
+            review_states: Vec::new(),
+            popup: None,
+        }
+    }
+
+    fn single_page_document() -> Document {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 600.into(), 800.into()],
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    #[test]
+    fn test_create_line_round_trips_through_reader() {
+        let mut line = annotation(
+            AnnotationType::Line,
+            AnnotationGeometry::Line {
+                start: Point { x: 10.0, y: 20.0 },
+                end: Point { x: 110.0, y: 20.0 },
+            },
+        );
+        line.appearance.line_endings = Some(LineEndings {
+            start: LineEnding::None,
+            end: LineEnding::OpenArrow,
+        });
+
+        let mut document = single_page_document();
+        create_annotation(&mut document, &line, 800.0, None).unwrap();
+
+        let page_id = document.get_pages()[&1];
+        let annotations = page_annotations(&document, page_id);
+        let created = annotations[0].unwrap();
+
+        assert_eq!(
+            created.line(800.0),
+            Some((Point { x: 10.0, y: 20.0 }, Point { x: 110.0, y: 20.0 }))
+        );
+        assert_eq!(created.line_endings(), line.appearance.line_endings);
+        assert_eq!(created.color(), Some("#0000FF".to_string()));
+        assert_eq!(created.border_width(), Some(2.0));
+        assert!(created.dict.get(b"AP").is_ok());
+    }
+
+    #[test]
+    fn test_create_reply_popup_and_review_state() {
+        let mut document = single_page_document();
+        let mut note = annotation(
+            AnnotationType::Text,
+            AnnotationGeometry::Rect(PdfRect {
+                left: 100.0,
+                top: 100.0,
+                right: 120.0,
+                bottom: 120.0,
+            }),
+        );
+        note.popup = Some(AnnotationPopup {
+            rect: PdfRect {
+                left: 130.0,
+                top: 100.0,
+                right: 330.0,
+                bottom: 200.0,
+            },
+            open: true,
+        });
+        let note_id = create_annotation(&mut document, &note, 800.0, None).unwrap();
+
+        let mut reply = annotation(AnnotationType::Text, note.geometry.clone());
+        reply.id = "reply".into();
+        reply.in_reply_to = Some(note.id.clone());
+        create_annotation(&mut document, &reply, 800.0, None).unwrap();
+
+        let status = ReviewStatus {
+            state: ReviewState::Accepted,
+            author: Some("Reviewer".into()),
+            date: None,
+        };
+        create_review_state(&mut document, 0, "reply", &status).unwrap();
+
+        let page_id = document.get_pages()[&1];
+        let annotations = page_annotations(&document, page_id);
+        assert_eq!(annotations.len(), 4);
+
+        let popup = annotations[0].unwrap().popup().unwrap();
+        assert_eq!(popup.parent(), Some(note_id));
+        assert_eq!(
+            popup.rect(800.0),
+            note.popup.as_ref().map(|p| p.rect.clone())
+        );
+
+        let reply = annotations[2].unwrap();
+        assert_eq!(reply.in_reply_to(), Some(note_id));
+
+        let state = annotations[3].unwrap();
+        assert_eq!(state.in_reply_to(), reply.object_id);
+        assert_eq!(state.review_state(), Some(ReviewState::Accepted));
+        assert!(create_review_state(&mut document, 0, "missing", &status).is_err());
+
+        remove_annotation(&mut document, 0, &note.id).unwrap();
+        assert!(page_annotations(&document, page_id).is_empty());
+        assert!(document.get_object(note_id).is_err());
+        assert!(remove_annotation(&mut document, 0, &note.id).is_err());
+    }
+
+    #[test]
+    fn test_create_ink_smooths_strokes() {
+        let ink = annotation(
+            AnnotationType::Ink,
+            AnnotationGeometry::InkPaths(vec![vec![
+                Point { x: 0.0, y: 0.0 },
+                Point { x: 0.1, y: 0.1 },
+                Point { x: 10.0, y: 10.0 },
+                Point { x: 20.0, y: 0.0 },
+            ]]),
+        );
+
+        let (dict, _) = build_dictionary(&ink, 800.0).unwrap();
+        let strokes = dict.get(b"InkList").unwrap().as_array().unwrap();
+        let first = strokes[0].as_array().unwrap();
+
+        // The jittery second point is dropped: three points, six numbers.
+        assert_eq!(first.len(), 6);
+    }
+
+    #[test]
+    fn test_create_rejects_mismatched_geometry() {
+        let line = annotation(
+            AnnotationType::Line,
+            AnnotationGeometry::Points(vec![Point { x: 0.0, y: 0.0 }]),
+        );
+        assert!(build_dictionary(&line, 800.0).is_err());
+
+        let widget = annotation(
+            AnnotationType::Widget,
+            AnnotationGeometry::Rect(line.rect.clone()),
+        );
+        assert!(build_dictionary(&widget, 800.0).is_err());
+    }
+
+    #[test]
+    fn test_pdf_date_format() {
+        let date = pdf_date_now();
+        assert!(date.starts_with("D:20"));
+        assert_eq!(date.len(), 17);
+    }
+}
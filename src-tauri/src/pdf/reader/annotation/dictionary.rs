@@ -0,0 +1,561 @@
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::pdf::reader::annotation::{
+    BorderStyle, LineEnding, LineEndings, PdfRect, Point, Quad, ReviewState,
+};
+
+/// Read-only view of an annotation dictionary.
+///
+/// pdfium-render does not expose line coordinates, vertices, ink lists or line
+/// endings, so these are read from the raw dictionary through lopdf instead.
+/// All returned points are converted to the top-left origin used by the frontend.
+#[derive(Clone, Copy)]
+pub struct AnnotationDictionary<'a> {
+    pub document: &'a Document,
+    pub dict: &'a Dictionary,
+
+    /// Object id of the dictionary, `None` when it is stored inline in `/Annots`
+    pub object_id: Option<ObjectId>,
+}
+
+/// Returns the dictionaries of the page's `/Annots` array in array order.
+///
+/// The order matches the annotation indices used by pdfium, so entries that are
+/// not dictionaries are kept as `None` instead of being skipped.
+pub fn page_annotations(
+    document: &Document,
+    page_id: ObjectId,
+) -> Vec<Option<AnnotationDictionary<'_>>> {
+    let annots = document
+        .get_dictionary(page_id)
+        .and_then(|page| page.get_deref(b"Annots", document))
+        .and_then(|annots| annots.as_array());
+
+    let Ok(annots) = annots else {
+        return Vec::new();
+    };
+
+    annots
+        .iter()
+        .map(|object| {
+            let (object_id, object) = document.dereference(object).ok()?;
+            let dict = object.as_dict().ok()?;
+
+            Some(AnnotationDictionary {
+                document,
+                dict,
+                object_id,
+            })
+        })
+        .collect()
+}
+
+/// Finds the annotation with the given id on a page.
+///
+/// Ids are the annotation name (`/NM`) when present; otherwise the positional
+/// `{prefix}-{page}-{index}` id assigned by `get_annotations`.
+pub fn find_annotation(
+    document: &Document,
+    page_id: ObjectId,
+    page_index: u16,
+    annotation_id: &str,
+) -> Option<ObjectId> {
+    let annotations = page_annotations(document, page_id);
+
+    let by_name = annotations
+        .iter()
+        .flatten()
+        .find(|annotation| annotation.name().as_deref() == Some(annotation_id));
+
+    let annotation = by_name.or_else(|| {
+        let mut parts = annotation_id.rsplitn(3, '-');
+        let index = parts.next()?.parse::<usize>().ok()?;
+        let page = parts.next()?.parse::<u16>().ok()?;
+        parts.next()?;
+
+        (page == page_index)
+            .then(|| annotations.get(index))
+            .flatten()?
+            .as_ref()
+            .filter(|annotation| annotation.name().is_none())
+    })?;
+
+    annotation.object_id
+}
+
+impl<'a> AnnotationDictionary<'a> {
+    fn get(&self, key: &[u8]) -> Option<&'a Object> {
+        self.dict.get_deref(key, self.document).ok()
+    }
+
+    fn numbers(&self, key: &[u8]) -> Option<Vec<f32>> {
+        let array = self.get(key)?.as_array().ok()?;
+        Some(numbers(self.document, array))
+    }
+
+    /// Start and end point of a Line annotation (`/L`).
+    pub fn line(&self, page_height: f32) -> Option<(Point, Point)> {
+        let values = self.numbers(b"L")?;
+        if values.len() < 4 {
+            return None;
+        }
+
+        Some((
+            to_point(values[0], values[1], page_height),
+            to_point(values[2], values[3], page_height),
+        ))
+    }
+
+    /// Vertices of a Polygon or Polyline annotation (`/Vertices`).
+    pub fn vertices(&self, page_height: f32) -> Option<Vec<Point>> {
+        let values = self.numbers(b"Vertices")?;
+        let points = to_points(&values, page_height);
+        (!points.is_empty()).then_some(points)
+    }
+
+    /// Quads of a Redact annotation (`/QuadPoints`), which pdfium does not expose.
+    pub fn quads(&self, page_height: f32) -> Option<Vec<Quad>> {
+        let values = self.numbers(b"QuadPoints")?;
+        let quads = to_points(&values, page_height)
+            .chunks_exact(4)
+            .map(|q| Quad {
+                p1: q[0].clone(),
+                p2: q[1].clone(),
+                p3: q[2].clone(),
+                p4: q[3].clone(),
+            })
+            .collect::<Vec<_>>();
+        (!quads.is_empty()).then_some(quads)
+    }
+
+    /// Strokes of an Ink annotation (`/InkList`).
+    pub fn ink_paths(&self, page_height: f32) -> Option<Vec<Vec<Point>>> {
+        let strokes = self.get(b"InkList")?.as_array().ok()?;
+
+        let paths = strokes
+            .iter()
+            .filter_map(|stroke| {
+                let (_, stroke) = self.document.dereference(stroke).ok()?;
+                let values = numbers(self.document, stroke.as_array().ok()?);
+                let points = to_points(&values, page_height);
+                (!points.is_empty()).then_some(points)
+            })
+            .collect::<Vec<_>>();
+
+        (!paths.is_empty()).then_some(paths)
+    }
+
+    /// Line endings (`/LE`). FreeText callouts store a single name that applies to
+    /// the start of the callout line.
+    pub fn line_endings(&self) -> Option<LineEndings> {
+        match self.get(b"LE")? {
+            Object::Array(names) => {
+                let ending = |index: usize| {
+                    names
+                        .get(index)
+                        .and_then(|name| name.as_name().ok())
+                        .map(LineEnding::from_name)
+                        .unwrap_or_default()
+                };
+
+                Some(LineEndings {
+                    start: ending(0),
+                    end: ending(1),
+                })
+            }
+            Object::Name(name) => Some(LineEndings {
+                start: LineEnding::from_name(name),
+                end: LineEnding::None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Border width from the border style dictionary (`/BS /W`), falling back to the
+    /// legacy `/Border` array.
+    pub fn border_width(&self) -> Option<f32> {
+        if let Some(Object::Dictionary(style)) = self.get(b"BS") {
+            if let Ok(width) = style.get_deref(b"W", self.document) {
+                return width.as_float().ok();
+            }
+        }
+
+        self.numbers(b"Border")
+            .and_then(|border| border.get(2).copied())
+    }
+
+    /// Border style (`/BS /S`) and dash pattern (`/BS /D`).
+    pub fn border_style(&self) -> Option<(BorderStyle, Option<Vec<f32>>)> {
+        let Some(Object::Dictionary(style)) = self.get(b"BS") else {
+            return None;
+        };
+
+        let kind = style
+            .get_deref(b"S", self.document)
+            .and_then(|name| name.as_name())
+            .map(BorderStyle::from_name)
+            .unwrap_or_default();
+
+        let dashes = style
+            .get_deref(b"D", self.document)
+            .and_then(|dashes| dashes.as_array())
+            .ok()
+            .map(|dashes| numbers(self.document, dashes))
+            .filter(|dashes| !dashes.is_empty());
+
+        Some((kind, dashes))
+    }
+
+    /// Stroke colour (`/C`) as `#RRGGBB`. An empty array means transparent.
+    pub fn color(&self) -> Option<String> {
+        self.numbers(b"C")
+            .and_then(|components| to_hex(&components))
+    }
+
+    /// Interior (fill) colour of Square, Circle, Polygon and Line endings (`/IC`).
+    pub fn interior_color(&self) -> Option<String> {
+        self.numbers(b"IC")
+            .and_then(|components| to_hex(&components))
+    }
+
+    /// Constant opacity (`/CA`).
+    pub fn opacity(&self) -> Option<f32> {
+        self.get(b"CA")?
+            .as_float()
+            .ok()
+            .map(|opacity| opacity.clamp(0.0, 1.0))
+    }
+
+    /// Rich text contents (`/RC`), stored either as a text string or a stream.
+    pub fn rich_contents(&self) -> Option<String> {
+        match self.get(b"RC")? {
+            Object::Stream(stream) => {
+                let content = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                Some(String::from_utf8_lossy(&content).into_owned())
+            }
+            object => lopdf::decode_text_string(object).ok(),
+        }
+    }
+
+    /// Font size from the default appearance string (`/DA`) of FreeText annotations.
+    pub fn font_size(&self) -> Option<f32> {
+        let appearance = self.get(b"DA")?.as_str().ok()?;
+        let appearance = String::from_utf8_lossy(appearance);
+        let tokens = appearance.split_whitespace().collect::<Vec<_>>();
+
+        tokens
+            .iter()
+            .position(|token| *token == "Tf")
+            .filter(|index| *index > 0)
+            .and_then(|index| tokens[index - 1].parse().ok())
+    }
+
+    /// Annotation name (`/NM`), unique among the annotations of a page.
+    pub fn name(&self) -> Option<String> {
+        lopdf::decode_text_string(self.get(b"NM")?)
+            .ok()
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Subtype name (`/Subtype`).
+    pub fn subtype(&self) -> Option<&'a [u8]> {
+        self.get(b"Subtype")?.as_name().ok()
+    }
+
+    /// Annotation this one replies to (`/IRT`). Group replies (`/RT /Group`) are
+    /// not part of a discussion and are ignored.
+    pub fn in_reply_to(&self) -> Option<ObjectId> {
+        let reply_type = self.get(b"RT").and_then(|name| name.as_name().ok());
+        if reply_type == Some(b"Group".as_slice()) {
+            return None;
+        }
+
+        self.dict.get(b"IRT").ok()?.as_reference().ok()
+    }
+
+    /// Review state (`/State`) of a state annotation.
+    pub fn review_state(&self) -> Option<ReviewState> {
+        self.get(b"State")?
+            .as_name()
+            .ok()
+            .and_then(ReviewState::from_name)
+    }
+
+    /// Popup annotation of a markup annotation (`/Popup`).
+    pub fn popup(&self) -> Option<AnnotationDictionary<'a>> {
+        let object_id = self.dict.get(b"Popup").ok()?.as_reference().ok()?;
+        let dict = self.document.get_dictionary(object_id).ok()?;
+
+        Some(AnnotationDictionary {
+            document: self.document,
+            dict,
+            object_id: Some(object_id),
+        })
+    }
+
+    /// Parent annotation of a popup (`/Parent`).
+    pub fn parent(&self) -> Option<ObjectId> {
+        self.dict.get(b"Parent").ok()?.as_reference().ok()
+    }
+
+    /// Whether a popup is initially open (`/Open`).
+    pub fn is_open(&self) -> bool {
+        self.get(b"Open")
+            .and_then(|open| open.as_bool().ok())
+            .unwrap_or(false)
+    }
+
+    /// Bounding box (`/Rect`) normalised to the top-left origin.
+    pub fn rect(&self, page_height: f32) -> Option<PdfRect> {
+        let values = self.numbers(b"Rect")?;
+        let [x1, y1, x2, y2] = values[..] else {
+            return None;
+        };
+
+        Some(PdfRect {
+            left: x1.min(x2),
+            top: page_height - y1.max(y2),
+            right: x1.max(x2),
+            bottom: page_height - y1.min(y2),
+        })
+    }
+
+    /// Icon name of Text and Stamp annotations (`/Name`).
+    pub fn icon(&self) -> Option<String> {
+        self.get(b"Name")?
+            .as_name()
+            .ok()
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+    }
+}
+
+fn numbers(document: &Document, array: &[Object]) -> Vec<f32> {
+    array
+        .iter()
+        .filter_map(|value| {
+            document
+                .dereference(value)
+                .ok()
+                .and_then(|(_, value)| value.as_float().ok())
+        })
+        .collect()
+}
+
+/// Converts a Gray, RGB or CMYK colour array to `#RRGGBB`.
+fn to_hex(components: &[f32]) -> Option<String> {
+    let (r, g, b) = match components {
+        [gray] => (*gray, *gray, *gray),
+        [r, g, b] => (*r, *g, *b),
+        [c, m, y, k] => (
+            (1.0 - c) * (1.0 - k),
+            (1.0 - m) * (1.0 - k),
+            (1.0 - y) * (1.0 - k),
+        ),
+        _ => return None,
+    };
+
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    Some(format!(
+        "#{:02X}{:02X}{:02X}",
+        channel(r),
+        channel(g),
+        channel(b)
+    ))
+}
+
+fn to_point(x: f32, y: f32, page_height: f32) -> Point {
+    Point {
+        x,
+        y: page_height - y,
+    }
+}
+
+fn to_points(values: &[f32], page_height: f32) -> Vec<Point> {
+    values
+        .chunks_exact(2)
+        .map(|pair| to_point(pair[0], pair[1], page_height))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn document_with_annotation(annotation: Dictionary) -> (Document, ObjectId) {
+        let mut document = Document::with_version("1.7");
+        let annotation_id = document.add_object(annotation);
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Annots" => vec![Object::Reference(annotation_id), Object::Null],
+        });
+        (document, page_id)
+    }
+
+    #[test]
+    fn test_page_annotations_keeps_indices() {
+        let (document, page_id) = document_with_annotation(dictionary! {
+            "Subtype" => "Text",
+        });
+
+        let annotations = page_annotations(&document, page_id);
+        assert_eq!(annotations.len(), 2);
+        assert!(annotations[0].is_some());
+        assert!(annotations[1].is_none());
+    }
+
+    #[test]
+    fn test_find_annotation_by_name_and_position() {
+        let mut document = Document::with_version("1.7");
+        let named = document.add_object(dictionary! {
+            "Subtype" => "Text",
+            "NM" => Object::string_literal("note-1"),
+        });
+        let unnamed = document.add_object(dictionary! { "Subtype" => "Square" });
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Annots" => vec![Object::Reference(named), Object::Reference(unnamed)],
+        });
+
+        assert_eq!(
+            find_annotation(&document, page_id, 0, "note-1"),
+            Some(named)
+        );
+        assert_eq!(
+            find_annotation(&document, page_id, 0, "sr-0-1"),
+            Some(unnamed)
+        );
+        // Named annotations are only addressed by their name
+        assert_eq!(find_annotation(&document, page_id, 0, "tx-0-0"), None);
+        assert_eq!(find_annotation(&document, page_id, 1, "sr-0-1"), None);
+    }
+
+    #[test]
+    fn test_reply_state_and_popup() {
+        let mut document = Document::with_version("1.7");
+        let parent = document.add_object(dictionary! { "Subtype" => "Highlight" });
+        let popup = document.add_object(dictionary! {
+            "Subtype" => "Popup",
+            "Parent" => Object::Reference(parent),
+            "Rect" => vec![200.into(), 700.into(), 100.into(), 600.into()],
+            "Open" => true,
+        });
+        let state = document.add_object(dictionary! {
+            "Subtype" => "Text",
+            "IRT" => Object::Reference(parent),
+            "State" => "Accepted",
+            "StateModel" => "Review",
+        });
+        let group = document.add_object(dictionary! {
+            "Subtype" => "Text",
+            "IRT" => Object::Reference(parent),
+            "RT" => "Group",
+        });
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Annots" => vec![parent.into(), popup.into(), state.into(), group.into()],
+        });
+        document
+            .get_dictionary_mut(parent)
+            .unwrap()
+            .set("Popup", Object::Reference(popup));
+
+        let annotations = page_annotations(&document, page_id);
+        let popup = annotations[0].unwrap().popup().unwrap();
+        assert_eq!(popup.object_id, annotations[1].unwrap().object_id);
+        assert_eq!(popup.parent(), Some(parent));
+        assert!(popup.is_open());
+        assert_eq!(
+            popup.rect(800.0),
+            Some(PdfRect {
+                left: 100.0,
+                top: 100.0,
+                right: 200.0,
+                bottom: 200.0,
+            })
+        );
+
+        let state = annotations[2].unwrap();
+        assert_eq!(state.in_reply_to(), Some(parent));
+        assert_eq!(state.review_state(), Some(ReviewState::Accepted));
+        assert_eq!(annotations[3].unwrap().in_reply_to(), None);
+    }
+
+    #[test]
+    fn test_line_and_endings() {
+        let (document, page_id) = document_with_annotation(dictionary! {
+            "Subtype" => "Line",
+            "L" => vec![10.into(), 20.into(), 110.5.into(), 220.into()],
+            "LE" => vec![Object::Name(b"None".to_vec()), Object::Name(b"ClosedArrow".to_vec())],
+            "BS" => dictionary! { "W" => 2 },
+        });
+
+        let annotation = page_annotations(&document, page_id)[0].unwrap();
+        let (start, end) = annotation.line(800.0).unwrap();
+        assert_eq!(start, Point { x: 10.0, y: 780.0 });
+        assert_eq!(end, Point { x: 110.5, y: 580.0 });
+        assert_eq!(
+            annotation.line_endings(),
+            Some(LineEndings {
+                start: LineEnding::None,
+                end: LineEnding::ClosedArrow,
+            })
+        );
+        assert_eq!(annotation.border_width(), Some(2.0));
+    }
+
+    #[test]
+    fn test_ink_paths_and_free_text_style() {
+        let (document, page_id) = document_with_annotation(dictionary! {
+            "Subtype" => "Ink",
+            "InkList" => vec![
+                vec![0.into(), 0.into(), 5.into(), 5.into()].into(),
+                vec![1.into(), 2.into()].into(),
+            ],
+            "DA" => Object::string_literal("0 0 1 rg /Helv 12.5 Tf"),
+            "Border" => vec![0.into(), 0.into(), 3.into()],
+        });
+
+        let annotation = page_annotations(&document, page_id)[0].unwrap();
+        let paths = annotation.ink_paths(100.0).unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0][1], Point { x: 5.0, y: 95.0 });
+        assert_eq!(annotation.font_size(), Some(12.5));
+        assert_eq!(annotation.border_width(), Some(3.0));
+        assert_eq!(annotation.vertices(100.0), None);
+    }
+
+    #[test]
+    fn test_colors_and_opacity() {
+        let (document, page_id) = document_with_annotation(dictionary! {
+            "Subtype" => "Square",
+            "C" => vec![1.into(), 0.9.into(), 0.into()],
+            "IC" => vec![0.5.into()],
+            "CA" => 0.4,
+            "BS" => dictionary! {
+                "W" => 1,
+                "S" => "D",
+                "D" => vec![3.into(), 2.into()],
+            },
+        });
+
+        let annotation = page_annotations(&document, page_id)[0].unwrap();
+        assert_eq!(annotation.color(), Some("#FFE600".to_string()));
+        assert_eq!(annotation.interior_color(), Some("#808080".to_string()));
+        assert_eq!(annotation.opacity(), Some(0.4));
+        assert_eq!(
+            annotation.border_style(),
+            Some((BorderStyle::Dashed, Some(vec![3.0, 2.0])))
+        );
+    }
+
+    #[test]
+    fn test_transparent_and_cmyk_colors() {
+        assert_eq!(to_hex(&[]), None);
+        assert_eq!(to_hex(&[0.0, 0.0, 0.0, 1.0]), Some("#000000".to_string()));
+        assert_eq!(to_hex(&[0.0, 1.0, 1.0, 0.0]), Some("#FF0000".to_string()));
+    }
+}
@@ -0,0 +1,563 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use lopdf::Document;
+use pdfium_render::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::annotation::annotations::writable_sidecar;
+use crate::pdf::reader::annotation::create::{
+    build_dictionary, create_annotation, create_review_state, page_id, update_annotation,
+};
+use crate::pdf::reader::annotation::dictionary::{find_annotation, page_annotations};
+use crate::pdf::reader::annotation::fdf::write_fdf;
+use crate::pdf::reader::annotation::query::date_digits;
+use crate::pdf::reader::annotation::sidecar::{SidecarFile, SidecarStore};
+use crate::pdf::reader::annotation::stamp::{custom_stamp, StampLibrary};
+use crate::pdf::reader::annotation::xfdf::{parse_xfdf, write_xfdf};
+use crate::pdf::reader::annotation::{
+    get_annotations, save_document, Annotation, ReviewStateRecord, ReviewStatus, SourceFile,
+};
+use crate::pdf::DocumentId;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationExchangeFormat {
+    Xfdf,
+    Fdf,
+}
+
+/// Outcome of merging imported annotations into a document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AnnotationImportSummary {
+    /// Annotations that did not exist in the document
+    pub added: usize,
+
+    /// Existing annotations replaced by a more recently modified import
+    pub updated: usize,
+
+    /// Annotations that were unchanged, unsupported or whose reply target is missing
+    pub skipped: usize,
+
+    /// Review states added to annotations
+    pub review_states: usize,
+}
+
+/// Exports the annotations of an open document to `dest` without touching the PDF.
+pub fn export_annotations(
+    documents: &HashMap<DocumentId, PdfDocument>,
+    file: &SourceFile,
+    id: &DocumentId,
+    format: AnnotationExchangeFormat,
+    dest: &Path,
+) -> Result<(), String> {
+    let document = documents.get(id).ok_or("Document not found")?;
+    let annotations = get_annotations(documents, file, id, None)?;
+    let page_heights = page_heights(document);
+    let file_name = file.file_name();
+
+    let bytes = match format {
+        AnnotationExchangeFormat::Xfdf => {
+            write_xfdf(&annotations, &page_heights, file_name.as_deref())?.into_bytes()
+        }
+        AnnotationExchangeFormat::Fdf => {
+            write_fdf(&annotations, &page_heights, file_name.as_deref())?
+        }
+    };
+
+    std::fs::write(dest, bytes).map_err(|e| format!("Failed to write annotations: {e}"))
+}
+
+/// Imports annotations from an XFDF file and merges them into the document.
+///
+/// Annotations are matched to existing ones by name on the same page; the PDF is
+/// only rewritten when something was added or updated. Documents that keep their
+/// annotations in a sidecar get the imported annotations there instead.
+pub fn import_annotations<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
+    id: &DocumentId,
+    source: &Path,
+    stamps: &StampLibrary,
+    sidecar: Option<&SidecarStore>,
+) -> Result<AnnotationImportSummary, String> {
+    let document = documents.get(id).ok_or("Document not found")?;
+    let page_heights = page_heights(document);
+
+    let xml =
+        std::fs::read_to_string(source).map_err(|e| format!("Failed to read annotations: {e}"))?;
+    let imported = parse_xfdf(&xml, &page_heights)?;
+
+    if let Some((store, mut sidecar)) = writable_sidecar(sidecar, file)? {
+        let summary = merge_sidecar_annotations(
+            &mut sidecar,
+            file.document()?,
+            imported.annotations,
+            imported.review_states,
+            &page_heights,
+            Some(stamps),
+        );
+        if summary.added + summary.updated + summary.review_states > 0 {
+            store.save(&file.fingerprint, &sidecar)?;
+        }
+        return Ok(summary);
+    }
+
+    let mut target = file.document()?.clone();
+    let summary = merge_annotations(
+        &mut target,
+        imported.annotations,
+        imported.review_states,
+        &page_heights,
+        Some(stamps),
+    );
+
+    if summary.added + summary.updated + summary.review_states > 0 {
+        documents.remove(id);
+        save_document(file, &target, &file.path)?;
+    }
+
+    Ok(summary)
+}
+
+/// Merges imported annotations into `document`.
+///
+/// An annotation whose name already exists on its page replaces the existing one
+/// only when its modification date is newer. Replies are created after the
+/// annotations they reply to, and review states are skipped when the same reviewer
+/// already set the same state. Custom stamps missing from `stamps` are skipped.
+pub fn merge_annotations(
+    document: &mut Document,
+    annotations: Vec<Annotation>,
+    review_states: Vec<ReviewStateRecord>,
+    page_heights: &[f32],
+    stamps: Option<&StampLibrary>,
+) -> AnnotationImportSummary {
+    let mut summary = AnnotationImportSummary::default();
+
+    for annotation in reply_order(annotations) {
+        let Some(page_height) = page_heights.get(annotation.page_index as usize).copied() else {
+            summary.skipped += 1;
+            continue;
+        };
+        let Ok(page_id) = page_id(document, annotation.page_index) else {
+            summary.skipped += 1;
+            continue;
+        };
+
+        match find_annotation(document, page_id, annotation.page_index, &annotation.id) {
+            Some(existing) => {
+                let modified = document
+                    .get_dictionary(existing)
+                    .and_then(|dict| dict.get(b"M"))
+                    .ok()
+                    .and_then(|date| lopdf::decode_text_string(date).ok());
+
+                if is_newer(&annotation, modified.as_deref())
+                    && update_annotation(document, existing, &annotation, page_height, stamps)
+                        .is_ok()
+                {
+                    summary.updated += 1;
+                } else {
+                    summary.skipped += 1;
+                }
+            }
+            None => match create_annotation(document, &annotation, page_height, stamps) {
+                Ok(_) => summary.added += 1,
+                Err(_) => summary.skipped += 1,
+            },
+        }
+    }
+
+    for state in review_states {
+        let Ok(page_id) = page_id(document, state.page_index) else {
+            continue;
+        };
+        let Some(target) =
+            find_annotation(document, page_id, state.page_index, &state.annotation_id)
+        else {
+            continue;
+        };
+
+        if !has_review_state(document, page_id, target, &state.status)
+            && create_review_state(
+                document,
+                state.page_index,
+                &state.annotation_id,
+                &state.status,
+            )
+            .is_ok()
+        {
+            summary.review_states += 1;
+        }
+    }
+
+    summary
+}
+
+/// Merges imported annotations into the sidecar of a document whose PDF is left
+/// untouched, following the rules of [`merge_annotations`].
+///
+/// Annotations already in the PDF cannot be replaced and are skipped; replies and
+/// review states may target annotations in either.
+pub fn merge_sidecar_annotations(
+    sidecar: &mut SidecarFile,
+    document: &Document,
+    annotations: Vec<Annotation>,
+    review_states: Vec<ReviewStateRecord>,
+    page_heights: &[f32],
+    stamps: Option<&StampLibrary>,
+) -> AnnotationImportSummary {
+    let mut summary = AnnotationImportSummary::default();
+
+    let in_document = |page_index: u16, annotation_id: &str| {
+        let page_id = page_id(document, page_index).ok()?;
+        find_annotation(document, page_id, page_index, annotation_id)
+            .map(|target| (page_id, target))
+    };
+
+    for annotation in reply_order(annotations) {
+        let Some(page_height) = page_heights.get(annotation.page_index as usize).copied() else {
+            summary.skipped += 1;
+            continue;
+        };
+        if in_document(annotation.page_index, &annotation.id).is_some() {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let target_found = annotation.in_reply_to.as_deref().is_none_or(|target| {
+            in_document(annotation.page_index, target).is_some()
+                || sidecar.annotations.iter().any(|a| a.id == target)
+        });
+        let valid = target_found
+            && build_dictionary(&annotation, page_height).is_ok()
+            && custom_stamp(&annotation, stamps).is_ok();
+
+        match sidecar
+            .annotations
+            .iter()
+            .position(|a| a.id == annotation.id)
+        {
+            Some(index) => {
+                let modified = sidecar.annotations[index].metadata.modified_date.clone();
+                if valid && is_newer(&annotation, modified.as_deref()) {
+                    sidecar.annotations[index] = annotation;
+                    summary.updated += 1;
+                } else {
+                    summary.skipped += 1;
+                }
+            }
+            None if valid => {
+                sidecar.annotations.push(annotation);
+                summary.added += 1;
+            }
+            None => summary.skipped += 1,
+        }
+    }
+
+    for state in review_states {
+        let target = in_document(state.page_index, &state.annotation_id);
+        let in_sidecar = sidecar
+            .annotations
+            .iter()
+            .any(|a| a.id == state.annotation_id);
+        if target.is_none() && !in_sidecar {
+            continue;
+        }
+
+        let already_set = target.is_some_and(|(page_id, target)| {
+            has_review_state(document, page_id, target, &state.status)
+        }) || sidecar.review_states.iter().any(|existing| {
+            existing.annotation_id == state.annotation_id
+                && existing.status.state == state.status.state
+                && existing.status.author == state.status.author
+        });
+
+        if !already_set {
+            sidecar.review_states.push(state);
+            summary.review_states += 1;
+        }
+    }
+
+    summary
+}
+
+/// Whether an imported annotation was modified after the existing one with the
+/// same name.
+fn is_newer(annotation: &Annotation, existing: Option<&str>) -> bool {
+    match (&annotation.metadata.modified_date, existing) {
+        (Some(imported), Some(existing)) => date_digits(imported) > date_digits(existing),
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Whether the same reviewer already set the same state on an annotation in the
+/// PDF.
+fn has_review_state(
+    document: &Document,
+    page_id: lopdf::ObjectId,
+    target: lopdf::ObjectId,
+    status: &ReviewStatus,
+) -> bool {
+    page_annotations(document, page_id)
+        .into_iter()
+        .flatten()
+        .any(|existing| {
+            existing.in_reply_to() == Some(target)
+                && existing.review_state() == Some(status.state)
+                && existing
+                    .dict
+                    .get(b"T")
+                    .ok()
+                    .and_then(|author| lopdf::decode_text_string(author).ok())
+                    == status.author
+        })
+}
+
+/// Orders annotations so that every reply comes after the annotation it replies to.
+fn reply_order(annotations: Vec<Annotation>) -> Vec<Annotation> {
+    let parents = annotations
+        .iter()
+        .map(|annotation| (annotation.id.clone(), annotation.in_reply_to.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let depth = |annotation: &Annotation| {
+        let mut depth = 0;
+        let mut current = annotation.in_reply_to.as_ref();
+        while let Some(parent) = current {
+            if depth > parents.len() {
+                break;
+            }
+            depth += 1;
+            current = parents.get(parent).and_then(|parent| parent.as_ref());
+        }
+        depth
+    };
+
+    let mut ordered = annotations
+        .into_iter()
+        .map(|annotation| (depth(&annotation), annotation))
+        .collect::<Vec<_>>();
+    ordered.sort_by_key(|(depth, _)| *depth);
+
+    ordered
+        .into_iter()
+        .map(|(_, annotation)| annotation)
+        .collect()
+}
+
+pub fn page_heights(document: &PdfDocument) -> Vec<f32> {
+    document
+        .pages()
+        .iter()
+        .map(|page| page.height().value)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::annotation::dictionary::page_annotations;
+    use lopdf::{dictionary, Object};
+
+    fn single_page_document() -> Document {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 600.into(), 800.into()],
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    const XFDF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xfdf xmlns="http://ns.adobe.com/xfdf/">
+  <annots>
+    <text page="0" rect="10,10,30,30" name="reply" inreplyto="note" date="D:20240101000000Z">
+      <contents>Agreed</contents>
+    </text>
+    <text page="0" rect="10,10,30,30" name="note" date="D:20240101000000Z">
+      <contents>First</contents>
+    </text>
+    <text page="0" rect="10,10,30,30" name="state" inreplyto="note" state="Accepted" statemodel="Review" title="Bo"/>
+    <text page="3" rect="10,10,30,30" name="elsewhere"/>
+  </annots>
+</xfdf>"#;
+
+    #[test]
+    fn test_merge_orders_replies_and_is_idempotent() {
+        let mut document = single_page_document();
+
+        let imported = parse_xfdf(XFDF, &[800.0]).unwrap();
+        let summary = merge_annotations(
+            &mut document,
+            imported.annotations,
+            imported.review_states,
+            &[800.0],
+            None,
+        );
+        assert_eq!(
+            summary,
+            AnnotationImportSummary {
+                added: 2,
+                updated: 0,
+                skipped: 0,
+                review_states: 1,
+            }
+        );
+
+        let page_id = document.get_pages()[&1];
+        let annotations = page_annotations(&document, page_id);
+        let note = annotations[0].unwrap();
+        assert_eq!(note.name().as_deref(), Some("note"));
+        assert_eq!(annotations[1].unwrap().in_reply_to(), note.object_id);
+
+        let imported = parse_xfdf(XFDF, &[800.0]).unwrap();
+        let summary = merge_annotations(
+            &mut document,
+            imported.annotations,
+            imported.review_states,
+            &[800.0],
+            None,
+        );
+        assert_eq!(summary.added + summary.updated + summary.review_states, 0);
+        assert_eq!(summary.skipped, 2);
+    }
+
+    #[test]
+    fn test_merge_updates_newer_annotations_in_place() {
+        let mut document = single_page_document();
+        let imported = parse_xfdf(XFDF, &[800.0]).unwrap();
+        merge_annotations(
+            &mut document,
+            imported.annotations,
+            imported.review_states,
+            &[800.0],
+            None,
+        );
+
+        let page_id = document.get_pages()[&1];
+        let before = find_annotation(&document, page_id, 0, "note");
+
+        let newer = XFDF.replace(
+            "D:20240101000000Z\">\n      <contents>First",
+            "D:20250101000000Z\">\n      <contents>Edited",
+        );
+        let imported = parse_xfdf(&newer, &[800.0]).unwrap();
+        let summary = merge_annotations(
+            &mut document,
+            imported.annotations,
+            imported.review_states,
+            &[800.0],
+            None,
+        );
+        assert_eq!(summary.updated, 1);
+
+        let note = find_annotation(&document, page_id, 0, "note");
+        assert_eq!(note, before);
+        let contents = document
+            .get_dictionary(note.unwrap())
+            .unwrap()
+            .get(b"Contents")
+            .unwrap();
+        assert_eq!(lopdf::decode_text_string(contents).unwrap(), "Edited");
+    }
+
+    #[test]
+    fn test_merge_into_sidecar_leaves_document_untouched() {
+        let document = single_page_document();
+        let mut sidecar = SidecarFile::new(None);
+
+        let imported = parse_xfdf(XFDF, &[800.0]).unwrap();
+        let summary = merge_sidecar_annotations(
+            &mut sidecar,
+            &document,
+            imported.annotations,
+            imported.review_states,
+            &[800.0],
+            None,
+        );
+        assert_eq!(
+            summary,
+            AnnotationImportSummary {
+                added: 2,
+                updated: 0,
+                skipped: 0,
+                review_states: 1,
+            }
+        );
+        assert!(page_annotations(&document, document.get_pages()[&1]).is_empty());
+        let ids = sidecar
+            .annotations
+            .iter()
+            .map(|a| a.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["note", "reply"]);
+        assert_eq!(sidecar.review_states[0].annotation_id, "note");
+
+        let imported = parse_xfdf(XFDF, &[800.0]).unwrap();
+        let summary = merge_sidecar_annotations(
+            &mut sidecar,
+            &document,
+            imported.annotations,
+            imported.review_states,
+            &[800.0],
+            None,
+        );
+        assert_eq!(summary.added + summary.updated + summary.review_states, 0);
+    }
+
+    #[test]
+    fn test_merge_into_sidecar_skips_document_annotations() {
+        // The note is already in the PDF; its reply goes to the sidecar.
+        let mut document = single_page_document();
+        let imported = parse_xfdf(XFDF, &[800.0]).unwrap();
+        let note = imported
+            .annotations
+            .iter()
+            .find(|a| a.id == "note")
+            .cloned();
+        merge_annotations(
+            &mut document,
+            vec![note.unwrap()],
+            Vec::new(),
+            &[800.0],
+            None,
+        );
+
+        let mut sidecar = SidecarFile::new(None);
+        let summary = merge_sidecar_annotations(
+            &mut sidecar,
+            &document,
+            imported.annotations,
+            imported.review_states,
+            &[800.0],
+            None,
+        );
+        assert_eq!(
+            (summary.added, summary.skipped, summary.review_states),
+            (1, 1, 1)
+        );
+        assert_eq!(sidecar.annotations[0].id, "reply");
+        assert_eq!(
+            page_annotations(&document, document.get_pages()[&1]).len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use lopdf::{dictionary, Document, Object, ObjectId};
+
+use crate::pdf::reader::annotation::create::{
+    build_dictionary, popup_dictionary, review_state_dictionary,
+};
+use crate::pdf::reader::annotation::Annotation;
+
+/// Writes annotations as an FDF file.
+///
+/// FDF uses PDF syntax: each annotation is written as a full annotation dictionary
+/// (with its appearance stream) carrying the zero-based `/Page` it belongs to, and
+/// replies reference their target through `/IRT`. Subtypes that cannot be created
+/// are skipped, together with their replies.
+pub fn write_fdf(
+    annotations: &[Annotation],
+    page_heights: &[f32],
+    file_name: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    let mut document = Document::with_version("1.2");
+    let mut annots = Vec::new();
+    let mut names = HashMap::new();
+
+    for annotation in annotations {
+        add_thread(
+            &mut document,
+            &mut annots,
+            &mut names,
+            annotation,
+            page_heights,
+        )?;
+    }
+
+    let mut fdf = dictionary! { "Annots" => annots };
+    if let Some(file_name) = file_name {
+        fdf.set("F", Object::string_literal(file_name));
+    }
+
+    let catalog_id = document.add_object(dictionary! { "FDF" => fdf });
+    document.trailer.set("Root", catalog_id);
+
+    let mut buffer = Vec::new();
+    document
+        .save_to(&mut buffer)
+        .map_err(|e| format!("Failed to write FDF: {e}"))?;
+
+    // lopdf always writes a PDF header; the FDF header has the same length, so the
+    // cross-reference offsets stay valid.
+    if buffer.starts_with(b"%PDF-") {
+        buffer[1..4].copy_from_slice(b"FDF");
+    }
+
+    Ok(buffer)
+}
+
+fn add_thread(
+    document: &mut Document,
+    annots: &mut Vec<Object>,
+    names: &mut HashMap<String, ObjectId>,
+    annotation: &Annotation,
+    page_heights: &[f32],
+) -> Result<(), String> {
+    let Some(page_height) = page_heights.get(annotation.page_index as usize).copied() else {
+        return Ok(());
+    };
+
+    let Ok((mut dict, appearance)) = build_dictionary(annotation, page_height) else {
+        return Ok(());
+    };
+
+    dict.set("Page", annotation.page_index as i64);
+    if let Some(target) = annotation
+        .in_reply_to
+        .as_ref()
+        .and_then(|target| names.get(target))
+    {
+        dict.set("IRT", Object::Reference(*target));
+        dict.set("RT", "R");
+    }
+
+    let appearance_id = document.add_object(appearance);
+    dict.set("AP", dictionary! { "N" => appearance_id });
+
+    let rect = dict.get(b"Rect").cloned().unwrap_or(Object::Null);
+    let annotation_id = document.add_object(dict);
+    annots.push(Object::Reference(annotation_id));
+    names.insert(annotation.id.clone(), annotation_id);
+
+    if let Some(popup) = &annotation.popup {
+        let mut popup = popup_dictionary(popup, annotation_id, page_height);
+        popup.set("Page", annotation.page_index as i64);
+
+        let popup_id = document.add_object(popup);
+        annots.push(Object::Reference(popup_id));
+
+        if let Ok(dict) = document.get_dictionary_mut(annotation_id) {
+            dict.set("Popup", Object::Reference(popup_id));
+        }
+    }
+
+    for status in &annotation.review_states {
+        let mut state = review_state_dictionary(rect.clone(), annotation_id, status);
+        state.set("Page", annotation.page_index as i64);
+        annots.push(Object::Reference(document.add_object(state)));
+    }
+
+    for reply in &annotation.replies {
+        add_thread(document, annots, names, reply, page_heights)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::annotation::{
+        AnnotationAppearance, AnnotationFlags, AnnotationGeometry, AnnotationMetadata,
+        AnnotationType, PdfRect, ReviewState, ReviewStatus,
+    };
+
+    #[test]
+    fn test_write_fdf_links_replies() {
+        let rect = PdfRect {
+            left: 10.0,
+            top: 10.0,
+            right: 30.0,
+            bottom: 30.0,
+        };
+        let note = |id: &str| Annotation {
+            id: id.into(),
+            page_index: 0,
+            subtype: AnnotationType::Text,
+            rect: rect.clone(),
+            geometry: AnnotationGeometry::Rect(rect.clone()),
+            appearance: AnnotationAppearance {
+                color: "#FFFF00".into(),
+                interior_color: None,
+                opacity: 1.0,
+                border_width: None,
+                border_style: None,
+                dash_pattern: None,
+                line_endings: None,
+                font_size: None,
+                icon: None,
+            },
+            metadata: AnnotationMetadata {
+                author: None,
+                contents: Some(id.into()),
+                rich_contents: None,
+                creation_date: None,
+                modified_date: None,
+            },
+            flags: AnnotationFlags::default(),
+            in_reply_to: None,
+            replies: Vec::new(),
+            review_states: Vec::new(),
+            popup: None,
+        };
+
+        let mut root = note("root");
+        let mut reply = note("reply");
+        reply.in_reply_to = Some("root".into());
+        root.replies.push(reply);
+        root.review_states.push(ReviewStatus {
+            state: ReviewState::Rejected,
+            author: None,
+            date: None,
+        });
+
+        let fdf = write_fdf(&[root], &[800.0], Some("paper.pdf")).unwrap();
+        assert!(fdf.starts_with(b"%FDF-1.2"));
+
+        let text = String::from_utf8_lossy(&fdf);
+        assert!(text.contains("/FDF"));
+        assert!(text.contains("/IRT"));
+        assert!(text.contains("/Rejected"));
+        assert!(text.contains("(paper.pdf)"));
+    }
+}
@@ -1,8 +1,25 @@
 use serde::{Deserialize, Serialize};
 
 pub mod annotations;
+pub mod appearance;
+pub mod create;
+pub mod dictionary;
+pub mod exchange;
+pub mod fdf;
+pub mod query;
+pub mod sidecar;
+pub mod source;
+pub mod stamp;
+pub mod summary;
+pub mod xfdf;
 
 pub use annotations::*;
+pub use exchange::*;
+pub use query::*;
+pub use sidecar::*;
+pub use source::*;
+pub use stamp::*;
+pub use summary::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", content = "data", rename_all = "lowercase")]
@@ -79,21 +96,211 @@ pub enum AnnotationType {
     Redacted,
 }
 
+impl AnnotationType {
+    pub fn from_name(name: &[u8]) -> Self {
+        match name {
+            b"Text" => AnnotationType::Text,
+            b"Link" => AnnotationType::Link,
+            b"FreeText" => AnnotationType::FreeText,
+            b"Line" => AnnotationType::Line,
+            b"Square" => AnnotationType::Square,
+            b"Circle" => AnnotationType::Circle,
+            b"Polygon" => AnnotationType::Polygon,
+            b"PolyLine" => AnnotationType::Polyline,
+            b"Highlight" => AnnotationType::Highlight,
+            b"Underline" => AnnotationType::Underline,
+            b"Squiggly" => AnnotationType::Squiggly,
+            b"StrikeOut" => AnnotationType::Strikeout,
+            b"Stamp" => AnnotationType::Stamp,
+            b"Caret" => AnnotationType::Caret,
+            b"Ink" => AnnotationType::Ink,
+            b"Popup" => AnnotationType::Popup,
+            b"FileAttachment" => AnnotationType::FileAttachment,
+            b"Sound" => AnnotationType::Sound,
+            b"Movie" => AnnotationType::Movie,
+            b"Widget" => AnnotationType::Widget,
+            b"Screen" => AnnotationType::Screen,
+            b"PrinterMark" => AnnotationType::PrinterMark,
+            b"TrapNet" => AnnotationType::TrapNet,
+            b"Watermark" => AnnotationType::Watermark,
+            b"3D" => AnnotationType::ThreeD,
+            b"RichMedia" => AnnotationType::RichMedia,
+            b"Redact" => AnnotationType::Redacted,
+            _ => AnnotationType::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    None,
+    Square,
+    Circle,
+    Diamond,
+    OpenArrow,
+    ClosedArrow,
+    Butt,
+    ROpenArrow,
+    RClosedArrow,
+    Slash,
+}
+
+impl LineEnding {
+    pub fn from_name(name: &[u8]) -> Self {
+        match name {
+            b"Square" => LineEnding::Square,
+            b"Circle" => LineEnding::Circle,
+            b"Diamond" => LineEnding::Diamond,
+            b"OpenArrow" => LineEnding::OpenArrow,
+            b"ClosedArrow" => LineEnding::ClosedArrow,
+            b"Butt" => LineEnding::Butt,
+            b"ROpenArrow" => LineEnding::ROpenArrow,
+            b"RClosedArrow" => LineEnding::RClosedArrow,
+            b"Slash" => LineEnding::Slash,
+            _ => LineEnding::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Beveled,
+    Inset,
+    Underline,
+}
+
+impl BorderStyle {
+    pub fn from_name(name: &[u8]) -> Self {
+        match name {
+            b"D" => BorderStyle::Dashed,
+            b"B" => BorderStyle::Beveled,
+            b"I" => BorderStyle::Inset,
+            b"U" => BorderStyle::Underline,
+            _ => BorderStyle::Solid,
+        }
+    }
+}
+
+/// Line endings of Line, Polyline and FreeText callout annotations
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LineEndings {
+    pub start: LineEnding,
+    pub end: LineEnding,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnnotationAppearance {
     pub color: String,
+
+    /// Fill colour of Square, Circle, Polygon and closed line endings
+    pub interior_color: Option<String>,
     pub opacity: f32,
     pub border_width: Option<f32>,
+    pub border_style: Option<BorderStyle>,
+
+    /// Dash lengths for `BorderStyle::Dashed`
+    pub dash_pattern: Option<Vec<f32>>,
+
+    /// Line, Polyline and FreeText callout endings
+    pub line_endings: Option<LineEndings>,
+
+    /// FreeText font size from the default appearance string
+    pub font_size: Option<f32>,
+
+    /// Icon name of Text and Stamp annotations (e.g. "Comment", "Approved")
+    pub icon: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnnotationMetadata {
     pub author: Option<String>,
     pub contents: Option<String>,
+
+    /// XHTML rich text contents (`/RC`), when the author provided them
+    pub rich_contents: Option<String>,
     pub creation_date: Option<String>,
     pub modified_date: Option<String>,
 }
 
+/// Review state set on an annotation (`/State` with `/StateModel /Review` or `/Marked`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewState {
+    None,
+    Accepted,
+    Rejected,
+    Cancelled,
+    Completed,
+    Marked,
+    Unmarked,
+}
+
+impl ReviewState {
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"None" => Some(ReviewState::None),
+            b"Accepted" => Some(ReviewState::Accepted),
+            b"Rejected" => Some(ReviewState::Rejected),
+            b"Cancelled" => Some(ReviewState::Cancelled),
+            b"Completed" => Some(ReviewState::Completed),
+            b"Marked" => Some(ReviewState::Marked),
+            b"Unmarked" => Some(ReviewState::Unmarked),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReviewState::None => "None",
+            ReviewState::Accepted => "Accepted",
+            ReviewState::Rejected => "Rejected",
+            ReviewState::Cancelled => "Cancelled",
+            ReviewState::Completed => "Completed",
+            ReviewState::Marked => "Marked",
+            ReviewState::Unmarked => "Unmarked",
+        }
+    }
+
+    /// State model the state belongs to (`/StateModel`)
+    pub fn model(&self) -> &'static str {
+        match self {
+            ReviewState::Marked | ReviewState::Unmarked => "Marked",
+            _ => "Review",
+        }
+    }
+}
+
+/// A review state set by one reviewer. Stored in the PDF as a hidden Text
+/// annotation replying to the reviewed annotation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewStatus {
+    pub state: ReviewState,
+    pub author: Option<String>,
+    pub date: Option<String>,
+}
+
+/// A review state together with the annotation it applies to, for states that are
+/// stored or exchanged separately from the annotation itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewStateRecord {
+    pub page_index: u16,
+    pub annotation_id: String,
+    pub status: ReviewStatus,
+}
+
+/// Popup window associated with a markup annotation (`/Popup`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AnnotationPopup {
+    pub rect: PdfRect,
+    pub open: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AnnotationFlags {
     pub hidden: bool,
@@ -124,4 +331,20 @@ pub struct Annotation {
 
     /// Annotation behavior flags
     pub flags: AnnotationFlags,
+
+    /// Id of the annotation this one replies to (`/IRT`)
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+
+    /// Replies to this annotation in document order, each with its own replies
+    #[serde(default)]
+    pub replies: Vec<Annotation>,
+
+    /// Review states set on this annotation, oldest first
+    #[serde(default)]
+    pub review_states: Vec<ReviewStatus>,
+
+    /// Associated popup window
+    #[serde(default)]
+    pub popup: Option<AnnotationPopup>,
 }
@@ -0,0 +1,416 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use pdfium_render::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::annotation::{
+    get_annotations, Annotation, AnnotationType, SidecarStore, SourceFile,
+};
+use crate::pdf::DocumentId;
+use crate::utils::page_selection::PageSelectionParser;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationSortKey {
+    /// Document order: page, then top to bottom
+    #[default]
+    Page,
+    Date,
+    Author,
+    Type,
+    Color,
+}
+
+/// Filters for the annotations panel. Empty filters match every annotation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnnotationQuery {
+    /// Authors of the annotation or one of its replies
+    #[serde(default)]
+    pub authors: Vec<String>,
+
+    #[serde(default)]
+    pub subtypes: Vec<AnnotationType>,
+
+    /// `#RRGGBB` colours, compared case-insensitively
+    #[serde(default)]
+    pub colors: Vec<String>,
+
+    /// Earliest modification date, as a PDF date or ISO 8601 date
+    pub from: Option<String>,
+
+    /// Latest modification date, inclusive; `2024-03-01` includes the whole day
+    pub to: Option<String>,
+
+    /// Case-insensitive text in the contents of the annotation or its replies
+    pub text: Option<String>,
+
+    /// Page selection such as `1-3,5`, 1-based
+    pub pages: Option<String>,
+
+    #[serde(default)]
+    pub sort_by: AnnotationSortKey,
+
+    #[serde(default)]
+    pub descending: bool,
+
+    #[serde(default)]
+    pub offset: usize,
+
+    /// Maximum number of annotations returned, all when `None`
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnnotationQueryResult {
+    /// Number of matching annotations before `offset` and `limit` are applied
+    pub total: usize,
+    pub annotations: Vec<Annotation>,
+}
+
+/// Returns the annotations of an open document that match `query`.
+pub fn query_annotations(
+    documents: &HashMap<DocumentId, PdfDocument>,
+    file: &SourceFile,
+    id: &DocumentId,
+    query: &AnnotationQuery,
+    sidecar: Option<&SidecarStore>,
+) -> Result<AnnotationQueryResult, String> {
+    let document = documents.get(id).ok_or("Document not found")?;
+    let page_count = document.pages().len() as u32;
+    let annotations = get_annotations(documents, file, id, sidecar)?;
+
+    filter_annotations(annotations, query, page_count)
+}
+
+/// Filters, sorts and pages annotations. Replies stay nested in their annotation;
+/// a discussion matches the author and text filters when any reply does.
+pub fn filter_annotations(
+    annotations: Vec<Annotation>,
+    query: &AnnotationQuery,
+    page_count: u32,
+) -> Result<AnnotationQueryResult, String> {
+    let pages = match &query.pages {
+        Some(expr) if !expr.trim().is_empty() => Some(
+            PageSelectionParser::parse(expr)
+                .and_then(|selection| selection.resolve(page_count))
+                .map_err(|e| e.to_string())?,
+        ),
+        _ => None,
+    };
+
+    let authors = query
+        .authors
+        .iter()
+        .map(|author| author.to_lowercase())
+        .collect::<Vec<_>>();
+    let colors = query
+        .colors
+        .iter()
+        .map(|color| color.to_uppercase())
+        .collect::<Vec<_>>();
+    let text = query
+        .text
+        .as_deref()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_lowercase);
+    let from = query.from.as_deref().map(date_digits);
+    let to = query.to.as_deref().map(date_digits);
+
+    let mut matches = annotations
+        .into_iter()
+        .filter(|annotation| {
+            pages
+                .as_ref()
+                .is_none_or(|pages| pages.contains(&(annotation.page_index as u32 + 1)))
+        })
+        .filter(|annotation| {
+            query.subtypes.is_empty() || query.subtypes.contains(&annotation.subtype)
+        })
+        .filter(|annotation| {
+            colors.is_empty() || colors.contains(&annotation.appearance.color.to_uppercase())
+        })
+        .filter(|annotation| {
+            authors.is_empty()
+                || discussion(annotation).any(|entry| {
+                    entry
+                        .metadata
+                        .author
+                        .as_ref()
+                        .is_some_and(|author| authors.contains(&author.to_lowercase()))
+                })
+        })
+        .filter(|annotation| {
+            text.as_ref().is_none_or(|text| {
+                discussion(annotation).any(|entry| {
+                    entry
+                        .metadata
+                        .contents
+                        .as_ref()
+                        .is_some_and(|contents| contents.to_lowercase().contains(text))
+                })
+            })
+        })
+        .filter(|annotation| {
+            if from.is_none() && to.is_none() {
+                return true;
+            }
+            let Some(date) = date(annotation).map(date_digits) else {
+                return false;
+            };
+            from.as_ref()
+                .is_none_or(|from| within(&date, from) != Ordering::Less)
+                && to
+                    .as_ref()
+                    .is_none_or(|to| within(&date, to) != Ordering::Greater)
+        })
+        .collect::<Vec<_>>();
+
+    matches.sort_by(|a, b| {
+        let ordering = match query.sort_by {
+            AnnotationSortKey::Page => document_order(a, b),
+            AnnotationSortKey::Date => date(a).map(date_digits).cmp(&date(b).map(date_digits)),
+            AnnotationSortKey::Author => {
+                let author = |annotation: &Annotation| {
+                    annotation
+                        .metadata
+                        .author
+                        .as_ref()
+                        .map(|author| author.to_lowercase())
+                };
+                author(a).cmp(&author(b))
+            }
+            AnnotationSortKey::Type => format!("{:?}", a.subtype).cmp(&format!("{:?}", b.subtype)),
+            AnnotationSortKey::Color => a
+                .appearance
+                .color
+                .to_uppercase()
+                .cmp(&b.appearance.color.to_uppercase()),
+        };
+        let ordering = if query.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering.then_with(|| document_order(a, b))
+    });
+
+    let total = matches.len();
+    let annotations = matches
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok(AnnotationQueryResult { total, annotations })
+}
+
+/// The annotation followed by all of its replies.
+fn discussion(annotation: &Annotation) -> Box<dyn Iterator<Item = &Annotation> + '_> {
+    Box::new(std::iter::once(annotation).chain(annotation.replies.iter().flat_map(discussion)))
+}
+
+fn date(annotation: &Annotation) -> Option<&str> {
+    annotation
+        .metadata
+        .modified_date
+        .as_deref()
+        .or(annotation.metadata.creation_date.as_deref())
+}
+
+fn document_order(a: &Annotation, b: &Annotation) -> Ordering {
+    a.page_index
+        .cmp(&b.page_index)
+        .then(a.rect.top.total_cmp(&b.rect.top))
+        .then(a.rect.left.total_cmp(&b.rect.left))
+}
+
+/// Digits of a PDF date (`D:20240301120000Z`) or ISO 8601 date
+/// (`2024-03-01T12:00:00Z`) up to the seconds, ignoring the time zone.
+pub(crate) fn date_digits(date: &str) -> String {
+    date.trim_start_matches("D:")
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || matches!(c, '-' | ':' | 'T' | ' '))
+        .filter(char::is_ascii_digit)
+        .take(14)
+        .collect()
+}
+
+/// Compares a date with a bound at the bound's precision, so a bound of
+/// `20240301` covers every time on that day.
+fn within(date: &str, bound: &str) -> Ordering {
+    let length = bound.len().min(date.len());
+    date[..length].cmp(&bound[..length])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::annotation::{
+        AnnotationAppearance, AnnotationFlags, AnnotationGeometry, AnnotationMetadata, PdfRect,
+    };
+
+    fn annotation(
+        id: &str,
+        page_index: u16,
+        subtype: AnnotationType,
+        author: &str,
+        contents: &str,
+        date: &str,
+    ) -> Annotation {
+        let rect = PdfRect {
+            left: 10.0,
+            top: 10.0 * page_index as f32,
+            right: 30.0,
+            bottom: 30.0,
+        };
+        Annotation {
+            id: id.into(),
+            page_index,
+            subtype,
+            rect: rect.clone(),
+            geometry: AnnotationGeometry::Rect(rect),
+            appearance: AnnotationAppearance {
+                color: "#ffff00".into(),
+                interior_color: None,
+                opacity: 1.0,
+                border_width: None,
+                border_style: None,
+                dash_pattern: None,
+                line_endings: None,
+                font_size: None,
+                icon: None,
+            },
+            metadata: AnnotationMetadata {
+                author: Some(author.into()),
+                contents: Some(contents.into()),
+                rich_contents: None,
+                creation_date: None,
+                modified_date: Some(date.into()),
+            },
+            flags: AnnotationFlags::default(),
+            in_reply_to: None,
+            replies: Vec::new(),
+            review_states: Vec::new(),
+            popup: None,
+        }
+    }
+
+    fn annotations() -> Vec<Annotation> {
+        let mut note = annotation(
+            "note",
+            0,
+            AnnotationType::Text,
+            "Ana",
+            "Check the totals",
+            "D:20240301090000Z",
+        );
+        note.replies.push(annotation(
+            "reply",
+            0,
+            AnnotationType::Text,
+            "Bo",
+            "Fixed in the appendix",
+            "D:20240305090000Z",
+        ));
+
+        let mut square = annotation(
+            "square",
+            2,
+            AnnotationType::Square,
+            "Bo",
+            "Figure",
+            "D:20240210120000Z",
+        );
+        square.appearance.color = "#FF0000".into();
+
+        vec![
+            square,
+            note,
+            annotation(
+                "highlight",
+                1,
+                AnnotationType::Highlight,
+                "Ana",
+                "Typo",
+                "2024-03-01T18:30:00Z",
+            ),
+        ]
+    }
+
+    fn ids(result: &AnnotationQueryResult) -> Vec<&str> {
+        result
+            .annotations
+            .iter()
+            .map(|annotation| annotation.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_filters_match_discussions() {
+        let by_reply_author = AnnotationQuery {
+            authors: vec!["bo".into()],
+            ..Default::default()
+        };
+        let result = filter_annotations(annotations(), &by_reply_author, 3).unwrap();
+        assert_eq!(ids(&result), vec!["note", "square"]);
+
+        let by_reply_text = AnnotationQuery {
+            text: Some("APPENDIX".into()),
+            ..Default::default()
+        };
+        let result = filter_annotations(annotations(), &by_reply_text, 3).unwrap();
+        assert_eq!(ids(&result), vec!["note"]);
+
+        let by_type_color_and_page = AnnotationQuery {
+            subtypes: vec![AnnotationType::Square, AnnotationType::Highlight],
+            colors: vec!["#ff0000".into()],
+            pages: Some("2-3".into()),
+            ..Default::default()
+        };
+        let result = filter_annotations(annotations(), &by_type_color_and_page, 3).unwrap();
+        assert_eq!(ids(&result), vec!["square"]);
+    }
+
+    #[test]
+    fn test_date_digits() {
+        assert_eq!(date_digits("D:20240102030405+01'00'"), "20240102030405");
+        assert_eq!(date_digits("2024-01-02T03:04:05Z"), "20240102030405");
+        assert!(date_digits("D:20250101") > date_digits("D:20240101120000Z"));
+    }
+
+    #[test]
+    fn test_date_range_includes_whole_days() {
+        let query = AnnotationQuery {
+            from: Some("2024-03-01".into()),
+            to: Some("D:20240301".into()),
+            ..Default::default()
+        };
+        let result = filter_annotations(annotations(), &query, 3).unwrap();
+        assert_eq!(ids(&result), vec!["note", "highlight"]);
+    }
+
+    #[test]
+    fn test_sort_and_paging() {
+        let query = AnnotationQuery {
+            sort_by: AnnotationSortKey::Date,
+            descending: true,
+            offset: 1,
+            limit: Some(1),
+            ..Default::default()
+        };
+        let result = filter_annotations(annotations(), &query, 3).unwrap();
+        assert_eq!(result.total, 3);
+        assert_eq!(ids(&result), vec!["note"]);
+    }
+
+    #[test]
+    fn test_invalid_page_selection() {
+        let query = AnnotationQuery {
+            pages: Some("5".into()),
+            ..Default::default()
+        };
+        assert!(filter_annotations(annotations(), &query, 3).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::pdf::reader::annotation::{Annotation, ReviewStateRecord};
+use crate::utils::fs::write_atomic;
+
+const SIDECAR_VERSION: u32 = 1;
+
+/// Where the annotations of a document are written.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationStorage {
+    /// Annotations are written into the PDF
+    Document,
+
+    /// Annotations are kept in a sidecar file and the PDF is left untouched
+    Sidecar,
+}
+
+/// Annotations of one PDF kept outside the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SidecarFile {
+    pub version: u32,
+
+    /// Name of the PDF when the sidecar was created, for troubleshooting only
+    pub file_name: Option<String>,
+
+    /// Annotations in the order they were added; replies keep `in_reply_to`
+    pub annotations: Vec<Annotation>,
+    pub review_states: Vec<ReviewStateRecord>,
+}
+
+impl SidecarFile {
+    pub fn new(file_name: Option<String>) -> Self {
+        Self {
+            version: SIDECAR_VERSION,
+            file_name,
+            annotations: Vec::new(),
+            review_states: Vec::new(),
+        }
+    }
+
+    /// Removes an annotation together with its replies and review states.
+    /// Returns `false` when the annotation is not stored in the sidecar.
+    pub fn remove(&mut self, annotation_id: &str) -> bool {
+        if !self.annotations.iter().any(|a| a.id == annotation_id) {
+            return false;
+        }
+
+        let mut removed = vec![annotation_id.to_string()];
+        let mut index = 0;
+        while index < removed.len() {
+            let target = removed[index].clone();
+            removed.extend(
+                self.annotations
+                    .iter()
+                    .filter(|a| a.in_reply_to.as_deref() == Some(target.as_str()))
+                    .map(|a| a.id.clone()),
+            );
+            index += 1;
+        }
+
+        self.annotations.retain(|a| !removed.contains(&a.id));
+        self.review_states
+            .retain(|state| !removed.contains(&state.annotation_id));
+        true
+    }
+}
+
+/// Sidecar files in a directory (the app data dir), one JSON file per PDF keyed
+/// by the SHA-256 fingerprint of the file's contents.
+///
+/// Keying by content rather than path keeps annotations attached when a shared
+/// file is opened from a different mount point or copied.
+#[derive(Debug, Clone)]
+pub struct SidecarStore {
+    dir: PathBuf,
+}
+
+impl SidecarStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn file_path(&self, fingerprint: &str) -> PathBuf {
+        self.dir.join(format!("{fingerprint}.json"))
+    }
+
+    pub fn exists(&self, fingerprint: &str) -> bool {
+        self.file_path(fingerprint).is_file()
+    }
+
+    pub fn load(&self, fingerprint: &str) -> Result<Option<SidecarFile>, String> {
+        let path = self.file_path(fingerprint);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read annotation sidecar: {e}"))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse annotation sidecar: {e}"))
+    }
+
+    /// Writes the sidecar through a temporary file so an interrupted write never
+    /// loses existing annotations.
+    pub fn save(&self, fingerprint: &str, sidecar: &SidecarFile) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create annotation sidecar dir: {e}"))?;
+
+        let content = serde_json::to_string_pretty(sidecar)
+            .map_err(|e| format!("Failed to serialize annotation sidecar: {e}"))?;
+
+        write_atomic(&self.file_path(fingerprint), content.as_bytes())
+            .map_err(|e| format!("Failed to write annotation sidecar: {e}"))
+    }
+
+    pub fn remove(&self, fingerprint: &str) -> Result<(), String> {
+        match std::fs::remove_file(self.file_path(fingerprint)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to remove annotation sidecar: {e}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// SHA-256 of the file's contents as lowercase hex.
+pub fn file_fingerprint(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to open PDF: {e}"))?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read PDF: {e}"))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Adds sidecar annotations to the threads read from the PDF.
+///
+/// Replies and review states are attached to their target wherever it is stored;
+/// replies whose target no longer exists are shown as top-level annotations.
+pub fn merge_sidecar(threads: &mut Vec<Annotation>, sidecar: &SidecarFile) {
+    for annotation in &sidecar.annotations {
+        let target = annotation
+            .in_reply_to
+            .as_deref()
+            .and_then(|target| find_in_threads(threads, target));
+
+        match target {
+            Some(target) => target.replies.push(annotation.clone()),
+            None => threads.push(annotation.clone()),
+        }
+    }
+
+    for state in &sidecar.review_states {
+        if let Some(target) = find_in_threads(threads, &state.annotation_id) {
+            target.review_states.push(state.status.clone());
+        }
+    }
+}
+
+fn find_in_threads<'a>(threads: &'a mut [Annotation], id: &str) -> Option<&'a mut Annotation> {
+    for annotation in threads {
+        if annotation.id == id {
+            return Some(annotation);
+        }
+        if let Some(found) = find_in_threads(&mut annotation.replies, id) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::annotation::{
+        AnnotationAppearance, AnnotationFlags, AnnotationGeometry, AnnotationMetadata,
+        AnnotationType, PdfRect, ReviewState, ReviewStatus,
+    };
+
+    fn note(id: &str, in_reply_to: Option<&str>) -> Annotation {
+        let rect = PdfRect {
+            left: 0.0,
+            top: 0.0,
+            right: 20.0,
+            bottom: 20.0,
+        };
+
+        Annotation {
+            id: id.into(),
+            page_index: 0,
+            subtype: AnnotationType::Text,
+            rect: rect.clone(),
+            geometry: AnnotationGeometry::Rect(rect),
+            appearance: AnnotationAppearance {
+                color: "#FFFF00".into(),
+                interior_color: None,
+                opacity: 1.0,
+                border_width: None,
+                border_style: None,
+                dash_pattern: None,
+                line_endings: None,
+                font_size: None,
+                icon: None,
+            },
+            metadata: AnnotationMetadata {
+                author: None,
+                contents: None,
+                rich_contents: None,
+                creation_date: None,
+                modified_date: None,
+            },
+            flags: AnnotationFlags::default(),
+            in_reply_to: in_reply_to.map(Into::into),
+            replies: Vec::new(),
+            review_states: Vec::new(),
+            popup: None,
+        }
+    }
+
+    fn sidecar() -> SidecarFile {
+        let mut sidecar = SidecarFile::new(Some("contract.pdf".into()));
+        sidecar.annotations = vec![
+            note("side", None),
+            note("reply-to-pdf", Some("tx-0-0")),
+            note("reply-to-side", Some("side")),
+            note("nested", Some("reply-to-side")),
+        ];
+        sidecar.review_states.push(ReviewStateRecord {
+            page_index: 0,
+            annotation_id: "reply-to-side".into(),
+            status: ReviewStatus {
+                state: ReviewState::Completed,
+                author: None,
+                date: None,
+            },
+        });
+        sidecar
+    }
+
+    #[test]
+    fn test_merge_sidecar_threads() {
+        let mut threads = vec![note("tx-0-0", None)];
+        merge_sidecar(&mut threads, &sidecar());
+
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].replies[0].id, "reply-to-pdf");
+
+        let side = &threads[1];
+        assert_eq!(side.replies[0].id, "reply-to-side");
+        assert_eq!(side.replies[0].replies[0].id, "nested");
+        assert_eq!(side.replies[0].review_states.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_cascades_to_replies() {
+        let mut sidecar = sidecar();
+        assert!(!sidecar.remove("tx-0-0"));
+        assert!(sidecar.remove("side"));
+
+        let ids = sidecar
+            .annotations
+            .iter()
+            .map(|a| a.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["reply-to-pdf"]);
+        assert!(sidecar.review_states.is_empty());
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("velin-sidecar-{}", uuid::Uuid::new_v4()));
+        let store = SidecarStore::new(dir.clone());
+
+        assert!(store.load("abc").unwrap().is_none());
+        store.save("abc", &sidecar()).unwrap();
+        assert!(store.exists("abc"));
+
+        let loaded = store.load("abc").unwrap().unwrap();
+        assert_eq!(loaded.annotations.len(), 4);
+        assert_eq!(loaded.file_name.as_deref(), Some("contract.pdf"));
+
+        store.remove("abc").unwrap();
+        store.remove("abc").unwrap();
+        assert!(!store.exists("abc"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_file_fingerprint() {
+        let path = std::env::temp_dir().join(format!("velin-{}.pdf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"abc").unwrap();
+
+        assert_eq!(
+            file_fingerprint(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let _ = std::fs::remove_file(path);
+    }
+}
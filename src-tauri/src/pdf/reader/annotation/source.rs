@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+/// The file of an open document as the annotation readers and writers see it.
+///
+/// Hashing and parsing a large PDF is slow, so the worker keeps one per file
+/// revision and shares it between requests until the file is rewritten.
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+
+    /// SHA-256 of the contents, naming the document's sidecar
+    pub fingerprint: String,
+
+    /// The file parsed with lopdf, or why it could not be parsed
+    document: Result<lopdf::Document, String>,
+}
+
+impl SourceFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read PDF: {e}"))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            fingerprint: format!("{:x}", Sha256::digest(&bytes)),
+            document: lopdf::Document::load_mem(&bytes)
+                .map_err(|e| format!("Failed to load PDF: {e}")),
+        })
+    }
+
+    pub fn document(&self) -> Result<&lopdf::Document, String> {
+        self.document.as_ref().map_err(Clone::clone)
+    }
+
+    pub fn file_name(&self) -> Option<String> {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_file() {
+        let path = std::env::temp_dir().join(format!("velin-{}.pdf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"abc").unwrap();
+
+        let file = SourceFile::load(&path).unwrap();
+        assert_eq!(
+            file.fingerprint,
+            crate::pdf::reader::file_fingerprint(&path).unwrap()
+        );
+        assert!(file.document().is_err());
+        assert_eq!(
+            file.file_name(),
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        );
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,613 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::editor::inherited;
+use crate::pdf::reader::annotation::appearance::num;
+use crate::pdf::reader::annotation::{Annotation, AnnotationType};
+use crate::utils::fs::write_atomic;
+use crate::utils::objects::copy_object;
+
+const LIBRARY_VERSION: u32 = 1;
+const LIBRARY_FILE: &str = "library.json";
+
+/// Prefix of the built-in stamps that print the author and date under the label.
+const DYNAMIC_PREFIX: &str = "Dynamic";
+
+/// Built-in stamps: `/Name`, label and colour.
+const BUILTIN_STAMPS: [(&str, &str, &str); 3] = [
+    ("Approved", "Approved", "#2E7D32"),
+    ("Draft", "Draft", "#1565C0"),
+    ("Confidential", "Confidential", "#C62828"),
+];
+
+/// Default page size (US Letter) when a PDF stamp page has no usable `/MediaBox`.
+const DEFAULT_PAGE_BOX: [f32; 4] = [0.0, 0.0, 612.0, 792.0];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StampKind {
+    /// Vector stamp drawn from its label
+    Builtin,
+
+    /// PNG, JPEG or other raster image
+    Image,
+
+    /// Page of a PDF file
+    Page,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StampDefinition {
+    /// Icon name used as the annotation's `/Name`
+    pub id: String,
+    pub name: String,
+    pub kind: StampKind,
+
+    /// Built-in stamps that add the author and date under the label
+    #[serde(default)]
+    pub dynamic: bool,
+
+    /// Default colour of built-in stamps
+    pub color: Option<String>,
+
+    /// File in the library directory, for image and page stamps
+    pub file: Option<String>,
+
+    /// Zero-based page of a PDF stamp
+    pub page_index: Option<u16>,
+
+    /// Natural size in points, used for the initial placement
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LibraryFile {
+    version: u32,
+    stamps: Vec<StampDefinition>,
+}
+
+/// Custom stamps kept in a directory (the app data dir): their source files and a
+/// `library.json` index. Built-in stamps are listed first and cannot be removed.
+#[derive(Debug, Clone)]
+pub struct StampLibrary {
+    dir: PathBuf,
+}
+
+impl StampLibrary {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn list(&self) -> Result<Vec<StampDefinition>, String> {
+        let mut stamps = builtin_stamps();
+        stamps.extend(self.load()?);
+        Ok(stamps)
+    }
+
+    pub fn get(&self, id: &str) -> Result<StampDefinition, String> {
+        self.list()?
+            .into_iter()
+            .find(|stamp| stamp.id == id)
+            .ok_or_else(|| format!("Stamp {id} not found"))
+    }
+
+    /// Copies an image, or a page of a PDF, into the library as a new stamp.
+    pub fn add(
+        &self,
+        source: &Path,
+        name: &str,
+        page_index: Option<u16>,
+    ) -> Result<StampDefinition, String> {
+        let extension = source
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .ok_or("Stamp file has no extension")?;
+
+        let (kind, page_index, width, height) = if extension == "pdf" {
+            let document =
+                Document::load(source).map_err(|e| format!("Failed to load PDF: {e}"))?;
+            let page_index = page_index.unwrap_or(0);
+            let page_id = *document
+                .get_pages()
+                .get(&(page_index as u32 + 1))
+                .ok_or_else(|| format!("Page {} not found", page_index + 1))?;
+            let [left, bottom, right, top] = page_box(&document, page_id);
+            (
+                StampKind::Page,
+                Some(page_index),
+                right - left,
+                top - bottom,
+            )
+        } else {
+            let (width, height) = image::image_dimensions(source)
+                .map_err(|e| format!("Failed to read stamp image: {e}"))?;
+            (StampKind::Image, None, width as f32, height as f32)
+        };
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let file = format!("{id}.{extension}");
+
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create stamp library dir: {e}"))?;
+        std::fs::copy(source, self.dir.join(&file))
+            .map_err(|e| format!("Failed to copy stamp file: {e}"))?;
+
+        let name = match name.trim() {
+            "" => source
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Stamp".into()),
+            name => name.to_string(),
+        };
+
+        let stamp = StampDefinition {
+            id,
+            name,
+            kind,
+            dynamic: false,
+            color: None,
+            file: Some(file),
+            page_index,
+            width,
+            height,
+        };
+
+        let mut stamps = self.load()?;
+        stamps.push(stamp.clone());
+        self.save(stamps)?;
+
+        Ok(stamp)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        if builtin_stamps().iter().any(|stamp| stamp.id == id) {
+            return Err("Built-in stamps cannot be removed".into());
+        }
+
+        let mut stamps = self.load()?;
+        let index = stamps
+            .iter()
+            .position(|stamp| stamp.id == id)
+            .ok_or_else(|| format!("Stamp {id} not found"))?;
+        let stamp = stamps.remove(index);
+        self.save(stamps)?;
+
+        if let Some(path) = self.file_path(&stamp) {
+            let _ = std::fs::remove_file(path);
+        }
+
+        Ok(())
+    }
+
+    pub fn file_path(&self, stamp: &StampDefinition) -> Option<PathBuf> {
+        stamp.file.as_ref().map(|file| self.dir.join(file))
+    }
+
+    fn load(&self) -> Result<Vec<StampDefinition>, String> {
+        let path = self.dir.join(LIBRARY_FILE);
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read stamp library: {e}"))?;
+        serde_json::from_str::<LibraryFile>(&content)
+            .map(|library| library.stamps)
+            .map_err(|e| format!("Failed to parse stamp library: {e}"))
+    }
+
+    fn save(&self, stamps: Vec<StampDefinition>) -> Result<(), String> {
+        let library = LibraryFile {
+            version: LIBRARY_VERSION,
+            stamps,
+        };
+        let content = serde_json::to_string_pretty(&library)
+            .map_err(|e| format!("Failed to serialize stamp library: {e}"))?;
+
+        write_atomic(&self.dir.join(LIBRARY_FILE), content.as_bytes())
+    }
+}
+
+pub fn builtin_stamps() -> Vec<StampDefinition> {
+    [false, true]
+        .into_iter()
+        .flat_map(|dynamic| {
+            BUILTIN_STAMPS.map(|(id, name, color)| StampDefinition {
+                id: if dynamic {
+                    format!("{DYNAMIC_PREFIX}{id}")
+                } else {
+                    id.to_string()
+                },
+                name: name.to_string(),
+                kind: StampKind::Builtin,
+                dynamic,
+                color: Some(color.to_string()),
+                file: None,
+                page_index: None,
+                width: 180.0,
+                height: if dynamic { 60.0 } else { 45.0 },
+            })
+        })
+        .collect()
+}
+
+/// Label drawn for a stamp icon name and whether it is a dynamic stamp. Names that
+/// are not built-in (including standard names such as `NotApproved`) are drawn as
+/// their own label; custom stamps are drawn from the library and have none.
+pub fn stamp_label(name: &str) -> (String, bool) {
+    if is_custom_stamp(name) {
+        return (String::new(), false);
+    }
+
+    let (base, dynamic) = match name.strip_prefix(DYNAMIC_PREFIX) {
+        Some(base) if !base.is_empty() => (base, true),
+        _ => (name, false),
+    };
+
+    match BUILTIN_STAMPS.iter().find(|(id, _, _)| *id == base) {
+        Some((_, label, _)) => (label.to_string(), dynamic),
+        None => (name.to_string(), false),
+    }
+}
+
+/// Detail line of a dynamic stamp: `Ana Lima, 2024-03-01 14:05`.
+pub fn stamp_detail(author: Option<&str>, date: &str) -> String {
+    let digits = date
+        .trim_start_matches("D:")
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>();
+
+    let date = match digits.len() {
+        12.. => format!(
+            "{}-{}-{} {}:{}",
+            &digits[0..4],
+            &digits[4..6],
+            &digits[6..8],
+            &digits[8..10],
+            &digits[10..12]
+        ),
+        8.. => format!("{}-{}-{}", &digits[0..4], &digits[4..6], &digits[6..8]),
+        _ => digits,
+    };
+
+    match author.filter(|author| !author.is_empty()) {
+        Some(author) => format!("{author}, {date}"),
+        None => date,
+    }
+}
+
+/// Library stamp named by a Stamp annotation's icon, with its file. Built-in and
+/// standard stamp names give `None`; a custom stamp missing from the library (or
+/// with no library to look in) is an error, since there is nothing to draw.
+pub fn custom_stamp(
+    annotation: &Annotation,
+    library: Option<&StampLibrary>,
+) -> Result<Option<(StampDefinition, PathBuf)>, String> {
+    let name = match annotation.appearance.icon.as_deref() {
+        Some(name) if annotation.subtype == AnnotationType::Stamp && is_custom_stamp(name) => name,
+        _ => return Ok(None),
+    };
+
+    let library = library.ok_or_else(|| format!("Stamp {name} not found"))?;
+    let stamp = library.get(name)?;
+    let path = library.file_path(&stamp).ok_or("Stamp has no file")?;
+
+    Ok(Some((stamp, path)))
+}
+
+/// Custom stamps are named by the UUID the library gave them when they were added.
+pub fn is_custom_stamp(name: &str) -> bool {
+    uuid::Uuid::parse_str(name).is_ok()
+}
+
+/// Appearance stream showing a custom image or PDF page scaled to `rect`, in PDF
+/// coordinates.
+pub fn stamp_appearance(
+    document: &mut Document,
+    rect: [f32; 4],
+    stamp: &StampDefinition,
+    path: &Path,
+) -> Result<Stream, String> {
+    let (xobject_id, bbox) = match stamp.kind {
+        StampKind::Image => (image_xobject(document, path)?, [0.0, 0.0, 1.0, 1.0]),
+        StampKind::Page => page_xobject(document, path, stamp.page_index.unwrap_or(0))?,
+        StampKind::Builtin => return Err(format!("Stamp {} has no file", stamp.id)),
+    };
+
+    let [left, bottom, right, top] = rect;
+    let scale_x = (right - left) / (bbox[2] - bbox[0]);
+    let scale_y = (top - bottom) / (bbox[3] - bbox[1]);
+    let content = format!(
+        "q {} 0 0 {} {} {} cm /Stamp Do Q\n",
+        num(scale_x),
+        num(scale_y),
+        num(left - bbox[0] * scale_x),
+        num(bottom - bbox[1] * scale_y),
+    );
+
+    Ok(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "FormType" => 1,
+            "BBox" => vec![left.into(), bottom.into(), right.into(), top.into()],
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Stamp" => xobject_id },
+            },
+        },
+        content.into_bytes(),
+    ))
+}
+
+/// Adds an image XObject drawn in the unit square, with a soft mask when the image
+/// has transparency.
+pub fn image_xobject(document: &mut Document, path: &Path) -> Result<ObjectId, String> {
+    let image = image::open(path)
+        .map_err(|e| format!("Failed to load image: {e}"))?
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+
+    let rgb = image
+        .pixels()
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect::<Vec<_>>();
+    let alpha = image.pixels().map(|pixel| pixel[3]).collect::<Vec<_>>();
+
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => width as i64,
+        "Height" => height as i64,
+        "ColorSpace" => "DeviceRGB",
+        "BitsPerComponent" => 8,
+    };
+
+    if alpha.iter().any(|value| *value < u8::MAX) {
+        let mut mask = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => width as i64,
+                "Height" => height as i64,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            alpha,
+        );
+        let _ = mask.compress();
+        dict.set("SMask", document.add_object(mask));
+    }
+
+    let mut stream = Stream::new(dict, rgb);
+    let _ = stream.compress();
+
+    Ok(document.add_object(stream))
+}
+
+/// Adds a form XObject with the content and resources of a page of another PDF and
+/// returns it with its bounding box.
+fn page_xobject(
+    document: &mut Document,
+    path: &Path,
+    page_index: u16,
+) -> Result<(ObjectId, [f32; 4]), String> {
+    let source = Document::load(path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+    let page_id = *source
+        .get_pages()
+        .get(&(page_index as u32 + 1))
+        .ok_or_else(|| format!("Page {} not found", page_index + 1))?;
+
+    let content = source
+        .get_page_content(page_id)
+        .map_err(|e| format!("Failed to read stamp page: {e}"))?;
+    let bbox = page_box(&source, page_id);
+
+    // Only the resources are copied; following the page's own references would
+    // pull in the rest of the source document.
+    let resources = inherited(&source, page_id, b"Resources")
+        .cloned()
+        .unwrap_or_else(|| Object::Dictionary(Dictionary::new()));
+    let resources = copy_object(document, &source, &resources, &mut HashMap::new());
+
+    let mut stream = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "FormType" => 1,
+            "BBox" => bbox.iter().map(|value| Object::Real(*value)).collect::<Vec<_>>(),
+            "Resources" => resources,
+        },
+        content,
+    );
+    let _ = stream.compress();
+
+    Ok((document.add_object(stream), bbox))
+}
+
+/// Visible area of a page: its crop box, or media box.
+fn page_box(document: &Document, page_id: ObjectId) -> [f32; 4] {
+    [b"CropBox".as_slice(), b"MediaBox"]
+        .iter()
+        .find_map(|key| {
+            let values = inherited(document, page_id, key)?;
+            let values = document.dereference(values).ok()?.1.as_array().ok()?;
+            let values = values
+                .iter()
+                .map(|value| value.as_float().ok())
+                .collect::<Option<Vec<_>>>()?;
+            let [x1, y1, x2, y2] = values[..] else {
+                return None;
+            };
+            Some([x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)])
+        })
+        .filter(|[left, bottom, right, top]| right > left && top > bottom)
+        .unwrap_or(DEFAULT_PAGE_BOX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::annotation::create::{build_dictionary, create_annotation};
+    use crate::pdf::reader::annotation::dictionary::page_annotations;
+    use crate::pdf::reader::annotation::{
+        AnnotationAppearance, AnnotationFlags, AnnotationGeometry, AnnotationMetadata,
+        AnnotationType, PdfRect,
+    };
+
+    fn stamp(icon: &str) -> Annotation {
+        let rect = PdfRect {
+            left: 100.0,
+            top: 100.0,
+            right: 280.0,
+            bottom: 160.0,
+        };
+        Annotation {
+            id: String::new(),
+            page_index: 0,
+            subtype: AnnotationType::Stamp,
+            rect: rect.clone(),
+            geometry: AnnotationGeometry::Rect(rect),
+            appearance: AnnotationAppearance {
+                color: "#2E7D32".into(),
+                interior_color: None,
+                opacity: 1.0,
+                border_width: None,
+                border_style: None,
+                dash_pattern: None,
+                line_endings: None,
+                font_size: None,
+                icon: Some(icon.into()),
+            },
+            metadata: AnnotationMetadata {
+                author: Some("Ana Lima".into()),
+                contents: None,
+                rich_contents: None,
+                creation_date: Some("D:20240301140512Z".into()),
+                modified_date: None,
+            },
+            flags: AnnotationFlags::default(),
+            in_reply_to: None,
+            replies: Vec::new(),
+            review_states: Vec::new(),
+            popup: None,
+        }
+    }
+
+    fn single_page_document() -> Document {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 600.into(), 800.into()],
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("velin-stamps-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_labels_and_dynamic_detail() {
+        assert_eq!(stamp_label("Approved"), ("Approved".into(), false));
+        assert_eq!(stamp_label("DynamicDraft"), ("Draft".into(), true));
+        assert_eq!(stamp_label("NotApproved"), ("NotApproved".into(), false));
+
+        assert_eq!(
+            stamp_detail(Some("Ana Lima"), "D:20240301140512Z"),
+            "Ana Lima, 2024-03-01 14:05"
+        );
+        assert_eq!(stamp_detail(None, "D:20240301"), "2024-03-01");
+    }
+
+    #[test]
+    fn test_builtin_dynamic_stamp_appearance() {
+        let (dict, appearance) = build_dictionary(&stamp("DynamicApproved"), 800.0).unwrap();
+        assert_eq!(dict.get(b"Subtype").unwrap().as_name().unwrap(), b"Stamp");
+        assert_eq!(
+            dict.get(b"Name").unwrap().as_name().unwrap(),
+            b"DynamicApproved"
+        );
+
+        let content = String::from_utf8(appearance.content).unwrap();
+        let hex = |text: &str| text.bytes().map(|b| format!("{b:02X}")).collect::<String>();
+        assert!(content.contains(&hex("APPROVED")));
+        assert!(content.contains(&hex("Ana Lima, 2024-03-01 14:05")));
+    }
+
+    #[test]
+    fn test_library_add_list_remove_and_image_stamp() {
+        let dir = temp_dir();
+        let source = dir.join("seal.png");
+        image::RgbaImage::from_pixel(4, 2, image::Rgba([200, 0, 0, 128]))
+            .save(&source)
+            .unwrap();
+
+        let library = StampLibrary::new(dir.join("stamps"));
+        let seal = library.add(&source, "", None).unwrap();
+        assert_eq!(seal.name, "seal");
+        assert_eq!(seal.kind, StampKind::Image);
+        assert_eq!((seal.width, seal.height), (4.0, 2.0));
+        assert_eq!(library.list().unwrap().len(), builtin_stamps().len() + 1);
+
+        let mut document = single_page_document();
+        let annotation_id =
+            create_annotation(&mut document, &stamp(&seal.id), 800.0, Some(&library)).unwrap();
+
+        let page_id = document.get_pages()[&1];
+        let created = page_annotations(&document, page_id)[0].unwrap();
+        assert_eq!(created.object_id, Some(annotation_id));
+
+        let normal = created
+            .dict
+            .get(b"AP")
+            .and_then(|appearance| appearance.as_dict())
+            .and_then(|appearance| appearance.get(b"N"))
+            .and_then(|normal| normal.as_reference())
+            .unwrap();
+        let form = document.get_object(normal).unwrap().as_stream().unwrap();
+        assert_eq!(form.content, b"q 180 0 0 60 100 640 cm /Stamp Do Q\n");
+
+        let image_id = form
+            .dict
+            .get(b"Resources")
+            .and_then(|resources| resources.as_dict())
+            .and_then(|resources| resources.get(b"XObject"))
+            .and_then(|xobjects| xobjects.as_dict())
+            .and_then(|xobjects| xobjects.get(b"Stamp"))
+            .and_then(|image| image.as_reference())
+            .unwrap();
+        let image = document.get_object(image_id).unwrap().as_stream().unwrap();
+        assert!(image.dict.has(b"SMask"));
+
+        library.remove(&seal.id).unwrap();
+        assert!(library.get(&seal.id).is_err());
+        assert!(library.remove("Approved").is_err());
+        assert!(create_annotation(&mut document, &stamp(&seal.id), 800.0, Some(&library)).is_err());
+        assert!(create_annotation(&mut document, &stamp(&seal.id), 800.0, None).is_err());
+        assert_eq!(page_annotations(&document, page_id).len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::Path;
+
+use pdfium_render::prelude::{PdfDocument, PdfPage};
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::annotation::{
+    get_annotations, Annotation, AnnotationGeometry, AnnotationType, PdfRect, Quad, SidecarStore,
+    SourceFile,
+};
+use crate::pdf::DocumentId;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightSummaryFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightGrouping {
+    Page,
+    Color,
+}
+
+/// Highlights of a document with the text under them, grouped for export.
+#[derive(Debug, Clone, Serialize)]
+pub struct HighlightSummary {
+    pub file: Option<String>,
+    pub groups: Vec<HighlightGroup>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HighlightGroup {
+    /// "Page 3" or the colour, depending on the grouping
+    pub title: String,
+    pub entries: Vec<HighlightEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HighlightEntry {
+    /// One-based page number
+    pub page: u16,
+    pub subtype: AnnotationType,
+    pub color: String,
+
+    /// Text under the annotation's quads
+    pub text: String,
+    pub comment: Option<String>,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    pub replies: Vec<HighlightReply>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HighlightReply {
+    pub author: Option<String>,
+    pub comment: String,
+}
+
+/// Writes a summary of the document's text markup annotations to `dest`.
+pub fn export_highlight_summary(
+    documents: &HashMap<DocumentId, PdfDocument>,
+    file: &SourceFile,
+    id: &DocumentId,
+    format: HighlightSummaryFormat,
+    group_by: HighlightGrouping,
+    dest: &Path,
+    sidecar: Option<&SidecarStore>,
+) -> Result<(), String> {
+    let document = documents.get(id).ok_or("Document not found")?;
+    let annotations = get_annotations(documents, file, id, sidecar)?;
+
+    let mut page_chars = HashMap::new();
+    let mut entries = Vec::new();
+
+    for annotation in &annotations {
+        let AnnotationGeometry::QuadPoints(quads) = &annotation.geometry else {
+            continue;
+        };
+
+        let chars = match page_chars.entry(annotation.page_index) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let page = document
+                    .pages()
+                    .get(annotation.page_index)
+                    .map_err(|e| format!("Failed to get page: {e}"))?;
+                entry.insert(read_chars(&page)?)
+            }
+        };
+
+        entries.push(entry(annotation, text_under_quads(chars, quads)));
+    }
+
+    let summary = HighlightSummary {
+        file: file.file_name(),
+        groups: group_entries(entries, group_by),
+    };
+
+    let output = match format {
+        HighlightSummaryFormat::Markdown => to_markdown(&summary),
+        HighlightSummaryFormat::Html => to_html(&summary),
+        HighlightSummaryFormat::Json => serde_json::to_string_pretty(&summary)
+            .map_err(|e| format!("Failed to serialize summary: {e}"))?,
+    };
+
+    std::fs::write(dest, output).map_err(|e| format!("Failed to write summary: {e}"))
+}
+
+fn entry(annotation: &Annotation, text: String) -> HighlightEntry {
+    let metadata = &annotation.metadata;
+
+    HighlightEntry {
+        page: annotation.page_index + 1,
+        subtype: annotation.subtype.clone(),
+        color: annotation.appearance.color.clone(),
+        text,
+        comment: metadata.contents.clone().filter(|c| !c.trim().is_empty()),
+        author: metadata.author.clone(),
+        date: metadata
+            .modified_date
+            .as_deref()
+            .or(metadata.creation_date.as_deref())
+            .map(display_date),
+        replies: flatten_replies(annotation),
+    }
+}
+
+fn flatten_replies(annotation: &Annotation) -> Vec<HighlightReply> {
+    annotation
+        .replies
+        .iter()
+        .flat_map(|reply| {
+            let own = reply
+                .metadata
+                .contents
+                .clone()
+                .filter(|c| !c.trim().is_empty())
+                .map(|comment| HighlightReply {
+                    author: reply.metadata.author.clone(),
+                    comment,
+                });
+            own.into_iter().chain(flatten_replies(reply))
+        })
+        .collect()
+}
+
+/// Characters of a page in reading order with their top-left based bounds.
+/// Generated characters without bounds (spaces, line breaks) have `None`.
+fn read_chars(page: &PdfPage) -> Result<Vec<(char, Option<PdfRect>)>, String> {
+    let text_page = page.text().map_err(|e| e.to_string())?;
+    let page_height = page.height().value;
+    let chars = text_page.chars();
+
+    let mut result = Vec::with_capacity(chars.len());
+    for i in 0..chars.len() {
+        let ch = chars.get(i).map_err(|e| e.to_string())?;
+        let Some(unicode) = ch.unicode_char() else {
+            continue;
+        };
+
+        let rect = ch
+            .loose_bounds()
+            .ok()
+            .filter(|rect| rect.width().value > 0.0 && rect.height().value > 0.0)
+            .map(|rect| PdfRect {
+                left: rect.left().value,
+                top: page_height - rect.top().value,
+                right: rect.right().value,
+                bottom: page_height - rect.bottom().value,
+            });
+
+        result.push((unicode, rect));
+    }
+
+    Ok(result)
+}
+
+/// Collects the characters whose centre lies inside one of the quads.
+///
+/// Runs of characters outside the quads, and whitespace, become a single space.
+fn text_under_quads(chars: &[(char, Option<PdfRect>)], quads: &[Quad]) -> String {
+    let bounds = quads.iter().map(quad_bounds).collect::<Vec<_>>();
+
+    let mut text = String::new();
+    let mut gap = false;
+
+    for (ch, rect) in chars {
+        let inside = rect.as_ref().is_some_and(|rect| {
+            let x = (rect.left + rect.right) / 2.0;
+            let y = (rect.top + rect.bottom) / 2.0;
+            bounds
+                .iter()
+                .any(|b| x >= b.left && x <= b.right && y >= b.top && y <= b.bottom)
+        });
+
+        if inside && !ch.is_whitespace() {
+            if gap && !text.is_empty() {
+                text.push(' ');
+            }
+            text.push(*ch);
+            gap = false;
+        } else {
+            gap = true;
+        }
+    }
+
+    text
+}
+
+fn quad_bounds(quad: &Quad) -> PdfRect {
+    let xs = [quad.p1.x, quad.p2.x, quad.p3.x, quad.p4.x];
+    let ys = [quad.p1.y, quad.p2.y, quad.p3.y, quad.p4.y];
+
+    PdfRect {
+        left: xs.iter().copied().fold(f32::MAX, f32::min),
+        top: ys.iter().copied().fold(f32::MAX, f32::min),
+        right: xs.iter().copied().fold(f32::MIN, f32::max),
+        bottom: ys.iter().copied().fold(f32::MIN, f32::max),
+    }
+}
+
+/// Groups entries by page, or by colour in order of first appearance. Entries keep
+/// document order within a group.
+fn group_entries(entries: Vec<HighlightEntry>, group_by: HighlightGrouping) -> Vec<HighlightGroup> {
+    let mut groups: Vec<HighlightGroup> = Vec::new();
+
+    for entry in entries {
+        let title = match group_by {
+            HighlightGrouping::Page => format!("Page {}", entry.page),
+            HighlightGrouping::Color => entry.color.to_uppercase(),
+        };
+
+        match groups.iter_mut().find(|group| group.title == title) {
+            Some(group) => group.entries.push(entry),
+            None => groups.push(HighlightGroup {
+                title,
+                entries: vec![entry],
+            }),
+        }
+    }
+
+    groups
+}
+
+fn to_markdown(summary: &HighlightSummary) -> String {
+    let mut output = format!(
+        "# Highlights{}\n",
+        summary
+            .file
+            .as_ref()
+            .map(|file| format!(" — {file}"))
+            .unwrap_or_default()
+    );
+
+    for group in &summary.groups {
+        output.push_str(&format!("\n## {}\n", group.title));
+
+        for entry in &group.entries {
+            output.push('\n');
+            for line in entry.text.lines() {
+                output.push_str(&format!("> {line}\n"));
+            }
+            if entry.text.is_empty() {
+                output.push_str(">\n");
+            }
+
+            output.push_str(&format!("\n*{}*\n", details(entry).join(" · ")));
+
+            if let Some(comment) = &entry.comment {
+                output.push_str(&format!("\n{comment}\n"));
+            }
+            for reply in &entry.replies {
+                output.push_str(&format!(
+                    "\n- {}{}",
+                    reply
+                        .author
+                        .as_ref()
+                        .map(|author| format!("**{author}:** "))
+                        .unwrap_or_default(),
+                    reply.comment
+                ));
+            }
+            if !entry.replies.is_empty() {
+                output.push('\n');
+            }
+        }
+    }
+
+    output
+}
+
+fn to_html(summary: &HighlightSummary) -> String {
+    let title = format!(
+        "Highlights{}",
+        summary
+            .file
+            .as_ref()
+            .map(|file| format!(" — {file}"))
+            .unwrap_or_default()
+    );
+
+    let mut output = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+         <style>\n\
+         body {{ font-family: sans-serif; max-width: 48rem; margin: 2rem auto; }}\n\
+         blockquote {{ margin: 1rem 0 0.25rem; padding: 0.25rem 0.75rem; border-left: 4px solid; }}\n\
+         .details {{ color: #666; font-size: 0.875rem; }}\n\
+         </style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape_html(&title)
+    );
+
+    for group in &summary.groups {
+        output.push_str(&format!(
+            "<section>\n<h2>{}</h2>\n",
+            escape_html(&group.title)
+        ));
+
+        for entry in &group.entries {
+            output.push_str(&format!(
+                "<blockquote style=\"border-color: {}\">{}</blockquote>\n",
+                escape_html(&entry.color),
+                escape_html(&entry.text)
+            ));
+            output.push_str(&format!(
+                "<p class=\"details\">{}</p>\n",
+                escape_html(&details(entry).join(" · "))
+            ));
+
+            if let Some(comment) = &entry.comment {
+                output.push_str(&format!("<p>{}</p>\n", escape_html(comment)));
+            }
+            if !entry.replies.is_empty() {
+                output.push_str("<ul>\n");
+                for reply in &entry.replies {
+                    let author = reply
+                        .author
+                        .as_ref()
+                        .map(|author| format!("<strong>{}:</strong> ", escape_html(author)))
+                        .unwrap_or_default();
+                    output.push_str(&format!(
+                        "<li>{author}{}</li>\n",
+                        escape_html(&reply.comment)
+                    ));
+                }
+                output.push_str("</ul>\n");
+            }
+        }
+
+        output.push_str("</section>\n");
+    }
+
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+fn details(entry: &HighlightEntry) -> Vec<String> {
+    let mut details = vec![
+        format!("Page {}", entry.page),
+        format!("{:?}", entry.subtype),
+        entry.color.to_uppercase(),
+    ];
+    details.extend(entry.author.clone());
+    details.extend(entry.date.clone());
+    details
+}
+
+/// Formats `D:YYYYMMDD...` as `YYYY-MM-DD`; other values are returned unchanged.
+fn display_date(date: &str) -> String {
+    let digits = date.trim_start_matches("D:");
+    match (digits.get(0..4), digits.get(4..6), digits.get(6..8)) {
+        (Some(year), Some(month), Some(day)) if digits[..8].chars().all(|c| c.is_ascii_digit()) => {
+            format!("{year}-{month}-{day}")
+        }
+        _ => date.to_string(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::annotation::Point;
+
+    fn rect(left: f32, top: f32, right: f32, bottom: f32) -> Option<PdfRect> {
+        Some(PdfRect {
+            left,
+            top,
+            right,
+            bottom,
+        })
+    }
+
+    fn quad(left: f32, top: f32, right: f32, bottom: f32) -> Quad {
+        Quad {
+            p1: Point { x: left, y: top },
+            p2: Point { x: right, y: top },
+            p3: Point { x: left, y: bottom },
+            p4: Point {
+                x: right,
+                y: bottom,
+            },
+        }
+    }
+
+    #[test]
+    fn test_text_under_quads() {
+        let chars = vec![
+            ('N', rect(0.0, 0.0, 5.0, 10.0)),
+            ('o', rect(5.0, 0.0, 10.0, 10.0)),
+            (' ', None),
+            ('y', rect(15.0, 0.0, 20.0, 10.0)),
+            ('e', rect(20.0, 0.0, 25.0, 10.0)),
+            ('s', rect(25.0, 0.0, 30.0, 10.0)),
+            ('\n', None),
+            ('o', rect(0.0, 20.0, 5.0, 30.0)),
+            ('k', rect(5.0, 20.0, 10.0, 30.0)),
+            ('!', rect(10.0, 20.0, 15.0, 30.0)),
+        ];
+
+        let quads = [quad(14.0, 0.0, 31.0, 10.0), quad(0.0, 20.0, 11.0, 30.0)];
+        assert_eq!(text_under_quads(&chars, &quads), "yes ok");
+    }
+
+    #[test]
+    fn test_group_and_render() {
+        let entry = |page: u16, color: &str, text: &str| HighlightEntry {
+            page,
+            subtype: AnnotationType::Highlight,
+            color: color.into(),
+            text: text.into(),
+            comment: None,
+            author: None,
+            date: Some(display_date("D:20240315120000Z")),
+            replies: Vec::new(),
+        };
+
+        let mut first = entry(1, "#ffff00", "Tom & <Jerry>");
+        first.comment = Some("Classic".into());
+        first.replies.push(HighlightReply {
+            author: Some("Bo".into()),
+            comment: "Agreed".into(),
+        });
+
+        let entries = vec![
+            first,
+            entry(2, "#00FF00", "green"),
+            entry(3, "#FFFF00", "more"),
+        ];
+        let groups = group_entries(entries.clone(), HighlightGrouping::Color);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].title, "#FFFF00");
+        assert_eq!(groups[0].entries.len(), 2);
+
+        let summary = HighlightSummary {
+            file: Some("paper.pdf".into()),
+            groups: group_entries(entries, HighlightGrouping::Page),
+        };
+        assert_eq!(summary.groups.len(), 3);
+
+        let markdown = to_markdown(&summary);
+        assert!(markdown.starts_with("# Highlights — paper.pdf\n"));
+        assert!(markdown.contains("## Page 1\n\n> Tom & <Jerry>\n"));
+        assert!(markdown.contains("*Page 1 · Highlight · #FFFF00 · 2024-03-15*"));
+        assert!(markdown.contains("- **Bo:** Agreed"));
+
+        let html = to_html(&summary);
+        assert!(html.contains("Tom &amp; &lt;Jerry&gt;"));
+        assert!(html.contains("<li><strong>Bo:</strong> Agreed</li>"));
+    }
+}
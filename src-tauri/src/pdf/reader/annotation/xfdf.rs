@@ -0,0 +1,859 @@
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+use crate::pdf::reader::annotation::create::line_ending_name;
+use crate::pdf::reader::annotation::{
+    Annotation, AnnotationAppearance, AnnotationFlags, AnnotationGeometry, AnnotationMetadata,
+    AnnotationPopup, AnnotationType, BorderStyle, LineEnding, LineEndings, PdfRect, Point, Quad,
+    ReviewState, ReviewStateRecord, ReviewStatus,
+};
+
+const XFDF_NAMESPACE: &str = "http://ns.adobe.com/xfdf/";
+
+/// Annotations read from an XFDF file.
+///
+/// Annotations are flat and in file order; replies keep the name of their target in
+/// `in_reply_to`. Review states are listed separately because their target may be
+/// an annotation that only exists in the PDF.
+#[derive(Debug, Default)]
+pub struct XfdfAnnotations {
+    /// Value of `<f href>`, the PDF the annotations were exported from
+    pub file: Option<String>,
+    pub annotations: Vec<Annotation>,
+    pub review_states: Vec<ReviewStateRecord>,
+}
+
+/// Writes annotations as XFDF.
+///
+/// `annotations` is the threaded list returned by `get_annotations`; replies and
+/// review states are written as separate elements linked with `inreplyto`.
+/// `page_heights` converts the top-left coordinates of the model back to PDF space.
+pub fn write_xfdf(
+    annotations: &[Annotation],
+    page_heights: &[f32],
+    file_name: Option<&str>,
+) -> Result<String, String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+
+    let result = (|| -> std::io::Result<()> {
+        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+        writer.write_event(Event::Start(
+            BytesStart::new("xfdf")
+                .with_attributes([("xmlns", XFDF_NAMESPACE), ("xml:space", "preserve")]),
+        ))?;
+        writer.write_event(Event::Start(BytesStart::new("annots")))?;
+
+        for annotation in annotations {
+            write_thread(&mut writer, annotation, page_heights)?;
+        }
+
+        writer.write_event(Event::End(BytesEnd::new("annots")))?;
+
+        if let Some(file_name) = file_name {
+            writer.write_event(Event::Empty(
+                BytesStart::new("f").with_attributes([("href", file_name)]),
+            ))?;
+        }
+
+        writer.write_event(Event::End(BytesEnd::new("xfdf")))
+    })();
+
+    result.map_err(|e| format!("Failed to write XFDF: {e}"))?;
+
+    String::from_utf8(writer.into_inner()).map_err(|e| format!("Failed to write XFDF: {e}"))
+}
+
+fn write_thread(
+    writer: &mut Writer<Vec<u8>>,
+    annotation: &Annotation,
+    page_heights: &[f32],
+) -> std::io::Result<()> {
+    let Some(page_height) = page_heights.get(annotation.page_index as usize).copied() else {
+        return Ok(());
+    };
+
+    // Link annotations and popups are not part of the XFDF comment model.
+    if let Some(element) = element_name(&annotation.subtype) {
+        write_annotation(writer, annotation, element, page_height)?;
+    }
+
+    for (index, status) in annotation.review_states.iter().enumerate() {
+        write_review_state(writer, annotation, index, status, page_height)?;
+    }
+
+    for reply in &annotation.replies {
+        write_thread(writer, reply, page_heights)?;
+    }
+
+    Ok(())
+}
+
+fn write_annotation(
+    writer: &mut Writer<Vec<u8>>,
+    annotation: &Annotation,
+    element: &str,
+    page_height: f32,
+) -> std::io::Result<()> {
+    let appearance = &annotation.appearance;
+    let metadata = &annotation.metadata;
+
+    let mut attributes = vec![
+        ("page", annotation.page_index.to_string()),
+        ("rect", rect_value(&annotation.rect, page_height)),
+        ("name", annotation.id.clone()),
+        ("color", appearance.color.clone()),
+        ("opacity", number(appearance.opacity)),
+        ("flags", flags_value(&annotation.flags)),
+    ];
+
+    let optional = [
+        ("title", metadata.author.clone()),
+        ("date", metadata.modified_date.clone()),
+        ("creationdate", metadata.creation_date.clone()),
+        ("interior-color", appearance.interior_color.clone()),
+        ("width", appearance.border_width.map(number)),
+        (
+            "style",
+            appearance.border_style.map(|s| style_value(s).into()),
+        ),
+        (
+            "dashes",
+            appearance.dash_pattern.as_deref().map(numbers_value),
+        ),
+        ("icon", appearance.icon.clone()),
+        ("inreplyto", annotation.in_reply_to.clone()),
+        (
+            "replyType",
+            annotation.in_reply_to.as_ref().map(|_| "reply".into()),
+        ),
+    ];
+    attributes.extend(
+        optional
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|value| (key, value))),
+    );
+
+    match &annotation.geometry {
+        AnnotationGeometry::Line { start, end } => {
+            attributes.push((
+                "start",
+                points_value(std::slice::from_ref(start), page_height),
+            ));
+            attributes.push(("end", points_value(std::slice::from_ref(end), page_height)));
+        }
+        AnnotationGeometry::QuadPoints(quads) => {
+            let points = quads
+                .iter()
+                .flat_map(|q| [q.p1.clone(), q.p2.clone(), q.p3.clone(), q.p4.clone()])
+                .collect::<Vec<_>>();
+            attributes.push((
+                "coords",
+                points_value(&points, page_height).replace(';', ","),
+            ));
+        }
+        _ => {}
+    }
+
+    if let Some(endings) = &appearance.line_endings {
+        attributes.push(("head", line_ending_value(endings.start)));
+        attributes.push(("tail", line_ending_value(endings.end)));
+    }
+
+    writer.write_event(Event::Start(
+        BytesStart::new(element).with_attributes(attributes.iter().map(|(k, v)| (*k, v.as_str()))),
+    ))?;
+
+    if let Some(contents) = &metadata.contents {
+        write_text_element(writer, "contents", contents)?;
+    }
+
+    if let Some(rich_contents) = &metadata.rich_contents {
+        // Rich text is XHTML and is embedded as markup, not as escaped text.
+        writer.write_event(Event::Start(BytesStart::new("contents-richtext")))?;
+        writer.write_event(Event::Text(BytesText::from_escaped(rich_contents.as_str())))?;
+        writer.write_event(Event::End(BytesEnd::new("contents-richtext")))?;
+    }
+
+    if annotation.subtype == AnnotationType::FreeText {
+        let (r, g, b) = parse_rgb(&appearance.color);
+        let font_size = appearance.font_size.unwrap_or(12.0);
+        write_text_element(
+            writer,
+            "defaultappearance",
+            &format!(
+                "{} {} {} rg /Helv {} Tf",
+                number(r),
+                number(g),
+                number(b),
+                number(font_size)
+            ),
+        )?;
+    }
+
+    match &annotation.geometry {
+        AnnotationGeometry::Points(points) => {
+            write_text_element(writer, "vertices", &points_value(points, page_height))?;
+        }
+        AnnotationGeometry::InkPaths(paths) => {
+            writer.write_event(Event::Start(BytesStart::new("inklist")))?;
+            for path in paths {
+                write_text_element(writer, "gesture", &points_value(path, page_height))?;
+            }
+            writer.write_event(Event::End(BytesEnd::new("inklist")))?;
+        }
+        _ => {}
+    }
+
+    if let Some(popup) = &annotation.popup {
+        writer.write_event(Event::Empty(BytesStart::new("popup").with_attributes([
+            ("page", annotation.page_index.to_string().as_str()),
+            ("rect", rect_value(&popup.rect, page_height).as_str()),
+            ("open", if popup.open { "yes" } else { "no" }),
+        ])))?;
+    }
+
+    writer.write_event(Event::End(BytesEnd::new(element)))?;
+
+    Ok(())
+}
+
+/// Review states are written as hidden `text` elements replying to their target,
+/// matching the state annotations Acrobat exports.
+fn write_review_state(
+    writer: &mut Writer<Vec<u8>>,
+    annotation: &Annotation,
+    index: usize,
+    status: &ReviewStatus,
+    page_height: f32,
+) -> std::io::Result<()> {
+    let mut attributes = vec![
+        ("page", annotation.page_index.to_string()),
+        ("rect", rect_value(&annotation.rect, page_height)),
+        ("name", format!("{}-state-{index}", annotation.id)),
+        ("flags", "hidden,print".to_string()),
+        ("inreplyto", annotation.id.clone()),
+        ("replyType", "reply".to_string()),
+        ("state", status.state.name().to_string()),
+        ("statemodel", status.state.model().to_string()),
+    ];
+    if let Some(author) = &status.author {
+        attributes.push(("title", author.clone()));
+    }
+    if let Some(date) = &status.date {
+        attributes.push(("date", date.clone()));
+    }
+
+    writer.write_event(Event::Empty(
+        BytesStart::new("text").with_attributes(attributes.iter().map(|(k, v)| (*k, v.as_str()))),
+    ))?;
+
+    Ok(())
+}
+
+fn write_text_element(writer: &mut Writer<Vec<u8>>, name: &str, text: &str) -> std::io::Result<()> {
+    writer.write_event(Event::Start(BytesStart::new(name)))?;
+    writer.write_event(Event::Text(BytesText::new(text)))?;
+    writer.write_event(Event::End(BytesEnd::new(name)))?;
+    Ok(())
+}
+
+/// Reads the annotations of an XFDF file.
+///
+/// Elements for pages outside `page_heights` and unknown annotation elements are
+/// ignored.
+pub fn parse_xfdf(xml: &str, page_heights: &[f32]) -> Result<XfdfAnnotations, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut result = XfdfAnnotations::default();
+    let mut current: Option<Annotation> = None;
+    let mut current_state: Option<ReviewStateRecord> = None;
+    let mut page_height = 0.0;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+
+        let empty = matches!(event, Event::Empty(_));
+
+        match event {
+            Event::Start(element) | Event::Empty(element)
+                if current.is_none() && element_type(element.name().as_ref()).is_some() =>
+            {
+                let attributes = read_attributes(&element)?;
+                let subtype = element_type(element.name().as_ref()).unwrap();
+
+                let page_index = attribute(&attributes, "page")
+                    .and_then(|page| page.parse::<u16>().ok())
+                    .unwrap_or(0);
+                let in_range = match page_heights.get(page_index as usize) {
+                    Some(height) => {
+                        page_height = *height;
+                        true
+                    }
+                    None => false,
+                };
+
+                if let Some(state) = attribute(&attributes, "state")
+                    .and_then(|state| ReviewState::from_name(state.as_bytes()))
+                {
+                    let target = attribute(&attributes, "inreplyto");
+                    if let (true, Some(target)) = (in_range, target) {
+                        current_state = Some(ReviewStateRecord {
+                            page_index,
+                            annotation_id: target.to_string(),
+                            status: ReviewStatus {
+                                state,
+                                author: attribute(&attributes, "title").map(Into::into),
+                                date: attribute(&attributes, "date").map(Into::into),
+                            },
+                        });
+                    }
+                } else if in_range {
+                    current = Some(read_annotation(
+                        subtype,
+                        page_index,
+                        &attributes,
+                        page_height,
+                    ));
+                }
+
+                if empty {
+                    finish_element(&mut result, &mut current, &mut current_state);
+                } else if current.is_none() {
+                    // Skip the children of ignored elements and state annotations.
+                    reader
+                        .read_to_end(element.name())
+                        .map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+                    finish_element(&mut result, &mut current, &mut current_state);
+                }
+            }
+            Event::Start(element) if current.is_some() => {
+                let annotation = current.as_mut().unwrap();
+                let name = element.name();
+
+                match name.as_ref() {
+                    b"contents" => {
+                        let text = read_text(&mut reader, &element)?;
+                        annotation.metadata.contents = Some(text);
+                    }
+                    b"contents-richtext" => {
+                        let markup = reader
+                            .read_text(name)
+                            .map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+                        annotation.metadata.rich_contents = Some(markup.trim().to_string());
+                    }
+                    b"defaultappearance" => {
+                        let text = read_text(&mut reader, &element)?;
+                        annotation.appearance.font_size = font_size(&text);
+                    }
+                    b"vertices" => {
+                        let text = read_text(&mut reader, &element)?;
+                        annotation.geometry =
+                            AnnotationGeometry::Points(parse_points(&text, page_height));
+                    }
+                    b"gesture" => {
+                        let text = read_text(&mut reader, &element)?;
+                        let path = parse_points(&text, page_height);
+                        match &mut annotation.geometry {
+                            AnnotationGeometry::InkPaths(paths) => paths.push(path),
+                            geometry => *geometry = AnnotationGeometry::InkPaths(vec![path]),
+                        }
+                    }
+                    b"inklist" => {}
+                    _ => {
+                        reader
+                            .read_to_end(name)
+                            .map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+                    }
+                }
+            }
+            Event::Empty(element) if current.is_some() && element.name().as_ref() == b"popup" => {
+                let attributes = read_attributes(&element)?;
+                let annotation = current.as_mut().unwrap();
+                annotation.popup = attribute(&attributes, "rect")
+                    .and_then(|rect| parse_rect(rect, page_height))
+                    .map(|rect| AnnotationPopup {
+                        rect,
+                        open: attribute(&attributes, "open") == Some("yes"),
+                    });
+            }
+            Event::End(element)
+                if current.is_some() && element_type(element.name().as_ref()).is_some() =>
+            {
+                finish_element(&mut result, &mut current, &mut current_state);
+            }
+            Event::Empty(element) | Event::Start(element) if element.name().as_ref() == b"f" => {
+                let attributes = read_attributes(&element)?;
+                result.file = attribute(&attributes, "href").map(Into::into);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(result)
+}
+
+fn finish_element(
+    result: &mut XfdfAnnotations,
+    current: &mut Option<Annotation>,
+    current_state: &mut Option<ReviewStateRecord>,
+) {
+    if let Some(annotation) = current.take() {
+        result.annotations.push(annotation);
+    }
+    if let Some(state) = current_state.take() {
+        result.review_states.push(state);
+    }
+}
+
+fn read_annotation(
+    subtype: AnnotationType,
+    page_index: u16,
+    attributes: &[(String, String)],
+    page_height: f32,
+) -> Annotation {
+    let get = |key: &str| attribute(attributes, key);
+
+    let rect = get("rect")
+        .and_then(|rect| parse_rect(rect, page_height))
+        .unwrap_or(PdfRect {
+            left: 0.0,
+            top: 0.0,
+            right: 0.0,
+            bottom: 0.0,
+        });
+
+    let geometry = match subtype {
+        AnnotationType::Line => {
+            let point = |key| {
+                get(key)
+                    .map(|value| parse_points(value, page_height))
+                    .and_then(|points| points.into_iter().next())
+            };
+            match (point("start"), point("end")) {
+                (Some(start), Some(end)) => AnnotationGeometry::Line { start, end },
+                _ => AnnotationGeometry::Rect(rect.clone()),
+            }
+        }
+        AnnotationType::Highlight
+        | AnnotationType::Underline
+        | AnnotationType::Squiggly
+        | AnnotationType::Strikeout => {
+            let points = get("coords")
+                .map(|coords| parse_points(coords, page_height))
+                .unwrap_or_default();
+            AnnotationGeometry::QuadPoints(
+                points
+                    .chunks_exact(4)
+                    .map(|quad| Quad {
+                        p1: quad[0].clone(),
+                        p2: quad[1].clone(),
+                        p3: quad[2].clone(),
+                        p4: quad[3].clone(),
+                    })
+                    .collect(),
+            )
+        }
+        _ => AnnotationGeometry::Rect(rect.clone()),
+    };
+
+    let line_endings = match (get("head"), get("tail")) {
+        (None, None) => None,
+        (head, tail) => Some(LineEndings {
+            start: head
+                .map(|name| LineEnding::from_name(name.as_bytes()))
+                .unwrap_or_default(),
+            end: tail
+                .map(|name| LineEnding::from_name(name.as_bytes()))
+                .unwrap_or_default(),
+        }),
+    };
+
+    let flags = get("flags").unwrap_or_default();
+    let has_flag = |name: &str| {
+        flags
+            .split(',')
+            .any(|flag| flag.trim().eq_ignore_ascii_case(name))
+    };
+
+    let id = get("name")
+        .map(Into::into)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    Annotation {
+        id,
+        page_index,
+        subtype,
+        rect,
+        geometry,
+        appearance: AnnotationAppearance {
+            color: get("color").unwrap_or("#000000").to_string(),
+            interior_color: get("interior-color").map(Into::into),
+            opacity: get("opacity")
+                .and_then(|opacity| opacity.parse().ok())
+                .unwrap_or(1.0),
+            border_width: get("width").and_then(|width| width.parse().ok()),
+            border_style: get("style").map(style_from_value),
+            dash_pattern: get("dashes").map(parse_numbers),
+            line_endings,
+            font_size: None,
+            icon: get("icon").map(Into::into),
+        },
+        metadata: AnnotationMetadata {
+            author: get("title").map(Into::into),
+            contents: None,
+            rich_contents: None,
+            creation_date: get("creationdate").map(Into::into),
+            modified_date: get("date").map(Into::into),
+        },
+        flags: AnnotationFlags {
+            hidden: has_flag("hidden"),
+            locked: has_flag("locked"),
+            printable: has_flag("print"),
+            read_only: has_flag("readonly"),
+        },
+        in_reply_to: get("inreplyto")
+            .filter(|_| get("replyType").is_none_or(|kind| kind == "reply"))
+            .map(Into::into),
+        replies: Vec::new(),
+        review_states: Vec::new(),
+        popup: None,
+    }
+}
+
+fn read_attributes(element: &BytesStart) -> Result<Vec<(String, String)>, String> {
+    element
+        .attributes()
+        .map(|attribute| {
+            let attribute = attribute.map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+            let value = attribute
+                .unescape_value()
+                .map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+            Ok((
+                String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                value.into_owned(),
+            ))
+        })
+        .collect()
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.as_str())
+}
+
+fn read_text(reader: &mut Reader<&[u8]>, element: &BytesStart) -> Result<String, String> {
+    let raw = reader
+        .read_text(element.name())
+        .map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+    unescape(&raw)
+        .map(|text| text.into_owned())
+        .map_err(|e| format!("Failed to parse XFDF: {e}"))
+}
+
+fn element_name(subtype: &AnnotationType) -> Option<&'static str> {
+    match subtype {
+        AnnotationType::Text => Some("text"),
+        AnnotationType::FreeText => Some("freetext"),
+        AnnotationType::Square => Some("square"),
+        AnnotationType::Circle => Some("circle"),
+        AnnotationType::Line => Some("line"),
+        AnnotationType::Polygon => Some("polygon"),
+        AnnotationType::Polyline => Some("polyline"),
+        AnnotationType::Ink => Some("ink"),
+        AnnotationType::Highlight => Some("highlight"),
+        AnnotationType::Underline => Some("underline"),
+        AnnotationType::Squiggly => Some("squiggly"),
+        AnnotationType::Strikeout => Some("strikeout"),
+        AnnotationType::Stamp => Some("stamp"),
+        AnnotationType::Caret => Some("caret"),
+        _ => None,
+    }
+}
+
+fn element_type(name: &[u8]) -> Option<AnnotationType> {
+    match name {
+        b"text" => Some(AnnotationType::Text),
+        b"freetext" => Some(AnnotationType::FreeText),
+        b"square" => Some(AnnotationType::Square),
+        b"circle" => Some(AnnotationType::Circle),
+        b"line" => Some(AnnotationType::Line),
+        b"polygon" => Some(AnnotationType::Polygon),
+        b"polyline" => Some(AnnotationType::Polyline),
+        b"ink" => Some(AnnotationType::Ink),
+        b"highlight" => Some(AnnotationType::Highlight),
+        b"underline" => Some(AnnotationType::Underline),
+        b"squiggly" => Some(AnnotationType::Squiggly),
+        b"strikeout" => Some(AnnotationType::Strikeout),
+        b"stamp" => Some(AnnotationType::Stamp),
+        b"caret" => Some(AnnotationType::Caret),
+        _ => None,
+    }
+}
+
+fn style_value(style: BorderStyle) -> &'static str {
+    match style {
+        BorderStyle::Solid => "solid",
+        BorderStyle::Dashed => "dash",
+        BorderStyle::Beveled => "bevelled",
+        BorderStyle::Inset => "inset",
+        BorderStyle::Underline => "underline",
+    }
+}
+
+fn style_from_value(value: &str) -> BorderStyle {
+    match value {
+        "dash" => BorderStyle::Dashed,
+        "bevelled" => BorderStyle::Beveled,
+        "inset" => BorderStyle::Inset,
+        "underline" => BorderStyle::Underline,
+        _ => BorderStyle::Solid,
+    }
+}
+
+fn line_ending_value(ending: LineEnding) -> String {
+    String::from_utf8_lossy(line_ending_name(ending)).into_owned()
+}
+
+fn flags_value(flags: &AnnotationFlags) -> String {
+    [
+        (flags.hidden, "hidden"),
+        (flags.printable, "print"),
+        (flags.read_only, "readonly"),
+        (flags.locked, "locked"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| *name)
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+fn font_size(appearance: &str) -> Option<f32> {
+    let tokens = appearance.split_whitespace().collect::<Vec<_>>();
+    tokens
+        .iter()
+        .position(|token| *token == "Tf")
+        .filter(|index| *index > 0)
+        .and_then(|index| tokens[index - 1].parse().ok())
+}
+
+fn parse_rgb(color: &str) -> (f32, f32, f32) {
+    let hex = color.trim_start_matches('#');
+    let channel = |range: std::ops::Range<usize>| {
+        hex.get(range)
+            .and_then(|value| u8::from_str_radix(value, 16).ok())
+            .map(|value| value as f32 / 255.0)
+            .unwrap_or(0.0)
+    };
+    (channel(0..2), channel(2..4), channel(4..6))
+}
+
+fn rect_value(rect: &PdfRect, page_height: f32) -> String {
+    numbers_value(&[
+        rect.left,
+        page_height - rect.bottom,
+        rect.right,
+        page_height - rect.top,
+    ])
+}
+
+fn parse_rect(value: &str, page_height: f32) -> Option<PdfRect> {
+    let values = parse_numbers(value);
+    let [x1, y1, x2, y2] = values[..] else {
+        return None;
+    };
+
+    Some(PdfRect {
+        left: x1.min(x2),
+        top: page_height - y1.max(y2),
+        right: x1.max(x2),
+        bottom: page_height - y1.min(y2),
+    })
+}
+
+/// Points as `x,y;x,y`, the format of `vertices` and `gesture`.
+fn points_value(points: &[Point], page_height: f32) -> String {
+    points
+        .iter()
+        .map(|point| numbers_value(&[point.x, page_height - point.y]))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Accepts both `x,y;x,y` and plain comma separated coordinates.
+fn parse_points(value: &str, page_height: f32) -> Vec<Point> {
+    parse_numbers(value)
+        .chunks_exact(2)
+        .map(|pair| Point {
+            x: pair[0],
+            y: page_height - pair[1],
+        })
+        .collect()
+}
+
+fn parse_numbers(value: &str) -> Vec<f32> {
+    value
+        .split([',', ';', ' '])
+        .filter_map(|number| number.trim().parse().ok())
+        .collect()
+}
+
+fn numbers_value(values: &[f32]) -> String {
+    values
+        .iter()
+        .map(|value| number(*value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn number(value: f32) -> String {
+    let rounded = (value * 1000.0).round() / 1000.0;
+    format!("{rounded}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlight() -> Annotation {
+        Annotation {
+            id: "hl-1".into(),
+            page_index: 0,
+            subtype: AnnotationType::Highlight,
+            rect: PdfRect {
+                left: 10.0,
+                top: 100.0,
+                right: 60.0,
+                bottom: 112.0,
+            },
+            geometry: AnnotationGeometry::QuadPoints(vec![Quad {
+                p1: Point { x: 10.0, y: 100.0 },
+                p2: Point { x: 60.0, y: 100.0 },
+                p3: Point { x: 10.0, y: 112.0 },
+                p4: Point { x: 60.0, y: 112.0 },
+            }]),
+            appearance: AnnotationAppearance {
+                color: "#FFFF00".into(),
+                interior_color: None,
+                opacity: 0.5,
+                border_width: None,
+                border_style: None,
+                dash_pattern: None,
+                line_endings: None,
+                font_size: None,
+                icon: None,
+            },
+            metadata: AnnotationMetadata {
+                author: Some("Ana & Bo".into()),
+                contents: Some("Important <result>".into()),
+                rich_contents: None,
+                creation_date: None,
+                modified_date: Some("D:20240102030405Z".into()),
+            },
+            flags: AnnotationFlags {
+                printable: true,
+                ..Default::default()
+            },
+            in_reply_to: None,
+            replies: Vec::new(),
+            review_states: Vec::new(),
+            popup: Some(AnnotationPopup {
+                rect: PdfRect {
+                    left: 100.0,
+                    top: 100.0,
+                    right: 300.0,
+                    bottom: 200.0,
+                },
+                open: false,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_xfdf_round_trip() {
+        let mut root = highlight();
+
+        let mut reply = highlight();
+        reply.id = "reply-1".into();
+        reply.subtype = AnnotationType::Text;
+        reply.geometry = AnnotationGeometry::Rect(reply.rect.clone());
+        reply.in_reply_to = Some("hl-1".into());
+        reply.popup = None;
+        root.replies.push(reply);
+
+        root.review_states.push(ReviewStatus {
+            state: ReviewState::Accepted,
+            author: Some("Bo".into()),
+            date: None,
+        });
+
+        let xml = write_xfdf(&[root.clone()], &[800.0], Some("paper.pdf")).unwrap();
+        assert!(xml.contains("<highlight"));
+        assert!(xml.contains("coords=\"10,700,60,700,10,688,60,688\""));
+
+        let parsed = parse_xfdf(&xml, &[800.0]).unwrap();
+        assert_eq!(parsed.file.as_deref(), Some("paper.pdf"));
+        assert_eq!(parsed.annotations.len(), 2);
+
+        let highlight = &parsed.annotations[0];
+        assert_eq!(highlight.id, "hl-1");
+        assert_eq!(highlight.geometry, root.geometry);
+        assert_eq!(highlight.rect, root.rect);
+        assert_eq!(highlight.popup, root.popup);
+        assert_eq!(highlight.metadata.author, root.metadata.author);
+        assert_eq!(highlight.metadata.contents, root.metadata.contents);
+        assert_eq!(highlight.appearance.opacity, 0.5);
+        assert!(highlight.flags.printable);
+
+        assert_eq!(parsed.annotations[1].in_reply_to.as_deref(), Some("hl-1"));
+        assert_eq!(parsed.review_states.len(), 1);
+        assert_eq!(parsed.review_states[0].annotation_id, "hl-1");
+        assert_eq!(parsed.review_states[0].status.state, ReviewState::Accepted);
+    }
+
+    #[test]
+    fn test_parse_acrobat_style_xfdf() {
+        let xml = r##"<?xml version="1.0" encoding="UTF-8"?>
+<xfdf xmlns="http://ns.adobe.com/xfdf/" xml:space="preserve">
+  <annots>
+    <ink page="1" rect="0,0,100,100" color="#FF0000" name="ink-1" width="2">
+      <inklist>
+        <gesture>0,0;10,10</gesture>
+        <gesture>20,20;30,30</gesture>
+      </inklist>
+    </ink>
+    <polygon page="0" rect="0,0,10,10" name="pg" style="dash" dashes="3,2">
+      <vertices>0,0,10,0,10,10</vertices>
+    </polygon>
+    <line page="5" rect="0,0,1,1" start="0,0" end="1,1"/>
+    <fileattachment page="0" rect="0,0,1,1"/>
+  </annots>
+</xfdf>"##;
+
+        let parsed = parse_xfdf(xml, &[500.0, 800.0]).unwrap();
+        assert_eq!(parsed.annotations.len(), 2);
+
+        let ink = &parsed.annotations[0];
+        assert_eq!(ink.page_index, 1);
+        let AnnotationGeometry::InkPaths(paths) = &ink.geometry else {
+            panic!("expected ink paths");
+        };
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[1][0], Point { x: 20.0, y: 780.0 });
+
+        let polygon = &parsed.annotations[1];
+        assert_eq!(polygon.appearance.border_style, Some(BorderStyle::Dashed));
+        assert_eq!(polygon.appearance.dash_pattern, Some(vec![3.0, 2.0]));
+        let AnnotationGeometry::Points(points) = &polygon.geometry else {
+            panic!("expected vertices");
+        };
+        assert_eq!(points.len(), 3);
+    }
+}
@@ -0,0 +1,579 @@
+use std::path::Path;
+
+use lopdf::{dictionary, Dictionary, Document, Object};
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::pdf::reader::form::fields::{field_nodes, read_form_fields, text_value};
+use crate::pdf::reader::form::fill::set_field_value;
+use crate::pdf::reader::form::{FormField, FormFieldType, FormFieldValue};
+
+const XFDF_NAMESPACE: &str = "http://ns.adobe.com/xfdf/";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FormDataFormat {
+    Json,
+    Fdf,
+    Xfdf,
+}
+
+impl FormDataFormat {
+    /// Format matching the file extension of `path`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "json" => Some(FormDataFormat::Json),
+            "fdf" => Some(FormDataFormat::Fdf),
+            "xfdf" | "xml" => Some(FormDataFormat::Xfdf),
+            _ => None,
+        }
+    }
+}
+
+/// Field values by fully qualified name, in the order they were read.
+pub type FormData = Vec<(String, FormFieldValue)>;
+
+/// Outcome of filling a form from imported data.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FormImportSummary {
+    /// Fields set from the data
+    pub filled: usize,
+
+    /// Names in the data without a fillable field in the form
+    pub skipped: Vec<String>,
+}
+
+/// Serialises the values of the exportable fields: fields with a value that are
+/// not buttons, signatures or flagged NoExport.
+pub fn write_form_data(
+    fields: &[FormField],
+    format: FormDataFormat,
+    file_name: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    match format {
+        FormDataFormat::Json => write_json(fields).map(String::into_bytes),
+        FormDataFormat::Fdf => write_fdf(fields, file_name),
+        FormDataFormat::Xfdf => write_xfdf(fields, file_name).map(String::into_bytes),
+    }
+}
+
+/// Reads form data records. JSON holds one record as an object or several as an
+/// array of objects; FDF and XFDF hold a single record.
+pub fn parse_form_data(bytes: &[u8], format: FormDataFormat) -> Result<Vec<FormData>, String> {
+    match format {
+        FormDataFormat::Json => parse_json(bytes),
+        FormDataFormat::Fdf => parse_fdf(bytes).map(|data| vec![data]),
+        FormDataFormat::Xfdf => {
+            let xml =
+                std::str::from_utf8(bytes).map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+            parse_xfdf(xml).map(|data| vec![data])
+        }
+    }
+}
+
+/// Fills the form of `document` from a record.
+///
+/// Values are adapted to the field type, so spreadsheet-style values such as
+/// `"yes"` or `true` check a check box. Names without a fillable field are skipped;
+/// invalid values are errors.
+pub fn apply_form_data(
+    document: &mut Document,
+    data: &FormData,
+) -> Result<FormImportSummary, String> {
+    let fields = read_form_fields(document)?;
+    let nodes = field_nodes(document)?;
+    let mut summary = FormImportSummary::default();
+
+    for (name, value) in data {
+        let target = fields
+            .iter()
+            .zip(&nodes)
+            .find(|(field, _)| field.name == *name)
+            .filter(|(field, _)| !field.flags.read_only && is_fillable(field.field_type));
+
+        let Some((field, node)) = target else {
+            summary.skipped.push(name.clone());
+            continue;
+        };
+
+        set_field_value(document, node, &adapt(field, value))?;
+        summary.filled += 1;
+    }
+
+    Ok(summary)
+}
+
+fn is_fillable(field_type: FormFieldType) -> bool {
+    !matches!(
+        field_type,
+        FormFieldType::Button | FormFieldType::Signature | FormFieldType::Unknown
+    )
+}
+
+fn exported(fields: &[FormField]) -> impl Iterator<Item = (&FormField, &FormFieldValue)> {
+    fields
+        .iter()
+        .filter(|field| !field.flags.no_export && is_fillable(field.field_type))
+        .filter_map(|field| field.value.as_ref().map(|value| (field, value)))
+}
+
+/// Converts an imported value to the shape the field type expects.
+fn adapt(field: &FormField, value: &FormFieldValue) -> FormFieldValue {
+    let value = match value {
+        FormFieldValue::Selected(values)
+            if values.len() == 1 && field.field_type != FormFieldType::List =>
+        {
+            FormFieldValue::Text(values[0].clone())
+        }
+        value => value.clone(),
+    };
+
+    match (field.field_type, value) {
+        (FormFieldType::Checkbox, FormFieldValue::Text(text)) => {
+            let is_state = text == "Off"
+                || field
+                    .widgets
+                    .iter()
+                    .any(|widget| widget.on_state.as_deref() == Some(text.as_str()));
+            if is_state {
+                return FormFieldValue::Text(text);
+            }
+            match text.trim().to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" | "x" => FormFieldValue::Checked(true),
+                "false" | "no" | "off" | "0" | "" => FormFieldValue::Checked(false),
+                _ => FormFieldValue::Text(text),
+            }
+        }
+        (FormFieldType::Text, FormFieldValue::Checked(checked)) => {
+            FormFieldValue::Text(checked.to_string())
+        }
+        (_, value) => value,
+    }
+}
+
+/// Check box values are exported as the name of their selected state.
+fn state_name(field: &FormField, value: &FormFieldValue) -> Option<String> {
+    match value {
+        FormFieldValue::Checked(true) => Some(
+            field
+                .widgets
+                .iter()
+                .find_map(|widget| widget.on_state.clone())
+                .unwrap_or("Yes".into()),
+        ),
+        FormFieldValue::Checked(false) => Some("Off".into()),
+        FormFieldValue::Text(state) if field.field_type == FormFieldType::Radio => {
+            Some(state.clone())
+        }
+        _ => None,
+    }
+}
+
+fn write_json(fields: &[FormField]) -> Result<String, String> {
+    let record = exported(fields)
+        .map(|(field, value)| {
+            let value = match value {
+                FormFieldValue::Text(text) => Value::String(text.clone()),
+                FormFieldValue::Checked(checked) => Value::Bool(*checked),
+                FormFieldValue::Selected(values) => {
+                    values.iter().cloned().map(Value::String).collect()
+                }
+            };
+            (field.name.clone(), value)
+        })
+        .collect::<Map<_, _>>();
+
+    serde_json::to_string_pretty(&record).map_err(|e| format!("Failed to write JSON: {e}"))
+}
+
+fn parse_json(bytes: &[u8]) -> Result<Vec<FormData>, String> {
+    let value: Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Failed to parse JSON: {e}"))?;
+
+    let records = match value {
+        Value::Array(records) => records,
+        record => vec![record],
+    };
+
+    records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            let Value::Object(record) = record else {
+                return Err(format!("Record {} is not an object", index + 1));
+            };
+            let mut data = FormData::new();
+            json_values(record, "", &mut data);
+            Ok(data)
+        })
+        .collect()
+}
+
+/// Flattens a JSON record; nested objects name their fields `parent.child`.
+fn json_values(record: &Map<String, Value>, prefix: &str, data: &mut FormData) {
+    for (key, value) in record {
+        let name = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        let value = match value {
+            Value::Object(children) => {
+                json_values(children, &name, data);
+                continue;
+            }
+            Value::Null => continue,
+            Value::Bool(checked) => FormFieldValue::Checked(*checked),
+            Value::String(text) => FormFieldValue::Text(text.clone()),
+            Value::Number(number) => FormFieldValue::Text(number.to_string()),
+            Value::Array(values) => FormFieldValue::Selected(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Value::String(text) => text.clone(),
+                        value => value.to_string(),
+                    })
+                    .collect(),
+            ),
+        };
+        data.push((name, value));
+    }
+}
+
+/// Node of the field hierarchy written to FDF and XFDF, where `a.b` is field `b`
+/// under `a`.
+struct FieldTree {
+    name: String,
+    value: Option<Vec<String>>,
+    is_state: bool,
+    kids: Vec<FieldTree>,
+}
+
+fn field_tree(fields: &[FormField]) -> Vec<FieldTree> {
+    let mut roots: Vec<FieldTree> = Vec::new();
+
+    for (field, value) in exported(fields) {
+        let (values, is_state) = match (state_name(field, value), value) {
+            (Some(state), _) => (vec![state], true),
+            (None, FormFieldValue::Text(text)) => (vec![text.clone()], false),
+            (None, FormFieldValue::Selected(values)) => (values.clone(), false),
+            (None, FormFieldValue::Checked(_)) => continue,
+        };
+
+        let mut level = &mut roots;
+        let parts = field.name.split('.').collect::<Vec<_>>();
+        for (depth, part) in parts.iter().enumerate() {
+            let index = match level.iter().position(|node| node.name == *part) {
+                Some(index) => index,
+                None => {
+                    level.push(FieldTree {
+                        name: part.to_string(),
+                        value: None,
+                        is_state: false,
+                        kids: Vec::new(),
+                    });
+                    level.len() - 1
+                }
+            };
+            if depth == parts.len() - 1 {
+                level[index].value = Some(values.clone());
+                level[index].is_state = is_state;
+            }
+            level = &mut level[index].kids;
+        }
+    }
+
+    roots
+}
+
+fn write_fdf(fields: &[FormField], file_name: Option<&str>) -> Result<Vec<u8>, String> {
+    fn to_dictionary(node: &FieldTree) -> Dictionary {
+        let mut dict = dictionary! { "T" => lopdf::text_string(&node.name) };
+
+        match node.value.as_deref() {
+            Some([state]) if node.is_state => {
+                dict.set("V", Object::Name(state.clone().into_bytes()))
+            }
+            Some([value]) => dict.set("V", lopdf::text_string(value)),
+            Some(values) => dict.set(
+                "V",
+                values
+                    .iter()
+                    .map(|value| lopdf::text_string(value))
+                    .collect::<Vec<_>>(),
+            ),
+            None => {}
+        }
+        if !node.kids.is_empty() {
+            dict.set(
+                "Kids",
+                node.kids
+                    .iter()
+                    .map(|kid| Object::Dictionary(to_dictionary(kid)))
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        dict
+    }
+
+    let mut document = Document::with_version("1.2");
+    let fields = field_tree(fields)
+        .iter()
+        .map(|node| Object::Dictionary(to_dictionary(node)))
+        .collect::<Vec<_>>();
+
+    let mut fdf = dictionary! { "Fields" => fields };
+    if let Some(file_name) = file_name {
+        fdf.set("F", Object::string_literal(file_name));
+    }
+
+    let catalog_id = document.add_object(dictionary! { "FDF" => fdf });
+    document.trailer.set("Root", catalog_id);
+
+    let mut buffer = Vec::new();
+    document
+        .save_to(&mut buffer)
+        .map_err(|e| format!("Failed to write FDF: {e}"))?;
+
+    // lopdf always writes a PDF header; the FDF header has the same length, so the
+    // cross-reference offsets stay valid.
+    if buffer.starts_with(b"%PDF-") {
+        buffer[1..4].copy_from_slice(b"FDF");
+    }
+
+    Ok(buffer)
+}
+
+fn parse_fdf(bytes: &[u8]) -> Result<FormData, String> {
+    fn collect(document: &Document, field: &Object, prefix: &str, data: &mut FormData) {
+        let Some(dict) = document
+            .dereference(field)
+            .ok()
+            .and_then(|(_, field)| field.as_dict().ok())
+        else {
+            return;
+        };
+
+        let name = match dict.get(b"T").ok().and_then(text_value) {
+            Some(partial) if prefix.is_empty() => partial,
+            Some(partial) => format!("{prefix}.{partial}"),
+            None => prefix.to_string(),
+        };
+
+        let value = dict
+            .get_deref(b"V", document)
+            .ok()
+            .and_then(|value| match value {
+                Object::Array(values) => Some(FormFieldValue::Selected(
+                    values
+                        .iter()
+                        .filter_map(|value| text_value(document.dereference(value).ok()?.1))
+                        .collect(),
+                )),
+                value => text_value(value).map(FormFieldValue::Text),
+            });
+        if let Some(value) = value {
+            data.push((name.clone(), value));
+        }
+
+        if let Ok(kids) = dict
+            .get_deref(b"Kids", document)
+            .and_then(|kids| kids.as_array())
+        {
+            for kid in kids {
+                collect(document, kid, &name, data);
+            }
+        }
+    }
+
+    // lopdf only reads PDF headers; swap the FDF header back, as when writing.
+    let mut buffer = bytes.to_vec();
+    if buffer.starts_with(b"%FDF-") {
+        buffer[1..4].copy_from_slice(b"PDF");
+    }
+    let document = Document::load_mem(&buffer).map_err(|e| format!("Failed to parse FDF: {e}"))?;
+
+    let fields = document
+        .catalog()
+        .and_then(|catalog| catalog.get_deref(b"FDF", &document))
+        .and_then(|fdf| fdf.as_dict())
+        .and_then(|fdf| fdf.get_deref(b"Fields", &document))
+        .and_then(|fields| fields.as_array())
+        .map_err(|e| format!("Failed to parse FDF: {e}"))?;
+
+    let mut data = FormData::new();
+    for field in fields {
+        collect(&document, field, "", &mut data);
+    }
+
+    Ok(data)
+}
+
+fn write_xfdf(fields: &[FormField], file_name: Option<&str>) -> Result<String, String> {
+    fn write_node(writer: &mut Writer<Vec<u8>>, node: &FieldTree) -> std::io::Result<()> {
+        writer.write_event(Event::Start(
+            BytesStart::new("field").with_attributes([("name", node.name.as_str())]),
+        ))?;
+        for value in node.value.iter().flatten() {
+            writer.write_event(Event::Start(BytesStart::new("value")))?;
+            writer.write_event(Event::Text(BytesText::new(value)))?;
+            writer.write_event(Event::End(BytesEnd::new("value")))?;
+        }
+        for kid in &node.kids {
+            write_node(writer, kid)?;
+        }
+        writer.write_event(Event::End(BytesEnd::new("field")))?;
+        Ok(())
+    }
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+
+    let result = (|| -> std::io::Result<()> {
+        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+        writer.write_event(Event::Start(
+            BytesStart::new("xfdf")
+                .with_attributes([("xmlns", XFDF_NAMESPACE), ("xml:space", "preserve")]),
+        ))?;
+        writer.write_event(Event::Start(BytesStart::new("fields")))?;
+
+        for node in field_tree(fields) {
+            write_node(&mut writer, &node)?;
+        }
+
+        writer.write_event(Event::End(BytesEnd::new("fields")))?;
+
+        if let Some(file_name) = file_name {
+            writer.write_event(Event::Empty(
+                BytesStart::new("f").with_attributes([("href", file_name)]),
+            ))?;
+        }
+
+        writer.write_event(Event::End(BytesEnd::new("xfdf")))
+    })();
+
+    result.map_err(|e| format!("Failed to write XFDF: {e}"))?;
+
+    String::from_utf8(writer.into_inner()).map_err(|e| format!("Failed to write XFDF: {e}"))
+}
+
+fn parse_xfdf(xml: &str) -> Result<FormData, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut data = FormData::new();
+    // Open `<field>` elements: full name and the values read so far
+    let mut open: Vec<(String, Vec<String>)> = Vec::new();
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+
+        match event {
+            Event::Start(element) if element.name().as_ref() == b"field" => {
+                let name = element
+                    .try_get_attribute("name")
+                    .map_err(|e| format!("Failed to parse XFDF: {e}"))?
+                    .map(|name| name.unescape_value().map(|name| name.into_owned()))
+                    .transpose()
+                    .map_err(|e| format!("Failed to parse XFDF: {e}"))?
+                    .unwrap_or_default();
+                let name = match open.last() {
+                    Some((parent, _)) if !parent.is_empty() => format!("{parent}.{name}"),
+                    _ => name,
+                };
+                open.push((name, Vec::new()));
+            }
+            Event::Start(element) if element.name().as_ref() == b"value" => {
+                let raw = reader
+                    .read_text(element.name())
+                    .map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+                let value = unescape(&raw).map_err(|e| format!("Failed to parse XFDF: {e}"))?;
+                if let Some((_, values)) = open.last_mut() {
+                    values.push(value.into_owned());
+                }
+            }
+            Event::Empty(element) if element.name().as_ref() == b"value" => {
+                if let Some((_, values)) = open.last_mut() {
+                    values.push(String::new());
+                }
+            }
+            Event::End(element) if element.name().as_ref() == b"field" => {
+                if let Some((name, mut values)) = open.pop() {
+                    match values.len() {
+                        0 => {}
+                        1 => data.push((name, FormFieldValue::Text(values.remove(0)))),
+                        _ => data.push((name, FormFieldValue::Selected(values))),
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::form::fields::tests::form_document;
+
+    #[test]
+    fn test_form_data_round_trip() {
+        let fields = read_form_fields(&form_document()).unwrap();
+
+        for format in [
+            FormDataFormat::Json,
+            FormDataFormat::Fdf,
+            FormDataFormat::Xfdf,
+        ] {
+            let bytes = write_form_data(&fields, format, Some("form.pdf")).unwrap();
+            let data = parse_form_data(&bytes, format).unwrap().remove(0);
+
+            let names = data
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>();
+            assert!(names.contains(&"employee.name"), "{format:?}");
+            assert!(names.contains(&"contract"), "{format:?}");
+
+            let mut document = form_document();
+            let summary = apply_form_data(&mut document, &data).unwrap();
+            assert!(summary.skipped.is_empty(), "{format:?}");
+            assert_eq!(read_form_fields(&document).unwrap(), fields, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn test_apply_spreadsheet_record() {
+        let records = parse_form_data(
+            br#"[{"employee": {"name": "Bo Chen", "remote": "yes"}, "skills": "Go", "salary": 5000}]"#,
+            FormDataFormat::Json,
+        )
+        .unwrap();
+
+        let mut document = form_document();
+        let summary = apply_form_data(&mut document, &records[0]).unwrap();
+        assert_eq!(summary.filled, 3);
+        assert_eq!(summary.skipped, vec!["salary".to_string()]);
+
+        let fields = read_form_fields(&document).unwrap();
+        assert_eq!(
+            fields[0].value,
+            Some(FormFieldValue::Text("Bo Chen".into()))
+        );
+        assert_eq!(fields[1].value, Some(FormFieldValue::Checked(true)));
+        assert_eq!(
+            fields[4].value,
+            Some(FormFieldValue::Selected(vec!["Go".into()]))
+        );
+    }
+}
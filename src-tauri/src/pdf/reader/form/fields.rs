@@ -0,0 +1,655 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::pdf::editor::page_height;
+use crate::pdf::reader::form::{
+    FormField, FormFieldFlags, FormFieldOption, FormFieldType, FormFieldValue, FormWidget,
+};
+use crate::pdf::reader::PdfRect;
+use crate::pdf::DocumentId;
+
+/// Guards against cycles in malformed field trees.
+const MAX_FIELD_DEPTH: usize = 32;
+
+pub const FIELD_READ_ONLY: u32 = 1;
+pub const FIELD_REQUIRED: u32 = 1 << 1;
+pub const FIELD_NO_EXPORT: u32 = 1 << 2;
+pub const FIELD_MULTILINE: u32 = 1 << 12;
+pub const FIELD_PASSWORD: u32 = 1 << 13;
+pub const FIELD_RADIO: u32 = 1 << 15;
+pub const FIELD_PUSHBUTTON: u32 = 1 << 16;
+pub const FIELD_COMBO: u32 = 1 << 17;
+pub const FIELD_EDIT: u32 = 1 << 18;
+pub const FIELD_MULTI_SELECT: u32 = 1 << 21;
+pub const FIELD_COMB: u32 = 1 << 24;
+
+/// Terminal field of the AcroForm field tree together with its widget annotations.
+/// A field merged with its only widget lists its own id as the widget.
+#[derive(Debug, Clone)]
+pub struct FieldNode {
+    pub id: ObjectId,
+    pub name: String,
+    pub field_type: FormFieldType,
+    pub flags: u32,
+    pub widgets: Vec<ObjectId>,
+}
+
+/// Lists the form fields of an open document.
+pub fn get_form_fields(
+    paths: &HashMap<DocumentId, PathBuf>,
+    id: &DocumentId,
+) -> Result<Vec<FormField>, String> {
+    let path = paths.get(id).ok_or("Document path not found")?;
+    let document = Document::load(path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+
+    read_form_fields(&document)
+}
+
+/// Reads every terminal field of the document's AcroForm. Widget rectangles use the
+/// top-left origin of the frontend.
+pub fn read_form_fields(document: &Document) -> Result<Vec<FormField>, String> {
+    let pages = widget_pages(document);
+
+    let fields = field_nodes(document)?
+        .into_iter()
+        .map(|node| {
+            let dict = document
+                .get_dictionary(node.id)
+                .map_err(|e| format!("Failed to read field {}: {e}", node.name))?;
+
+            let widgets = node
+                .widgets
+                .iter()
+                .filter_map(|widget_id| {
+                    let (page_index, page_id) = *pages.get(widget_id)?;
+                    let widget = document.get_dictionary(*widget_id).ok()?;
+                    let [left, bottom, right, top] = widget_rect(document, widget)?;
+                    let height = page_height(document, page_id);
+
+                    Some(FormWidget {
+                        page_index,
+                        rect: PdfRect {
+                            left,
+                            top: height - top,
+                            right,
+                            bottom: height - bottom,
+                        },
+                        on_state: on_state(document, widget),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let max_length = field_attribute(document, node.id, b"MaxLen")
+                .and_then(|value| value.as_i64().ok())
+                .map(|value| value.max(0) as u32);
+
+            Ok(FormField {
+                label: dict.get(b"TU").ok().and_then(text_value),
+                field_type: node.field_type,
+                value: read_value(document, &node),
+                options: field_options(document, &node),
+                flags: field_flags(node.flags),
+                max_length,
+                widgets,
+                name: node.name,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(fields)
+}
+
+/// Walks the field tree of the document's AcroForm, returning the terminal fields
+/// in tree order. Documents without a form have no fields.
+pub fn field_nodes(document: &Document) -> Result<Vec<FieldNode>, String> {
+    let Some(acro_form) = acro_form(document) else {
+        return Ok(Vec::new());
+    };
+
+    let roots = acro_form
+        .get_deref(b"Fields", document)
+        .and_then(|fields| fields.as_array())
+        .map(|fields| references(fields))
+        .unwrap_or_default();
+
+    let mut nodes = Vec::new();
+    for root in roots {
+        collect_fields(document, root, "", None, 0, 0, &mut nodes);
+    }
+
+    Ok(nodes)
+}
+
+/// The document's interactive form dictionary (`/AcroForm`).
+pub fn acro_form(document: &Document) -> Option<&Dictionary> {
+    document
+        .catalog()
+        .ok()?
+        .get_deref(b"AcroForm", document)
+        .ok()?
+        .as_dict()
+        .ok()
+}
+
+/// Looks up an inheritable field attribute (`/V`, `/DA`, `/Q`, `/MaxLen`, ...) on
+/// the field or its ancestors.
+pub fn field_attribute<'a>(
+    document: &'a Document,
+    field_id: ObjectId,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let mut node_id = field_id;
+    for _ in 0..MAX_FIELD_DEPTH {
+        let node = document.get_dictionary(node_id).ok()?;
+        if let Ok(value) = node.get(key) {
+            return document.dereference(value).ok().map(|(_, value)| value);
+        }
+        node_id = node.get(b"Parent").ok()?.as_reference().ok()?;
+    }
+    None
+}
+
+/// Name of the appearance state a check box or radio button widget shows when
+/// selected: the first `/AP /N` state other than `Off`.
+pub fn on_state(document: &Document, widget: &Dictionary) -> Option<String> {
+    let normal = widget
+        .get_deref(b"AP", document)
+        .and_then(|appearance| appearance.as_dict())
+        .and_then(|appearance| appearance.get_deref(b"N", document))
+        .and_then(|normal| normal.as_dict())
+        .ok()?;
+
+    normal
+        .iter()
+        .map(|(state, _)| state)
+        .find(|state| state.as_slice() != b"Off")
+        .map(|state| String::from_utf8_lossy(state).into_owned())
+}
+
+/// Normalised widget rectangle in PDF space: `[left, bottom, right, top]`.
+pub fn widget_rect(document: &Document, widget: &Dictionary) -> Option<[f32; 4]> {
+    let values = widget.get_deref(b"Rect", document).ok()?.as_array().ok()?;
+    let values = values
+        .iter()
+        .map(|value| value.as_float().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let [x1, y1, x2, y2] = values[..] else {
+        return None;
+    };
+
+    Some([x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)])
+}
+
+/// Decodes a text string or name value.
+pub fn text_value(object: &Object) -> Option<String> {
+    match object {
+        Object::Name(name) => Some(String::from_utf8_lossy(name).into_owned()),
+        object => lopdf::decode_text_string(object).ok(),
+    }
+}
+
+/// Options of a combo or list box (`/Opt`), or the states of a radio group.
+pub fn field_options(document: &Document, node: &FieldNode) -> Vec<FormFieldOption> {
+    match node.field_type {
+        FormFieldType::Combo | FormFieldType::List => field_attribute(document, node.id, b"Opt")
+            .and_then(|options| options.as_array().ok())
+            .map(|options| {
+                options
+                    .iter()
+                    .filter_map(|option| match document.dereference(option).ok()?.1 {
+                        Object::Array(pair) => {
+                            let value = text_value(document.dereference(pair.first()?).ok()?.1)?;
+                            let label = pair
+                                .get(1)
+                                .and_then(|label| document.dereference(label).ok())
+                                .and_then(|(_, label)| text_value(label))
+                                .unwrap_or_else(|| value.clone());
+                            Some(FormFieldOption { value, label })
+                        }
+                        option => text_value(option).map(|value| FormFieldOption {
+                            label: value.clone(),
+                            value,
+                        }),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        FormFieldType::Radio | FormFieldType::Checkbox => {
+            // `/Opt` on a button field holds the export value of each widget.
+            let exports = field_attribute(document, node.id, b"Opt")
+                .and_then(|options| options.as_array().ok())
+                .cloned()
+                .unwrap_or_default();
+
+            let mut options: Vec<FormFieldOption> = Vec::new();
+            for (index, widget_id) in node.widgets.iter().enumerate() {
+                let Some(state) = document
+                    .get_dictionary(*widget_id)
+                    .ok()
+                    .and_then(|widget| on_state(document, widget))
+                else {
+                    continue;
+                };
+                if options.iter().any(|option| option.value == state) {
+                    continue;
+                }
+                let label = exports
+                    .get(index)
+                    .and_then(|label| document.dereference(label).ok())
+                    .and_then(|(_, label)| text_value(label))
+                    .unwrap_or_else(|| state.clone());
+                options.push(FormFieldOption {
+                    value: state,
+                    label,
+                });
+            }
+            options
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn collect_fields(
+    document: &Document,
+    field_id: ObjectId,
+    parent_name: &str,
+    parent_type: Option<&[u8]>,
+    parent_flags: u32,
+    depth: usize,
+    nodes: &mut Vec<FieldNode>,
+) {
+    if depth > MAX_FIELD_DEPTH {
+        return;
+    }
+    let Ok(dict) = document.get_dictionary(field_id) else {
+        return;
+    };
+
+    let name = match dict.get(b"T").ok().and_then(text_value) {
+        Some(partial) if parent_name.is_empty() => partial,
+        Some(partial) => format!("{parent_name}.{partial}"),
+        None => parent_name.to_string(),
+    };
+    let field_type = dict
+        .get(b"FT")
+        .and_then(|field_type| field_type.as_name())
+        .ok()
+        .or(parent_type);
+    let flags = dict
+        .get(b"Ff")
+        .and_then(|flags| flags.as_i64())
+        .map(|flags| flags as u32)
+        .unwrap_or(parent_flags);
+
+    let kids = dict
+        .get_deref(b"Kids", document)
+        .and_then(|kids| kids.as_array())
+        .map(|kids| references(kids))
+        .unwrap_or_default();
+
+    // Kids are either fields (with a partial name) or the widgets of this field.
+    let (fields, widgets): (Vec<_>, Vec<_>) = kids.into_iter().partition(|kid| {
+        document
+            .get_dictionary(*kid)
+            .is_ok_and(|kid| kid.has(b"T") || kid.has(b"Kids"))
+    });
+
+    for kid in &fields {
+        collect_fields(document, *kid, &name, field_type, flags, depth + 1, nodes);
+    }
+
+    if !fields.is_empty() && widgets.is_empty() {
+        return;
+    }
+
+    let widgets = if widgets.is_empty() && is_widget(dict) {
+        vec![field_id]
+    } else {
+        widgets
+    };
+
+    nodes.push(FieldNode {
+        id: field_id,
+        name,
+        field_type: match field_type {
+            Some(b"Tx") => FormFieldType::Text,
+            Some(b"Btn") if flags & FIELD_PUSHBUTTON != 0 => FormFieldType::Button,
+            Some(b"Btn") if flags & FIELD_RADIO != 0 => FormFieldType::Radio,
+            Some(b"Btn") => FormFieldType::Checkbox,
+            Some(b"Ch") if flags & FIELD_COMBO != 0 => FormFieldType::Combo,
+            Some(b"Ch") => FormFieldType::List,
+            Some(b"Sig") => FormFieldType::Signature,
+            _ => FormFieldType::Unknown,
+        },
+        flags,
+        widgets,
+    });
+}
+
+fn read_value(document: &Document, node: &FieldNode) -> Option<FormFieldValue> {
+    let value = field_attribute(document, node.id, b"V");
+
+    match node.field_type {
+        FormFieldType::Text | FormFieldType::Combo => {
+            value.and_then(text_value).map(FormFieldValue::Text)
+        }
+        FormFieldType::Checkbox => {
+            let state = value.and_then(text_value).or_else(|| {
+                node.widgets.iter().find_map(|widget_id| {
+                    let widget = document.get_dictionary(*widget_id).ok()?;
+                    text_value(widget.get(b"AS").ok()?)
+                })
+            });
+            Some(FormFieldValue::Checked(
+                state.is_some_and(|state| state != "Off"),
+            ))
+        }
+        FormFieldType::Radio => value
+            .and_then(text_value)
+            .filter(|state| state != "Off")
+            .map(FormFieldValue::Text),
+        FormFieldType::List => match value? {
+            Object::Array(values) => Some(FormFieldValue::Selected(
+                values
+                    .iter()
+                    .filter_map(|value| text_value(document.dereference(value).ok()?.1))
+                    .collect(),
+            )),
+            value => text_value(value).map(|value| FormFieldValue::Selected(vec![value])),
+        },
+        _ => None,
+    }
+}
+
+fn field_flags(flags: u32) -> FormFieldFlags {
+    let set = |flag: u32| flags & flag != 0;
+
+    FormFieldFlags {
+        read_only: set(FIELD_READ_ONLY),
+        required: set(FIELD_REQUIRED),
+        no_export: set(FIELD_NO_EXPORT),
+        multiline: set(FIELD_MULTILINE),
+        password: set(FIELD_PASSWORD),
+        comb: set(FIELD_COMB),
+        editable: set(FIELD_EDIT),
+        multi_select: set(FIELD_MULTI_SELECT),
+    }
+}
+
+fn is_widget(dict: &Dictionary) -> bool {
+    dict.get(b"Subtype")
+        .and_then(|subtype| subtype.as_name())
+        .is_ok_and(|subtype| subtype == b"Widget")
+}
+
+fn references(objects: &[Object]) -> Vec<ObjectId> {
+    objects
+        .iter()
+        .filter_map(|object| object.as_reference().ok())
+        .collect()
+}
+
+/// Maps widget annotations to the zero-based index and id of the page listing them
+/// in `/Annots`.
+fn widget_pages(document: &Document) -> HashMap<ObjectId, (u16, ObjectId)> {
+    let mut pages = HashMap::new();
+
+    for (number, page_id) in document.get_pages() {
+        let annots = document
+            .get_dictionary(page_id)
+            .and_then(|page| page.get_deref(b"Annots", document))
+            .and_then(|annots| annots.as_array());
+        let Ok(annots) = annots else {
+            continue;
+        };
+        for annotation_id in references(annots) {
+            pages.insert(annotation_id, (number as u16 - 1, page_id));
+        }
+    }
+
+    pages
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    /// One-page document with a text field and a check box under `employee`, a
+    /// radio group, a combo box and a multi-select list box.
+    pub fn form_document() -> Document {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let page_id = document.new_object_id();
+
+        let mut states = |on: &str| {
+            let bbox = || vec![0.into(), 0.into(), 12.into(), 12.into()];
+            let on_id = document.add_object(Stream::new(
+                dictionary! { "BBox" => bbox() },
+                b"0 g 0 0 10 10 re f".to_vec(),
+            ));
+            let off_id =
+                document.add_object(Stream::new(dictionary! { "BBox" => bbox() }, Vec::new()));
+            let mut normal = Dictionary::new();
+            normal.set(on, on_id);
+            normal.set("Off", off_id);
+            dictionary! { "N" => normal }
+        };
+        let remote_states = states("Yes");
+        let permanent_states = states("Permanent");
+        let fixed_states = states("Fixed");
+
+        let employee_id = document.new_object_id();
+        let name_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "Parent" => employee_id,
+            "P" => page_id,
+            "T" => Object::string_literal("name"),
+            "TU" => Object::string_literal("Full name"),
+            "FT" => "Tx",
+            "Ff" => FIELD_REQUIRED as i64,
+            "V" => Object::string_literal("Ana Lima"),
+            "MaxLen" => 20,
+            "DA" => Object::string_literal("/Helv 12 Tf 0 g"),
+            "Rect" => vec![100.into(), 100.into(), 300.into(), 120.into()],
+        });
+        let remote_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "Parent" => employee_id,
+            "T" => Object::string_literal("remote"),
+            "FT" => "Btn",
+            "V" => "Off",
+            "AS" => "Off",
+            "AP" => remote_states,
+            "Rect" => vec![100.into(), 140.into(), 112.into(), 152.into()],
+        });
+        document.objects.insert(
+            employee_id,
+            Object::Dictionary(dictionary! {
+                "T" => Object::string_literal("employee"),
+                "Kids" => vec![name_id.into(), remote_id.into()],
+            }),
+        );
+
+        let contract_id = document.new_object_id();
+        let permanent_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "Parent" => contract_id,
+            "AS" => "Permanent",
+            "AP" => permanent_states,
+            "Rect" => vec![100.into(), 180.into(), 112.into(), 192.into()],
+        });
+        let fixed_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "Parent" => contract_id,
+            "AS" => "Off",
+            "AP" => fixed_states,
+            "Rect" => vec![140.into(), 180.into(), 152.into(), 192.into()],
+        });
+        document.objects.insert(
+            contract_id,
+            Object::Dictionary(dictionary! {
+                "T" => Object::string_literal("contract"),
+                "FT" => "Btn",
+                "Ff" => FIELD_RADIO as i64,
+                "V" => "Permanent",
+                "Kids" => vec![permanent_id.into(), fixed_id.into()],
+            }),
+        );
+
+        let department_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "T" => Object::string_literal("department"),
+            "FT" => "Ch",
+            "Ff" => FIELD_COMBO as i64,
+            "Opt" => vec![
+                vec![Object::string_literal("HR"), Object::string_literal("Human resources")].into(),
+                vec![Object::string_literal("ENG"), Object::string_literal("Engineering")].into(),
+            ],
+            "V" => Object::string_literal("HR"),
+            "Rect" => vec![100.into(), 220.into(), 300.into(), 240.into()],
+        });
+        let skills_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "T" => Object::string_literal("skills"),
+            "FT" => "Ch",
+            "Ff" => FIELD_MULTI_SELECT as i64,
+            "Opt" => vec![
+                Object::string_literal("Rust"),
+                Object::string_literal("Go"),
+                Object::string_literal("SQL"),
+            ],
+            "Rect" => vec![100.into(), 300.into(), 300.into(), 360.into()],
+        });
+
+        let widgets: Vec<Object> = [
+            name_id,
+            remote_id,
+            permanent_id,
+            fixed_id,
+            department_id,
+            skills_id,
+        ]
+        .into_iter()
+        .map(Object::Reference)
+        .collect();
+        document.objects.insert(
+            page_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), 600.into(), 800.into()],
+                "Annots" => widgets,
+            }),
+        );
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "AcroForm" => dictionary! {
+                "Fields" => vec![
+                    employee_id.into(),
+                    contract_id.into(),
+                    department_id.into(),
+                    skills_id.into(),
+                ],
+                "DA" => Object::string_literal("/Helv 0 Tf 0 g"),
+                "DR" => dictionary! {
+                    "Font" => dictionary! {
+                        "Helv" => dictionary! {
+                            "Type" => "Font",
+                            "Subtype" => "Type1",
+                            "BaseFont" => "Helvetica",
+                            "Encoding" => "WinAnsiEncoding",
+                        },
+                    },
+                },
+            },
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    #[test]
+    fn test_read_form_fields() {
+        let document = form_document();
+        let fields = read_form_fields(&document).unwrap();
+
+        let names = fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "employee.name",
+                "employee.remote",
+                "contract",
+                "department",
+                "skills"
+            ]
+        );
+
+        let name = &fields[0];
+        assert_eq!(name.field_type, FormFieldType::Text);
+        assert_eq!(name.label.as_deref(), Some("Full name"));
+        assert_eq!(name.value, Some(FormFieldValue::Text("Ana Lima".into())));
+        assert_eq!(name.max_length, Some(20));
+        assert!(name.flags.required);
+        assert_eq!(
+            name.widgets[0].rect,
+            PdfRect {
+                left: 100.0,
+                top: 680.0,
+                right: 300.0,
+                bottom: 700.0,
+            }
+        );
+
+        let remote = &fields[1];
+        assert_eq!(remote.field_type, FormFieldType::Checkbox);
+        assert_eq!(remote.value, Some(FormFieldValue::Checked(false)));
+        assert_eq!(remote.widgets[0].on_state.as_deref(), Some("Yes"));
+
+        let contract = &fields[2];
+        assert_eq!(contract.field_type, FormFieldType::Radio);
+        assert_eq!(
+            contract.value,
+            Some(FormFieldValue::Text("Permanent".into()))
+        );
+        assert_eq!(
+            contract
+                .options
+                .iter()
+                .map(|option| option.value.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Permanent", "Fixed"]
+        );
+
+        let department = &fields[3];
+        assert_eq!(department.field_type, FormFieldType::Combo);
+        assert_eq!(department.options[1].label, "Engineering");
+        assert_eq!(department.options[1].value, "ENG");
+
+        let skills = &fields[4];
+        assert_eq!(skills.field_type, FormFieldType::List);
+        assert!(skills.flags.multi_select);
+        assert_eq!(skills.value, None);
+    }
+}
@@ -0,0 +1,831 @@
+use std::collections::HashMap;
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use pdfium_render::prelude::*;
+
+use crate::pdf::reader::annotation::appearance::{num, win_ansi_hex, wrap_text};
+use crate::pdf::reader::annotation::{save_document, SourceFile};
+use crate::pdf::reader::form::fields::{
+    acro_form, field_attribute, field_nodes, field_options, on_state, text_value, widget_rect,
+    FieldNode, FIELD_COMB, FIELD_EDIT, FIELD_MULTILINE, FIELD_MULTI_SELECT, FIELD_PASSWORD,
+    FIELD_READ_ONLY,
+};
+use crate::pdf::reader::form::{FormFieldType, FormFieldUpdate, FormFieldValue};
+use crate::pdf::DocumentId;
+
+/// Default appearance (`/DA`) when neither the field nor the form defines one.
+const DEFAULT_APPEARANCE: &str = "/Helv 0 Tf 0 g";
+
+/// Font size of auto-sized (`0 Tf`) multiline fields and list boxes, and the
+/// upper bound for auto-sized single-line fields.
+const AUTO_FONT_SIZE: f32 = 12.0;
+const MIN_FONT_SIZE: f32 = 4.0;
+
+const PADDING: f32 = 2.0;
+const LEADING: f32 = 1.15;
+
+/// Average Helvetica advance and cap height as a fraction of the font size.
+const CHAR_WIDTH: f32 = 0.5;
+const CAP_HEIGHT: f32 = 0.72;
+
+/// Advance of the ZapfDingbats check mark (`4`).
+const CHECK_WIDTH: f32 = 0.846;
+
+/// Highlight drawn behind the selected options of a list box.
+const SELECTION_COLOR: &str = "0.6 0.757 0.855 rg";
+
+/// What a text or choice field widget shows.
+enum FieldContent {
+    Text(String),
+    List {
+        labels: Vec<String>,
+        selected: Vec<usize>,
+    },
+}
+
+/// Font, size and colour parsed from a default appearance string.
+struct TextStyle {
+    font: String,
+    size: Option<f32>,
+    color: String,
+}
+
+/// Fills in fields of an open document, regenerates their appearances and saves it.
+pub fn fill_form_fields<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
+    id: &DocumentId,
+    updates: &[FormFieldUpdate],
+) -> Result<(), String> {
+    let mut document = file.document()?.clone();
+
+    fill_fields(&mut document, updates)?;
+
+    // Drop the worker's copy of the document before replacing the file; it is
+    // reopened with the new values on the next request.
+    documents.remove(id);
+
+    save_document(file, &document, &file.path)
+}
+
+/// Applies `updates` in order. Fields are matched by their fully qualified name.
+pub fn fill_fields(document: &mut Document, updates: &[FormFieldUpdate]) -> Result<(), String> {
+    let nodes = field_nodes(document)?;
+
+    for update in updates {
+        let node = nodes
+            .iter()
+            .find(|node| node.name == update.name)
+            .ok_or_else(|| format!("Field {} not found", update.name))?;
+        set_field_value(document, node, &update.value)?;
+    }
+
+    Ok(())
+}
+
+/// Generates the appearance of text and choice widgets that have none, from the
+/// field's current value. Forms filled by viewers relying on `/NeedAppearances`
+/// have no appearances to draw when flattened.
+pub fn generate_appearances(document: &mut Document) -> Result<usize, String> {
+    let mut generated = 0;
+
+    for node in field_nodes(document)? {
+        let missing = node
+            .widgets
+            .iter()
+            .copied()
+            .filter(|widget_id| {
+                document
+                    .get_dictionary(*widget_id)
+                    .is_ok_and(|widget| !widget.has(b"AP"))
+            })
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            continue;
+        }
+
+        let values = match field_attribute(document, node.id, b"V") {
+            Some(Object::Array(values)) => values.iter().filter_map(text_value).collect(),
+            Some(value) => text_value(value).into_iter().collect(),
+            None => Vec::new(),
+        };
+        let options = field_options(document, &node);
+        let label = |value: &String| {
+            options
+                .iter()
+                .find(|option| option.value == *value)
+                .map_or(value.clone(), |option| option.label.clone())
+        };
+
+        let content = match node.field_type {
+            FormFieldType::Text => {
+                let text = values.concat();
+                if node.flags & FIELD_PASSWORD != 0 {
+                    FieldContent::Text("*".repeat(text.chars().count()))
+                } else {
+                    FieldContent::Text(text)
+                }
+            }
+            FormFieldType::Combo => {
+                FieldContent::Text(values.first().map(label).unwrap_or_default())
+            }
+            FormFieldType::List => FieldContent::List {
+                selected: options
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, option)| values.contains(&option.value))
+                    .map(|(index, _)| index)
+                    .collect(),
+                labels: options.iter().map(|option| option.label.clone()).collect(),
+            },
+            _ => continue,
+        };
+
+        for widget_id in missing {
+            update_appearance(document, &node, widget_id, &content)?;
+            generated += 1;
+        }
+    }
+
+    Ok(generated)
+}
+
+/// Sets the value (`/V`) of a field and updates the appearance of its widgets.
+pub fn set_field_value(
+    document: &mut Document,
+    node: &FieldNode,
+    value: &FormFieldValue,
+) -> Result<(), String> {
+    if node.flags & FIELD_READ_ONLY != 0 {
+        return Err(format!("Field {} is read-only", node.name));
+    }
+
+    match node.field_type {
+        FormFieldType::Text => set_text(document, node, value),
+        FormFieldType::Checkbox | FormFieldType::Radio => set_state(document, node, value),
+        FormFieldType::Combo | FormFieldType::List => set_choice(document, node, value),
+        _ => Err(format!("Field {} cannot be filled", node.name)),
+    }
+}
+
+fn set_text(
+    document: &mut Document,
+    node: &FieldNode,
+    value: &FormFieldValue,
+) -> Result<(), String> {
+    let FormFieldValue::Text(text) = value else {
+        return Err(mismatch(node));
+    };
+
+    let max_length =
+        field_attribute(document, node.id, b"MaxLen").and_then(|max| max.as_i64().ok());
+    if let Some(max_length) = max_length {
+        if text.chars().count() as i64 > max_length {
+            return Err(format!(
+                "Value of {} is longer than {max_length} characters",
+                node.name
+            ));
+        }
+    }
+
+    field_mut(document, node)?.set("V", lopdf::text_string(text));
+
+    let shown = if node.flags & FIELD_PASSWORD != 0 {
+        "*".repeat(text.chars().count())
+    } else {
+        text.clone()
+    };
+    for widget_id in &node.widgets {
+        update_appearance(
+            document,
+            node,
+            *widget_id,
+            &FieldContent::Text(shown.clone()),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Selects a check box or radio button by switching the appearance state (`/AS`)
+/// of every widget of the field.
+fn set_state(
+    document: &mut Document,
+    node: &FieldNode,
+    value: &FormFieldValue,
+) -> Result<(), String> {
+    let states = node
+        .widgets
+        .iter()
+        .map(|widget_id| {
+            document
+                .get_dictionary(*widget_id)
+                .ok()
+                .and_then(|widget| on_state(document, widget))
+        })
+        .collect::<Vec<_>>();
+
+    let selected = match (node.field_type, value) {
+        (FormFieldType::Checkbox, FormFieldValue::Checked(true)) => Some(
+            states
+                .iter()
+                .flatten()
+                .next()
+                .cloned()
+                .unwrap_or("Yes".into()),
+        ),
+        (FormFieldType::Checkbox, FormFieldValue::Checked(false)) => None,
+        (_, FormFieldValue::Text(state)) if state.is_empty() || state == "Off" => None,
+        (_, FormFieldValue::Text(state)) => {
+            if states.iter().flatten().any(|on| on == state) {
+                Some(state.clone())
+            } else {
+                // Radio groups with `/Opt` may be filled by their export values.
+                let option = field_options(document, node)
+                    .into_iter()
+                    .find(|option| option.label == *state)
+                    .ok_or_else(|| format!("Option {state} not found for field {}", node.name))?;
+                Some(option.value)
+            }
+        }
+        _ => return Err(mismatch(node)),
+    };
+
+    field_mut(document, node)?.set(
+        "V",
+        Object::Name(selected.clone().unwrap_or("Off".into()).into_bytes()),
+    );
+
+    for (widget_id, state) in node.widgets.iter().zip(states) {
+        let on = match state {
+            Some(on) => on,
+            None if node.field_type == FormFieldType::Checkbox => {
+                check_appearance(document, *widget_id, "Yes")?;
+                "Yes".to_string()
+            }
+            None => continue,
+        };
+        let shown = if selected.as_ref() == Some(&on) {
+            on
+        } else {
+            "Off".into()
+        };
+        document
+            .get_dictionary_mut(*widget_id)
+            .map_err(|e| e.to_string())?
+            .set("AS", Object::Name(shown.into_bytes()));
+    }
+
+    Ok(())
+}
+
+/// Selects options of a combo or list box by value or label. Editable combo boxes
+/// also accept other text.
+fn set_choice(
+    document: &mut Document,
+    node: &FieldNode,
+    value: &FormFieldValue,
+) -> Result<(), String> {
+    let requested = match value {
+        FormFieldValue::Text(text) if text.is_empty() => Vec::new(),
+        FormFieldValue::Text(text) => vec![text.clone()],
+        FormFieldValue::Selected(values) => values.clone(),
+        FormFieldValue::Checked(_) => return Err(mismatch(node)),
+    };
+
+    let is_combo = node.field_type == FormFieldType::Combo;
+    if requested.len() > 1 && (is_combo || node.flags & FIELD_MULTI_SELECT == 0) {
+        return Err(format!("Field {} accepts a single option", node.name));
+    }
+
+    let options = field_options(document, node);
+    let mut indices = Vec::new();
+    let mut values = Vec::new();
+    let mut shown = String::new();
+
+    for requested in requested {
+        match options
+            .iter()
+            .position(|option| option.value == requested || option.label == requested)
+        {
+            Some(index) => {
+                indices.push(index);
+                values.push(options[index].value.clone());
+                shown = options[index].label.clone();
+            }
+            None if is_combo && node.flags & FIELD_EDIT != 0 => {
+                shown = requested.clone();
+                values.push(requested);
+            }
+            None => {
+                return Err(format!(
+                    "Option {requested} not found for field {}",
+                    node.name
+                ))
+            }
+        }
+    }
+    indices.sort_unstable();
+    indices.dedup();
+
+    let field = field_mut(document, node)?;
+    match &values[..] {
+        [] => {
+            field.remove(b"V");
+        }
+        [value] => field.set("V", lopdf::text_string(value)),
+        values => field.set(
+            "V",
+            values
+                .iter()
+                .map(|value| lopdf::text_string(value))
+                .collect::<Vec<_>>(),
+        ),
+    }
+    if indices.is_empty() || is_combo {
+        field.remove(b"I");
+    } else {
+        field.set(
+            "I",
+            indices
+                .iter()
+                .map(|index| Object::Integer(*index as i64))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    let content = if is_combo {
+        FieldContent::Text(shown)
+    } else {
+        FieldContent::List {
+            labels: options.into_iter().map(|option| option.label).collect(),
+            selected: indices,
+        }
+    };
+    for widget_id in &node.widgets {
+        update_appearance(document, node, *widget_id, &content)?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the normal appearance of a text or choice widget, following the field's
+/// default appearance (`/DA`), alignment (`/Q`) and the widget's `/MK` colours.
+fn update_appearance(
+    document: &mut Document,
+    node: &FieldNode,
+    widget_id: ObjectId,
+    content: &FieldContent,
+) -> Result<(), String> {
+    let form = acro_form(document);
+    let widget = document
+        .get_dictionary(widget_id)
+        .map_err(|e| format!("Failed to read widget of {}: {e}", node.name))?;
+    let [left, bottom, right, top] = widget_rect(document, widget)
+        .ok_or_else(|| format!("Widget of {} has no rectangle", node.name))?;
+    let (width, height) = (right - left, top - bottom);
+
+    let appearance = field_attribute(document, node.id, b"DA")
+        .or_else(|| form.and_then(|form| form.get(b"DA").ok()))
+        .and_then(text_value)
+        .unwrap_or(DEFAULT_APPEARANCE.into());
+    let style = TextStyle::parse(&appearance);
+
+    let font = form
+        .and_then(|form| form.get_deref(b"DR", document).ok())
+        .and_then(|resources| resources.as_dict().ok())
+        .and_then(|resources| resources.get_deref(b"Font", document).ok())
+        .and_then(|fonts| fonts.as_dict().ok())
+        .and_then(|fonts| fonts.get(style.font.as_bytes()).ok())
+        .cloned()
+        .unwrap_or_else(|| {
+            Object::Dictionary(dictionary! {
+                "Type" => "Font",
+                "Subtype" => "Type1",
+                "BaseFont" => "Helvetica",
+                "Encoding" => "WinAnsiEncoding",
+            })
+        });
+
+    let alignment = field_attribute(document, node.id, b"Q")
+        .or_else(|| form.and_then(|form| form.get(b"Q").ok()))
+        .and_then(|alignment| alignment.as_i64().ok())
+        .unwrap_or(0);
+    let max_length = field_attribute(document, node.id, b"MaxLen")
+        .and_then(|max| max.as_i64().ok())
+        .filter(|max| *max > 0);
+    let top_index = field_attribute(document, node.id, b"TI")
+        .and_then(|index| index.as_i64().ok())
+        .unwrap_or(0)
+        .max(0) as usize;
+
+    let characteristics = widget
+        .get_deref(b"MK", document)
+        .and_then(|characteristics| characteristics.as_dict())
+        .ok();
+    let color = |key: &[u8], stroke: bool| {
+        let values = characteristics?
+            .get_deref(key, document)
+            .ok()?
+            .as_array()
+            .ok()?
+            .iter()
+            .map(|value| value.as_float().map(num).ok())
+            .collect::<Option<Vec<_>>>()?;
+        let operator = match (values.len(), stroke) {
+            (1, false) => "g",
+            (3, false) => "rg",
+            (4, false) => "k",
+            (1, true) => "G",
+            (3, true) => "RG",
+            (4, true) => "K",
+            _ => return None,
+        };
+        Some(format!("{} {operator}", values.join(" ")))
+    };
+    let background = color(b"BG", false);
+    let border = color(b"BC", true);
+    let border_width = widget
+        .get_deref(b"BS", document)
+        .and_then(|style| style.as_dict())
+        .and_then(|style| style.get(b"W"))
+        .and_then(|width| width.as_float())
+        .unwrap_or(1.0);
+
+    let mut ops = Vec::new();
+    if let Some(background) = background {
+        ops.push(format!(
+            "{background} 0 0 {} {} re f",
+            num(width),
+            num(height)
+        ));
+    }
+    if let Some(border) = border.filter(|_| border_width > 0.0) {
+        ops.push(format!(
+            "{border} {} w {} {} {} {} re S",
+            num(border_width),
+            num(border_width / 2.0),
+            num(border_width / 2.0),
+            num(width - border_width),
+            num(height - border_width)
+        ));
+    }
+
+    ops.push("/Tx BMC".into());
+    ops.push("q".into());
+    ops.push(format!(
+        "{} {} {} {} re W n",
+        num(PADDING / 2.0),
+        num(PADDING / 2.0),
+        num(width - PADDING),
+        num(height - PADDING)
+    ));
+
+    let font_op = |size: f32| format!("/{} {} Tf {}", style.font, num(size), style.color);
+
+    match content {
+        FieldContent::Text(text) if node.flags & FIELD_MULTILINE != 0 => {
+            let size = style.size.unwrap_or(AUTO_FONT_SIZE);
+            let max_chars = ((width - PADDING * 4.0) / (size * CHAR_WIDTH)).max(1.0) as usize;
+
+            ops.push("BT".into());
+            ops.push(font_op(size));
+            ops.push(format!(
+                "{} {} Td",
+                num(PADDING * 2.0),
+                num(height - PADDING - size)
+            ));
+            ops.push(format!("{} TL", num(size * LEADING)));
+            for (index, line) in wrap_text(text, max_chars).iter().enumerate() {
+                if index > 0 {
+                    ops.push("T*".into());
+                }
+                ops.push(format!("<{}> Tj", win_ansi_hex(line)));
+            }
+            ops.push("ET".into());
+        }
+        FieldContent::Text(text) => {
+            let count = text.chars().count().max(1) as f32;
+            let comb = max_length.filter(|_| node.flags & FIELD_COMB != 0);
+            let size = style.size.unwrap_or_else(|| {
+                let cell = match comb {
+                    Some(max_length) => width / max_length as f32,
+                    None => (width - PADDING * 4.0) / count,
+                };
+                ((height - PADDING * 2.0) / LEADING)
+                    .min(cell / CHAR_WIDTH)
+                    .clamp(MIN_FONT_SIZE, AUTO_FONT_SIZE)
+            });
+            let baseline = (height - size * CAP_HEIGHT) / 2.0;
+
+            ops.push("BT".into());
+            ops.push(font_op(size));
+            match comb {
+                Some(max_length) => {
+                    let cell = width / max_length as f32;
+                    for (index, character) in text.chars().enumerate() {
+                        let x = cell * index as f32 + (cell - size * CHAR_WIDTH) / 2.0;
+                        ops.push(format!("1 0 0 1 {} {} Tm", num(x), num(baseline)));
+                        ops.push(format!("<{}> Tj", win_ansi_hex(&character.to_string())));
+                    }
+                }
+                None => {
+                    let text_width = count * size * CHAR_WIDTH;
+                    let x = match alignment {
+                        1 => (width - text_width) / 2.0,
+                        2 => width - PADDING * 2.0 - text_width,
+                        _ => PADDING * 2.0,
+                    };
+                    ops.push(format!("{} {} Td", num(x), num(baseline)));
+                    ops.push(format!("<{}> Tj", win_ansi_hex(text)));
+                }
+            }
+            ops.push("ET".into());
+        }
+        FieldContent::List { labels, selected } => {
+            let size = style.size.unwrap_or(AUTO_FONT_SIZE);
+            let line = size * LEADING;
+            let rows = (((height - PADDING) / line) as usize).max(1);
+
+            // Scroll so the first selected option is visible.
+            let first = selected.first().copied().unwrap_or(top_index);
+            let top_index = if first < top_index || first >= top_index + rows {
+                first
+            } else {
+                top_index
+            };
+
+            for row in 0..rows {
+                let index = top_index + row;
+                if selected.contains(&index) {
+                    ops.push(format!(
+                        "{SELECTION_COLOR} {} {} {} {} re f",
+                        num(PADDING / 2.0),
+                        num(height - PADDING / 2.0 - line * (row + 1) as f32),
+                        num(width - PADDING),
+                        num(line)
+                    ));
+                }
+            }
+
+            ops.push("BT".into());
+            ops.push(font_op(size));
+            for (row, label) in labels.iter().skip(top_index).take(rows).enumerate() {
+                let baseline = height - PADDING / 2.0 - line * (row + 1) as f32
+                    + (line - size * CAP_HEIGHT) / 2.0;
+                ops.push(format!(
+                    "1 0 0 1 {} {} Tm",
+                    num(PADDING * 2.0),
+                    num(baseline)
+                ));
+                ops.push(format!("<{}> Tj", win_ansi_hex(label)));
+            }
+            ops.push("ET".into());
+        }
+    }
+
+    ops.push("Q".into());
+    ops.push("EMC".into());
+
+    let mut fonts = Dictionary::new();
+    fonts.set(style.font.as_bytes(), font);
+    let stream = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "FormType" => 1,
+            "BBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+            "Resources" => dictionary! { "Font" => fonts },
+        },
+        format!("{}\n", ops.join("\n")).into_bytes(),
+    );
+
+    let appearance_id = document.add_object(stream);
+    document
+        .get_dictionary_mut(widget_id)
+        .map_err(|e| e.to_string())?
+        .set("AP", dictionary! { "N" => appearance_id });
+
+    Ok(())
+}
+
+/// Gives a check box widget without appearance streams a ZapfDingbats check mark
+/// for the `on` state and an empty `Off` state.
+fn check_appearance(document: &mut Document, widget_id: ObjectId, on: &str) -> Result<(), String> {
+    let widget = document
+        .get_dictionary(widget_id)
+        .map_err(|e| e.to_string())?;
+    let [left, bottom, right, top] =
+        widget_rect(document, widget).ok_or("Check box widget has no rectangle")?;
+    let (width, height) = (right - left, top - bottom);
+    let size = width.min(height) * 0.8;
+
+    let form = |content: String| {
+        Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "FormType" => 1,
+                "BBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+                "Resources" => dictionary! {
+                    "Font" => dictionary! {
+                        "ZaDb" => dictionary! {
+                            "Type" => "Font",
+                            "Subtype" => "Type1",
+                            "BaseFont" => "ZapfDingbats",
+                        },
+                    },
+                },
+            },
+            content.into_bytes(),
+        )
+    };
+
+    let checked = form(format!(
+        "q 0 g BT /ZaDb {} Tf {} {} Td (4) Tj ET Q\n",
+        num(size),
+        num((width - size * CHECK_WIDTH) / 2.0),
+        num((height - size * CAP_HEIGHT) / 2.0)
+    ));
+    let checked_id = document.add_object(checked);
+    let off_id = document.add_object(form(String::new()));
+
+    let mut normal = Dictionary::new();
+    normal.set(on, checked_id);
+    normal.set("Off", off_id);
+    document
+        .get_dictionary_mut(widget_id)
+        .map_err(|e| e.to_string())?
+        .set("AP", dictionary! { "N" => normal });
+
+    Ok(())
+}
+
+impl TextStyle {
+    /// Parses `/Helv 12 Tf 0 0 1 rg`. A size of 0 means auto-size and is returned
+    /// as `None`.
+    fn parse(appearance: &str) -> Self {
+        let mut style = TextStyle {
+            font: "Helv".into(),
+            size: None,
+            color: "0 g".into(),
+        };
+
+        let mut operands: Vec<&str> = Vec::new();
+        for token in appearance.split_whitespace() {
+            match token {
+                "Tf" => {
+                    if let [.., font, size] = operands[..] {
+                        style.font = font.trim_start_matches('/').to_string();
+                        style.size = size.parse().ok().filter(|size: &f32| *size > 0.0);
+                    }
+                }
+                "g" | "rg" | "k" => style.color = format!("{} {token}", operands.join(" ")),
+                _ => {
+                    operands.push(token);
+                    continue;
+                }
+            }
+            operands.clear();
+        }
+
+        style
+    }
+}
+
+fn field_mut<'a>(
+    document: &'a mut Document,
+    node: &FieldNode,
+) -> Result<&'a mut Dictionary, String> {
+    document
+        .get_dictionary_mut(node.id)
+        .map_err(|e| format!("Failed to update field {}: {e}", node.name))
+}
+
+fn mismatch(node: &FieldNode) -> String {
+    format!(
+        "Invalid value for {:?} field {}",
+        node.field_type, node.name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::form::fields::tests::form_document;
+    use crate::pdf::reader::form::read_form_fields;
+
+    fn update(name: &str, value: FormFieldValue) -> FormFieldUpdate {
+        FormFieldUpdate {
+            name: name.into(),
+            value,
+        }
+    }
+
+    fn normal_appearance(document: &Document, widget_id: ObjectId) -> String {
+        let normal = document
+            .get_dictionary(widget_id)
+            .and_then(|widget| widget.get(b"AP"))
+            .and_then(|appearance| appearance.as_dict())
+            .and_then(|appearance| appearance.get(b"N"))
+            .and_then(|normal| normal.as_reference())
+            .unwrap();
+        let stream = document.get_object(normal).unwrap().as_stream().unwrap();
+        String::from_utf8(stream.content.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_fill_fields() {
+        let mut document = form_document();
+        fill_fields(
+            &mut document,
+            &[
+                update("employee.name", FormFieldValue::Text("Bo Chen".into())),
+                update("employee.remote", FormFieldValue::Checked(true)),
+                update("contract", FormFieldValue::Text("Fixed".into())),
+                update("department", FormFieldValue::Text("Engineering".into())),
+                update(
+                    "skills",
+                    FormFieldValue::Selected(vec!["SQL".into(), "Rust".into()]),
+                ),
+            ],
+        )
+        .unwrap();
+
+        let fields = read_form_fields(&document).unwrap();
+        let values = fields
+            .iter()
+            .map(|field| field.value.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                Some(FormFieldValue::Text("Bo Chen".into())),
+                Some(FormFieldValue::Checked(true)),
+                Some(FormFieldValue::Text("Fixed".into())),
+                Some(FormFieldValue::Text("ENG".into())),
+                Some(FormFieldValue::Selected(vec!["SQL".into(), "Rust".into()])),
+            ]
+        );
+
+        let nodes = field_nodes(&document).unwrap();
+        let name = normal_appearance(&document, nodes[0].widgets[0]);
+        assert!(name.contains("/Helv 12 Tf 0 g"));
+        assert!(name.contains(&win_ansi_hex("Bo Chen")));
+
+        let department = normal_appearance(&document, nodes[3].widgets[0]);
+        assert!(department.contains(&win_ansi_hex("Engineering")));
+
+        let skills = normal_appearance(&document, nodes[4].widgets[0]);
+        assert_eq!(skills.matches(SELECTION_COLOR).count(), 2);
+
+        let states = nodes[2]
+            .widgets
+            .iter()
+            .map(|widget_id| {
+                let widget = document.get_dictionary(*widget_id).unwrap();
+                widget.get(b"AS").unwrap().as_name().unwrap().to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(states, vec![b"Off".to_vec(), b"Fixed".to_vec()]);
+    }
+
+    #[test]
+    fn test_fill_fields_rejects_invalid_values() {
+        let mut document = form_document();
+        let invalid = [
+            update("employee.name", FormFieldValue::Text("x".repeat(21))),
+            update("employee.name", FormFieldValue::Checked(true)),
+            update("contract", FormFieldValue::Text("Temporary".into())),
+            update("department", FormFieldValue::Text("Sales".into())),
+            update(
+                "department",
+                FormFieldValue::Selected(vec!["HR".into(), "ENG".into()]),
+            ),
+            update("missing", FormFieldValue::Text("x".into())),
+        ];
+        for update in invalid {
+            assert!(fill_fields(&mut document, &[update]).is_err());
+        }
+
+        let nodes = field_nodes(&document).unwrap();
+        document
+            .get_dictionary_mut(nodes[0].id)
+            .unwrap()
+            .set("Ff", FIELD_READ_ONLY as i64);
+        let error = fill_fields(
+            &mut document,
+            &[update("employee.name", FormFieldValue::Text("Bo".into()))],
+        )
+        .unwrap_err();
+        assert_eq!(error, "Field employee.name is read-only");
+    }
+
+    #[test]
+    fn test_parse_text_style() {
+        let style = TextStyle::parse("/TiRo 0 Tf 0 0 1 rg");
+        assert_eq!(style.font, "TiRo");
+        assert_eq!(style.size, None);
+        assert_eq!(style.color, "0 0 1 rg");
+    }
+}
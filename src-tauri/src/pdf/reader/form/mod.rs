@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::PdfRect;
+
+pub mod data;
+pub mod fields;
+pub mod fill;
+
+pub use data::*;
+pub use fields::*;
+pub use fill::*;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FormFieldType {
+    Text,
+    Checkbox,
+    Radio,
+    Button,
+    Combo,
+    List,
+    Signature,
+    Unknown,
+}
+
+/// Value of a form field.
+///
+/// Text and combo box fields hold `Text`, radio groups hold the export value of the
+/// selected button as `Text`, check boxes `Checked`, and list boxes `Selected`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+pub enum FormFieldValue {
+    Text(String),
+    Checked(bool),
+    Selected(Vec<String>),
+}
+
+/// Field flags (`/Ff`) relevant to filling in a form
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FormFieldFlags {
+    pub read_only: bool,
+    pub required: bool,
+    pub no_export: bool,
+
+    /// Text fields that wrap onto several lines
+    pub multiline: bool,
+    pub password: bool,
+
+    /// Text fields split into `max_length` equally spaced cells
+    pub comb: bool,
+
+    /// Combo boxes that accept values outside their options
+    pub editable: bool,
+    pub multi_select: bool,
+}
+
+/// Choice of a combo box, list box or radio group
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FormFieldOption {
+    /// Value stored in the field
+    pub value: String,
+
+    /// Text shown to the user
+    pub label: String,
+}
+
+/// Widget annotation showing a field on a page
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FormWidget {
+    pub page_index: u16,
+    pub rect: PdfRect,
+
+    /// Appearance state of check boxes and radio buttons when selected
+    pub on_state: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FormField {
+    /// Fully qualified name, e.g. `employee.address.city`
+    pub name: String,
+
+    /// Alternate name shown in the user interface (`/TU`)
+    pub label: Option<String>,
+    pub field_type: FormFieldType,
+    pub value: Option<FormFieldValue>,
+    pub options: Vec<FormFieldOption>,
+    pub flags: FormFieldFlags,
+    pub max_length: Option<u32>,
+    pub widgets: Vec<FormWidget>,
+}
+
+/// New value for the field named `name`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FormFieldUpdate {
+    pub name: String,
+    pub value: FormFieldValue,
+}
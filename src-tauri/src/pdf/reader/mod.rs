@@ -1,9 +1,19 @@
 pub mod annotation;
+pub mod form;
 pub mod metadata;
+pub mod redaction;
 pub mod render;
+pub mod scan;
+pub mod security;
+pub mod signature;
 pub mod text;
 
 pub use annotation::*;
+pub use form::*;
 pub use metadata::*;
+pub use redaction::*;
 pub use render::*;
+pub use scan::*;
+pub use security::*;
+pub use signature::*;
 pub use text::*;
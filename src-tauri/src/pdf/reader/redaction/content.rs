@@ -0,0 +1,957 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::pdf::reader::redaction::image::redact_image;
+use crate::pdf::reader::redaction::{RedactionSummary, Region};
+
+/// Transformation matrix `[a b c d e f]` as used by the `cm` operator.
+pub type Matrix = [f32; 6];
+
+pub const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Form XObjects may draw each other; deeper nesting is dropped when redacted.
+const MAX_FORM_DEPTH: usize = 8;
+
+/// Product `first × second`: applies `first`, then `second`.
+pub fn multiply(first: &Matrix, second: &Matrix) -> Matrix {
+    let [a, b, c, d, e, f] = *first;
+    let [a2, b2, c2, d2, e2, f2] = *second;
+    [
+        a * a2 + b * c2,
+        a * b2 + b * d2,
+        c * a2 + d * c2,
+        c * b2 + d * d2,
+        e * a2 + f * c2 + e2,
+        e * b2 + f * d2 + f2,
+    ]
+}
+
+pub fn transform(matrix: &Matrix, x: f32, y: f32) -> (f32, f32) {
+    let [a, b, c, d, e, f] = *matrix;
+    (a * x + c * y + e, b * x + d * y + f)
+}
+
+pub fn contains_point(region: &Region, (x, y): (f32, f32)) -> bool {
+    x >= region[0] && x <= region[2] && y >= region[1] && y <= region[3]
+}
+
+fn translation(x: f32, y: f32) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, x, y]
+}
+
+fn matrix(operands: &[Object]) -> Option<Matrix> {
+    let values = operands
+        .iter()
+        .map(|operand| operand.as_float().ok())
+        .collect::<Option<Vec<_>>>()?;
+    values.try_into().ok()
+}
+
+fn number(operands: &[Object], index: usize) -> Option<f32> {
+    operands.get(index)?.as_float().ok()
+}
+
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Object> {
+    document.dereference(object).ok().map(|(_, object)| object)
+}
+
+fn dictionary<'a>(
+    document: &'a Document,
+    parent: &'a Dictionary,
+    key: &[u8],
+) -> Option<&'a Dictionary> {
+    resolve(document, parent.get(key).ok()?)?.as_dict().ok()
+}
+
+/// Axis-aligned bounds in default user space.
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+    left: f32,
+    bottom: f32,
+    right: f32,
+    top: f32,
+}
+
+impl Bounds {
+    fn empty() -> Self {
+        Self {
+            left: f32::INFINITY,
+            bottom: f32::INFINITY,
+            right: f32::NEG_INFINITY,
+            top: f32::NEG_INFINITY,
+        }
+    }
+
+    /// Bounds of the unit square, where images are drawn, under a matrix.
+    pub fn unit_square(matrix: &Matrix) -> Self {
+        Self::of_rect(matrix, [0.0, 0.0, 1.0, 1.0])
+    }
+
+    fn of_rect(matrix: &Matrix, [x0, y0, x1, y1]: [f32; 4]) -> Self {
+        let mut bounds = Self::empty();
+        for (x, y) in [(x0, y0), (x1, y0), (x0, y1), (x1, y1)] {
+            bounds.add(transform(matrix, x, y));
+        }
+        bounds
+    }
+
+    fn add(&mut self, (x, y): (f32, f32)) {
+        self.left = self.left.min(x);
+        self.bottom = self.bottom.min(y);
+        self.right = self.right.max(x);
+        self.top = self.top.max(y);
+    }
+
+    pub fn intersects(&self, region: &Region) -> bool {
+        self.left <= region[2]
+            && self.right >= region[0]
+            && self.bottom <= region[3]
+            && self.top >= region[1]
+    }
+
+    /// The region lies inside these bounds.
+    fn contains(&self, region: &Region) -> bool {
+        self.left <= region[0]
+            && self.bottom <= region[1]
+            && self.right >= region[2]
+            && self.top >= region[3]
+    }
+
+    /// These bounds lie inside the region.
+    pub fn within(&self, region: &Region) -> bool {
+        region[0] <= self.left
+            && region[1] <= self.bottom
+            && region[2] >= self.right
+            && region[3] >= self.top
+    }
+}
+
+/// Glyph widths of a font, in text space units.
+struct FontMetrics {
+    /// Type0 fonts, which are assumed to use 2-byte codes (Identity-H and the like)
+    two_byte: bool,
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+}
+
+impl FontMetrics {
+    fn load(document: &Document, font: &Dictionary) -> Self {
+        let subtype = font
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .unwrap_or(b"");
+
+        if subtype == b"Type0" {
+            let descendant = font
+                .get(b"DescendantFonts")
+                .ok()
+                .and_then(|fonts| resolve(document, fonts))
+                .and_then(|fonts| fonts.as_array().ok())
+                .and_then(|fonts| fonts.first())
+                .and_then(|font| resolve(document, font))
+                .and_then(|font| font.as_dict().ok());
+
+            let mut widths = HashMap::new();
+            let mut default_width = 1000.0;
+            if let Some(descendant) = descendant {
+                if let Ok(width) = descendant.get(b"DW").and_then(Object::as_float) {
+                    default_width = width;
+                }
+                let entries = descendant
+                    .get(b"W")
+                    .ok()
+                    .and_then(|w| resolve(document, w))
+                    .and_then(|w| w.as_array().ok())
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                cid_widths(document, entries, &mut widths);
+            }
+
+            return Self {
+                two_byte: true,
+                widths: widths
+                    .into_iter()
+                    .map(|(cid, w)| (cid, w / 1000.0))
+                    .collect(),
+                default_width: default_width / 1000.0,
+            };
+        }
+
+        // Type3 glyph widths are in glyph space, mapped to text space by /FontMatrix
+        let scale = if subtype == b"Type3" {
+            font.get(b"FontMatrix")
+                .ok()
+                .and_then(|m| resolve(document, m))
+                .and_then(|m| m.as_array().ok())
+                .and_then(|m| m.first())
+                .and_then(|a| a.as_float().ok())
+                .unwrap_or(0.001)
+        } else {
+            0.001
+        };
+
+        let first_char = font.get(b"FirstChar").and_then(Object::as_i64).unwrap_or(0) as u32;
+        let widths = font
+            .get(b"Widths")
+            .ok()
+            .and_then(|w| resolve(document, w))
+            .and_then(|w| w.as_array().ok())
+            .map(|widths| {
+                widths
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, w)| {
+                        let width = resolve(document, w)?.as_float().ok()?;
+                        Some((first_char + i as u32, width * scale))
+                    })
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+
+        let missing_width = dictionary(document, font, b"FontDescriptor")
+            .and_then(|descriptor| descriptor.get(b"MissingWidth").ok())
+            .and_then(|w| w.as_float().ok());
+
+        // Standard 14 fonts come without widths; assume an average glyph
+        let default_width = match missing_width {
+            Some(width) => width * scale,
+            None if widths.is_empty() => 0.5,
+            None => 0.0,
+        };
+
+        Self {
+            two_byte: false,
+            widths,
+            default_width,
+        }
+    }
+
+    /// Splits a string operand into character codes, each with its bytes.
+    fn codes<'b>(&self, bytes: &'b [u8]) -> Vec<(u32, &'b [u8])> {
+        if self.two_byte {
+            bytes
+                .chunks(2)
+                .map(|code| (code.iter().fold(0, |acc, b| (acc << 8) | *b as u32), code))
+                .collect()
+        } else {
+            bytes.chunks(1).map(|code| (code[0] as u32, code)).collect()
+        }
+    }
+
+    fn width(&self, code: u32) -> f32 {
+        self.widths
+            .get(&code)
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+}
+
+/// Reads a CIDFont `/W` array: `c [w1 w2 ...]` or `c_first c_last w`.
+fn cid_widths(document: &Document, entries: &[Object], widths: &mut HashMap<u32, f32>) {
+    let mut i = 0;
+    while i + 1 < entries.len() {
+        let Ok(first) = entries[i].as_i64() else {
+            return;
+        };
+        match resolve(document, &entries[i + 1]) {
+            Some(Object::Array(list)) => {
+                for (offset, width) in list.iter().enumerate() {
+                    if let Ok(width) = width.as_float() {
+                        widths.insert(first as u32 + offset as u32, width);
+                    }
+                }
+                i += 2;
+            }
+            Some(last) => {
+                let (Ok(last), Some(Ok(width))) =
+                    (last.as_i64(), entries.get(i + 2).map(Object::as_float))
+                else {
+                    return;
+                };
+                for cid in first..=last.min(first + 0xFFFF) {
+                    widths.insert(cid as u32, width);
+                }
+                i += 3;
+            }
+            None => return,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    font: Option<Rc<FontMetrics>>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scaling: f32,
+    leading: f32,
+    rise: f32,
+}
+
+impl GraphicsState {
+    fn new(ctm: Matrix) -> Self {
+        Self {
+            ctm,
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        }
+    }
+}
+
+/// Path under construction, split into subpaths so that only the ones touching a
+/// redacted area are dropped.
+#[derive(Default)]
+struct Path {
+    subpaths: Vec<(Vec<Operation>, Bounds)>,
+    clip: Option<Operation>,
+}
+
+impl Path {
+    fn add(&mut self, operation: Operation, ctm: &Matrix) {
+        let values = operation
+            .operands
+            .iter()
+            .filter_map(|operand| operand.as_float().ok())
+            .collect::<Vec<_>>();
+
+        match operation.operator.as_str() {
+            "re" if values.len() == 4 => {
+                let [x, y, w, h] = [values[0], values[1], values[2], values[3]];
+                let bounds = Bounds::of_rect(ctm, [x, y, x + w, y + h]);
+                self.subpaths.push((vec![operation], bounds));
+            }
+            "m" => {
+                let mut bounds = Bounds::empty();
+                for point in values.chunks_exact(2) {
+                    bounds.add(transform(ctm, point[0], point[1]));
+                }
+                self.subpaths.push((vec![operation], bounds));
+            }
+            _ => {
+                if self.subpaths.is_empty() {
+                    self.subpaths.push((Vec::new(), Bounds::empty()));
+                }
+                let (operations, bounds) = self.subpaths.last_mut().unwrap();
+                for point in values.chunks_exact(2) {
+                    bounds.add(transform(ctm, point[0], point[1]));
+                }
+                operations.push(operation);
+            }
+        }
+    }
+
+    /// Ends the path with a painting operator. Subpaths crossing a redacted area
+    /// are dropped; a subpath enclosing a whole area (a page background, say) is
+    /// kept, as the area is painted over anyway.
+    fn finish(
+        &mut self,
+        paint: Operation,
+        regions: &[Region],
+        summary: &mut RedactionSummary,
+    ) -> Vec<Operation> {
+        let Path { subpaths, clip } = std::mem::take(self);
+
+        let redacted = |bounds: &Bounds| {
+            regions
+                .iter()
+                .any(|region| bounds.intersects(region) && !bounds.contains(region))
+        };
+        let removed = if paint.operator == "n" {
+            0
+        } else {
+            subpaths
+                .iter()
+                .filter(|(_, bounds)| redacted(bounds))
+                .count()
+        };
+
+        let mut output = Vec::new();
+        if removed == 0 {
+            output.extend(subpaths.into_iter().flat_map(|(operations, _)| operations));
+            output.extend(clip);
+            output.push(paint);
+            return output;
+        }
+
+        summary.paths += removed;
+
+        // A clipping path still has to clip what follows; it is only no longer painted
+        if let Some(clip) = clip {
+            output.extend(subpaths.into_iter().flat_map(|(operations, _)| operations));
+            output.push(clip);
+            output.push(Operation::new("n", vec![]));
+            return output;
+        }
+
+        let kept = subpaths
+            .into_iter()
+            .filter(|(_, bounds)| !redacted(bounds))
+            .flat_map(|(operations, _)| operations)
+            .collect::<Vec<_>>();
+        if !kept.is_empty() {
+            output.extend(kept);
+            output.push(paint);
+        }
+        output
+    }
+}
+
+/// Rewrites content streams without the text, images and vector graphics that
+/// fall inside the redacted regions.
+pub struct Redactor<'d> {
+    document: &'d mut Document,
+    regions: &'d [Region],
+    summary: &'d mut RedactionSummary,
+}
+
+impl<'d> Redactor<'d> {
+    pub fn new(
+        document: &'d mut Document,
+        regions: &'d [Region],
+        summary: &'d mut RedactionSummary,
+    ) -> Self {
+        Self {
+            document,
+            regions,
+            summary,
+        }
+    }
+
+    /// Replaces the content of a page with its redacted content, followed by
+    /// `overlay`, which is drawn in default user space.
+    pub fn redact_page(&mut self, page_id: ObjectId, overlay: &[u8]) -> Result<(), String> {
+        // Streams are joined with a line break, as a token may not span two streams
+        let mut content = Vec::new();
+        for stream_id in self.document.get_page_contents(page_id) {
+            if let Ok(stream) = self
+                .document
+                .get_object(stream_id)
+                .and_then(Object::as_stream)
+            {
+                match stream.decompressed_content() {
+                    Ok(data) => content.extend(data),
+                    Err(_) => content.extend(&stream.content),
+                }
+                content.push(b'\n');
+            }
+        }
+
+        let mut resources = page_resources(self.document, page_id);
+        let redacted = self.redact_content(&content, &mut resources, IDENTITY, 0)?;
+
+        let mut output = b"q\n".to_vec();
+        output.extend(redacted);
+        output.extend(b"\nQ\n");
+        output.extend(overlay);
+
+        let mut stream = Stream::new(Dictionary::new(), output);
+        let _ = stream.compress();
+        let stream_id = self.document.add_object(stream);
+
+        let page = self
+            .document
+            .get_dictionary_mut(page_id)
+            .map_err(|e| format!("Failed to get page: {e}"))?;
+        page.set("Contents", stream_id);
+        page.set("Resources", resources);
+        Ok(())
+    }
+
+    fn redact_content(
+        &mut self,
+        content: &[u8],
+        resources: &mut Dictionary,
+        ctm: Matrix,
+        depth: usize,
+    ) -> Result<Vec<u8>, String> {
+        // Content that cannot be parsed cannot be redacted; refuse rather than keep it
+        let content =
+            Content::decode(content).map_err(|e| format!("Failed to parse content stream: {e}"))?;
+
+        let mut output = Vec::with_capacity(content.operations.len());
+        let mut state = GraphicsState::new(ctm);
+        let mut saved = Vec::new();
+        let mut fonts: HashMap<Vec<u8>, Rc<FontMetrics>> = HashMap::new();
+        let mut text_matrix = IDENTITY;
+        let mut line_matrix = IDENTITY;
+        let mut path = Path::default();
+
+        for operation in content.operations {
+            let operands = &operation.operands;
+            match operation.operator.as_str() {
+                "q" => {
+                    saved.push(state.clone());
+                    output.push(operation);
+                }
+                "Q" => {
+                    if let Some(previous) = saved.pop() {
+                        state = previous;
+                    }
+                    output.push(operation);
+                }
+                "cm" => {
+                    if let Some(m) = matrix(operands) {
+                        state.ctm = multiply(&m, &state.ctm);
+                    }
+                    output.push(operation);
+                }
+                "BT" => {
+                    text_matrix = IDENTITY;
+                    line_matrix = IDENTITY;
+                    output.push(operation);
+                }
+                "Tf" => {
+                    if let Some(name) = operands.first().and_then(|name| name.as_name().ok()) {
+                        let font = fonts
+                            .entry(name.to_vec())
+                            .or_insert_with(|| {
+                                let font = dictionary(self.document, resources, b"Font")
+                                    .and_then(|fonts| dictionary(self.document, fonts, name))
+                                    .map(|font| FontMetrics::load(self.document, font))
+                                    .unwrap_or_else(|| {
+                                        FontMetrics::load(self.document, &Dictionary::new())
+                                    });
+                                Rc::new(font)
+                            })
+                            .clone();
+                        state.font = Some(font);
+                    }
+                    state.font_size = number(operands, 1).unwrap_or(state.font_size);
+                    output.push(operation);
+                }
+                "Tc" | "Tw" | "Tz" | "TL" | "Ts" => {
+                    if let Some(value) = number(operands, 0) {
+                        match operation.operator.as_str() {
+                            "Tc" => state.char_spacing = value,
+                            "Tw" => state.word_spacing = value,
+                            "Tz" => state.horizontal_scaling = value / 100.0,
+                            "TL" => state.leading = value,
+                            _ => state.rise = value,
+                        }
+                    }
+                    output.push(operation);
+                }
+                "Td" | "TD" => {
+                    if let (Some(x), Some(y)) = (number(operands, 0), number(operands, 1)) {
+                        if operation.operator == "TD" {
+                            state.leading = -y;
+                        }
+                        line_matrix = multiply(&translation(x, y), &line_matrix);
+                        text_matrix = line_matrix;
+                    }
+                    output.push(operation);
+                }
+                "Tm" => {
+                    if let Some(m) = matrix(operands) {
+                        line_matrix = m;
+                        text_matrix = m;
+                    }
+                    output.push(operation);
+                }
+                "T*" => {
+                    line_matrix = multiply(&translation(0.0, -state.leading), &line_matrix);
+                    text_matrix = line_matrix;
+                    output.push(operation);
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    let operator = operation.operator.as_str();
+                    if operator == "\"" {
+                        state.word_spacing = number(operands, 0).unwrap_or(state.word_spacing);
+                        state.char_spacing = number(operands, 1).unwrap_or(state.char_spacing);
+                        output.push(Operation::new("Tw", vec![Object::Real(state.word_spacing)]));
+                        output.push(Operation::new("Tc", vec![Object::Real(state.char_spacing)]));
+                    }
+                    if operator == "'" || operator == "\"" {
+                        line_matrix = multiply(&translation(0.0, -state.leading), &line_matrix);
+                        text_matrix = line_matrix;
+                        output.push(Operation::new("T*", vec![]));
+                    }
+
+                    let elements = match operator {
+                        "TJ" => operands
+                            .first()
+                            .and_then(|array| array.as_array().ok())
+                            .cloned()
+                            .unwrap_or_default(),
+                        _ => operands.last().cloned().into_iter().collect(),
+                    };
+
+                    match self.show_text(&state, &mut text_matrix, &elements) {
+                        Some(shown) => {
+                            output.push(Operation::new("TJ", vec![Object::Array(shown)]))
+                        }
+                        None if operator == "Tj" || operator == "TJ" => {
+                            output.push(operation.clone())
+                        }
+                        None => output.push(Operation::new("Tj", elements)),
+                    }
+                }
+                "m" | "l" | "c" | "v" | "y" | "h" | "re" => path.add(operation, &state.ctm),
+                "W" | "W*" => path.clip = Some(operation),
+                "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "n" => {
+                    output.extend(path.finish(operation, self.regions, self.summary));
+                }
+                "Do" => {
+                    if let Some(name) = operands.first().and_then(|name| name.as_name().ok()) {
+                        let name = name.to_vec();
+                        self.draw_xobject(&name, &state.ctm, resources, depth, &mut output)?;
+                    }
+                }
+                "BI" => self.inline_image(operation, &state.ctm, resources, &mut output),
+                _ => output.push(operation),
+            }
+        }
+
+        Content { operations: output }
+            .encode()
+            .map_err(|e| format!("Failed to write content stream: {e}"))
+    }
+
+    /// Shows text, leaving out glyphs whose centre lies in a redacted region. The
+    /// space they took is kept as a `TJ` offset so the remaining glyphs do not
+    /// move. Returns `None` when nothing was removed.
+    fn show_text(
+        &mut self,
+        state: &GraphicsState,
+        text_matrix: &mut Matrix,
+        elements: &[Object],
+    ) -> Option<Vec<Object>> {
+        let font = state.font.clone()?;
+        let size = state.font_size;
+        let scaling = state.horizontal_scaling;
+
+        let mut output = Vec::new();
+        let mut current = Vec::new();
+        let mut format = StringFormat::Literal;
+        let mut offset = 0.0;
+        let mut removed = 0;
+
+        fn push_string(output: &mut Vec<Object>, current: &mut Vec<u8>, format: StringFormat) {
+            if !current.is_empty() {
+                output.push(Object::String(std::mem::take(current), format));
+            }
+        }
+        fn push_offset(output: &mut Vec<Object>, offset: &mut f32) {
+            if *offset != 0.0 {
+                output.push(Object::Real(*offset));
+                *offset = 0.0;
+            }
+        }
+
+        for element in elements {
+            match element {
+                Object::String(bytes, string_format) => {
+                    format = *string_format;
+                    for (code, raw) in font.codes(bytes) {
+                        let width = font.width(code);
+                        let rendering = multiply(
+                            &[size * scaling, 0.0, 0.0, size, 0.0, state.rise],
+                            &multiply(text_matrix, &state.ctm),
+                        );
+                        // Centre of the glyph box, taking glyphs as 0.6 em high
+                        let centre = transform(&rendering, width / 2.0, 0.3);
+
+                        let spacing = state.char_spacing
+                            + if !font.two_byte && code == 32 {
+                                state.word_spacing
+                            } else {
+                                0.0
+                            };
+                        let advance = (width * size + spacing) * scaling;
+
+                        if self
+                            .regions
+                            .iter()
+                            .any(|region| contains_point(region, centre))
+                        {
+                            removed += 1;
+                            push_string(&mut output, &mut current, format);
+                            if size * scaling != 0.0 {
+                                offset -= advance * 1000.0 / (size * scaling);
+                            }
+                        } else {
+                            push_offset(&mut output, &mut offset);
+                            current.extend_from_slice(raw);
+                        }
+
+                        *text_matrix = multiply(&translation(advance, 0.0), text_matrix);
+                    }
+                }
+                other => {
+                    let Ok(adjustment) = other.as_float() else {
+                        continue;
+                    };
+                    push_string(&mut output, &mut current, format);
+                    offset += adjustment;
+                    *text_matrix = multiply(
+                        &translation(-adjustment / 1000.0 * size * scaling, 0.0),
+                        text_matrix,
+                    );
+                }
+            }
+        }
+
+        if removed == 0 {
+            return None;
+        }
+
+        // The trailing offset still moves the text that follows
+        push_string(&mut output, &mut current, format);
+        push_offset(&mut output, &mut offset);
+        self.summary.glyphs += removed;
+        Some(output)
+    }
+
+    fn draw_xobject(
+        &mut self,
+        name: &[u8],
+        ctm: &Matrix,
+        resources: &mut Dictionary,
+        depth: usize,
+        output: &mut Vec<Operation>,
+    ) -> Result<(), String> {
+        let keep = Operation::new("Do", vec![Object::Name(name.to_vec())]);
+
+        let Some((xobject_id, stream)) = dictionary(self.document, resources, b"XObject")
+            .and_then(|xobjects| xobjects.get(name).ok())
+            .and_then(|xobject| xobject.as_reference().ok())
+            .and_then(|xobject_id| {
+                let stream = self
+                    .document
+                    .get_object(xobject_id)
+                    .and_then(Object::as_stream)
+                    .ok()?;
+                Some((xobject_id, stream))
+            })
+        else {
+            output.push(keep);
+            return Ok(());
+        };
+
+        let subtype = stream
+            .dict
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .unwrap_or_default()
+            .to_vec();
+        match subtype.as_slice() {
+            b"Image" => {
+                let bounds = Bounds::unit_square(ctm);
+                if !self.regions.iter().any(|region| bounds.intersects(region)) {
+                    output.push(keep);
+                    return Ok(());
+                }
+
+                self.summary.images += 1;
+                if self.regions.iter().any(|region| bounds.within(region)) {
+                    return Ok(());
+                }
+
+                // Images that cannot be decoded are dropped entirely
+                if let Some(image_id) = redact_image(self.document, xobject_id, ctm, self.regions) {
+                    let name = add_xobject(self.document, resources, image_id);
+                    output.push(Operation::new("Do", vec![Object::Name(name)]));
+                }
+            }
+            b"Form" => {
+                let form_matrix = stream
+                    .dict
+                    .get(b"Matrix")
+                    .and_then(Object::as_array)
+                    .ok()
+                    .and_then(|m| matrix(m))
+                    .unwrap_or(IDENTITY);
+                let form_ctm = multiply(&form_matrix, ctm);
+
+                let bbox = stream
+                    .dict
+                    .get(b"BBox")
+                    .and_then(Object::as_array)
+                    .ok()
+                    .and_then(|bbox| {
+                        bbox.iter()
+                            .map(|v| v.as_float().ok())
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .and_then(|bbox| <[f32; 4]>::try_from(bbox).ok());
+                if let Some(bbox) = bbox {
+                    let bounds = Bounds::of_rect(&form_ctm, bbox);
+                    if !self.regions.iter().any(|region| bounds.intersects(region)) {
+                        output.push(keep);
+                        return Ok(());
+                    }
+                }
+
+                if depth >= MAX_FORM_DEPTH {
+                    return Ok(());
+                }
+
+                let content = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                let mut dict = stream.dict.clone();
+                let mut form_resources = dictionary(self.document, &stream.dict, b"Resources")
+                    .cloned()
+                    .unwrap_or_else(|| resources.clone());
+
+                // Forms can be shared between pages, so the redacted one is a copy
+                let redacted =
+                    self.redact_content(&content, &mut form_resources, form_ctm, depth + 1)?;
+                dict.remove(b"Filter");
+                dict.remove(b"DecodeParms");
+                dict.set("Resources", form_resources);
+
+                let mut form = Stream::new(dict, redacted);
+                let _ = form.compress();
+                let form_id = self.document.add_object(form);
+
+                let name = add_xobject(self.document, resources, form_id);
+                output.push(Operation::new("Do", vec![Object::Name(name)]));
+            }
+            _ => output.push(keep),
+        }
+
+        Ok(())
+    }
+
+    /// Inline images touching a region are dropped. `Content::encode` cannot
+    /// write inline images back, so the others become image XObjects.
+    fn inline_image(
+        &mut self,
+        operation: Operation,
+        ctm: &Matrix,
+        resources: &mut Dictionary,
+        output: &mut Vec<Operation>,
+    ) {
+        let Some(Object::Stream(image)) = operation.operands.into_iter().next() else {
+            return;
+        };
+
+        let bounds = Bounds::unit_square(ctm);
+        if self.regions.iter().any(|region| bounds.intersects(region)) {
+            self.summary.images += 1;
+            return;
+        }
+
+        let image = image_xobject(self.document, resources, image);
+        let image_id = self.document.add_object(image);
+        let name = add_xobject(self.document, resources, image_id);
+        output.push(Operation::new("Do", vec![Object::Name(name)]));
+    }
+}
+
+/// Resources of a page, including inherited ones, as a direct dictionary that new
+/// XObjects can be added to.
+pub fn page_resources(document: &Document, page_id: ObjectId) -> Dictionary {
+    let mut node = document.get_dictionary(page_id).ok();
+    while let Some(dict) = node {
+        if let Some(resources) = dictionary(document, dict, b"Resources") {
+            return resources.clone();
+        }
+        node = dictionary(document, dict, b"Parent");
+    }
+    Dictionary::new()
+}
+
+pub fn add_xobject(
+    document: &Document,
+    resources: &mut Dictionary,
+    xobject_id: ObjectId,
+) -> Vec<u8> {
+    let mut xobjects = dictionary(document, resources, b"XObject")
+        .cloned()
+        .unwrap_or_default();
+
+    let name = (0..)
+        .map(|i| format!("X{i}").into_bytes())
+        .find(|name| !xobjects.has(name))
+        .unwrap_or_default();
+    xobjects.set(name.clone(), xobject_id);
+    resources.set("XObject", xobjects);
+
+    name
+}
+
+/// Expands the abbreviated keys and names of an inline image into an image
+/// XObject.
+pub fn image_xobject(document: &Document, resources: &Dictionary, image: Stream) -> Stream {
+    fn expand_name(name: &[u8]) -> &[u8] {
+        match name {
+            b"G" => b"DeviceGray",
+            b"RGB" => b"DeviceRGB",
+            b"CMYK" => b"DeviceCMYK",
+            b"I" => b"Indexed",
+            b"AHx" => b"ASCIIHexDecode",
+            b"A85" => b"ASCII85Decode",
+            b"LZW" => b"LZWDecode",
+            b"Fl" => b"FlateDecode",
+            b"RL" => b"RunLengthDecode",
+            b"CCF" => b"CCITTFaxDecode",
+            b"DCT" => b"DCTDecode",
+            other => other,
+        }
+    }
+    fn expand(object: &Object) -> Object {
+        match object {
+            Object::Name(name) => Object::Name(expand_name(name).to_vec()),
+            Object::Array(items) => Object::Array(items.iter().map(expand).collect()),
+            other => other.clone(),
+        }
+    }
+
+    let mut dict = Dictionary::new();
+    dict.set("Type", "XObject");
+    dict.set("Subtype", "Image");
+    for (key, value) in image.dict.iter() {
+        let key: &[u8] = match key.as_slice() {
+            b"BPC" => b"BitsPerComponent",
+            b"CS" => b"ColorSpace",
+            b"D" => b"Decode",
+            b"DP" => b"DecodeParms",
+            b"F" => b"Filter",
+            b"H" => b"Height",
+            b"IM" => b"ImageMask",
+            b"I" => b"Interpolate",
+            b"W" => b"Width",
+            b"L" | b"Length" => continue,
+            other => other,
+        };
+
+        let value = match (key, value) {
+            // Named colour spaces other than the device ones live in the resources
+            (b"ColorSpace", Object::Name(name)) if expand_name(name) == name.as_slice() => {
+                dictionary(document, resources, b"ColorSpace")
+                    .and_then(|spaces| spaces.get(name).ok())
+                    .cloned()
+                    .unwrap_or_else(|| value.clone())
+            }
+            (b"ColorSpace" | b"Filter", value) => expand(value),
+            _ => value.clone(),
+        };
+        dict.set(key.to_vec(), value);
+    }
+
+    Stream::new(dict, image.content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix() {
+        let scaled = multiply(&[2.0, 0.0, 0.0, 2.0, 0.0, 0.0], &translation(10.0, 20.0));
+        assert_eq!(transform(&scaled, 1.0, 1.0), (12.0, 22.0));
+        assert!(Bounds::unit_square(&scaled).within(&[10.0, 20.0, 12.0, 22.0]));
+    }
+}
@@ -0,0 +1,167 @@
+use image::ImageFormat;
+use lopdf::{Document, Object, ObjectId, Stream};
+
+use crate::pdf::reader::redaction::content::{contains_point, transform, Matrix};
+use crate::pdf::reader::redaction::Region;
+
+/// Filters whose output lopdf can decode to raw samples.
+const DECODABLE_FILTERS: [&[u8]; 5] = [
+    b"FlateDecode",
+    b"LZWDecode",
+    b"ASCIIHexDecode",
+    b"ASCII85Decode",
+    b"RunLengthDecode",
+];
+
+/// Copies an image XObject with the pixels inside the regions cleared. Returns
+/// `None` for images it cannot decode, which the caller drops instead.
+pub fn redact_image(
+    document: &mut Document,
+    image_id: ObjectId,
+    ctm: &Matrix,
+    regions: &[Region],
+) -> Option<ObjectId> {
+    let stream = document
+        .get_object(image_id)
+        .ok()?
+        .as_stream()
+        .ok()?
+        .clone();
+    let dict = &stream.dict;
+
+    let width = dict.get(b"Width").and_then(Object::as_i64).ok()? as usize;
+    let height = dict.get(b"Height").and_then(Object::as_i64).ok()? as usize;
+    if dict
+        .get(b"ImageMask")
+        .and_then(Object::as_bool)
+        .unwrap_or(false)
+    {
+        return None;
+    }
+
+    let filters = match dict.get(b"Filter") {
+        Ok(Object::Name(name)) => vec![name.as_slice()],
+        Ok(Object::Array(names)) => names
+            .iter()
+            .filter_map(|name| name.as_name().ok())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    // JPEG images are written back as decoded RGB samples
+    let (mut samples, channels, rgb) = if filters == [b"DCTDecode".as_slice()] {
+        let image = image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg)
+            .ok()?
+            .to_rgb8();
+        if image.width() as usize != width || image.height() as usize != height {
+            return None;
+        }
+        (image.into_raw(), 3, true)
+    } else {
+        if filters
+            .iter()
+            .any(|filter| !DECODABLE_FILTERS.contains(filter))
+        {
+            return None;
+        }
+        if dict
+            .get(b"BitsPerComponent")
+            .and_then(Object::as_i64)
+            .ok()?
+            != 8
+        {
+            return None;
+        }
+        let samples = if filters.is_empty() {
+            stream.content.clone()
+        } else {
+            stream.decompressed_content().ok()?
+        };
+        (
+            samples,
+            channels(document, dict.get(b"ColorSpace").ok()?)?,
+            false,
+        )
+    };
+
+    if samples.len() < width * height * channels {
+        return None;
+    }
+
+    // Image space maps the unit square, top row first
+    for row in 0..height {
+        for column in 0..width {
+            let centre = transform(
+                ctm,
+                (column as f32 + 0.5) / width as f32,
+                1.0 - (row as f32 + 0.5) / height as f32,
+            );
+            if regions.iter().any(|region| contains_point(region, centre)) {
+                let start = (row * width + column) * channels;
+                samples[start..start + channels].fill(0);
+            }
+        }
+    }
+
+    let mut dict = stream.dict.clone();
+    dict.remove(b"Filter");
+    dict.remove(b"DecodeParms");
+    if rgb {
+        dict.set("ColorSpace", "DeviceRGB");
+        dict.remove(b"Decode");
+    }
+
+    // Colour-key masks keep working on the new samples; explicit masks are images
+    // of their own and would still show the redacted shapes.
+    if dict.get(b"Mask").and_then(Object::as_reference).is_ok() {
+        dict.remove(b"Mask");
+    }
+    if let Ok(mask_id) = dict.get(b"SMask").and_then(Object::as_reference) {
+        match redact_image(document, mask_id, ctm, regions) {
+            Some(mask_id) => dict.set("SMask", mask_id),
+            None => {
+                dict.remove(b"SMask");
+            }
+        }
+    }
+
+    let mut image = Stream::new(dict, samples);
+    let _ = image.compress();
+    Some(document.add_object(image))
+}
+
+/// Number of colour components per sample of a colour space.
+fn channels(document: &Document, color_space: &Object) -> Option<usize> {
+    let color_space = document.dereference(color_space).ok()?.1;
+
+    let (family, parameters) = match color_space {
+        Object::Name(name) => (name.as_slice(), &[][..]),
+        Object::Array(items) => (items.first()?.as_name().ok()?, &items[1..]),
+        _ => return None,
+    };
+
+    match family {
+        b"DeviceGray" | b"CalGray" | b"Indexed" | b"Separation" => Some(1),
+        b"DeviceRGB" | b"CalRGB" | b"Lab" => Some(3),
+        b"DeviceCMYK" => Some(4),
+        b"ICCBased" => {
+            let profile = document
+                .dereference(parameters.first()?)
+                .ok()?
+                .1
+                .as_stream()
+                .ok()?;
+            profile
+                .dict
+                .get(b"N")
+                .and_then(Object::as_i64)
+                .ok()
+                .map(|n| n as usize)
+        }
+        b"DeviceN" => {
+            let names = document.dereference(parameters.first()?).ok()?.1;
+            names.as_array().ok().map(Vec::len)
+        }
+        _ => None,
+    }
+}
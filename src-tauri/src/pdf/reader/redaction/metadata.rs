@@ -0,0 +1,100 @@
+use lopdf::{decode_text_string, text_string, Dictionary, Document, Object};
+
+/// Removes redacted text from the document information dictionary and the XMP
+/// metadata, where titles and subjects often repeat it. Returns the number of
+/// entries changed.
+pub fn strip_metadata(document: &mut Document, texts: &[String]) -> usize {
+    let mut terms = texts
+        .iter()
+        .map(|text| text.trim().to_string())
+        // Very short fragments would take unrelated letters out of titles
+        .filter(|text| text.chars().count() >= 3)
+        .collect::<Vec<_>>();
+    // Longer terms first so that a shorter one does not split them
+    terms.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    terms.dedup();
+
+    if terms.is_empty() {
+        return 0;
+    }
+
+    let mut stripped = 0;
+
+    match document.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(info_id) => {
+            if let Ok(info) = document.get_dictionary_mut(info_id) {
+                stripped += strip_info(info, &terms);
+            }
+        }
+        Err(_) => {
+            if let Ok(info) = document
+                .trailer
+                .get_mut(b"Info")
+                .and_then(Object::as_dict_mut)
+            {
+                stripped += strip_info(info, &terms);
+            }
+        }
+    }
+
+    let metadata_id = document
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Metadata"))
+        .and_then(Object::as_reference);
+    if let Ok(stream) = metadata_id
+        .and_then(|id| document.get_object_mut(id))
+        .and_then(Object::as_stream_mut)
+    {
+        let content = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+
+        if let Ok(xmp) = String::from_utf8(content) {
+            let redacted = terms.iter().fold(xmp.clone(), |xmp, term| {
+                xmp.replace(term.as_str(), "")
+                    .replace(&escape_xml(term), "")
+            });
+            if redacted != xmp {
+                stream.dict.remove(b"Filter");
+                stream.dict.remove(b"DecodeParms");
+                stream.set_plain_content(redacted.into_bytes());
+                stripped += 1;
+            }
+        }
+    }
+
+    stripped
+}
+
+fn strip_info(info: &mut Dictionary, terms: &[String]) -> usize {
+    let mut changed = Vec::new();
+    for (key, value) in info.iter() {
+        let Ok(text) = decode_text_string(value) else {
+            continue;
+        };
+        let redacted = terms
+            .iter()
+            .fold(text.clone(), |text, term| text.replace(term.as_str(), ""));
+        if redacted != text {
+            changed.push((key.clone(), redacted.trim().to_string()));
+        }
+    }
+
+    for (key, value) in &changed {
+        if value.is_empty() {
+            info.remove(key);
+        } else {
+            info.set(key.clone(), text_string(value));
+        }
+    }
+
+    changed.len()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
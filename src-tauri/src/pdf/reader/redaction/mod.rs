@@ -0,0 +1,681 @@
+pub mod content;
+pub mod image;
+pub mod metadata;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use pdfium_render::prelude::{PdfDocument, PdfPoints};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::annotation::appearance::{num, parse_color};
+use crate::pdf::reader::annotation::create::page_id;
+use crate::pdf::reader::redaction::content::Redactor;
+use crate::pdf::reader::redaction::metadata::strip_metadata;
+use crate::pdf::reader::{
+    add_annotations, build_search_index, document_to_bytes, get_annotation_storage,
+    get_annotations, page_heights, Annotation, AnnotationAppearance, AnnotationFlags,
+    AnnotationGeometry, AnnotationMetadata, AnnotationStorage, AnnotationType, PdfRect, Point,
+    Quad, SidecarStore, SourceFile,
+};
+use crate::pdf::DocumentId;
+use crate::utils::fs::write_atomic;
+
+/// Area of a page in default user space: left, bottom, right, top.
+pub type Region = [f32; 4];
+
+/// What to look for when marking redactions by search.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+pub enum RedactionPattern {
+    /// Literal text, matched ignoring case
+    Text(String),
+
+    /// Regular expression, for IDs and other formats
+    Regex(String),
+    Email,
+    Phone,
+}
+
+impl RedactionPattern {
+    fn regex(&self) -> Result<Regex, String> {
+        let pattern = match self {
+            RedactionPattern::Text(text) => format!("(?i){}", regex::escape(text)),
+            RedactionPattern::Regex(pattern) => pattern.clone(),
+            RedactionPattern::Email => r"[\w.+-]+@[\w-]+(\.[\w-]+)+".to_string(),
+            RedactionPattern::Phone => r"\+?\d[\d ().-]{6,}\d".to_string(),
+        };
+
+        Regex::new(&pattern).map_err(|e| format!("Invalid pattern: {e}"))
+    }
+}
+
+/// What applying the redactions removed from the document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RedactionSummary {
+    pub pages: usize,
+    pub glyphs: usize,
+
+    /// Images removed or with pixels cleared
+    pub images: usize,
+
+    /// Vector subpaths removed
+    pub paths: usize,
+
+    /// Annotations and form fields in redacted areas, besides the Redact annotations
+    pub annotations: usize,
+
+    /// Document information and XMP entries the redacted text was removed from
+    pub metadata: usize,
+}
+
+/// Redacted areas of a page, in default user space.
+pub struct RedactionArea {
+    pub page_index: u16,
+    pub regions: Vec<Region>,
+
+    /// Colour of the boxes drawn over the areas
+    pub fill: (f32, f32, f32),
+}
+
+/// Marks every match of the patterns with a Redact annotation, which is only
+/// applied by [`apply_redactions`]. Returns the new annotations.
+pub fn mark_redactions<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
+    id: &DocumentId,
+    patterns: &[RedactionPattern],
+    sidecar: Option<&SidecarStore>,
+) -> Result<Vec<Annotation>, String> {
+    let document = documents.get(id).ok_or("Document not found")?;
+    let regexes = patterns
+        .iter()
+        .map(RedactionPattern::regex)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let index = build_search_index(document)?;
+    let mut marks = Vec::new();
+
+    for page in &index.pages {
+        let byte_to_char = page
+            .text
+            .char_indices()
+            .enumerate()
+            .map(|(c_idx, (b_idx, _))| (b_idx, c_idx))
+            .collect::<HashMap<_, _>>();
+
+        for regex in &regexes {
+            for found in regex.find_iter(&page.text) {
+                let Some(&start) = byte_to_char.get(&found.start()) else {
+                    continue;
+                };
+                let count = found.as_str().chars().count();
+                let rects = page
+                    .char_map
+                    .iter()
+                    .skip(start)
+                    .take(count)
+                    .map(|meta| {
+                        [
+                            meta.rect.left().value,
+                            meta.rect.bottom().value,
+                            meta.rect.right().value,
+                            meta.rect.top().value,
+                        ]
+                    })
+                    .filter(|[left, bottom, right, top]| right > left && top > bottom)
+                    .collect::<Vec<_>>();
+
+                let quads = line_quads(&rects, page.height);
+                if !quads.is_empty() {
+                    marks.push(redaction_mark(page.page, quads));
+                }
+            }
+        }
+    }
+
+    if !marks.is_empty() {
+        add_annotations(documents, file, id, marks.clone(), sidecar)?;
+    }
+
+    Ok(marks)
+}
+
+/// Applies the Redact annotations of a document: the text, images and graphics
+/// under them are removed from the content, the areas are painted over and the
+/// redacted text is removed from the metadata. Without `dest` the document is
+/// redacted in place.
+pub fn apply_redactions<'a>(
+    documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
+    file: &SourceFile,
+    id: &DocumentId,
+    sidecar: Option<&SidecarStore>,
+    dest: Option<&Path>,
+) -> Result<RedactionSummary, String> {
+    let in_place = dest.is_none_or(|dest| dest == file.path);
+
+    if in_place {
+        if let Some(store) = sidecar {
+            if get_annotation_storage(file, store)? == AnnotationStorage::Sidecar {
+                return Err(
+                    "This document is not modified in place. Save the redacted document as a new file."
+                        .into(),
+                );
+            }
+        }
+    }
+
+    let marks = get_annotations(documents, file, id, sidecar)?
+        .into_iter()
+        .filter(|annotation| annotation.subtype == AnnotationType::Redacted)
+        .collect::<Vec<_>>();
+    if marks.is_empty() {
+        return Err("No redactions are marked in this document".into());
+    }
+
+    let document = documents.get(id).ok_or("Document not found")?;
+    let heights = page_heights(document);
+
+    let mut areas = Vec::new();
+    let mut texts = Vec::new();
+    for mark in &marks {
+        let height = *heights
+            .get(mark.page_index as usize)
+            .ok_or("Redaction is on a page that does not exist")?;
+        let regions = mark_regions(mark, height);
+
+        let page = document
+            .pages()
+            .get(mark.page_index)
+            .map_err(|e| format!("Failed to get page: {e}"))?;
+        let text = page.text().map_err(|e| e.to_string())?;
+        for [left, bottom, right, top] in &regions {
+            texts.push(text.inside_rect(pdfium_render::prelude::PdfRect::new(
+                PdfPoints::new(*bottom),
+                PdfPoints::new(*left),
+                PdfPoints::new(*top),
+                PdfPoints::new(*right),
+            )));
+        }
+
+        let fill = mark
+            .appearance
+            .interior_color
+            .as_deref()
+            .unwrap_or("#000000");
+        areas.push(RedactionArea {
+            page_index: mark.page_index,
+            regions,
+            fill: parse_color(fill)?,
+        });
+    }
+
+    let mut source = file.document()?.clone();
+    let summary = redact_document(&mut source, &areas, &texts)?;
+
+    // Rewritten in full: an incremental update would keep the redacted content in
+    // the earlier revision of the file.
+    let bytes = document_to_bytes(&mut source)?;
+    match dest {
+        Some(dest) if !in_place => write_atomic(dest, &bytes)?,
+        _ => {
+            // Drop the worker's copy of the document before replacing the file
+            documents.remove(id);
+            write_atomic(&file.path, &bytes)?;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Redacts the areas of a document loaded with lopdf. `texts` is the text found
+/// in the areas, removed from the metadata as well.
+pub fn redact_document(
+    document: &mut Document,
+    areas: &[RedactionArea],
+    texts: &[String],
+) -> Result<RedactionSummary, String> {
+    let mut summary = RedactionSummary::default();
+
+    let mut pages: BTreeMap<u16, Vec<&RedactionArea>> = BTreeMap::new();
+    for area in areas {
+        pages.entry(area.page_index).or_default().push(area);
+    }
+
+    for (page_index, areas) in pages {
+        let page_id = page_id(document, page_index)?;
+        let regions = areas
+            .iter()
+            .flat_map(|area| area.regions.iter().copied())
+            .collect::<Vec<_>>();
+
+        let mut overlay = String::from("q\n");
+        for area in &areas {
+            let (r, g, b) = area.fill;
+            overlay.push_str(&format!("{} {} {} rg\n", num(r), num(g), num(b)));
+            for [left, bottom, right, top] in &area.regions {
+                overlay.push_str(&format!(
+                    "{} {} {} {} re f\n",
+                    num(*left),
+                    num(*bottom),
+                    num(right - left),
+                    num(top - bottom)
+                ));
+            }
+        }
+        overlay.push_str("Q\n");
+
+        Redactor::new(document, &regions, &mut summary)
+            .redact_page(page_id, overlay.as_bytes())
+            .map_err(|e| format!("Failed to redact page {}: {e}", page_index + 1))?;
+
+        summary.annotations += remove_annotations(document, page_id, &regions);
+        summary.pages += 1;
+    }
+
+    summary.metadata = strip_metadata(document, texts);
+
+    // The original content streams, images and forms must not be written out
+    document.prune_objects();
+
+    Ok(summary)
+}
+
+/// Removes the Redact annotations of a page and the annotations inside the
+/// redacted regions. Widgets are also removed from the form fields.
+fn remove_annotations(document: &mut Document, page_id: ObjectId, regions: &[Region]) -> usize {
+    let Some(annots) = document
+        .get_dictionary(page_id)
+        .ok()
+        .and_then(|page| page.get(b"Annots").ok())
+        .and_then(|annots| document.dereference(annots).ok())
+        .and_then(|(_, annots)| annots.as_array().ok())
+        .cloned()
+    else {
+        return 0;
+    };
+
+    let annotation = |item: &Object| -> Option<Dictionary> {
+        document.dereference(item).ok()?.1.as_dict().ok().cloned()
+    };
+
+    let mut removed = Vec::new();
+    let mut redacted = 0;
+    for item in &annots {
+        let Some(dict) = annotation(item) else {
+            continue;
+        };
+        let subtype = dict
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .unwrap_or_default();
+        let rect = dict
+            .get(b"Rect")
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|rect| {
+                rect.iter()
+                    .map(|v| v.as_float().ok())
+                    .collect::<Option<Vec<_>>>()
+            })
+            .filter(|rect| rect.len() == 4);
+
+        let inside = rect.is_some_and(|rect| {
+            let (left, right) = (rect[0].min(rect[2]), rect[0].max(rect[2]));
+            let (bottom, top) = (rect[1].min(rect[3]), rect[1].max(rect[3]));
+            regions.iter().any(|region| {
+                left <= region[2] && right >= region[0] && bottom <= region[3] && top >= region[1]
+            })
+        });
+
+        if subtype == b"Redact" {
+            removed.push(item.clone());
+        } else if inside && subtype != b"Popup" {
+            removed.push(item.clone());
+            redacted += 1;
+        }
+    }
+
+    // Popups go with their parent annotation
+    let removed_ids = removed
+        .iter()
+        .filter_map(|item| item.as_reference().ok())
+        .collect::<Vec<_>>();
+    for item in &annots {
+        let parent = annotation(item)
+            .and_then(|dict| dict.get(b"Parent").and_then(Object::as_reference).ok());
+        let subtype = annotation(item).and_then(|dict| {
+            dict.get(b"Subtype")
+                .and_then(Object::as_name)
+                .ok()
+                .map(<[u8]>::to_vec)
+        });
+        if subtype.as_deref() == Some(b"Popup")
+            && parent.is_some_and(|parent| removed_ids.contains(&parent))
+        {
+            removed.push(item.clone());
+        }
+    }
+
+    if removed.is_empty() {
+        return 0;
+    }
+
+    let kept = annots
+        .into_iter()
+        .filter(|item| !removed.contains(item))
+        .collect::<Vec<_>>();
+    if let Ok(page) = document.get_dictionary_mut(page_id) {
+        if kept.is_empty() {
+            page.remove(b"Annots");
+        } else {
+            page.set("Annots", kept);
+        }
+    }
+
+    remove_fields(document, &removed_ids);
+    redacted
+}
+
+/// Drops removed widgets from the AcroForm field tree.
+fn remove_fields(document: &mut Document, widgets: &[ObjectId]) {
+    let keep = |items: &Vec<Object>| {
+        items
+            .iter()
+            .filter(|item| !item.as_reference().is_ok_and(|id| widgets.contains(&id)))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    let parents = widgets
+        .iter()
+        .filter_map(|id| document.get_dictionary(*id).ok())
+        .filter_map(|widget| widget.get(b"Parent").and_then(Object::as_reference).ok())
+        .collect::<Vec<_>>();
+    for parent_id in parents {
+        if let Ok(parent) = document.get_dictionary_mut(parent_id) {
+            if let Ok(kids) = parent.get(b"Kids").and_then(Object::as_array).map(keep) {
+                parent.set("Kids", kids);
+            }
+        }
+    }
+
+    let acro_form_id = document
+        .catalog()
+        .and_then(|catalog| catalog.get(b"AcroForm"))
+        .and_then(Object::as_reference);
+    let acro_form = match acro_form_id {
+        Ok(acro_form_id) => document.get_dictionary_mut(acro_form_id).ok(),
+        Err(_) => document
+            .catalog_mut()
+            .and_then(|catalog| catalog.get_mut(b"AcroForm"))
+            .and_then(Object::as_dict_mut)
+            .ok(),
+    };
+    if let Some(acro_form) = acro_form {
+        if let Ok(fields) = acro_form
+            .get(b"Fields")
+            .and_then(Object::as_array)
+            .map(keep)
+        {
+            acro_form.set("Fields", fields);
+        }
+    }
+}
+
+/// Regions of a Redact annotation in default user space: its quads, or its
+/// rectangle when it has none.
+fn mark_regions(mark: &Annotation, page_height: f32) -> Vec<Region> {
+    let region = |points: &[&Point]| {
+        let xs = points.iter().map(|p| p.x);
+        let ys = points.iter().map(|p| page_height - p.y);
+        [
+            xs.clone().fold(f32::INFINITY, f32::min),
+            ys.clone().fold(f32::INFINITY, f32::min),
+            xs.fold(f32::NEG_INFINITY, f32::max),
+            ys.fold(f32::NEG_INFINITY, f32::max),
+        ]
+    };
+
+    match &mark.geometry {
+        AnnotationGeometry::QuadPoints(quads) if !quads.is_empty() => quads
+            .iter()
+            .map(|quad| region(&[&quad.p1, &quad.p2, &quad.p3, &quad.p4]))
+            .collect(),
+        _ => {
+            let rect = &mark.rect;
+            vec![region(&[
+                &Point {
+                    x: rect.left,
+                    y: rect.top,
+                },
+                &Point {
+                    x: rect.right,
+                    y: rect.bottom,
+                },
+            ])]
+        }
+    }
+}
+
+/// Joins the boxes of consecutive characters on the same line into one quad per
+/// line, in top-left page coordinates.
+fn line_quads(rects: &[Region], page_height: f32) -> Vec<Quad> {
+    let mut lines: Vec<Region> = Vec::new();
+
+    for rect in rects {
+        match lines.last_mut() {
+            Some(line) if same_line(line, rect) => {
+                line[0] = line[0].min(rect[0]);
+                line[1] = line[1].min(rect[1]);
+                line[2] = line[2].max(rect[2]);
+                line[3] = line[3].max(rect[3]);
+            }
+            _ => lines.push(*rect),
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|[left, bottom, right, top]| {
+            let (top, bottom) = (page_height - top, page_height - bottom);
+            Quad {
+                p1: Point { x: left, y: top },
+                p2: Point { x: right, y: top },
+                p3: Point { x: left, y: bottom },
+                p4: Point {
+                    x: right,
+                    y: bottom,
+                },
+            }
+        })
+        .collect()
+}
+
+fn same_line(line: &Region, rect: &Region) -> bool {
+    let overlap = line[3].min(rect[3]) - line[1].max(rect[1]);
+    overlap > (line[3] - line[1]).min(rect[3] - rect[1]) / 2.0
+}
+
+fn redaction_mark(page_index: u16, quads: Vec<Quad>) -> Annotation {
+    let points = quads.iter().flat_map(|q| [&q.p1, &q.p2, &q.p3, &q.p4]);
+    let mut rect = PdfRect {
+        left: f32::INFINITY,
+        top: f32::INFINITY,
+        right: f32::NEG_INFINITY,
+        bottom: f32::NEG_INFINITY,
+    };
+    for point in points {
+        rect.left = rect.left.min(point.x);
+        rect.top = rect.top.min(point.y);
+        rect.right = rect.right.max(point.x);
+        rect.bottom = rect.bottom.max(point.y);
+    }
+
+    Annotation {
+        id: uuid::Uuid::new_v4().to_string(),
+        page_index,
+        subtype: AnnotationType::Redacted,
+        rect,
+        geometry: AnnotationGeometry::QuadPoints(quads),
+        appearance: AnnotationAppearance {
+            color: "#FF0000".into(),
+            interior_color: Some("#000000".into()),
+            opacity: 1.0,
+            border_width: Some(1.0),
+            border_style: None,
+            dash_pattern: None,
+            line_endings: None,
+            font_size: None,
+            icon: None,
+        },
+        metadata: AnnotationMetadata {
+            author: None,
+            contents: None,
+            rich_contents: None,
+            creation_date: None,
+            modified_date: None,
+        },
+        flags: AnnotationFlags::default(),
+        in_reply_to: None,
+        replies: Vec::new(),
+        review_states: Vec::new(),
+        popup: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::Content;
+    use lopdf::{dictionary, Stream};
+
+    fn document(content: &[u8]) -> Document {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let font_id = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+            "FirstChar" => 32,
+            "Widths" => vec![Object::Integer(600); 95],
+        });
+        let content_id = document.add_object(Stream::new(dictionary! {}, content.to_vec()));
+        let redact_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Redact",
+            "Rect" => vec![27.into(), 95.into(), 64.into(), 110.into()],
+        });
+        let note_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "Rect" => vec![5.into(), 5.into(), 25.into(), 25.into()],
+        });
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 200.into(), 200.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            "Annots" => vec![redact_id.into(), note_id.into()],
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        let info_id = document.add_object(dictionary! {
+            "Title" => lopdf::text_string("Report for jane@example.com"),
+            "Author" => lopdf::text_string("jane@example.com"),
+        });
+        document.trailer.set("Info", info_id);
+        document
+    }
+
+    #[test]
+    fn test_redact_document() {
+        // "SECRET" runs from x = 28 to 64: Courier glyphs are 6 units wide at 10pt
+        let mut document = document(
+            b"BT /F1 10 Tf 10 100 Td (AB SECRET CD) Tj ET\n\
+              0 0 200 200 re f 10 10 20 20 re f 150 10 20 20 re f",
+        );
+        let areas = [RedactionArea {
+            page_index: 0,
+            regions: vec![[27.0, 95.0, 64.0, 110.0], [0.0, 0.0, 40.0, 40.0]],
+            fill: (0.0, 0.0, 0.0),
+        }];
+
+        let summary =
+            redact_document(&mut document, &areas, &["jane@example.com".to_string()]).unwrap();
+        assert_eq!(summary.pages, 1);
+        assert_eq!(summary.glyphs, 6);
+        // The background enclosing the region stays, the square inside it goes
+        assert_eq!(summary.paths, 1);
+        assert_eq!(summary.annotations, 1);
+        assert_eq!(summary.metadata, 2);
+
+        let page_id = page_id(&document, 0).unwrap();
+        assert_eq!(document.get_page_contents(page_id).len(), 1);
+        assert!(!document.get_dictionary(page_id).unwrap().has(b"Annots"));
+
+        let content = Content::decode(&document.get_page_content(page_id).unwrap()).unwrap();
+        let shown = content
+            .operations
+            .iter()
+            .filter(|op| op.operator == "TJ")
+            .flat_map(|op| op.operands[0].as_array().unwrap().clone())
+            .filter_map(|item| item.as_str().ok().map(<[u8]>::to_vec))
+            .collect::<Vec<_>>()
+            .concat();
+        assert_eq!(shown, b"AB  CD");
+        // Two kept rectangles and the two boxes drawn over the regions
+        let rectangles = content
+            .operations
+            .iter()
+            .filter(|op| op.operator == "re")
+            .count();
+        assert_eq!(rectangles, 4);
+
+        let info = document
+            .trailer
+            .get(b"Info")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .unwrap();
+        assert_eq!(
+            lopdf::decode_text_string(info.get(b"Title").unwrap()).unwrap(),
+            "Report for"
+        );
+        assert!(!info.has(b"Author"));
+    }
+
+    #[test]
+    fn test_redaction_pattern() {
+        let email = RedactionPattern::Email.regex().unwrap();
+        assert_eq!(
+            email
+                .find("Contact jane.doe+x@mail.example.org now")
+                .unwrap()
+                .as_str(),
+            "jane.doe+x@mail.example.org"
+        );
+        let phone = RedactionPattern::Phone.regex().unwrap();
+        assert_eq!(
+            phone.find("Call +1 (555) 010-9999.").unwrap().as_str(),
+            "+1 (555) 010-9999"
+        );
+        let text = RedactionPattern::Text("a.b".into()).regex().unwrap();
+        assert!(text.is_match("A.B") && !text.is_match("axb"));
+        assert!(RedactionPattern::Regex("(".into()).regex().is_err());
+    }
+}
@@ -1,5 +1,8 @@
-use pdfium_render::prelude::{PdfDocument, PdfPoints, PdfRenderConfig, Pdfium};
-use serde::Serialize;
+use pdfium_render::prelude::{
+    PdfColor, PdfDocument, PdfPage, PdfPageRenderRotation, PdfPoints, PdfRect, PdfRenderConfig,
+    Pdfium,
+};
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, path::Path};
 use webp;
 
@@ -7,6 +10,30 @@ use crate::pdf::DocumentId;
 
 const PREVIEW_WIDTH: i32 = 100;
 
+/// Tint drawn over fillable fields when `highlight_fields` is set.
+const FIELD_HIGHLIGHT: PdfColor = PdfColor::new(204, 215, 255, 100);
+
+/// Rendering options shared by full pages and tiles.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderOptions {
+    /// Draws form field widgets with their current values through pdfium's form
+    /// handle, which is initialised when the document is loaded
+    pub forms: bool,
+
+    /// Tints fillable fields so they stand out, like other viewers do
+    pub highlight_fields: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            forms: true,
+            highlight_fields: false,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct RenderedPage {
     pub width: i32,
@@ -23,14 +50,17 @@ pub struct RenderedTile {
     pub pixels: Vec<u8>,
 }
 
+/// Opens a document from a copy of its file in memory, so that no worker holds the
+/// file open and it can be replaced while the document is open.
 pub fn open<'a>(
     id: DocumentId,
     path: &Path,
     documents: &mut HashMap<DocumentId, PdfDocument<'a>>,
     pdfium: &'a Pdfium,
 ) -> Result<(), String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to open PDF: {}", e))?;
     let document = pdfium
-        .load_pdf_from_file(path, None)
+        .load_pdf_from_byte_vec(bytes, None)
         .map_err(|e| format!("Failed to open PDF: {}", e))?;
     documents.insert(id, document);
     Ok(())
@@ -41,6 +71,7 @@ pub fn render_page(
     id: &DocumentId,
     page_index: u16,
     target_width: i32,
+    options: &RenderOptions,
 ) -> Result<RenderedPage, String> {
     let document = documents.get(id).ok_or("Document not found")?;
 
@@ -49,7 +80,7 @@ pub fn render_page(
         .get(page_index)
         .map_err(|e| format!("Failed to get page: {e}"))?;
 
-    let config = PdfRenderConfig::new().set_target_width(target_width);
+    let config = render_config(options).set_target_width(target_width);
 
     let bitmap = page
         .render_with_config(&config)
@@ -78,6 +109,7 @@ pub fn render_tile(
     tile_y: i32,
     tile_width: i32,
     tile_height: i32,
+    options: &RenderOptions,
 ) -> Result<RenderedTile, String> {
     let document = documents.get(id).ok_or("Document not found")?;
 
@@ -86,6 +118,15 @@ pub fn render_tile(
         .get(page_index)
         .map_err(|e| format!("Failed to get page: {e}"))?;
 
+    if options.forms && document.form().is_some() {
+        return render_form_tile(
+            page,
+            target_width,
+            [tile_x, tile_y, tile_width, tile_height],
+            options,
+        );
+    }
+
     let scale = target_width as f32 / page.width().value;
 
     let config = PdfRenderConfig::new()
@@ -152,6 +193,139 @@ pub fn generate_preview(
     Ok(webp_bytes)
 }
 
+/// Renders a tile of a page with form fields drawn.
+///
+/// pdfium draws form fields only without a transformation matrix, scaling the crop
+/// box to the bitmap. The page is therefore cropped to the tile while it renders
+/// and then given back its own crop box.
+fn render_form_tile(
+    mut page: PdfPage,
+    target_width: i32,
+    [x, y, width, height]: [i32; 4],
+    options: &RenderOptions,
+) -> Result<RenderedTile, String> {
+    let scale = target_width as f32 / page.width().value;
+    let page_height = (page.height().value * scale).round() as i32;
+
+    // Only the part of the tile on the page is rendered; the rest stays white.
+    let left = x.clamp(0, target_width);
+    let top = y.clamp(0, page_height);
+    let right = (x + width).clamp(0, target_width);
+    let bottom = (y + height).clamp(0, page_height);
+
+    let pixels = if right > left && bottom > top {
+        let bounds = page
+            .boundaries()
+            .bounding()
+            .map_err(|e| format!("Failed to get page bounds: {e}"))?
+            .bounds;
+        let rotation = page.rotation().unwrap_or(PdfPageRenderRotation::None);
+        let region = [left, top, right - left, bottom - top].map(|value| value as f32 / scale);
+
+        page.boundaries_mut()
+            .set_crop(tile_bounds(bounds, rotation, region))
+            .map_err(|e| format!("Failed to crop page: {e}"))?;
+        let config = render_config(options).set_fixed_size(right - left, bottom - top);
+        let rendered = page
+            .render_with_config(&config)
+            .map(|bitmap| (bitmap.as_raw_bytes(), bitmap.width(), bitmap.height()));
+        page.boundaries_mut()
+            .set_crop(bounds)
+            .map_err(|e| format!("Failed to crop page: {e}"))?;
+        let (pixels, rendered_width, rendered_height) =
+            rendered.map_err(|e| format!("Rendering Error: {e}"))?;
+
+        crop(
+            &pixels,
+            rendered_width,
+            rendered_height,
+            [x - left, y - top, width, height],
+        )
+    } else {
+        crop(&[], 0, 0, [0, 0, width, height])
+    };
+
+    let webp_bytes = rgba_to_webp(&pixels, width as u32, height as u32, 90.0)?;
+
+    Ok(RenderedTile {
+        x,
+        y,
+        width,
+        height,
+        pixels: webp_bytes,
+    })
+}
+
+/// Converts the `[x, y, width, height]` region of a page as displayed, in points
+/// from its top-left corner, to the region of the unrotated page within `bounds`.
+fn tile_bounds(
+    bounds: PdfRect,
+    rotation: PdfPageRenderRotation,
+    [x, y, width, height]: [f32; 4],
+) -> PdfRect {
+    let (left, bottom, right, top) = (
+        bounds.left().value,
+        bounds.bottom().value,
+        bounds.right().value,
+        bounds.top().value,
+    );
+
+    // Display x and y run along these page edges after rotating clockwise.
+    match rotation {
+        PdfPageRenderRotation::None => {
+            PdfRect::new_from_values(top - y - height, left + x, top - y, left + x + width)
+        }
+        PdfPageRenderRotation::Degrees90 => {
+            PdfRect::new_from_values(bottom + x, left + y, bottom + x + width, left + y + height)
+        }
+        PdfPageRenderRotation::Degrees180 => PdfRect::new_from_values(
+            bottom + y,
+            right - x - width,
+            bottom + y + height,
+            right - x,
+        ),
+        PdfPageRenderRotation::Degrees270 => {
+            PdfRect::new_from_values(top - x - width, right - y - height, top - x, right - y)
+        }
+    }
+}
+
+fn render_config(options: &RenderOptions) -> PdfRenderConfig {
+    let config = PdfRenderConfig::new().render_form_data(options.forms);
+
+    if options.forms && options.highlight_fields {
+        config.highlight_all_form_fields(FIELD_HIGHLIGHT)
+    } else {
+        config
+    }
+}
+
+/// Copies the `[x, y, width, height]` region of a 4-byte-per-pixel bitmap. Pixels
+/// outside the source are white, like the background of a fixed-size render.
+fn crop(
+    pixels: &[u8],
+    width: i32,
+    height: i32,
+    [x, y, tile_width, tile_height]: [i32; 4],
+) -> Vec<u8> {
+    let mut tile = vec![u8::MAX; (tile_width.max(0) * tile_height.max(0) * 4) as usize];
+
+    let left = x.clamp(0, width);
+    let right = (x + tile_width).clamp(0, width);
+    if right <= left {
+        return tile;
+    }
+
+    for row in y.max(0)..(y + tile_height).min(height) {
+        let source = ((row * width + left) * 4) as usize;
+        let target = (((row - y) * tile_width + left - x) * 4) as usize;
+        let length = ((right - left) * 4) as usize;
+        tile[target..target + length].copy_from_slice(&pixels[source..source + length]);
+    }
+
+    tile
+}
+
 fn rgba_to_webp(
     rgba: &[u8],
     width: u32,
@@ -174,11 +348,51 @@ mod tests {
         let documents = HashMap::new();
         let id = "non_existent".to_string();
 
-        let result = render_page(&documents, &id, 0, 800);
+        let result = render_page(&documents, &id, 0, 800, &RenderOptions::default());
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "Document not found");
     }
 
+    #[test]
+    fn test_crop_pads_outside_page() {
+        // 3x2 bitmap whose pixels hold their index
+        let pixels = (0..6u8).flat_map(|pixel| [pixel; 4]).collect::<Vec<_>>();
+
+        let tile = crop(&pixels, 3, 2, [1, 1, 3, 2]);
+        let values = tile.chunks(4).map(|pixel| pixel[0]).collect::<Vec<_>>();
+        assert_eq!(values, vec![4, 5, 255, 255, 255, 255]);
+
+        let outside = crop(&pixels, 3, 2, [5, 0, 2, 2]);
+        assert!(outside.iter().all(|value| *value == u8::MAX));
+    }
+
+    #[test]
+    fn test_tile_bounds_follow_rotation() {
+        // A 100x200 page with its crop box at (10, 20)
+        let bounds = PdfRect::new_from_values(20.0, 10.0, 220.0, 110.0);
+        let edges = |rect: PdfRect| {
+            [rect.left(), rect.bottom(), rect.right(), rect.top()].map(|value| value.value)
+        };
+
+        let upright = tile_bounds(bounds, PdfPageRenderRotation::None, [5.0, 10.0, 20.0, 30.0]);
+        assert_eq!(edges(upright), [15.0, 180.0, 35.0, 210.0]);
+
+        // Turned clockwise, the top-left of the display is the bottom-left of the page.
+        let turned = tile_bounds(
+            bounds,
+            PdfPageRenderRotation::Degrees90,
+            [5.0, 10.0, 20.0, 30.0],
+        );
+        assert_eq!(edges(turned), [20.0, 25.0, 50.0, 45.0]);
+
+        let upside_down = tile_bounds(
+            bounds,
+            PdfPageRenderRotation::Degrees180,
+            [5.0, 10.0, 20.0, 30.0],
+        );
+        assert_eq!(edges(upside_down), [85.0, 30.0, 105.0, 60.0]);
+    }
+
     #[test]
     fn test_render_open_invalid_path() {
         let pdfium = Pdfium::default();
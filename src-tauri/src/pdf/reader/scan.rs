@@ -0,0 +1,1012 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use lopdf::xref::XrefEntry;
+use lopdf::{decode_text_string, Dictionary, Document, Object, ObjectId, Stream};
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::read_signatures;
+
+/// Keys whose actions run on an event rather than a click: the open action,
+/// additional-action triggers and document-level scripts.
+const AUTOMATIC_KEYS: [&[u8]; 3] = [b"OpenAction", b"AA", b"JavaScript"];
+
+/// Action types (`/S`) defined by the PDF specification. Other dictionaries use
+/// `/S` too, such as border styles and structure elements.
+const ACTION_TYPES: [&[u8]; 20] = [
+    b"GoTo",
+    b"GoToR",
+    b"GoToE",
+    b"GoToDp",
+    b"Launch",
+    b"Thread",
+    b"URI",
+    b"Sound",
+    b"Movie",
+    b"Hide",
+    b"Named",
+    b"SubmitForm",
+    b"ResetForm",
+    b"ImportData",
+    b"SetOCGState",
+    b"Rendition",
+    b"Trans",
+    b"GoTo3DView",
+    b"JavaScript",
+    b"RichMediaExecute",
+];
+
+/// File extensions that Windows, macOS or Linux may run when the file is opened.
+const EXECUTABLE_EXTENSIONS: [&str; 27] = [
+    "exe", "dll", "scr", "com", "pif", "cpl", "msi", "msp", "bat", "cmd", "ps1", "vbs", "vbe",
+    "js", "jse", "wsf", "wsh", "hta", "jar", "lnk", "reg", "sh", "app", "apk", "docm", "xlsm",
+    "pptm",
+];
+
+/// Signatures of Windows, ELF and Mach-O executables and scripts.
+const EXECUTABLE_MAGIC: [&[u8]; 7] = [
+    b"MZ",
+    b"\x7fELF",
+    b"\xfe\xed\xfa\xce",
+    b"\xfe\xed\xfa\xcf",
+    b"\xce\xfa\xed\xfe",
+    b"\xcf\xfa\xed\xfe",
+    b"#!",
+];
+
+/// Longest script excerpt put in a finding.
+const EXCERPT_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskSeverity {
+    /// Worth knowing, but common in harmless documents
+    Info,
+    Low,
+    Medium,
+
+    /// Can run code or send data without the user noticing
+    High,
+}
+
+impl RiskSeverity {
+    /// One level up, for actions that run without a click.
+    fn raised(self) -> Self {
+        match self {
+            RiskSeverity::Info => RiskSeverity::Low,
+            RiskSeverity::Low => RiskSeverity::Medium,
+            RiskSeverity::Medium | RiskSeverity::High => RiskSeverity::High,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RiskKind {
+    JavaScript,
+    LaunchAction,
+    UriAction,
+    SubmitFormAction,
+    ImportDataAction,
+    EmbeddedFile,
+    EmbeddedExecutable,
+
+    /// Other documents, files or web addresses the document loads content from
+    ExternalReference,
+    MalformedXref,
+    IncrementalUpdate,
+
+    /// Objects kept in the file that the reader does not show
+    HiddenObjects,
+    ChangedAfterSigning,
+
+    /// The document needs a password, so only its unencrypted structure was checked
+    Encrypted,
+
+    /// The file could not be parsed as a PDF
+    Unreadable,
+}
+
+impl RiskKind {
+    fn description(self) -> &'static str {
+        match self {
+            RiskKind::JavaScript => "Runs JavaScript",
+            RiskKind::LaunchAction => "Starts an application or opens a file",
+            RiskKind::UriAction => "Opens a web address",
+            RiskKind::SubmitFormAction => "Sends form data to an address",
+            RiskKind::ImportDataAction => "Imports form data from a file",
+            RiskKind::EmbeddedFile => "Contains an embedded file",
+            RiskKind::EmbeddedExecutable => "Contains an embedded program or script",
+            RiskKind::ExternalReference => "Refers to another document or file",
+            RiskKind::MalformedXref => "The cross-reference table is damaged",
+            RiskKind::IncrementalUpdate => "The document was changed after it was first saved",
+            RiskKind::HiddenObjects => "The file holds objects that are not shown",
+            RiskKind::ChangedAfterSigning => "The document was changed after it was signed",
+            RiskKind::Encrypted => "The document needs a password, so its content was not checked",
+            RiskKind::Unreadable => "The file could not be read as a PDF",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskFinding {
+    pub kind: RiskKind,
+    pub severity: RiskSeverity,
+    pub message: String,
+
+    /// Object number of the object holding the construct
+    pub object_id: Option<u32>,
+    pub page_index: Option<u16>,
+
+    /// Web address, file name or script excerpt the construct refers to
+    pub target: Option<String>,
+
+    /// Runs when the document, a page or a field is opened, closed or changed,
+    /// without a click
+    pub automatic: bool,
+}
+
+impl RiskFinding {
+    fn new(kind: RiskKind, severity: RiskSeverity) -> Self {
+        Self {
+            kind,
+            severity,
+            message: kind.description().to_string(),
+            object_id: None,
+            page_index: None,
+            target: None,
+            automatic: false,
+        }
+    }
+
+    fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    fn target(mut self, target: Option<String>) -> Self {
+        self.target = target.filter(|target| !target.is_empty());
+        self
+    }
+}
+
+/// What a document could do when opened, found without rendering it or running
+/// any of its scripts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskReport {
+    /// Highest severity of the findings, `None` when nothing was found
+    pub severity: Option<RiskSeverity>,
+
+    /// Most severe first
+    pub findings: Vec<RiskFinding>,
+
+    /// Saved revisions, counting the original one
+    pub revisions: usize,
+}
+
+pub fn scan_document(path: &Path) -> Result<RiskReport, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read PDF: {e}"))?;
+
+    Ok(scan_bytes(&bytes))
+}
+
+pub fn scan_bytes(bytes: &[u8]) -> RiskReport {
+    let mut findings = Vec::new();
+
+    // lopdf ignores anything before the header, and so do the offsets in the file
+    let base = bytes
+        .windows(5)
+        .position(|window| window == b"%PDF-")
+        .unwrap_or(0);
+    let body = &bytes[base..];
+
+    let document = match Document::load_mem(bytes) {
+        Ok(document) => Some(document),
+        Err(e) => {
+            findings.push(
+                RiskFinding::new(RiskKind::Unreadable, RiskSeverity::High)
+                    .message(format!("The file could not be read as a PDF: {e}")),
+            );
+            None
+        }
+    };
+
+    findings.extend(check_xref(body, document.as_ref()));
+    let (revisions, update_findings) = check_updates(body, document.as_ref());
+    findings.extend(update_findings);
+
+    match &document {
+        Some(document) if !document.is_encrypted() => {
+            findings.extend(Scanner::new(document).scan());
+
+            for signature in read_signatures(bytes).unwrap_or_default() {
+                if signature.modified_after_signing {
+                    let signer = signature.signer_name.unwrap_or(signature.field_name);
+                    findings.push(RiskFinding {
+                        page_index: signature.page_index,
+                        ..RiskFinding::new(RiskKind::ChangedAfterSigning, RiskSeverity::Medium)
+                            .message(format!("The document was changed after {signer} signed it"))
+                    });
+                }
+            }
+        }
+        _ => {
+            if document.is_some() {
+                findings.push(RiskFinding::new(RiskKind::Encrypted, RiskSeverity::Info));
+            }
+            findings.extend(scan_names(body));
+        }
+    }
+
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+
+    RiskReport {
+        severity: findings.iter().map(|finding| finding.severity).max(),
+        findings,
+        revisions,
+    }
+}
+
+struct Scanner<'a> {
+    document: &'a Document,
+    pages: HashMap<ObjectId, u16>,
+    automatic: HashSet<ObjectId>,
+    findings: Vec<RiskFinding>,
+}
+
+#[derive(Clone, Copy)]
+struct Context {
+    object_id: ObjectId,
+    automatic: bool,
+
+    /// Inside an action, whose file specifications are reported with it
+    in_action: bool,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(document: &'a Document) -> Self {
+        // Pages and their annotations, direct or not
+        let mut pages = HashMap::new();
+        for (number, page_id) in document.get_pages() {
+            let index = number.saturating_sub(1) as u16;
+            pages.insert(page_id, index);
+
+            let annots = document
+                .get_dictionary(page_id)
+                .and_then(|page| page.get_deref(b"Annots", document))
+                .and_then(Object::as_array);
+            for annot_id in annots
+                .into_iter()
+                .flatten()
+                .filter_map(|annot| annot.as_reference().ok())
+            {
+                pages.insert(annot_id, index);
+            }
+        }
+
+        Self {
+            document,
+            pages,
+            automatic: automatic_objects(document),
+            findings: Vec::new(),
+        }
+    }
+
+    fn scan(mut self) -> Vec<RiskFinding> {
+        let document = self.document;
+
+        for (id, object) in &document.objects {
+            let context = Context {
+                object_id: *id,
+                automatic: self.automatic.contains(id),
+                in_action: false,
+            };
+            self.walk(object, context);
+        }
+
+        // XFA forms carry their own scripts, which some readers run
+        let xfa = document
+            .catalog()
+            .ok()
+            .and_then(|catalog| catalog.get_deref(b"AcroForm", document).ok())
+            .and_then(|form| form.as_dict().ok())
+            .is_some_and(|form| form.has(b"XFA"));
+        if xfa {
+            self.findings.push(
+                RiskFinding::new(RiskKind::JavaScript, RiskSeverity::Medium)
+                    .message("Has an XFA form, which can contain scripts"),
+            );
+        }
+
+        self.findings
+    }
+
+    fn walk(&mut self, object: &Object, context: Context) {
+        match object {
+            Object::Array(items) => {
+                for item in items {
+                    self.walk(item, context);
+                }
+            }
+            Object::Dictionary(dict) => self.visit(dict, None, context),
+            Object::Stream(stream) => self.visit(&stream.dict, Some(stream), context),
+            _ => {}
+        }
+    }
+
+    fn visit(&mut self, dict: &Dictionary, stream: Option<&Stream>, mut context: Context) {
+        let is_action = name(dict, b"Type") == Some(b"Action".as_slice())
+            || name(dict, b"S").is_some_and(|kind| ACTION_TYPES.contains(&kind));
+        if is_action || dict.has(b"JS") {
+            self.action(dict, context);
+            context.in_action = true;
+        }
+        if dict.has(b"EF") {
+            self.embedded_file(dict, context);
+        } else if !context.in_action && name(dict, b"FS") == Some(b"URL".as_slice()) {
+            let target = file_name(self.document, dict);
+            self.push(
+                RiskFinding::new(RiskKind::ExternalReference, RiskSeverity::Medium)
+                    .message("Refers to a file on the web")
+                    .target(target),
+                context,
+            );
+        }
+
+        if stream.is_some() && dict.has(b"F") {
+            let target = dict
+                .get(b"F")
+                .ok()
+                .and_then(|spec| file_target(self.document, spec));
+            self.push(
+                RiskFinding::new(RiskKind::ExternalReference, RiskSeverity::Medium)
+                    .message("Reads stream data from an external file")
+                    .target(target),
+                context,
+            );
+        }
+        if let Ok(reference) = dict
+            .get_deref(b"Ref", self.document)
+            .and_then(Object::as_dict)
+        {
+            let target = reference
+                .get(b"F")
+                .ok()
+                .and_then(|spec| file_target(self.document, spec));
+            self.push(
+                RiskFinding::new(RiskKind::ExternalReference, RiskSeverity::Medium)
+                    .message("Shows a page of another document")
+                    .target(target),
+                context,
+            );
+        }
+
+        for (key, value) in dict.iter() {
+            let mut child = context;
+            child.automatic |= AUTOMATIC_KEYS.contains(&key.as_slice());
+            self.walk(value, child);
+        }
+    }
+
+    fn action(&mut self, dict: &Dictionary, context: Context) {
+        let document = self.document;
+
+        // Rendition and other actions may carry a script besides their own type
+        if let Ok(script) = dict.get_deref(b"JS", document) {
+            self.push(
+                RiskFinding::new(RiskKind::JavaScript, RiskSeverity::High)
+                    .target(script_excerpt(script)),
+                context,
+            );
+        }
+
+        let file = || {
+            dict.get(b"F")
+                .ok()
+                .and_then(|spec| file_target(document, spec))
+                // Windows launch parameters
+                .or_else(|| {
+                    dict.get_deref(b"Win", document)
+                        .and_then(Object::as_dict)
+                        .and_then(|win| win.get(b"F"))
+                        .ok()
+                        .and_then(|spec| file_target(document, spec))
+                })
+        };
+
+        let finding =
+            match name(dict, b"S").unwrap_or_default() {
+                b"Launch" => {
+                    RiskFinding::new(RiskKind::LaunchAction, RiskSeverity::High).target(file())
+                }
+                b"URI" => RiskFinding::new(RiskKind::UriAction, RiskSeverity::Low).target(
+                    dict.get(b"URI")
+                        .and_then(Object::as_str)
+                        .ok()
+                        .map(|uri| String::from_utf8_lossy(uri).into_owned()),
+                ),
+                b"SubmitForm" => RiskFinding::new(RiskKind::SubmitFormAction, RiskSeverity::Medium)
+                    .target(file()),
+                b"ImportData" => RiskFinding::new(RiskKind::ImportDataAction, RiskSeverity::Medium)
+                    .target(file()),
+                b"GoToR" => RiskFinding::new(RiskKind::ExternalReference, RiskSeverity::Medium)
+                    .message("Opens another document")
+                    .target(file()),
+                _ => return,
+            };
+        self.push(finding, context);
+    }
+
+    fn embedded_file(&mut self, spec: &Dictionary, context: Context) {
+        let document = self.document;
+        let name = file_name(document, spec).unwrap_or_default();
+
+        let content = spec
+            .get_deref(b"EF", document)
+            .and_then(Object::as_dict)
+            .and_then(|files| {
+                files
+                    .get_deref(b"UF", document)
+                    .or_else(|_| files.get_deref(b"F", document))
+            })
+            .and_then(Object::as_stream)
+            .map(|stream| {
+                stream
+                    .get_plain_content()
+                    .unwrap_or_else(|_| stream.content.clone())
+            })
+            .unwrap_or_default();
+
+        let extension = Path::new(&name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let executable = EXECUTABLE_EXTENSIONS.contains(&extension.as_str())
+            || EXECUTABLE_MAGIC
+                .iter()
+                .any(|magic| content.starts_with(magic));
+
+        let finding = if executable {
+            RiskFinding::new(RiskKind::EmbeddedExecutable, RiskSeverity::High)
+        } else {
+            RiskFinding::new(RiskKind::EmbeddedFile, RiskSeverity::Low)
+        };
+        self.push(finding.target(Some(name)), context);
+    }
+
+    fn push(&mut self, mut finding: RiskFinding, context: Context) {
+        if context.automatic {
+            finding.severity = finding.severity.raised();
+        }
+        finding.automatic = context.automatic;
+        finding.object_id = Some(context.object_id.0);
+        finding.page_index = self.pages.get(&context.object_id).copied();
+        self.findings.push(finding);
+    }
+}
+
+/// Actions and name tree nodes reached from the open action, additional actions
+/// and the document-level scripts, following chained actions.
+fn automatic_objects(document: &Document) -> HashSet<ObjectId> {
+    fn collect(object: &Object, automatic: bool, references: &mut Vec<ObjectId>) {
+        match object {
+            Object::Reference(id) if automatic => references.push(*id),
+            Object::Array(items) => {
+                for item in items {
+                    collect(item, automatic, references);
+                }
+            }
+            Object::Dictionary(dict) => collect_dictionary(dict, automatic, references),
+            Object::Stream(stream) => collect_dictionary(&stream.dict, automatic, references),
+            _ => {}
+        }
+    }
+
+    fn collect_dictionary(dict: &Dictionary, automatic: bool, references: &mut Vec<ObjectId>) {
+        for (key, value) in dict.iter() {
+            collect(
+                value,
+                automatic || AUTOMATIC_KEYS.contains(&key.as_slice()),
+                references,
+            );
+        }
+    }
+
+    // Only actions and name trees pass it on, so that a submitted field or the
+    // destination page does not become automatic itself
+    let passes_on = |dict: &Dictionary| {
+        dict.has(b"S")
+            || dict.has(b"JS")
+            || dict.has(b"Names")
+            || (dict.has(b"Kids") && !dict.has(b"Type") && !dict.has(b"FT") && !dict.has(b"T"))
+    };
+
+    let mut pending = Vec::new();
+    for object in document.objects.values() {
+        collect(object, false, &mut pending);
+    }
+
+    let mut automatic = HashSet::new();
+    while let Some(id) = pending.pop() {
+        if automatic.contains(&id) {
+            continue;
+        }
+        let Ok(object) = document.get_object(id) else {
+            continue;
+        };
+        if object.as_dict().is_ok_and(passes_on) {
+            automatic.insert(id);
+            collect(object, true, &mut pending);
+        }
+    }
+
+    automatic
+}
+
+fn name<'a>(dict: &'a Dictionary, key: &[u8]) -> Option<&'a [u8]> {
+    dict.get(key).and_then(Object::as_name).ok()
+}
+
+/// Name of the file a file specification refers to.
+fn file_name(document: &Document, spec: &Dictionary) -> Option<String> {
+    spec.get_deref(b"UF", document)
+        .or_else(|_| spec.get_deref(b"F", document))
+        .ok()
+        .and_then(|name| decode_text_string(name).ok())
+}
+
+fn file_target(document: &Document, spec: &Object) -> Option<String> {
+    match document.dereference(spec).ok()?.1 {
+        Object::Dictionary(spec) => file_name(document, spec),
+        other => decode_text_string(other).ok(),
+    }
+}
+
+fn script_excerpt(script: &Object) -> Option<String> {
+    let text = match script {
+        Object::Stream(stream) => String::from_utf8_lossy(
+            &stream
+                .get_plain_content()
+                .unwrap_or_else(|_| stream.content.clone()),
+        )
+        .into_owned(),
+        other => decode_text_string(other).ok()?,
+    };
+
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    Some(text.chars().take(EXCERPT_LENGTH).collect())
+}
+
+/// Checks that `startxref` and the cross-reference entries point at what they
+/// claim to. Readers that repair a damaged table may show other content than
+/// those that trust it.
+fn check_xref(bytes: &[u8], document: Option<&Document>) -> Vec<RiskFinding> {
+    let mut findings = Vec::new();
+    let object_header = Regex::new(r"^\s*(\d+)\s+(\d+)\s+obj\b").unwrap();
+
+    let start = bytes
+        .windows(9)
+        .rposition(|window| window == b"startxref")
+        .and_then(|position| {
+            let digits = bytes[position + 9..]
+                .iter()
+                .skip_while(|b| b.is_ascii_whitespace())
+                .take_while(|b| b.is_ascii_digit())
+                .copied()
+                .collect::<Vec<_>>();
+            std::str::from_utf8(&digits).ok()?.parse::<usize>().ok()
+        });
+
+    match start {
+        None => findings.push(
+            RiskFinding::new(RiskKind::MalformedXref, RiskSeverity::Medium)
+                .message("The file has no startxref entry"),
+        ),
+        Some(start) => {
+            let section = bytes.get(start..).unwrap_or_default();
+            let trimmed = section
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .map_or(section, |position| &section[position..]);
+            if !trimmed.starts_with(b"xref") && !object_header.is_match(section) {
+                findings.push(
+                    RiskFinding::new(RiskKind::MalformedXref, RiskSeverity::Medium)
+                        .message("startxref does not point to a cross-reference section"),
+                );
+            }
+        }
+    }
+
+    let Some(document) = document else {
+        return findings;
+    };
+
+    let mut misplaced = Vec::new();
+    for (id, entry) in &document.reference_table.entries {
+        let XrefEntry::Normal { offset, generation } = entry else {
+            continue;
+        };
+
+        let header = bytes
+            .get(*offset as usize..)
+            .and_then(|object| object_header.captures(object))
+            .map(|captures| (parse_number(&captures[1]), parse_number(&captures[2])));
+        if header != Some((Some(*id), Some(u32::from(*generation)))) {
+            misplaced.push(*id);
+        }
+    }
+
+    if !misplaced.is_empty() {
+        findings.push(RiskFinding {
+            object_id: misplaced.first().copied(),
+            ..RiskFinding::new(RiskKind::MalformedXref, RiskSeverity::Medium)
+                .message(format!(
+                    "{} cross-reference entries point to the wrong place",
+                    misplaced.len()
+                ))
+                .target(Some(object_list(&misplaced)))
+        });
+    }
+
+    findings
+}
+
+/// Counts the revisions of the file and finds object definitions that the final
+/// cross-reference table does not use: earlier versions of changed objects and
+/// objects no revision lists.
+fn check_updates(bytes: &[u8], document: Option<&Document>) -> (usize, Vec<RiskFinding>) {
+    let mut findings = Vec::new();
+
+    let mut ends = bytes
+        .windows(5)
+        .enumerate()
+        .filter(|(_, window)| *window == b"%%EOF")
+        .map(|(position, _)| position)
+        .collect::<Vec<_>>();
+    // The first-page section of a linearized file ends with its own marker
+    let head = &bytes[..bytes.len().min(1024)];
+    if ends.len() > 1 && head.windows(11).any(|window| window == b"/Linearized") {
+        ends.remove(0);
+    }
+    let revisions = ends.len().max(1);
+
+    let definitions = Regex::new(r"(?:^|[\r\n])[ \t]*(\d+)[ \t\r\n]+(\d+)[ \t\r\n]+obj\b")
+        .unwrap()
+        .captures_iter(bytes)
+        .filter_map(|captures| {
+            let offset = captures.get(1)?.start();
+            Some((parse_number(&captures[1])?, offset))
+        })
+        .collect::<Vec<_>>();
+
+    if revisions > 1 {
+        let first_end = ends.first().copied().unwrap_or_default();
+        let updated = definitions
+            .iter()
+            .filter(|(_, offset)| *offset > first_end)
+            .map(|(id, _)| *id)
+            .collect::<HashSet<_>>();
+        findings.push(
+            RiskFinding::new(RiskKind::IncrementalUpdate, RiskSeverity::Info).message(format!(
+                "The document was changed {} times after it was first saved, adding or replacing {} objects",
+                revisions - 1,
+                updated.len()
+            )),
+        );
+    }
+
+    let Some(document) = document else {
+        return (revisions, findings);
+    };
+
+    let entries = &document.reference_table.entries;
+    let mut replaced = Vec::new();
+    let mut unlisted = Vec::new();
+    for (id, offset) in definitions {
+        match entries.get(&id) {
+            Some(XrefEntry::Normal {
+                offset: current, ..
+            }) if *current as usize == offset => {}
+            Some(XrefEntry::Normal { .. } | XrefEntry::Compressed { .. }) => replaced.push(id),
+            _ => unlisted.push(id),
+        }
+    }
+
+    if !replaced.is_empty() {
+        findings.push(
+            RiskFinding::new(RiskKind::HiddenObjects, RiskSeverity::Low)
+                .message(format!(
+                    "{} earlier versions of changed objects are still in the file",
+                    replaced.len()
+                ))
+                .target(Some(object_list(&replaced))),
+        );
+    }
+    if !unlisted.is_empty() {
+        findings.push(
+            RiskFinding::new(RiskKind::HiddenObjects, RiskSeverity::Medium)
+                .message(format!(
+                    "{} objects in the file are not listed in any cross-reference table or were deleted",
+                    unlisted.len()
+                ))
+                .target(Some(object_list(&unlisted))),
+        );
+    }
+
+    (revisions, findings)
+}
+
+/// Looks for risky names in the raw file when its objects cannot be read, e.g.
+/// because it is encrypted or damaged. Names in compressed object streams are
+/// not found.
+fn scan_names(bytes: &[u8]) -> Vec<RiskFinding> {
+    const NAMES: [(&[u8], RiskKind, RiskSeverity); 9] = [
+        (b"JavaScript", RiskKind::JavaScript, RiskSeverity::High),
+        (b"JS", RiskKind::JavaScript, RiskSeverity::High),
+        (b"Launch", RiskKind::LaunchAction, RiskSeverity::High),
+        (b"URI", RiskKind::UriAction, RiskSeverity::Low),
+        (
+            b"SubmitForm",
+            RiskKind::SubmitFormAction,
+            RiskSeverity::Medium,
+        ),
+        (
+            b"ImportData",
+            RiskKind::ImportDataAction,
+            RiskSeverity::Medium,
+        ),
+        (b"GoToR", RiskKind::ExternalReference, RiskSeverity::Medium),
+        (b"EmbeddedFile", RiskKind::EmbeddedFile, RiskSeverity::Low),
+        (b"XFA", RiskKind::JavaScript, RiskSeverity::Medium),
+    ];
+    let is_delimiter = |b: u8| b.is_ascii_whitespace() || b"()<>[]{}/%".contains(&b);
+
+    let mut counts = [0; NAMES.len()];
+    let mut escaped = [false; NAMES.len()];
+    let mut position = 0;
+    while position < bytes.len() {
+        if bytes[position] != b'/' {
+            position += 1;
+            continue;
+        }
+
+        let start = position + 1;
+        let end = bytes[start..]
+            .iter()
+            .position(|b| is_delimiter(*b))
+            .map_or(bytes.len(), |length| start + length);
+        let raw = &bytes[start..end];
+        position = end;
+
+        // `#xx` escapes are a common way to hide names from simple scanners
+        let decoded = decode_name(raw);
+        if let Some(index) = NAMES
+            .iter()
+            .position(|(name, ..)| *name == decoded.as_slice())
+        {
+            counts[index] += 1;
+            escaped[index] |= decoded != raw;
+        }
+    }
+
+    let mut findings: Vec<RiskFinding> = Vec::new();
+    for (index, (name, kind, severity)) in NAMES.iter().enumerate() {
+        if counts[index] == 0 {
+            continue;
+        }
+
+        let name = String::from_utf8_lossy(name);
+        let mut message = format!(
+            "{} ({name} found {} times in data that could not be read)",
+            kind.description(),
+            counts[index]
+        );
+        let severity = if escaped[index] {
+            message.push_str(", with escaped characters to hide it");
+            RiskSeverity::High
+        } else {
+            *severity
+        };
+
+        findings.push(RiskFinding::new(*kind, severity).message(message));
+    }
+
+    findings
+}
+
+fn decode_name(raw: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(raw.len());
+    let mut index = 0;
+    while index < raw.len() {
+        let hex = raw
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (raw[index], hex) {
+            (b'#', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn parse_number(digits: &[u8]) -> Option<u32> {
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+fn object_list(ids: &[u32]) -> String {
+    ids.iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a PDF from numbered object bodies, with an optional update that
+    /// replaces or adds objects.
+    fn pdf(objects: &[&str], update: &[(u32, &str)]) -> Vec<u8> {
+        let mut bytes = b"%PDF-1.7\n".to_vec();
+        let mut offsets = Vec::new();
+        for (index, body) in objects.iter().enumerate() {
+            offsets.push(bytes.len());
+            bytes.extend(format!("{} 0 obj\n{body}\nendobj\n", index + 1).as_bytes());
+        }
+
+        let xref = bytes.len();
+        bytes.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in &offsets {
+            bytes.extend(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        bytes.extend(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+                objects.len() + 1
+            )
+            .as_bytes(),
+        );
+
+        if !update.is_empty() {
+            let mut offsets = Vec::new();
+            for (id, body) in update {
+                offsets.push((*id, bytes.len()));
+                bytes.extend(format!("{id} 0 obj\n{body}\nendobj\n").as_bytes());
+            }
+            let update_xref = bytes.len();
+            bytes.extend(b"xref\n");
+            for (id, offset) in offsets {
+                bytes.extend(format!("{id} 1\n{offset:010} 00000 n \n").as_bytes());
+            }
+            bytes.extend(
+                format!(
+                    "trailer\n<< /Size {} /Root 1 0 R /Prev {xref} >>\nstartxref\n{update_xref}\n%%EOF\n",
+                    objects.len() + 2
+                )
+                .as_bytes(),
+            );
+        }
+
+        bytes
+    }
+
+    const CATALOG: &str = "<< /Type /Catalog /Pages 2 0 R /OpenAction 4 0 R /Names << /EmbeddedFiles << /Names [(invoice.pdf.exe) 6 0 R] >> >> >>";
+    const PAGES: &str = "<< /Type /Pages /Kids [3 0 R] /Count 1 >>";
+    const PAGE: &str = "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] /Annots [5 0 R] >>";
+    const SCRIPT: &str = "<< /S /JavaScript /JS (app.launchURL\\('http://example.com'\\)) >>";
+    const LINK: &str = "<< /Type /Annot /Subtype /Link /Rect [0 0 10 10] /A << /S /URI /URI (http://example.com) >> >>";
+    const FILE: &str = "<< /Type /Filespec /F (invoice.pdf.exe) /EF << /F 7 0 R >> >>";
+    const PROGRAM: &str = "<< /Type /EmbeddedFile /Length 4 >>\nstream\nMZ\x00\x00\nendstream";
+
+    fn finding(report: &RiskReport, kind: RiskKind) -> &RiskFinding {
+        report
+            .findings
+            .iter()
+            .find(|finding| finding.kind == kind)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_scan_actions() {
+        let bytes = pdf(&[CATALOG, PAGES, PAGE, SCRIPT, LINK, FILE, PROGRAM], &[]);
+        let report = scan_bytes(&bytes);
+
+        assert_eq!(report.severity, Some(RiskSeverity::High));
+        assert_eq!(report.revisions, 1);
+
+        let script = finding(&report, RiskKind::JavaScript);
+        assert!(script.automatic);
+        assert_eq!(script.object_id, Some(4));
+        assert_eq!(
+            script.target.as_deref(),
+            Some("app.launchURL('http://example.com')")
+        );
+
+        let link = finding(&report, RiskKind::UriAction);
+        assert!(!link.automatic);
+        assert_eq!(link.severity, RiskSeverity::Low);
+        assert_eq!(link.page_index, Some(0));
+        assert_eq!(link.target.as_deref(), Some("http://example.com"));
+
+        let program = finding(&report, RiskKind::EmbeddedExecutable);
+        assert_eq!(program.target.as_deref(), Some("invoice.pdf.exe"));
+
+        assert!(report
+            .findings
+            .iter()
+            .all(|finding| finding.kind != RiskKind::MalformedXref
+                && finding.kind != RiskKind::HiddenObjects));
+        assert!(report
+            .findings
+            .windows(2)
+            .all(|pair| pair[0].severity >= pair[1].severity));
+    }
+
+    #[test]
+    fn test_scan_updates() {
+        let clean_page = "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] >>";
+        let catalog = "<< /Type /Catalog /Pages 2 0 R >>";
+        let bytes = pdf(
+            &[catalog, PAGES, PAGE, SCRIPT, LINK],
+            &[(3, clean_page), (6, "<< /Hidden true >>")],
+        );
+        let report = scan_bytes(&bytes);
+
+        assert_eq!(report.revisions, 2);
+        assert!(report
+            .findings
+            .iter()
+            .any(|finding| finding.kind == RiskKind::IncrementalUpdate));
+        let hidden = finding(&report, RiskKind::HiddenObjects);
+        assert_eq!(hidden.severity, RiskSeverity::Low);
+        assert_eq!(hidden.target.as_deref(), Some("3"));
+        // The link is no longer on the page, but its object is still in the file
+        assert_eq!(finding(&report, RiskKind::UriAction).page_index, None);
+
+        // A table entry that points into another object
+        let mut damaged = pdf(&[catalog, PAGES, clean_page], &[]);
+        let text = String::from_utf8(damaged.clone()).unwrap();
+        let entry = text.find("00000 n").unwrap() - 11;
+        damaged[entry..entry + 10].copy_from_slice(b"0000000000");
+        let report = scan_bytes(&damaged);
+        assert!(report
+            .findings
+            .iter()
+            .any(|finding| finding.kind == RiskKind::MalformedXref));
+    }
+
+    #[test]
+    fn test_scan_web_file_under_structure() {
+        // A structure element's /S is its role, not an action type
+        let catalog = "<< /Type /Catalog /Pages 2 0 R >>";
+        let clean_page = "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] >>";
+        let element =
+            "<< /Type /StructElem /S /Figure /K << /FS /URL /F (http://example.com/data.pdf) >> >>";
+        let report = scan_bytes(&pdf(&[catalog, PAGES, clean_page, element], &[]));
+
+        let web = finding(&report, RiskKind::ExternalReference);
+        assert_eq!(web.message, "Refers to a file on the web");
+        assert_eq!(web.target.as_deref(), Some("http://example.com/data.pdf"));
+    }
+
+    #[test]
+    fn test_scan_names() {
+        let findings = scan_names(b"<< /S /J#61vaScript /JS 5 0 R >> << /S/URI >>");
+
+        assert_eq!(findings.len(), 3);
+        assert_eq!(findings[0].kind, RiskKind::JavaScript);
+        assert_eq!(findings[0].severity, RiskSeverity::High);
+        assert!(findings[0].message.contains("escaped"));
+        assert_eq!(findings[2].kind, RiskKind::UriAction);
+    }
+}
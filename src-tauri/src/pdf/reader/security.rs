@@ -0,0 +1,314 @@
+use std::path::Path;
+
+use lopdf::{Dictionary, Document, Object, Permissions};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncryptionAlgorithm {
+    /// RC4, only for readers that predate AES. New documents use 128-bit keys
+    Rc4,
+    /// 128-bit AES (PDF 1.6)
+    Aes128,
+    /// 256-bit AES (PDF 2.0, revision 6)
+    #[default]
+    Aes256,
+}
+
+/// What a user who opened the document without the owner password may do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentPermissions {
+    // Mapped 1-to-1 to lopdf Permissions bitflags
+    pub allow_printing: bool,
+    pub allow_high_quality_printing: bool,
+    pub allow_modifying: bool,
+    pub allow_copying: bool,
+    pub allow_annotating: bool,
+    pub allow_form_filling: bool,
+    pub allow_assembly: bool,
+}
+
+impl DocumentPermissions {
+    pub fn to_permissions(self) -> Permissions {
+        // COPYABLE_FOR_ACCESSIBILITY is deprecated since PDF 2.0 but must always be set for
+        // backward compatibility with viewers following earlier specifications.
+        let mut permissions = Permissions::COPYABLE_FOR_ACCESSIBILITY;
+
+        for (allowed, flag) in [
+            (self.allow_printing, Permissions::PRINTABLE),
+            (
+                self.allow_high_quality_printing,
+                Permissions::PRINTABLE_IN_HIGH_QUALITY,
+            ),
+            (self.allow_modifying, Permissions::MODIFIABLE),
+            (self.allow_copying, Permissions::COPYABLE),
+            (self.allow_annotating, Permissions::ANNOTABLE),
+            (self.allow_form_filling, Permissions::FILLABLE),
+            (self.allow_assembly, Permissions::ASSEMBLABLE),
+        ] {
+            if allowed {
+                permissions |= flag;
+            }
+        }
+
+        permissions
+    }
+
+    pub fn from_permissions(permissions: Permissions) -> Self {
+        Self {
+            allow_printing: permissions.contains(Permissions::PRINTABLE),
+            allow_high_quality_printing: permissions
+                .contains(Permissions::PRINTABLE_IN_HIGH_QUALITY),
+            allow_modifying: permissions.contains(Permissions::MODIFIABLE),
+            allow_copying: permissions.contains(Permissions::COPYABLE),
+            allow_annotating: permissions.contains(Permissions::ANNOTABLE),
+            allow_form_filling: permissions.contains(Permissions::FILLABLE),
+            allow_assembly: permissions.contains(Permissions::ASSEMBLABLE),
+        }
+    }
+}
+
+/// Encryption of a document and what it permits.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSecurity {
+    pub encrypted: bool,
+
+    /// `None` for unencrypted documents and unknown security handlers
+    pub algorithm: Option<EncryptionAlgorithm>,
+    pub key_length: Option<u32>,
+
+    /// `V` and `R` entries of the encryption dictionary
+    pub version: Option<i64>,
+    pub revision: Option<i64>,
+
+    /// Opening the document needs a user password
+    pub requires_password: bool,
+
+    /// The owner password was given, lifting the permissions below
+    pub owner_unlocked: bool,
+
+    pub permissions: DocumentPermissions,
+}
+
+/// Actions the reader refuses when the document's permissions forbid them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestrictedAction {
+    CopyText,
+    Annotate,
+    /// Filling in form fields, which the annotate permission also allows
+    FillForm,
+    /// Changing pages, page content or document properties
+    Modify,
+}
+
+/// Reads the encryption and permissions of a PDF file.
+pub fn read_security(path: &Path) -> Result<DocumentSecurity, String> {
+    let document = Document::load(path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+    Ok(document_security(&document))
+}
+
+pub fn document_security(document: &Document) -> DocumentSecurity {
+    let Some(encrypt) = encryption_dictionary(document) else {
+        return DocumentSecurity {
+            encrypted: false,
+            algorithm: None,
+            key_length: None,
+            version: None,
+            revision: None,
+            requires_password: false,
+            owner_unlocked: false,
+            permissions: DocumentPermissions::from_permissions(Permissions::all()),
+        };
+    };
+
+    let integer = |key: &[u8]| encrypt.get(key).and_then(Object::as_i64).ok();
+    let version = integer(b"V");
+    let length = integer(b"Length").map(|length| length as u32);
+
+    // The method of the default crypt filter tells AES and RC4 apart from V4 on
+    let method = encrypt
+        .get(b"StmF")
+        .and_then(Object::as_name)
+        .ok()
+        .and_then(|filter| {
+            encrypt
+                .get(b"CF")
+                .and_then(Object::as_dict)
+                .and_then(|filters| filters.get(filter))
+                .and_then(Object::as_dict)
+                .and_then(|filter| filter.get(b"CFM"))
+                .and_then(Object::as_name)
+                .ok()
+        });
+
+    let (algorithm, key_length) = match (version, method) {
+        (Some(1), _) => (Some(EncryptionAlgorithm::Rc4), Some(40)),
+        (Some(2 | 3), _) => (Some(EncryptionAlgorithm::Rc4), length.or(Some(40))),
+        (Some(4), Some(b"AESV2")) => (Some(EncryptionAlgorithm::Aes128), Some(128)),
+        (Some(4), Some(b"V2")) => (Some(EncryptionAlgorithm::Rc4), length.or(Some(128))),
+        (Some(5), _) => (Some(EncryptionAlgorithm::Aes256), Some(256)),
+        _ => (None, length),
+    };
+
+    // The value is a signed 32-bit integer
+    let permissions = integer(b"P")
+        .map(|bits| Permissions::from_bits_truncate(bits as i32 as u64))
+        .unwrap_or_else(Permissions::all);
+
+    DocumentSecurity {
+        encrypted: true,
+        algorithm,
+        key_length,
+        version,
+        revision: integer(b"R"),
+        // lopdf decrypts documents whose user password is empty
+        requires_password: document.encryption_state.is_none(),
+        owner_unlocked: false,
+        permissions: DocumentPermissions::from_permissions(permissions),
+    }
+}
+
+/// Checks the owner password of a PDF file. Unencrypted files have nothing to
+/// unlock and accept any password.
+pub fn verify_owner_password(path: &Path, password: &str) -> Result<(), String> {
+    let document = Document::load(path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+    check_owner_password(document, password)
+}
+
+pub fn check_owner_password(mut document: Document, password: &str) -> Result<(), String> {
+    // Put back the encryption dictionary lopdf drops after decrypting on load
+    if let Some(encrypt) = encryption_dictionary(&document).filter(|_| !document.is_encrypted()) {
+        let id = document.add_object(encrypt);
+        document.trailer.set("Encrypt", id);
+    }
+
+    if !document.is_encrypted() {
+        return Ok(());
+    }
+
+    document
+        .authenticate_owner_password(password)
+        .map_err(|_| "Incorrect owner password".to_string())
+}
+
+/// Refuses an action the document does not permit, unless it was unlocked with
+/// its owner password.
+pub fn ensure_allowed(security: &DocumentSecurity, action: RestrictedAction) -> Result<(), String> {
+    if security.owner_unlocked {
+        return Ok(());
+    }
+
+    let permissions = &security.permissions;
+    match action {
+        RestrictedAction::CopyText if !permissions.allow_copying => Err(
+            "This document does not allow copying its text. Enter the owner password to lift the restriction."
+                .into(),
+        ),
+        RestrictedAction::Annotate if !permissions.allow_annotating => Err(
+            "This document does not allow saving annotations into it. Enter the owner password, or keep the annotations in a sidecar file."
+                .into(),
+        ),
+        RestrictedAction::FillForm
+            if !permissions.allow_form_filling && !permissions.allow_annotating =>
+        {
+            Err("This document does not allow filling in its form. Enter the owner password to lift the restriction.".into())
+        }
+        RestrictedAction::Modify if !permissions.allow_modifying => Err(
+            "This document does not allow changing it. Enter the owner password to lift the restriction."
+                .into(),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Encryption dictionary of a document, rebuilt from the decryption state when
+/// lopdf decrypted the document on load.
+fn encryption_dictionary(document: &Document) -> Option<Dictionary> {
+    match &document.encryption_state {
+        Some(state) => state.encode().ok(),
+        None => document.get_encrypted().ok().cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::tools::encrypt_document;
+    use lopdf::{dictionary, LoadOptions};
+
+    fn encrypted(algorithm: EncryptionAlgorithm, user_password: &str) -> Vec<u8> {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => Vec::<Object>::new(),
+                "Count" => 0,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let permissions = DocumentPermissions {
+            allow_printing: true,
+            ..Default::default()
+        };
+        encrypt_document(
+            &mut document,
+            algorithm,
+            "owner-secret",
+            user_password,
+            permissions.to_permissions(),
+        )
+        .unwrap();
+
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_document_security() {
+        let document = Document::load_mem(&encrypted(EncryptionAlgorithm::Aes256, "")).unwrap();
+        let security = document_security(&document);
+        assert!(security.encrypted);
+        assert_eq!(security.algorithm, Some(EncryptionAlgorithm::Aes256));
+        assert_eq!(security.key_length, Some(256));
+        assert_eq!(security.revision, Some(6));
+        assert!(!security.requires_password);
+        assert!(security.permissions.allow_printing);
+        assert!(!security.permissions.allow_copying);
+
+        assert!(ensure_allowed(&security, RestrictedAction::CopyText).is_err());
+        assert!(ensure_allowed(&security, RestrictedAction::Annotate).is_err());
+        assert!(ensure_allowed(&security, RestrictedAction::FillForm).is_err());
+        assert!(ensure_allowed(&security, RestrictedAction::Modify).is_err());
+        let unlocked = DocumentSecurity {
+            owner_unlocked: true,
+            ..security
+        };
+        assert!(ensure_allowed(&unlocked, RestrictedAction::CopyText).is_ok());
+        assert!(ensure_allowed(&unlocked, RestrictedAction::Modify).is_ok());
+
+        assert!(check_owner_password(document.clone(), "owner-secret").is_ok());
+        assert!(check_owner_password(document, "wrong").is_err());
+
+        let bytes = encrypted(EncryptionAlgorithm::Aes128, "user-secret");
+        let document = Document::load_mem(&bytes).unwrap();
+        let security = document_security(&document);
+        assert_eq!(security.algorithm, Some(EncryptionAlgorithm::Aes128));
+        assert!(security.requires_password);
+        assert!(check_owner_password(document, "owner-secret").is_ok());
+
+        let document =
+            Document::load_mem_with_options(&bytes, LoadOptions::with_password("user-secret"))
+                .unwrap();
+        assert!(!document_security(&document).requires_password);
+    }
+}
@@ -0,0 +1,862 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::{SignedData, SignerIdentifier, SignerInfo};
+use const_oid::db::{rfc5280, rfc5911, rfc5912};
+use der::asn1::{AnyRef, ObjectIdentifier, OctetString};
+use der::{Decode, Encode, Reader, SliceReader, Tag, TagNumber, Tagged};
+use lopdf::{Dictionary, Document, Object};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use x509_cert::ext::pkix::SubjectKeyIdentifier;
+use x509_cert::Certificate;
+
+use crate::pdf::reader::form::fields::{field_nodes, read_form_fields, text_value};
+use crate::pdf::reader::FormFieldType;
+use crate::pdf::DocumentId;
+
+const SUB_FILTER_SHA1: &[u8] = b"adbe.pkcs7.sha1";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureStatus {
+    /// The signed bytes match the digest and the signature matches the signer's key
+    Valid,
+
+    /// The signed bytes no longer match the digest
+    Altered,
+
+    /// The signature does not match the signer's certificate
+    Invalid,
+
+    /// The digest matches, but the signature could not be checked
+    Unverified,
+
+    /// The signature value or byte range cannot be read
+    Malformed,
+
+    /// Signature field that has not been signed
+    Unsigned,
+}
+
+/// Byte range of the file covered by a signature
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct SignedRange {
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignerCertificate {
+    /// Distinguished names, e.g. `CN=Ana Lima,O=Example`
+    pub subject: String,
+    pub issuer: String,
+
+    /// Hexadecimal serial number
+    pub serial_number: String,
+
+    /// Validity period as PDF dates
+    pub not_before: String,
+    pub not_after: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureInfo {
+    pub field_name: String,
+    pub page_index: Option<u16>,
+    pub status: SignatureStatus,
+
+    /// Why the signature is not valid
+    pub message: Option<String>,
+
+    /// Signer name from the signature dictionary or the certificate common name
+    pub signer_name: Option<String>,
+    pub certificate: Option<SignerCertificate>,
+
+    /// Signing time claimed by the signer, as a PDF date. Taken from the signed
+    /// attributes, or from the signature dictionary (`/M`) when they have none.
+    pub signing_time: Option<String>,
+    pub reason: Option<String>,
+    pub location: Option<String>,
+    pub contact_info: Option<String>,
+
+    /// Signature format (`/SubFilter`), e.g. `adbe.pkcs7.detached`
+    pub sub_filter: Option<String>,
+    pub digest_algorithm: Option<String>,
+    pub byte_range: Vec<SignedRange>,
+
+    /// The byte range starts at the beginning of the file and leaves out only the
+    /// signature value, so the whole revision up to the signature is signed.
+    pub covers_revision: bool,
+
+    /// Data was appended to the file after the signed revision
+    pub modified_after_signing: bool,
+}
+
+/// Digest algorithms accepted in signatures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    fn from_oid(oid: &ObjectIdentifier) -> Option<Self> {
+        match *oid {
+            rfc5912::ID_SHA_1 => Some(DigestAlgorithm::Sha1),
+            rfc5912::ID_SHA_256 => Some(DigestAlgorithm::Sha256),
+            rfc5912::ID_SHA_384 => Some(DigestAlgorithm::Sha384),
+            rfc5912::ID_SHA_512 => Some(DigestAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha1 => "SHA-1",
+            DigestAlgorithm::Sha256 => "SHA-256",
+            DigestAlgorithm::Sha384 => "SHA-384",
+            DigestAlgorithm::Sha512 => "SHA-512",
+        }
+    }
+
+    fn hash(self, parts: &[&[u8]]) -> Vec<u8> {
+        fn hash<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().to_vec()
+        }
+
+        match self {
+            DigestAlgorithm::Sha1 => hash::<sha1::Sha1>(parts),
+            DigestAlgorithm::Sha256 => hash::<sha2::Sha256>(parts),
+            DigestAlgorithm::Sha384 => hash::<sha2::Sha384>(parts),
+            DigestAlgorithm::Sha512 => hash::<sha2::Sha512>(parts),
+        }
+    }
+
+    fn pkcs1v15(self) -> Pkcs1v15Sign {
+        match self {
+            DigestAlgorithm::Sha1 => Pkcs1v15Sign::new::<sha1::Sha1>(),
+            DigestAlgorithm::Sha256 => Pkcs1v15Sign::new::<sha2::Sha256>(),
+            DigestAlgorithm::Sha384 => Pkcs1v15Sign::new::<sha2::Sha384>(),
+            DigestAlgorithm::Sha512 => Pkcs1v15Sign::new::<sha2::Sha512>(),
+        }
+    }
+}
+
+/// Result of checking a signature value against the signed bytes.
+struct Verification {
+    status: SignatureStatus,
+    message: Option<String>,
+    certificate: Option<Certificate>,
+    signing_time: Option<String>,
+    digest_algorithm: Option<DigestAlgorithm>,
+}
+
+impl Verification {
+    fn failed(status: SignatureStatus, message: impl Into<String>) -> Self {
+        Verification {
+            status,
+            message: Some(message.into()),
+            certificate: None,
+            signing_time: None,
+            digest_algorithm: None,
+        }
+    }
+}
+
+/// Lists the signature fields of an open document and verifies their signatures.
+pub fn get_signatures(
+    paths: &HashMap<DocumentId, PathBuf>,
+    id: &DocumentId,
+) -> Result<Vec<SignatureInfo>, String> {
+    let path = paths.get(id).ok_or("Document path not found")?;
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read PDF: {e}"))?;
+
+    read_signatures(&bytes)
+}
+
+/// Verifies every signature of the file offline: the byte range, the digest of the
+/// signed bytes and the signature value. Certificates are reported but not
+/// validated against trust anchors or revocation lists.
+pub fn read_signatures(bytes: &[u8]) -> Result<Vec<SignatureInfo>, String> {
+    let document = Document::load_mem(bytes).map_err(|e| format!("Failed to load PDF: {e}"))?;
+
+    let fields = read_form_fields(&document)?;
+    let nodes = field_nodes(&document)?;
+
+    let signatures = fields
+        .iter()
+        .zip(&nodes)
+        .filter(|(field, _)| field.field_type == FormFieldType::Signature)
+        .map(|(field, node)| {
+            let page_index = field.widgets.first().map(|widget| widget.page_index);
+            let value = document
+                .get_dictionary(node.id)
+                .and_then(|dict| dict.get_deref(b"V", &document))
+                .and_then(Object::as_dict);
+
+            match value {
+                Ok(value) => read_signature(&document, bytes, &field.name, page_index, value),
+                Err(_) => SignatureInfo {
+                    field_name: field.name.clone(),
+                    page_index,
+                    status: SignatureStatus::Unsigned,
+                    message: None,
+                    signer_name: None,
+                    certificate: None,
+                    signing_time: None,
+                    reason: None,
+                    location: None,
+                    contact_info: None,
+                    sub_filter: None,
+                    digest_algorithm: None,
+                    byte_range: Vec::new(),
+                    covers_revision: false,
+                    modified_after_signing: false,
+                },
+            }
+        })
+        .collect();
+
+    Ok(signatures)
+}
+
+fn read_signature(
+    document: &Document,
+    bytes: &[u8],
+    field_name: &str,
+    page_index: Option<u16>,
+    value: &Dictionary,
+) -> SignatureInfo {
+    let text = |key: &[u8]| {
+        value
+            .get_deref(key, document)
+            .ok()
+            .and_then(text_value)
+            .filter(|text| !text.is_empty())
+    };
+    let sub_filter = value
+        .get(b"SubFilter")
+        .and_then(Object::as_name)
+        .ok()
+        .map(<[u8]>::to_vec);
+
+    let byte_range = value
+        .get_deref(b"ByteRange", document)
+        .and_then(Object::as_array)
+        .map(|range| {
+            range
+                .chunks(2)
+                .filter_map(|pair| match pair {
+                    [offset, length] => Some(SignedRange {
+                        offset: offset.as_i64().ok()?.try_into().ok()?,
+                        length: length.as_i64().ok()?.try_into().ok()?,
+                    }),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let contents = value
+        .get_deref(b"Contents", document)
+        .and_then(Object::as_str)
+        .unwrap_or_default();
+
+    let signed_end = byte_range
+        .last()
+        .map(|range| range.offset.saturating_add(range.length))
+        .unwrap_or(0);
+    let trailing = bytes.get(signed_end as usize..).unwrap_or_default();
+    let modified_after_signing = trailing.iter().any(|b| !b.is_ascii_whitespace());
+    let covers_revision = covers_revision(bytes, &byte_range, contents);
+
+    let verification = match signed_bytes(bytes, &byte_range) {
+        _ if contents.is_empty() => {
+            Verification::failed(SignatureStatus::Malformed, "Signature has no value")
+        }
+        _ if !covers_revision => Verification::failed(
+            SignatureStatus::Malformed,
+            "Byte range leaves data outside the signature value unsigned",
+        ),
+        Some(parts) => verify_cms(contents, &parts, sub_filter.as_deref()),
+        None => Verification::failed(
+            SignatureStatus::Malformed,
+            "Byte range lies outside the file",
+        ),
+    };
+
+    let certificate = verification.certificate.as_ref();
+    let signer_name = text(b"Name").or_else(|| certificate.and_then(common_name));
+
+    SignatureInfo {
+        field_name: field_name.to_string(),
+        page_index,
+        status: verification.status,
+        message: verification.message,
+        signer_name,
+        certificate: certificate.map(|certificate| {
+            let tbs = &certificate.tbs_certificate;
+            SignerCertificate {
+                subject: tbs.subject.to_string(),
+                issuer: tbs.issuer.to_string(),
+                serial_number: tbs
+                    .serial_number
+                    .as_bytes()
+                    .iter()
+                    .map(|b| format!("{b:02X}"))
+                    .collect(),
+                not_before: pdf_date(tbs.validity.not_before.to_date_time()),
+                not_after: pdf_date(tbs.validity.not_after.to_date_time()),
+            }
+        }),
+        signing_time: verification.signing_time.or_else(|| text(b"M")),
+        reason: text(b"Reason"),
+        location: text(b"Location"),
+        contact_info: text(b"ContactInfo"),
+        sub_filter: sub_filter.map(|name| String::from_utf8_lossy(&name).into_owned()),
+        digest_algorithm: verification
+            .digest_algorithm
+            .map(|algorithm| algorithm.name().to_string()),
+        byte_range,
+        covers_revision,
+        modified_after_signing,
+    }
+}
+
+/// Checks that the byte range starts at the beginning of the file and that its only
+/// gap is the hexadecimal string holding the signature value.
+fn covers_revision(bytes: &[u8], byte_range: &[SignedRange], contents: &[u8]) -> bool {
+    let [first, second] = byte_range else {
+        return false;
+    };
+    if first.offset != 0 || second.offset < first.length {
+        return false;
+    }
+
+    let gap = bytes.get(first.length as usize..second.offset as usize);
+    gap.is_some_and(|gap| {
+        gap.len() == contents.len() * 2 + 2
+            && gap.first() == Some(&b'<')
+            && gap.last() == Some(&b'>')
+            && gap[1..gap.len() - 1].iter().all(u8::is_ascii_hexdigit)
+    })
+}
+
+fn signed_bytes<'a>(bytes: &'a [u8], byte_range: &[SignedRange]) -> Option<Vec<&'a [u8]>> {
+    byte_range
+        .iter()
+        .map(|range| {
+            let end = range.offset.checked_add(range.length)?;
+            bytes.get(range.offset as usize..end as usize)
+        })
+        .collect()
+}
+
+fn verify_cms(contents: &[u8], parts: &[&[u8]], sub_filter: Option<&[u8]>) -> Verification {
+    // The value is padded with zeros up to the reserved size, so only the first
+    // DER element is read.
+    let content_info =
+        SliceReader::new(contents).and_then(|mut reader| ContentInfo::decode(&mut reader));
+    let signed_data = match content_info {
+        Ok(info) if info.content_type == rfc5911::ID_SIGNED_DATA => {
+            info.content.decode_as::<SignedData>()
+        }
+        Ok(_) => {
+            return Verification::failed(
+                SignatureStatus::Malformed,
+                "Signature value is not CMS signed data",
+            )
+        }
+        Err(e) => {
+            return Verification::failed(
+                SignatureStatus::Malformed,
+                format!("Failed to parse signature: {e}"),
+            )
+        }
+    };
+    let signed_data = match signed_data {
+        Ok(signed_data) => signed_data,
+        Err(e) => {
+            return Verification::failed(
+                SignatureStatus::Malformed,
+                format!("Failed to parse signature: {e}"),
+            )
+        }
+    };
+    let Some(signer_info) = signed_data.signer_infos.0.iter().next() else {
+        return Verification::failed(SignatureStatus::Malformed, "Signature has no signer");
+    };
+
+    let mut verification = Verification {
+        status: SignatureStatus::Valid,
+        message: None,
+        certificate: signer_certificate(&signed_data, signer_info).cloned(),
+        signing_time: signed_attribute(signer_info, rfc5911::ID_SIGNING_TIME)
+            .and_then(|time| time.to_der().ok())
+            .and_then(|time| x509_cert::time::Time::from_der(&time).ok())
+            .map(|time| pdf_date(time.to_date_time())),
+        digest_algorithm: DigestAlgorithm::from_oid(&signer_info.digest_alg.oid),
+    };
+
+    let Some(algorithm) = verification.digest_algorithm else {
+        verification.status = SignatureStatus::Unverified;
+        verification.message = Some(format!(
+            "Unsupported digest algorithm {}",
+            signer_info.digest_alg.oid
+        ));
+        return verification;
+    };
+
+    // adbe.pkcs7.sha1 signs the SHA-1 digest of the byte range as encapsulated
+    // content; the other formats sign the byte range itself.
+    let econtent = signed_data
+        .encap_content_info
+        .econtent
+        .as_ref()
+        .and_then(|content| content.decode_as::<OctetString>().ok());
+    let content = match (&econtent, sub_filter) {
+        (Some(econtent), Some(SUB_FILTER_SHA1)) => {
+            if econtent.as_bytes() != DigestAlgorithm::Sha1.hash(parts) {
+                return Verification {
+                    status: SignatureStatus::Altered,
+                    message: Some("Document digest does not match the signed bytes".into()),
+                    ..verification
+                };
+            }
+            vec![econtent.as_bytes()]
+        }
+        _ => parts.to_vec(),
+    };
+    let digest = algorithm.hash(&content);
+
+    // With signed attributes the signature covers the attributes, which carry the
+    // content digest; without them it covers the content.
+    let signed_message = match &signer_info.signed_attrs {
+        Some(_) => {
+            let message_digest = signed_attribute(signer_info, rfc5911::ID_MESSAGE_DIGEST)
+                .and_then(|digest| digest.decode_as::<OctetString>().ok());
+            match message_digest {
+                Some(message_digest) if message_digest.as_bytes() == digest => {}
+                Some(_) => {
+                    return Verification {
+                        status: SignatureStatus::Altered,
+                        message: Some("Document digest does not match the signed bytes".into()),
+                        ..verification
+                    }
+                }
+                None => {
+                    return Verification {
+                        status: SignatureStatus::Malformed,
+                        message: Some("Signature has no message digest".into()),
+                        ..verification
+                    }
+                }
+            }
+
+            match raw_signed_attributes(contents) {
+                Ok(Some(attributes)) => algorithm.hash(&[&attributes]),
+                _ => {
+                    return Verification {
+                        status: SignatureStatus::Malformed,
+                        message: Some("Failed to read signed attributes".into()),
+                        ..verification
+                    }
+                }
+            }
+        }
+        None => digest,
+    };
+
+    let Some(certificate) = &verification.certificate else {
+        return Verification {
+            status: SignatureStatus::Unverified,
+            message: Some("Signer certificate is not included".into()),
+            ..verification
+        };
+    };
+
+    let (status, message) = match verify_signature(
+        certificate,
+        algorithm,
+        &signed_message,
+        signer_info.signature.as_bytes(),
+    ) {
+        Ok(true) => (SignatureStatus::Valid, None),
+        Ok(false) => (
+            SignatureStatus::Invalid,
+            Some("Signature does not match the signer certificate".to_string()),
+        ),
+        Err(e) => (SignatureStatus::Unverified, Some(e)),
+    };
+
+    Verification {
+        status,
+        message,
+        ..verification
+    }
+}
+
+/// Checks the signature over a precomputed digest with the certificate's public key.
+fn verify_signature(
+    certificate: &Certificate,
+    algorithm: DigestAlgorithm,
+    digest: &[u8],
+    signature: &[u8],
+) -> Result<bool, String> {
+    let public_key = &certificate.tbs_certificate.subject_public_key_info;
+    let public_key_der = public_key
+        .to_der()
+        .map_err(|e| format!("Failed to read signer key: {e}"))?;
+
+    match public_key.algorithm.oid {
+        rfc5912::RSA_ENCRYPTION => {
+            let key = RsaPublicKey::from_public_key_der(&public_key_der)
+                .map_err(|e| format!("Failed to read signer key: {e}"))?;
+            Ok(key.verify(algorithm.pkcs1v15(), digest, signature).is_ok())
+        }
+        rfc5912::ID_EC_PUBLIC_KEY => {
+            use p256::ecdsa::signature::hazmat::PrehashVerifier;
+
+            let key = p256::ecdsa::VerifyingKey::from_public_key_der(&public_key_der)
+                .map_err(|_| "Only P-256 elliptic curve keys are supported".to_string())?;
+            let signature = p256::ecdsa::Signature::from_der(signature)
+                .map_err(|e| format!("Failed to read signature value: {e}"))?;
+            Ok(key.verify_prehash(digest, &signature).is_ok())
+        }
+        oid => Err(format!("Unsupported signature algorithm {oid}")),
+    }
+}
+
+/// Certificate named by the signer identifier among the certificates in the
+/// signature.
+fn signer_certificate<'a>(
+    signed_data: &'a SignedData,
+    signer_info: &SignerInfo,
+) -> Option<&'a Certificate> {
+    let mut certificates = signed_data
+        .certificates
+        .iter()
+        .flat_map(|certificates| certificates.0.iter())
+        .filter_map(|certificate| match certificate {
+            CertificateChoices::Certificate(certificate) => Some(certificate),
+            _ => None,
+        });
+
+    certificates.find(|certificate| {
+        let tbs = &certificate.tbs_certificate;
+        match &signer_info.sid {
+            SignerIdentifier::IssuerAndSerialNumber(id) => {
+                tbs.issuer == id.issuer && tbs.serial_number == id.serial_number
+            }
+            SignerIdentifier::SubjectKeyIdentifier(id) => tbs
+                .extensions
+                .iter()
+                .flatten()
+                .filter(|extension| extension.extn_id == rfc5280::ID_CE_SUBJECT_KEY_IDENTIFIER)
+                .filter_map(|extension| {
+                    SubjectKeyIdentifier::from_der(extension.extn_value.as_bytes()).ok()
+                })
+                .any(|key_id| key_id == *id),
+        }
+    })
+}
+
+fn signed_attribute(signer_info: &SignerInfo, oid: ObjectIdentifier) -> Option<&der::Any> {
+    signer_info
+        .signed_attrs
+        .iter()
+        .flat_map(|attributes| attributes.iter())
+        .find(|attribute| attribute.oid == oid)
+        .and_then(|attribute| attribute.values.iter().next())
+}
+
+/// Signed attributes exactly as encoded by the signer, re-tagged as a SET as they
+/// are signed (RFC 5652, 5.4). Decoding and re-encoding them would sort the
+/// attributes, which breaks signatures over attributes in another order.
+fn raw_signed_attributes(contents: &[u8]) -> der::Result<Option<Vec<u8>>> {
+    let content_info = AnyRef::decode(&mut SliceReader::new(contents)?)?;
+    let mut reader = SliceReader::new(content_info.value())?;
+    ObjectIdentifier::decode(&mut reader)?;
+    let explicit = AnyRef::decode(&mut reader)?;
+    let signed_data = AnyRef::from_der(explicit.value())?;
+
+    // signerInfos is the last field of SignedData
+    let mut reader = SliceReader::new(signed_data.value())?;
+    let mut signer_infos = None;
+    while !reader.is_finished() {
+        signer_infos = Some(AnyRef::decode(&mut reader)?);
+    }
+    let Some(signer_infos) = signer_infos else {
+        return Ok(None);
+    };
+    let signer_info = AnyRef::decode(&mut SliceReader::new(signer_infos.value())?)?;
+
+    // version, sid and digestAlgorithm come before the signed attributes
+    let mut reader = SliceReader::new(signer_info.value())?;
+    for _ in 0..3 {
+        AnyRef::decode(&mut reader)?;
+    }
+    let attributes = AnyRef::decode(&mut reader)?;
+    let tag = Tag::ContextSpecific {
+        constructed: true,
+        number: TagNumber::N0,
+    };
+    if attributes.tag() != tag {
+        return Ok(None);
+    }
+
+    AnyRef::new(Tag::Set, attributes.value())?
+        .to_der()
+        .map(Some)
+}
+
+pub fn common_name(certificate: &Certificate) -> Option<String> {
+    certificate
+        .tbs_certificate
+        .subject
+        .0
+        .iter()
+        .flat_map(|name| name.0.iter())
+        .find(|attribute| attribute.oid == const_oid::db::rfc4519::CN)
+        .and_then(|attribute| {
+            let name = attribute.to_string();
+            name.strip_prefix("CN=").map(str::to_string)
+        })
+}
+
+fn pdf_date(time: der::DateTime) -> String {
+    format!(
+        "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+        time.year(),
+        time.month(),
+        time.day(),
+        time.hour(),
+        time.minutes(),
+        time.seconds()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use cms::builder::{create_signing_time_attribute, SignedDataBuilder, SignerInfoBuilder};
+    use cms::cert::IssuerAndSerialNumber;
+    use cms::signed_data::EncapsulatedContentInfo;
+    use lopdf::{dictionary, StringFormat};
+    use p256::ecdsa::{DerSignature, SigningKey};
+    use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+    use x509_cert::name::Name;
+    use x509_cert::serial_number::SerialNumber;
+    use x509_cert::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+    use x509_cert::time::Validity;
+
+    const PLACEHOLDER: i64 = 9_999_999_999;
+    const CONTENTS_SIZE: usize = 4096;
+
+    /// Single-page document with a signed `Approval` field and an empty `Witness`
+    /// field, signed with a self-signed P-256 certificate.
+    fn signed_document() -> Vec<u8> {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let page_id = document.new_object_id();
+
+        let value_id = document.add_object(dictionary! {
+            "Type" => "Sig",
+            "Filter" => "Adobe.PPKLite",
+            "SubFilter" => "adbe.pkcs7.detached",
+            "ByteRange" => vec![0.into(), PLACEHOLDER.into(), PLACEHOLDER.into(), PLACEHOLDER.into()],
+            "Contents" => Object::String(vec![0; CONTENTS_SIZE], StringFormat::Hexadecimal),
+            "Reason" => Object::string_literal("Approved"),
+            "M" => Object::string_literal("D:20240102030405Z"),
+        });
+        let signed_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "FT" => "Sig",
+            "T" => Object::string_literal("Approval"),
+            "V" => value_id,
+            "P" => page_id,
+            "Rect" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+        });
+        let empty_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "FT" => "Sig",
+            "T" => Object::string_literal("Witness"),
+            "P" => page_id,
+            "Rect" => vec![100.into(), 100.into(), 200.into(), 150.into()],
+        });
+
+        document.objects.insert(
+            page_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                "Annots" => vec![signed_id.into(), empty_id.into()],
+            }),
+        );
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "AcroForm" => dictionary! {
+                "Fields" => vec![signed_id.into(), empty_id.into()],
+                "SigFlags" => 3,
+            },
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes).unwrap();
+
+        // lopdf writes the placeholder numbers as they are, so the real byte range
+        // can be written over them without moving any offsets.
+        let placeholder = format!("[0 {PLACEHOLDER} {PLACEHOLDER} {PLACEHOLDER}]");
+        let range_at = find(&bytes, placeholder.as_bytes());
+
+        let contents_start = find(&bytes, b"<0000");
+        let contents_end = contents_start + CONTENTS_SIZE * 2 + 2;
+        let byte_range = format!(
+            "[0 {contents_start} {contents_end} {}]",
+            bytes.len() - contents_end
+        );
+        bytes[range_at..range_at + placeholder.len()]
+            .copy_from_slice(format!("{byte_range:<width$}", width = placeholder.len()).as_bytes());
+
+        let digest = sha2::Sha256::new()
+            .chain_update(&bytes[..contents_start])
+            .chain_update(&bytes[contents_end..])
+            .finalize();
+        let signature = hex(&sign(&digest));
+        bytes[contents_start + 1..contents_start + 1 + signature.len()]
+            .copy_from_slice(signature.as_bytes());
+
+        bytes
+    }
+
+    fn find(bytes: &[u8], needle: &[u8]) -> usize {
+        bytes
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .unwrap()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02X}")).collect()
+    }
+
+    fn sign(digest: &[u8]) -> Vec<u8> {
+        let key = SigningKey::from_bytes(&[7u8; 32].into()).unwrap();
+        let public_key = SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap();
+        let certificate = CertificateBuilder::new(
+            Profile::Root,
+            SerialNumber::from(42u32),
+            Validity::from_now(Duration::from_secs(3_600)).unwrap(),
+            Name::from_str("CN=Test Signer,O=Velin").unwrap(),
+            public_key,
+            &key,
+        )
+        .unwrap()
+        .build::<DerSignature>()
+        .unwrap();
+
+        let content = EncapsulatedContentInfo {
+            econtent_type: rfc5911::ID_DATA,
+            econtent: None,
+        };
+        let digest_algorithm = AlgorithmIdentifierOwned {
+            oid: rfc5912::ID_SHA_256,
+            parameters: None,
+        };
+        let sid = SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+            issuer: certificate.tbs_certificate.issuer.clone(),
+            serial_number: certificate.tbs_certificate.serial_number.clone(),
+        });
+
+        let mut signer =
+            SignerInfoBuilder::new(&key, sid, digest_algorithm.clone(), &content, Some(digest))
+                .unwrap();
+        signer
+            .add_signed_attribute(create_signing_time_attribute().unwrap())
+            .unwrap();
+
+        SignedDataBuilder::new(&content)
+            .add_digest_algorithm(digest_algorithm)
+            .unwrap()
+            .add_certificate(CertificateChoices::Certificate(certificate))
+            .unwrap()
+            .add_signer_info::<_, DerSignature>(signer)
+            .unwrap()
+            .build()
+            .unwrap()
+            .to_der()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let signatures = read_signatures(&signed_document()).unwrap();
+        assert_eq!(signatures.len(), 2);
+
+        let signed = &signatures[0];
+        assert_eq!(signed.field_name, "Approval");
+        assert_eq!(
+            signed.status,
+            SignatureStatus::Valid,
+            "{:?}",
+            signed.message
+        );
+        assert_eq!(signed.signer_name.as_deref(), Some("Test Signer"));
+        assert_eq!(signed.reason.as_deref(), Some("Approved"));
+        assert_eq!(signed.digest_algorithm.as_deref(), Some("SHA-256"));
+        assert!(signed.covers_revision);
+        assert!(!signed.modified_after_signing);
+        assert!(signed.signing_time.as_deref().unwrap().starts_with("D:20"));
+        assert_ne!(signed.signing_time.as_deref(), Some("D:20240102030405Z"));
+
+        let certificate = signed.certificate.as_ref().unwrap();
+        assert_eq!(certificate.subject, "CN=Test Signer,O=Velin");
+        assert_eq!(certificate.serial_number, "2A");
+
+        assert_eq!(signatures[1].field_name, "Witness");
+        assert_eq!(signatures[1].status, SignatureStatus::Unsigned);
+    }
+
+    #[test]
+    fn test_detect_altered_and_appended_data() {
+        let signed = signed_document();
+
+        let mut altered = signed.clone();
+        let at = find(&altered, b"/Count 1");
+        altered[at + 7] = b'2';
+        let signatures = read_signatures(&altered).unwrap();
+        assert_eq!(signatures[0].status, SignatureStatus::Altered);
+
+        let mut appended = signed;
+        appended.extend_from_slice(b"% annotations added later\n");
+        let signatures = read_signatures(&appended).unwrap();
+        assert_eq!(signatures[0].status, SignatureStatus::Valid);
+        assert!(signatures[0].modified_after_signing);
+    }
+}
@@ -41,7 +41,7 @@ pub struct SearchHit {
     pub rects: Vec<SearchHitRect>,
 }
 
-fn build_search_index(document: &PdfDocument) -> Result<SearchIndex, String> {
+pub fn build_search_index(document: &PdfDocument) -> Result<SearchIndex, String> {
     let mut pages = Vec::new();
 
     for (page_index, page) in document.pages().iter().enumerate() {
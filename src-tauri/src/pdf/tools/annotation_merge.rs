@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+
+use lopdf::{Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::annotation::create::append_to_page;
+use crate::pdf::reader::annotation::dictionary::{page_annotations, AnnotationDictionary};
+use crate::pdf::reader::annotation::query::date_digits;
+use crate::pdf::reader::document_to_bytes;
+use crate::utils::fs::write_atomic;
+use crate::utils::objects::copy_object;
+
+/// Stands in for a reply target that has not been merged, so the reply matches
+/// nothing in the merged document.
+const UNMERGED: ObjectId = (0, 0);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotationMergeInput {
+    pub base_path: String,
+
+    /// Annotated copies of the base document, merged in order
+    pub copy_paths: Vec<String>,
+    pub output_path: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AnnotationMergeReport {
+    /// Annotations copied from the annotated copies
+    pub added: usize,
+
+    /// Base annotations replaced by the version edited in a copy
+    pub updated: usize,
+
+    /// Annotations already in the base document or an earlier copy
+    pub duplicates: usize,
+
+    /// Annotations edited differently in more than one copy
+    pub conflicts: Vec<AnnotationConflict>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AnnotationConflict {
+    pub page_index: u16,
+    pub annotation_id: String,
+
+    /// Copies that edited the annotation, in merge order
+    pub sources: Vec<String>,
+
+    /// Copy whose version was kept, the most recently modified one
+    pub kept: String,
+}
+
+/// What identifies an annotation across copies when it has no common name.
+#[derive(Debug, Clone, PartialEq)]
+struct Fingerprint {
+    subtype: Vec<u8>,
+    rect: Vec<i64>,
+    contents: Option<String>,
+    author: Option<String>,
+    state: Option<Vec<u8>>,
+    color: Vec<i64>,
+    reply_to: Option<ObjectId>,
+}
+
+/// Edit of a base annotation made in one or more copies.
+struct Edit {
+    page_index: u16,
+    sources: Vec<String>,
+    kept: String,
+    modified: String,
+    fingerprint: Fingerprint,
+}
+
+pub fn merge_annotation_copies(
+    input: AnnotationMergeInput,
+) -> Result<AnnotationMergeReport, String> {
+    let mut document =
+        Document::load(&input.base_path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+
+    let copies = input
+        .copy_paths
+        .iter()
+        .map(|path| {
+            Document::load(path)
+                .map(|copy| (path.clone(), copy))
+                .map_err(|e| format!("Failed to load {path}: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let report = merge_copies(&mut document, &copies)?;
+
+    write_atomic(
+        input.output_path.as_ref(),
+        &document_to_bytes(&mut document)?,
+    )?;
+
+    Ok(report)
+}
+
+/// Merges the annotations of annotated copies into `document`, their common base.
+///
+/// Annotations are matched by name (`/NM`) on the same page, or else by subtype,
+/// position, contents, author and reply target. A named annotation edited in a
+/// copy replaces the base version; when copies edit it differently, the most
+/// recently modified version wins and the conflict is reported. Annotations
+/// deleted in a copy are kept, and form fields are not merged.
+pub fn merge_copies(
+    document: &mut Document,
+    copies: &[(String, Document)],
+) -> Result<AnnotationMergeReport, String> {
+    let base = document.clone();
+    let pages = document.get_pages();
+
+    let mut report = AnnotationMergeReport::default();
+    let mut edits = HashMap::new();
+
+    for (source, copy) in copies {
+        let copy_pages = copy.get_pages();
+        if copy_pages.len() != pages.len() {
+            return Err(format!(
+                "{source} does not have the same pages as the base document"
+            ));
+        }
+
+        // Page references (`/P`, link destinations) resolve to the merged pages.
+        let mut ids = copy_pages
+            .iter()
+            .map(|(number, copy_page)| (*copy_page, pages[number]))
+            .collect::<HashMap<_, _>>();
+
+        for (number, copy_page) in &copy_pages {
+            let page_index = (*number - 1) as u16;
+            let page_id = pages[number];
+
+            let mut annotations = page_annotations(copy, *copy_page)
+                .into_iter()
+                .flatten()
+                .filter(|annotation| {
+                    annotation.object_id.is_some()
+                        && !matches!(annotation.subtype(), Some(b"Popup" | b"Widget"))
+                })
+                .collect::<Vec<_>>();
+            // Replies are matched after the annotations they reply to.
+            annotations.sort_by_key(|annotation| annotation.in_reply_to().is_some());
+
+            for annotation in annotations {
+                let copy_id = annotation.object_id.unwrap_or(UNMERGED);
+                let fingerprint = fingerprint(&annotation, Some(&ids));
+
+                let named = annotation.name().and_then(|name| {
+                    let target = page_annotations(document, page_id)
+                        .into_iter()
+                        .flatten()
+                        .find(|existing| existing.name().as_deref() == Some(name.as_str()))?
+                        .object_id?;
+                    Some((name, target))
+                });
+
+                if let Some((name, target_id)) = named {
+                    map_annotation(document, copy, copy_id, target_id, &mut ids);
+
+                    // Annotations added by an earlier copy are compared with their
+                    // merged version.
+                    let reference = if base.objects.contains_key(&target_id) {
+                        &base
+                    } else {
+                        &*document
+                    };
+                    let original = reference.get_dictionary(target_id).ok().map(|dict| {
+                        let original = AnnotationDictionary {
+                            document: reference,
+                            dict,
+                            object_id: Some(target_id),
+                        };
+                        self::fingerprint(&original, None)
+                    });
+                    if original.as_ref() == Some(&fingerprint) {
+                        report.duplicates += 1;
+                        continue;
+                    }
+
+                    let modified = modified_date(&annotation);
+                    match edits.get_mut(&(page_index, name.clone())) {
+                        None => {
+                            replace_annotation(document, copy, copy_id, target_id, &mut ids);
+                            report.updated += 1;
+                            edits.insert(
+                                (page_index, name),
+                                Edit {
+                                    page_index,
+                                    sources: vec![source.clone()],
+                                    kept: source.clone(),
+                                    modified,
+                                    fingerprint,
+                                },
+                            );
+                        }
+                        Some(edit) if edit.fingerprint == fingerprint => {
+                            report.duplicates += 1;
+                        }
+                        Some(edit) => {
+                            edit.sources.push(source.clone());
+                            if modified > edit.modified {
+                                replace_annotation(document, copy, copy_id, target_id, &mut ids);
+                                edit.kept = source.clone();
+                                edit.modified = modified;
+                                edit.fingerprint = fingerprint;
+                            }
+                        }
+                    }
+                    continue;
+                }
+
+                let existing = page_annotations(document, page_id)
+                    .into_iter()
+                    .flatten()
+                    .find(|existing| self::fingerprint(existing, None) == fingerprint)
+                    .and_then(|existing| existing.object_id);
+
+                match existing {
+                    Some(target_id) => {
+                        map_annotation(document, copy, copy_id, target_id, &mut ids);
+                        report.duplicates += 1;
+                    }
+                    None => {
+                        add_annotation(document, copy, copy_id, page_id, &mut ids)?;
+                        report.added += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut conflicts = edits
+        .into_iter()
+        .filter(|(_, edit)| edit.sources.len() > 1)
+        .map(|((_, annotation_id), edit)| AnnotationConflict {
+            page_index: edit.page_index,
+            annotation_id,
+            sources: edit.sources,
+            kept: edit.kept,
+        })
+        .collect::<Vec<_>>();
+    conflicts
+        .sort_by(|a, b| (a.page_index, &a.annotation_id).cmp(&(b.page_index, &b.annotation_id)));
+    report.conflicts = conflicts;
+
+    Ok(report)
+}
+
+fn fingerprint(
+    annotation: &AnnotationDictionary,
+    ids: Option<&HashMap<ObjectId, ObjectId>>,
+) -> Fingerprint {
+    let document = annotation.document;
+    let text = |key: &[u8]| {
+        annotation
+            .dict
+            .get_deref(key, document)
+            .ok()
+            .and_then(|value| lopdf::decode_text_string(value).ok())
+            .filter(|value| !value.is_empty())
+    };
+    let numbers = |key: &[u8], scale: f32| {
+        annotation
+            .dict
+            .get_deref(key, document)
+            .and_then(|values| values.as_array())
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(|value| value.as_float().ok())
+                    .map(|value| (value * scale).round() as i64)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let reply_to = annotation.in_reply_to().map(|reply_to| match ids {
+        Some(ids) => ids.get(&reply_to).copied().unwrap_or(UNMERGED),
+        None => reply_to,
+    });
+
+    Fingerprint {
+        subtype: annotation.subtype().unwrap_or_default().to_vec(),
+        rect: numbers(b"Rect", 1.0),
+        contents: text(b"Contents"),
+        author: text(b"T"),
+        state: annotation
+            .dict
+            .get(b"State")
+            .and_then(|state| state.as_name())
+            .ok()
+            .map(<[u8]>::to_vec),
+        color: numbers(b"C", 1000.0),
+        reply_to,
+    }
+}
+
+fn modified_date(annotation: &AnnotationDictionary) -> String {
+    annotation
+        .dict
+        .get(b"M")
+        .ok()
+        .and_then(|date| lopdf::decode_text_string(date).ok())
+        .map(|date| date_digits(&date))
+        .unwrap_or_default()
+}
+
+/// Records that `copy_id` is `target_id` in the merged document, along with their
+/// popups, so replies and references resolve to the merged annotation.
+fn map_annotation(
+    document: &Document,
+    copy: &Document,
+    copy_id: ObjectId,
+    target_id: ObjectId,
+    ids: &mut HashMap<ObjectId, ObjectId>,
+) {
+    ids.insert(copy_id, target_id);
+
+    let popup = |document: &Document, annotation_id| {
+        document
+            .get_dictionary(annotation_id)
+            .and_then(|annotation| annotation.get(b"Popup"))
+            .and_then(|popup| popup.as_reference())
+            .ok()
+    };
+    if let (Some(copy_popup), Some(target_popup)) =
+        (popup(copy, copy_id), popup(document, target_id))
+    {
+        ids.insert(copy_popup, target_popup);
+    }
+}
+
+/// Replaces the merged annotation with the copy's version, keeping its object id.
+fn replace_annotation(
+    document: &mut Document,
+    copy: &Document,
+    copy_id: ObjectId,
+    target_id: ObjectId,
+    ids: &mut HashMap<ObjectId, ObjectId>,
+) {
+    let Ok(annotation) = copy.get_object(copy_id) else {
+        return;
+    };
+    let annotation = copy_object(document, copy, annotation, ids);
+    document.objects.insert(target_id, annotation);
+}
+
+/// Copies an annotation and its popup into the merged document.
+fn add_annotation(
+    document: &mut Document,
+    copy: &Document,
+    copy_id: ObjectId,
+    page_id: ObjectId,
+    ids: &mut HashMap<ObjectId, ObjectId>,
+) -> Result<(), String> {
+    // A reply merged earlier may already have copied the annotation it replies to.
+    let existing = ids.get(&copy_id).copied();
+    let target_id = match copy_object(document, copy, &Object::Reference(copy_id), ids) {
+        Object::Reference(target_id) => target_id,
+        _ => return Ok(()),
+    };
+
+    let mut added = vec![target_id];
+    if let Ok(Object::Reference(popup_id)) = document
+        .get_dictionary(target_id)
+        .and_then(|annotation| annotation.get(b"Popup"))
+    {
+        added.push(*popup_id);
+    }
+
+    let on_page = page_annotations(document, page_id)
+        .into_iter()
+        .flatten()
+        .filter_map(|annotation| annotation.object_id)
+        .collect::<Vec<_>>();
+    for annotation_id in added {
+        if existing.is_none() || !on_page.contains(&annotation_id) {
+            append_to_page(document, page_id, annotation_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Dictionary};
+
+    fn base() -> Document {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let note_id = document.add_object(dictionary! {
+            "Subtype" => "Text",
+            "NM" => Object::string_literal("note"),
+            "Rect" => vec![10.into(), 10.into(), 30.into(), 30.into()],
+            "Contents" => Object::string_literal("Check this"),
+            "M" => Object::string_literal("D:20240101000000Z"),
+        });
+        let square_id = document.add_object(dictionary! {
+            "Subtype" => "Square",
+            "Rect" => vec![100.into(), 100.into(), 200.into(), 150.into()],
+        });
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 600.into(), 800.into()],
+            "Annots" => vec![note_id.into(), square_id.into()],
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    fn page_id(document: &Document) -> ObjectId {
+        document.get_pages()[&1]
+    }
+
+    fn edit_note(document: &mut Document, contents: &str, modified: &str) {
+        let note_id = page_annotations(document, page_id(document))[0]
+            .unwrap()
+            .object_id
+            .unwrap();
+        let note = document.get_dictionary_mut(note_id).unwrap();
+        note.set("Contents", Object::string_literal(contents));
+        note.set("M", Object::string_literal(modified));
+    }
+
+    fn add(document: &mut Document, annotation: Dictionary) -> ObjectId {
+        let page_id = page_id(document);
+        let annotation_id = document.add_object(annotation);
+        append_to_page(document, page_id, annotation_id).unwrap();
+        annotation_id
+    }
+
+    fn contents(document: &Document) -> Vec<Option<String>> {
+        page_annotations(document, page_id(document))
+            .into_iter()
+            .flatten()
+            .map(|annotation| {
+                annotation
+                    .dict
+                    .get(b"Contents")
+                    .ok()
+                    .and_then(|value| lopdf::decode_text_string(value).ok())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_adds_new_annotations_without_duplicates() {
+        let mut first = base();
+        add(
+            &mut first,
+            dictionary! {
+                "Subtype" => "Text",
+                "NM" => Object::string_literal("from-first"),
+                "Rect" => vec![50.into(), 50.into(), 70.into(), 70.into()],
+                "Contents" => Object::string_literal("First reviewer"),
+            },
+        );
+
+        // The second reviewer worked on a copy of the first reviewer's file.
+        let mut second = first.clone();
+        let second_page = page_id(&second);
+        let note_id = page_annotations(&second, page_id(&second))[0]
+            .unwrap()
+            .object_id
+            .unwrap();
+        add(
+            &mut second,
+            dictionary! {
+                "Subtype" => "Text",
+                "Rect" => vec![10.into(), 10.into(), 30.into(), 30.into()],
+                "Contents" => Object::string_literal("Agreed"),
+                "IRT" => note_id,
+                "P" => second_page,
+            },
+        );
+
+        let mut document = base();
+        let report = merge_copies(
+            &mut document,
+            &[("first.pdf".into(), first), ("second.pdf".into(), second)],
+        )
+        .unwrap();
+
+        assert_eq!(report.added, 2);
+        assert_eq!(report.duplicates, 5);
+        assert!(report.conflicts.is_empty());
+
+        let annotations = page_annotations(&document, page_id(&document));
+        assert_eq!(annotations.len(), 4);
+        let reply = annotations[3].unwrap();
+        assert_eq!(reply.in_reply_to(), annotations[0].unwrap().object_id);
+        assert_eq!(
+            reply.dict.get(b"P").unwrap().as_reference().unwrap(),
+            page_id(&document)
+        );
+    }
+
+    #[test]
+    fn test_merge_reports_conflicting_edits() {
+        let mut first = base();
+        edit_note(&mut first, "Fix the figure", "D:20240301000000Z");
+        let mut second = base();
+        edit_note(&mut second, "Remove the figure", "D:20240201000000Z");
+        let mut third = base();
+        edit_note(&mut third, "Fix the figure", "D:20240301000000Z");
+
+        let mut document = base();
+        let report = merge_copies(
+            &mut document,
+            &[
+                ("first.pdf".into(), first),
+                ("second.pdf".into(), second),
+                ("third.pdf".into(), third),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(report.updated, 1);
+        assert_eq!(
+            report.conflicts,
+            vec![AnnotationConflict {
+                page_index: 0,
+                annotation_id: "note".into(),
+                sources: vec!["first.pdf".into(), "second.pdf".into()],
+                kept: "first.pdf".into(),
+            }]
+        );
+        assert_eq!(
+            contents(&document),
+            vec![Some("Fix the figure".into()), None]
+        );
+    }
+}
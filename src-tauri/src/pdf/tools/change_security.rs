@@ -0,0 +1,135 @@
+use lopdf::Document;
+use serde::Deserialize;
+
+use crate::pdf::reader::{check_owner_password, document_security};
+use crate::pdf::tools::{encrypt_document, unlock_document, ProtectInput};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSecurityInput {
+    /// Owner password the document is protected with now
+    pub current_password: String,
+
+    /// New passwords, permissions and algorithm
+    #[serde(flatten)]
+    pub protection: ProtectInput,
+}
+
+/// Re-encrypts a protected PDF with new passwords, permissions or algorithm
+/// without writing it out unencrypted in between.
+pub fn change_security_pdf(input: ChangeSecurityInput) -> Result<(), String> {
+    let bytes = std::fs::read(&input.protection.input_path)
+        .map_err(|e| format!("Failed to read PDF: {e}"))?;
+
+    let mut doc = change_security(&bytes, &input)?;
+
+    doc.save(&input.protection.output_path)
+        .map_err(|e| format!("Failed to save PDF: {e}"))?;
+
+    Ok(())
+}
+
+fn change_security(bytes: &[u8], input: &ChangeSecurityInput) -> Result<Document, String> {
+    let doc = Document::load_mem(bytes).map_err(|e| format!("Failed to load PDF: {e}"))?;
+
+    if !document_security(&doc).encrypted {
+        return Err("Input PDF is not encrypted. Protect it instead.".into());
+    }
+
+    // The user password opens the document but must not lift its permissions
+    check_owner_password(doc, &input.current_password)?;
+
+    let (mut doc, _) = unlock_document(bytes, Some(&input.current_password))?;
+
+    let protection = &input.protection;
+    encrypt_document(
+        &mut doc,
+        protection.algorithm,
+        &protection.owner_password,
+        protection.user_password.as_deref().unwrap_or_default(),
+        protection.permissions.to_permissions(),
+    )?;
+
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::{DocumentPermissions, EncryptionAlgorithm};
+    use lopdf::{dictionary, LoadOptions, Object, Permissions};
+
+    fn encrypted() -> Vec<u8> {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => Vec::<Object>::new(),
+                "Count" => 0,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+
+        encrypt_document(
+            &mut doc,
+            EncryptionAlgorithm::Rc4,
+            "old-owner",
+            "old-user",
+            Permissions::empty(),
+        )
+        .unwrap();
+
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn input(current_password: &str) -> ChangeSecurityInput {
+        ChangeSecurityInput {
+            current_password: current_password.into(),
+            protection: ProtectInput {
+                input_path: String::new(),
+                output_path: String::new(),
+                owner_password: "new-owner".into(),
+                user_password: None,
+                algorithm: EncryptionAlgorithm::Aes256,
+                permissions: DocumentPermissions {
+                    allow_copying: true,
+                    ..Default::default()
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_change_security() {
+        let bytes = encrypted();
+
+        // The open password is not enough to change the security
+        assert!(change_security(&bytes, &input("old-user")).is_err());
+        assert!(change_security(&bytes, &input("wrong")).is_err());
+
+        let mut doc = change_security(&bytes, &input("old-owner")).unwrap();
+        let mut changed = Vec::new();
+        doc.save_to(&mut changed).unwrap();
+
+        // No open password any more, so lopdf decrypts it on load
+        let doc = Document::load_mem(&changed).unwrap();
+        let security = document_security(&doc);
+        assert_eq!(security.algorithm, Some(EncryptionAlgorithm::Aes256));
+        assert!(!security.requires_password);
+        assert!(security.permissions.allow_copying);
+        assert!(!security.permissions.allow_printing);
+        assert!(check_owner_password(doc, "new-owner").is_ok());
+
+        let reopened =
+            Document::load_mem_with_options(&changed, LoadOptions::with_password("old-owner"));
+        assert!(reopened.map_or(true, |doc| check_owner_password(doc, "old-owner").is_err()));
+    }
+}
@@ -0,0 +1,575 @@
+use std::collections::BTreeSet;
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::editor::inherited;
+use crate::pdf::reader::annotation::appearance::num;
+use crate::pdf::reader::annotation::dictionary::page_annotations;
+use crate::pdf::reader::{document_to_bytes, AnnotationType};
+use crate::utils::fs::write_atomic;
+use crate::utils::page_selection::PageSelectionParser;
+
+/// Annotation flags (`/F`) that keep an annotation from being displayed: Hidden
+/// and NoView.
+const HIDDEN_FLAGS: i64 = 1 << 1 | 1 << 5;
+
+const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlattenInput {
+    pub input_path: String,
+    pub output_path: String,
+    pub pages: Option<String>,
+
+    /// Annotation types to flatten. Every type except links is flattened when
+    /// empty; form fields are flattened by the form tool.
+    pub annotation_types: Option<Vec<AnnotationType>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FlattenSummary {
+    /// Annotations drawn into the page content
+    pub flattened: usize,
+
+    /// Hidden annotations, popups and replies removed without being drawn
+    pub removed: usize,
+
+    /// Visible annotations without an appearance stream, left on the page
+    pub skipped: usize,
+}
+
+impl FlattenSummary {
+    fn add(&mut self, other: FlattenSummary) {
+        self.flattened += other.flattened;
+        self.removed += other.removed;
+        self.skipped += other.skipped;
+    }
+}
+
+/// Normal appearance of an annotation and the matrix placing it on the page.
+struct Placement {
+    stream_id: ObjectId,
+    matrix: [f32; 6],
+}
+
+pub fn flatten_pdf(input: FlattenInput) -> Result<FlattenSummary, String> {
+    let mut document =
+        Document::load(&input.input_path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+
+    let pages = document.get_pages();
+    let page_numbers = match &input.pages {
+        Some(expr) if !expr.trim().is_empty() => PageSelectionParser::parse(expr)
+            .and_then(|selection| selection.resolve(pages.len() as u32))
+            .map_err(|e| e.to_string())?,
+        _ => pages.keys().copied().collect(),
+    };
+
+    let types = input.annotation_types.unwrap_or_default();
+    let selected = |subtype: &AnnotationType| match subtype {
+        AnnotationType::Widget => false,
+        _ if types.is_empty() => *subtype != AnnotationType::Link,
+        _ => types.contains(subtype),
+    };
+
+    let mut summary = FlattenSummary::default();
+    for page_number in page_numbers {
+        let page_id = *pages
+            .get(&page_number)
+            .ok_or_else(|| format!("Page {page_number} not found"))?;
+        summary.add(flatten_page_annotations(&mut document, page_id, selected)?);
+    }
+
+    write_atomic(
+        input.output_path.as_ref(),
+        &document_to_bytes(&mut document)?,
+    )?;
+
+    Ok(summary)
+}
+
+/// Draws the normal appearance of the page's selected annotations into its content
+/// and removes the annotations.
+///
+/// Popups, replies and review states go with the annotation they belong to.
+/// Hidden annotations are removed without being drawn; visible annotations without
+/// an appearance stream are kept, since flattening them would lose them.
+pub fn flatten_page_annotations(
+    document: &mut Document,
+    page_id: ObjectId,
+    selected: impl Fn(&AnnotationType) -> bool,
+) -> Result<FlattenSummary, String> {
+    let mut summary = FlattenSummary::default();
+    let mut placements = Vec::new();
+    let mut removed = BTreeSet::new();
+    let mut links = Vec::new();
+
+    for (index, annotation) in page_annotations(document, page_id).iter().enumerate() {
+        let Some(annotation) = annotation else {
+            continue;
+        };
+
+        let linked = annotation.in_reply_to().or_else(|| annotation.parent());
+        links.push((index, annotation.object_id, linked));
+
+        let subtype = AnnotationType::from_name(annotation.subtype().unwrap_or_default());
+        if subtype == AnnotationType::Popup
+            || annotation.in_reply_to().is_some()
+            || !selected(&subtype)
+        {
+            continue;
+        }
+
+        let flags = annotation
+            .dict
+            .get(b"F")
+            .and_then(|flags| flags.as_i64())
+            .unwrap_or(0);
+
+        if flags & HIDDEN_FLAGS != 0 {
+            summary.removed += 1;
+        } else if let Some(placement) = placement(document, annotation.dict) {
+            placements.push(placement);
+            summary.flattened += 1;
+        } else {
+            summary.skipped += 1;
+            continue;
+        }
+        removed.insert(index);
+    }
+
+    // Popups, replies and states point at the annotation they belong to, possibly
+    // through other replies.
+    let mut removed_ids = links
+        .iter()
+        .filter(|(index, _, _)| removed.contains(index))
+        .filter_map(|(_, object_id, _)| *object_id)
+        .collect::<Vec<_>>();
+    loop {
+        let dependents = links
+            .iter()
+            .filter(|(index, object_id, linked)| {
+                !removed.contains(index)
+                    && object_id.is_some()
+                    && linked.is_some_and(|linked| removed_ids.contains(&linked))
+            })
+            .map(|(index, object_id, _)| (*index, object_id.unwrap_or_default()))
+            .collect::<Vec<_>>();
+        if dependents.is_empty() {
+            break;
+        }
+        for (index, object_id) in dependents {
+            removed.insert(index);
+            removed_ids.push(object_id);
+            summary.removed += 1;
+        }
+    }
+
+    if removed.is_empty() {
+        return Ok(summary);
+    }
+
+    let mut content = String::new();
+    for placement in placements {
+        if let Ok(Object::Stream(stream)) = document.get_object_mut(placement.stream_id) {
+            stream.dict.set("Type", "XObject");
+            stream.dict.set("Subtype", "Form");
+        }
+
+        let name = add_page_resource(
+            document,
+            page_id,
+            b"XObject",
+            "FlAnnot",
+            Object::Reference(placement.stream_id),
+        )?;
+        let matrix = placement.matrix.map(num).join(" ");
+        content.push_str(&format!("q {matrix} cm /{name} Do Q\n"));
+    }
+
+    if !content.is_empty() {
+        append_content(document, page_id, content.into_bytes())?;
+    }
+
+    remove_page_annotations(document, page_id, &removed)?;
+    for object_id in removed_ids {
+        document.objects.remove(&object_id);
+    }
+
+    Ok(summary)
+}
+
+/// Resolves the annotation's normal appearance (`/AP /N`), choosing the state
+/// named by `/AS` when there are several.
+fn placement(document: &Document, annotation: &Dictionary) -> Option<Placement> {
+    let normal = annotation
+        .get_deref(b"AP", document)
+        .and_then(|appearance| appearance.as_dict())
+        .and_then(|appearance| appearance.get(b"N"))
+        .ok()?;
+
+    let stream_id = match document.dereference(normal).ok()? {
+        (Some(stream_id), Object::Stream(_)) => stream_id,
+        (_, Object::Dictionary(states)) => {
+            let state = annotation
+                .get(b"AS")
+                .and_then(|state| state.as_name())
+                .ok()?;
+            states
+                .get(state)
+                .and_then(|stream| stream.as_reference())
+                .ok()?
+        }
+        _ => return None,
+    };
+
+    let stream = document.get_object(stream_id).ok()?.as_stream().ok()?;
+    let bbox = numbers::<4>(document, stream.dict.get(b"BBox").ok()?)?;
+    let form_matrix = match stream.dict.get(b"Matrix") {
+        Ok(matrix) => numbers::<6>(document, matrix)?,
+        Err(_) => IDENTITY,
+    };
+    let rect = numbers::<4>(document, annotation.get(b"Rect").ok()?)?;
+
+    Some(Placement {
+        stream_id,
+        matrix: fit_to_rect(bbox, form_matrix, rect),
+    })
+}
+
+/// Matrix that maps the form's bounding box, transformed by its own matrix, onto
+/// the annotation rectangle (ISO 32000-1, 12.5.5).
+fn fit_to_rect(bbox: [f32; 4], form_matrix: [f32; 6], rect: [f32; 4]) -> [f32; 6] {
+    let [a, b, c, d, e, f] = form_matrix;
+    let corners = [
+        (bbox[0], bbox[1]),
+        (bbox[2], bbox[1]),
+        (bbox[0], bbox[3]),
+        (bbox[2], bbox[3]),
+    ]
+    .map(|(x, y)| (a * x + c * y + e, b * x + d * y + f));
+
+    let min_x = corners.iter().map(|p| p.0).fold(f32::INFINITY, f32::min);
+    let max_x = corners
+        .iter()
+        .map(|p| p.0)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let min_y = corners.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+    let max_y = corners
+        .iter()
+        .map(|p| p.1)
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    let left = rect[0].min(rect[2]);
+    let bottom = rect[1].min(rect[3]);
+    let scale = |target: f32, source: f32| {
+        if source.abs() < f32::EPSILON {
+            1.0
+        } else {
+            target / source
+        }
+    };
+    let scale_x = scale((rect[2] - rect[0]).abs(), max_x - min_x);
+    let scale_y = scale((rect[3] - rect[1]).abs(), max_y - min_y);
+
+    [
+        scale_x,
+        0.0,
+        0.0,
+        scale_y,
+        left - min_x * scale_x,
+        bottom - min_y * scale_y,
+    ]
+}
+
+fn numbers<const N: usize>(document: &Document, object: &Object) -> Option<[f32; N]> {
+    let values = document.dereference(object).ok()?.1.as_array().ok()?;
+    let values = values
+        .iter()
+        .map(|value| value.as_float().ok())
+        .collect::<Option<Vec<_>>>()?;
+    values.try_into().ok()
+}
+
+/// Adds an entry to a resource category of the page and returns its name.
+///
+/// The page gets its own copy of its (possibly inherited or shared) resources, so
+/// the new entry does not leak into other pages.
+pub fn add_page_resource(
+    document: &mut Document,
+    page_id: ObjectId,
+    category: &[u8],
+    prefix: &str,
+    value: Object,
+) -> Result<String, String> {
+    let direct = |object: Option<&Object>| {
+        object
+            .and_then(|object| document.dereference(object).ok())
+            .and_then(|(_, object)| object.as_dict().ok())
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    let mut resources = direct(inherited(document, page_id, b"Resources"));
+    let mut entries = direct(resources.get(category).ok());
+
+    let name = (1..)
+        .map(|n| format!("{prefix}{n}"))
+        .find(|name| !entries.has(name.as_bytes()))
+        .unwrap_or_default();
+    entries.set(name.clone(), value);
+    resources.set(category, entries);
+
+    document
+        .get_dictionary_mut(page_id)
+        .map_err(|e| e.to_string())?
+        .set("Resources", resources);
+
+    Ok(name)
+}
+
+/// Appends a content stream to the page, drawn on top of the existing content.
+///
+/// The existing content is wrapped in `q`/`Q` so graphics state it leaves behind
+/// does not affect the new content.
+pub fn append_content(
+    document: &mut Document,
+    page_id: ObjectId,
+    content: Vec<u8>,
+) -> Result<(), String> {
+    let existing = document
+        .get_dictionary(page_id)
+        .map_err(|e| e.to_string())?
+        .get(b"Contents")
+        .ok()
+        .and_then(|contents| document.dereference(contents).ok());
+
+    let mut contents = match existing {
+        Some((_, Object::Array(streams))) => streams.clone(),
+        Some((Some(stream_id), Object::Stream(_))) => vec![Object::Reference(stream_id)],
+        _ => Vec::new(),
+    };
+
+    let content = if contents.is_empty() {
+        content
+    } else {
+        let save_id = document.add_object(Stream::new(dictionary! {}, b"q\n".to_vec()));
+        contents.insert(0, Object::Reference(save_id));
+        [b"Q\n".as_slice(), &content].concat()
+    };
+    let content_id = document.add_object(Stream::new(dictionary! {}, content));
+    contents.push(Object::Reference(content_id));
+
+    document
+        .get_dictionary_mut(page_id)
+        .map_err(|e| e.to_string())?
+        .set("Contents", contents);
+
+    Ok(())
+}
+
+/// Removes the entries at `indices` from the page's `/Annots` array.
+fn remove_page_annotations(
+    document: &mut Document,
+    page_id: ObjectId,
+    indices: &BTreeSet<usize>,
+) -> Result<(), String> {
+    let annots_ref = document
+        .get_dictionary(page_id)
+        .map_err(|e| e.to_string())?
+        .get(b"Annots")
+        .and_then(|annots| annots.as_reference())
+        .ok();
+
+    let annots = match annots_ref {
+        Some(annots_id) => document.get_object_mut(annots_id).ok(),
+        None => document
+            .get_dictionary_mut(page_id)
+            .map_err(|e| e.to_string())?
+            .get_mut(b"Annots")
+            .ok(),
+    };
+
+    let mut empty = false;
+    if let Some(Object::Array(annots)) = annots {
+        let mut index = 0;
+        annots.retain(|_| {
+            let keep = !indices.contains(&index);
+            index += 1;
+            keep
+        });
+        empty = annots.is_empty();
+    }
+
+    if empty {
+        document
+            .get_dictionary_mut(page_id)
+            .map_err(|e| e.to_string())?
+            .remove(b"Annots");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn appearance(document: &mut Document) -> ObjectId {
+        document.add_object(Stream::new(
+            dictionary! {
+                "BBox" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+            },
+            b"0 0 1 rg 0 0 10 10 re f".to_vec(),
+        ))
+    }
+
+    fn page_with(document: &mut Document, annotations: Vec<Dictionary>) -> ObjectId {
+        let shared_resources = document.add_object(dictionary! { "Font" => dictionary! {} });
+        let content = document.add_object(Stream::new(dictionary! {}, b"0 g".to_vec()));
+        let annots = annotations
+            .into_iter()
+            .map(|annotation| Object::Reference(document.add_object(annotation)))
+            .collect::<Vec<_>>();
+        document.add_object(dictionary! {
+            "Type" => "Page",
+            "Resources" => shared_resources,
+            "Contents" => content,
+            "Annots" => annots,
+        })
+    }
+
+    #[test]
+    fn test_fit_to_rect() {
+        let matrix = fit_to_rect(
+            [0.0, 0.0, 10.0, 20.0],
+            IDENTITY,
+            [100.0, 200.0, 120.0, 220.0],
+        );
+        assert_eq!(matrix, [2.0, 0.0, 0.0, 1.0, 100.0, 200.0]);
+
+        // A rotated form: the transformed box is 20 wide and 10 high.
+        let rotated = fit_to_rect(
+            [0.0, 0.0, 10.0, 20.0],
+            [0.0, 1.0, -1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 20.0, 10.0],
+        );
+        assert_eq!(rotated, [1.0, 0.0, 0.0, 1.0, 20.0, 0.0]);
+    }
+
+    #[test]
+    fn test_flatten_draws_appearance_and_removes_annotation() {
+        let mut document = Document::with_version("1.7");
+        let stream_id = appearance(&mut document);
+        let page_id = page_with(
+            &mut document,
+            vec![dictionary! {
+                "Subtype" => "Square",
+                "Rect" => vec![50.into(), 60.into(), 70.into(), 80.into()],
+                "AP" => dictionary! { "N" => stream_id },
+            }],
+        );
+
+        let summary = flatten_page_annotations(&mut document, page_id, |_| true).unwrap();
+        assert_eq!(summary.flattened, 1);
+
+        let page = document.get_dictionary(page_id).unwrap();
+        assert!(!page.has(b"Annots"));
+
+        let resources = page.get(b"Resources").unwrap().as_dict().unwrap();
+        let xobjects = resources.get(b"XObject").unwrap().as_dict().unwrap();
+        assert_eq!(
+            xobjects.get(b"FlAnnot1").unwrap().as_reference().unwrap(),
+            stream_id
+        );
+        assert!(resources.has(b"Font"));
+
+        let contents = page.get(b"Contents").unwrap().as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        let last = document
+            .get_object(contents[2].as_reference().unwrap())
+            .unwrap()
+            .as_stream()
+            .unwrap();
+        assert_eq!(last.content, b"Q\nq 2 0 0 2 50 60 cm /FlAnnot1 Do Q\n");
+
+        let form = document.get_object(stream_id).unwrap().as_stream().unwrap();
+        assert_eq!(
+            form.dict.get(b"Subtype").unwrap().as_name().unwrap(),
+            b"Form"
+        );
+    }
+
+    #[test]
+    fn test_flatten_respects_selection_flags_and_appearances() {
+        let mut document = Document::with_version("1.7");
+        let stream_id = appearance(&mut document);
+        let rect = vec![0.into(), 0.into(), 10.into(), 10.into()];
+        let page_id = page_with(
+            &mut document,
+            vec![
+                dictionary! {
+                    "Subtype" => "Highlight",
+                    "Rect" => rect.clone(),
+                    "AP" => dictionary! { "N" => stream_id },
+                },
+                dictionary! {
+                    "Subtype" => "Ink",
+                    "Rect" => rect.clone(),
+                    "AP" => dictionary! { "N" => stream_id },
+                },
+                dictionary! { "Subtype" => "Text", "Rect" => rect.clone(), "F" => 2 },
+                dictionary! { "Subtype" => "Text", "Rect" => rect },
+            ],
+        );
+
+        let summary = flatten_page_annotations(&mut document, page_id, |subtype| {
+            *subtype != AnnotationType::Ink
+        })
+        .unwrap();
+        assert_eq!(
+            summary,
+            FlattenSummary {
+                flattened: 1,
+                removed: 1,
+                skipped: 1,
+            }
+        );
+
+        let remaining = page_annotations(&document, page_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|annotation| annotation.subtype().map(<[u8]>::to_vec))
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, vec![b"Ink".to_vec(), b"Text".to_vec()]);
+    }
+
+    #[test]
+    fn test_flatten_removes_popup_and_replies() {
+        let mut document = Document::with_version("1.7");
+        let stream_id = appearance(&mut document);
+        let page_id = page_with(&mut document, Vec::new());
+
+        let note_id = document.add_object(dictionary! {
+            "Subtype" => "Text",
+            "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+            "AP" => dictionary! { "N" => dictionary! { "On" => stream_id } },
+            "AS" => "On",
+        });
+        let popup_id =
+            document.add_object(dictionary! { "Subtype" => "Popup", "Parent" => note_id });
+        let reply_id = document.add_object(dictionary! { "Subtype" => "Text", "IRT" => note_id });
+        document.get_dictionary_mut(page_id).unwrap().set(
+            "Annots",
+            vec![note_id.into(), popup_id.into(), reply_id.into()],
+        );
+
+        let summary = flatten_page_annotations(&mut document, page_id, |_| true).unwrap();
+        assert_eq!(summary.flattened, 1);
+        assert_eq!(summary.removed, 2);
+        assert!(!document.get_dictionary(page_id).unwrap().has(b"Annots"));
+        assert!(!document.objects.contains_key(&popup_id));
+        assert!(!document.objects.contains_key(&reply_id));
+    }
+}
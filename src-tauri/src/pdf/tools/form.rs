@@ -0,0 +1,386 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use lopdf::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::{
+    apply_form_data, document_to_bytes, generate_appearances, parse_form_data, read_form_fields,
+    write_form_data, AnnotationType, FormData, FormDataFormat, FormFieldValue, FormImportSummary,
+};
+use crate::pdf::tools::{flatten_page_annotations, FlattenSummary};
+use crate::utils::fs::write_atomic;
+
+const DEFAULT_FILE_NAME: &str = "{stem}-{n}";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormExportInput {
+    pub input_path: String,
+    pub output_path: String,
+
+    /// Detected from the output extension when not given
+    pub format: Option<FormDataFormat>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormImportInput {
+    pub input_path: String,
+    pub data_path: String,
+    pub output_path: String,
+
+    /// Detected from the data extension when not given
+    pub format: Option<FormDataFormat>,
+
+    /// Draw the filled fields into the pages and remove the form
+    #[serde(default)]
+    pub flatten: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormBatchInput {
+    pub input_path: String,
+
+    /// JSON array with one record per output document
+    pub data_path: String,
+    pub output_dir: String,
+
+    /// Output file name without extension. `{stem}` is the template name, `{n}` the
+    /// record number and `{field.name}` a value from the record.
+    pub file_name: Option<String>,
+
+    #[serde(default)]
+    pub flatten: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlattenFormInput {
+    pub input_path: String,
+    pub output_path: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FormBatchSummary {
+    /// Written documents, in record order
+    pub files: Vec<String>,
+
+    /// Names in the records without a fillable field in the form
+    pub skipped: Vec<String>,
+}
+
+/// Writes the form's field values to a JSON, FDF or XFDF file and returns the number
+/// of fields exported.
+pub fn export_form_data(input: FormExportInput) -> Result<usize, String> {
+    let document =
+        Document::load(&input.input_path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+    let format = data_format(input.format, &input.output_path)?;
+
+    let fields = read_form_fields(&document)?;
+    let file_name = Path::new(&input.input_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    let bytes = write_form_data(&fields, format, file_name.as_deref())?;
+
+    write_atomic(input.output_path.as_ref(), &bytes)?;
+
+    let exported = parse_form_data(&bytes, format)?
+        .first()
+        .map(Vec::len)
+        .unwrap_or(0);
+    Ok(exported)
+}
+
+/// Fills the form from a data file. A JSON file with several records fills the form
+/// from the first one; use `fill_form_batch` for one document per record.
+pub fn import_form_data(input: FormImportInput) -> Result<FormImportSummary, String> {
+    let mut document =
+        Document::load(&input.input_path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+    let format = data_format(input.format, &input.data_path)?;
+
+    let records = read_records(&input.data_path, format)?;
+    let record = records
+        .first()
+        .ok_or_else(|| "The data file has no records".to_string())?;
+
+    let summary = apply_form_data(&mut document, record)?;
+    if input.flatten {
+        flatten_form(&mut document)?;
+    }
+
+    write_atomic(
+        input.output_path.as_ref(),
+        &document_to_bytes(&mut document)?,
+    )?;
+
+    Ok(summary)
+}
+
+/// Writes one filled copy of the template for each record of a JSON file.
+pub fn fill_form_batch(input: FormBatchInput) -> Result<FormBatchSummary, String> {
+    let template =
+        Document::load(&input.input_path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+    let records = read_records(&input.data_path, FormDataFormat::Json)?;
+    if records.is_empty() {
+        return Err("The data file has no records".into());
+    }
+
+    let stem = Path::new(&input.input_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or("form".into());
+    let pattern = input.file_name.as_deref().unwrap_or(DEFAULT_FILE_NAME);
+
+    let output_dir = Path::new(&input.output_dir);
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create output directory: {e}"))?;
+
+    let mut summary = FormBatchSummary::default();
+    let mut used = HashSet::new();
+
+    for (index, record) in records.iter().enumerate() {
+        let mut document = template.clone();
+        let filled = apply_form_data(&mut document, record)
+            .map_err(|e| format!("Record {}: {e}", index + 1))?;
+        if input.flatten {
+            flatten_form(&mut document)?;
+        }
+
+        for name in filled.skipped {
+            if !summary.skipped.contains(&name) {
+                summary.skipped.push(name);
+            }
+        }
+
+        let name = unique_name(
+            &file_name(pattern, &stem, index + 1, record),
+            &mut used,
+            output_dir,
+        );
+        let output_path = output_dir.join(format!("{name}.pdf"));
+        write_atomic(&output_path, &document_to_bytes(&mut document)?)?;
+
+        summary
+            .files
+            .push(output_path.to_string_lossy().into_owned());
+    }
+
+    Ok(summary)
+}
+
+pub fn flatten_form_pdf(input: FlattenFormInput) -> Result<FlattenSummary, String> {
+    let mut document =
+        Document::load(&input.input_path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+
+    let summary = flatten_form(&mut document)?;
+
+    write_atomic(
+        input.output_path.as_ref(),
+        &document_to_bytes(&mut document)?,
+    )?;
+
+    Ok(summary)
+}
+
+/// Draws the appearance of every form field into its page and removes the form, so
+/// the values become static content. The form is kept when a widget could not be
+/// drawn.
+pub fn flatten_form(document: &mut Document) -> Result<FlattenSummary, String> {
+    generate_appearances(document)?;
+    let mut summary = FlattenSummary::default();
+
+    for page_id in document.get_pages().into_values() {
+        let page = flatten_page_annotations(document, page_id, |subtype| {
+            *subtype == AnnotationType::Widget
+        })?;
+        summary.flattened += page.flattened;
+        summary.removed += page.removed;
+        summary.skipped += page.skipped;
+    }
+
+    if summary.skipped == 0 {
+        document
+            .catalog_mut()
+            .map_err(|e| e.to_string())?
+            .remove(b"AcroForm");
+    }
+
+    Ok(summary)
+}
+
+fn data_format(format: Option<FormDataFormat>, path: &str) -> Result<FormDataFormat, String> {
+    format
+        .or_else(|| FormDataFormat::from_path(Path::new(path)))
+        .ok_or_else(|| format!("Unknown form data format: {path}"))
+}
+
+fn read_records(path: &str, format: FormDataFormat) -> Result<Vec<FormData>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read form data: {e}"))?;
+    parse_form_data(&bytes, format)
+}
+
+/// Expands the placeholders of a batch file name pattern.
+fn file_name(pattern: &str, stem: &str, number: usize, record: &FormData) -> String {
+    let mut name = String::new();
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let key = &rest[start + 1..start + end];
+        let value = match key {
+            "stem" => stem.to_string(),
+            "n" => number.to_string(),
+            key => record
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| match value {
+                    FormFieldValue::Text(text) => text.clone(),
+                    FormFieldValue::Checked(checked) => checked.to_string(),
+                    FormFieldValue::Selected(values) => values.join("-"),
+                })
+                .unwrap_or_default(),
+        };
+        name.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    let name = name.trim().trim_matches('.').to_string();
+
+    if name.is_empty() {
+        format!("{stem}-{number}")
+    } else {
+        name
+    }
+}
+
+/// A name not used earlier in the batch whose file does not exist yet, so that
+/// neither earlier outputs nor unrelated files (the template included) are
+/// overwritten.
+fn unique_name(name: &str, used: &mut HashSet<String>, output_dir: &Path) -> String {
+    let unique = (1..)
+        .map(|n| match n {
+            1 => name.to_string(),
+            n => format!("{name} ({n})"),
+        })
+        .find(|candidate| {
+            !used.contains(&candidate.to_lowercase())
+                && !output_dir.join(format!("{candidate}.pdf")).exists()
+        })
+        .unwrap_or_default();
+    used.insert(unique.to_lowercase());
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::form::fields::tests::form_document;
+
+    #[test]
+    fn test_file_name() {
+        let record = vec![
+            (
+                "employee.name".to_string(),
+                FormFieldValue::Text("Ana/Lima".into()),
+            ),
+            ("employee.remote".to_string(), FormFieldValue::Checked(true)),
+        ];
+
+        assert_eq!(
+            file_name(DEFAULT_FILE_NAME, "contract", 3, &record),
+            "contract-3"
+        );
+        assert_eq!(
+            file_name("{employee.name} {employee.remote}", "contract", 1, &record),
+            "Ana_Lima true"
+        );
+        assert_eq!(file_name("{missing}", "contract", 2, &record), "contract-2");
+
+        let mut used = HashSet::new();
+        let dir = std::env::temp_dir().join(format!("velin-names-{}", uuid::Uuid::new_v4()));
+        assert_eq!(unique_name("a", &mut used, &dir), "a");
+        assert_eq!(unique_name("A", &mut used, &dir), "A (2)");
+    }
+
+    #[test]
+    fn test_fill_form_batch_keeps_existing_files() {
+        let dir = std::env::temp_dir().join(format!("velin-batch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let template_path = dir.join("contract.pdf");
+        let template = document_to_bytes(&mut form_document()).unwrap();
+        std::fs::write(&template_path, &template).unwrap();
+        std::fs::write(dir.join("contract (2).pdf"), b"existing").unwrap();
+
+        let data_path = dir.join("records.json");
+        std::fs::write(
+            &data_path,
+            r#"[{"employee.name": "Ana"}, {"employee.name": "Bo"}, {"employee.name": "Cy"}]"#,
+        )
+        .unwrap();
+
+        let summary = fill_form_batch(FormBatchInput {
+            input_path: template_path.to_string_lossy().into_owned(),
+            data_path: data_path.to_string_lossy().into_owned(),
+            output_dir: dir.to_string_lossy().into_owned(),
+            file_name: Some("{stem}".into()),
+            flatten: false,
+        })
+        .unwrap();
+
+        let names = summary
+            .files
+            .iter()
+            .map(|file| {
+                Path::new(file)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["contract (3).pdf", "contract (4).pdf", "contract (5).pdf"]
+        );
+        assert_eq!(std::fs::read(&template_path).unwrap(), template);
+        assert_eq!(
+            std::fs::read(dir.join("contract (2).pdf")).unwrap(),
+            b"existing"
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_flatten_form() {
+        let mut document = form_document();
+        let record = vec![(
+            "employee.name".to_string(),
+            FormFieldValue::Text("Bo Chen".into()),
+        )];
+        apply_form_data(&mut document, &record).unwrap();
+
+        let summary = flatten_form(&mut document).unwrap();
+        assert_eq!(summary.flattened, 6);
+        assert_eq!(summary.skipped, 0);
+        assert!(!document.catalog().unwrap().has(b"AcroForm"));
+        assert!(read_form_fields(&document).unwrap().is_empty());
+    }
+}
@@ -1,22 +1,34 @@
+pub mod annotation_merge;
+pub mod change_security;
 pub mod compress;
 pub mod extract;
+pub mod flatten;
+pub mod form;
 pub mod image;
 pub mod image_to_pdf;
 pub mod merge;
 pub mod protect;
 pub mod rotate;
+pub mod sanitize;
+pub mod sign;
 pub mod split;
 pub mod unlock;
 pub mod watermark;
 
+pub use annotation_merge::*;
+pub use change_security::*;
 pub use compress::*;
 pub use extract::*;
+pub use flatten::*;
+pub use form::*;
 pub use image::*;
 pub use image_to_pdf::*;
 pub use merge::*;
 pub use protect::*;
 pub use rotate::*;
+pub use sanitize::*;
 use serde::Deserialize;
+pub use sign::*;
 pub use split::*;
 pub use unlock::*;
 pub use watermark::*;
@@ -1,83 +1,113 @@
-use lopdf::encryption::crypt_filters::{Aes128CryptFilter, CryptFilter};
-use lopdf::{Document, EncryptionState, EncryptionVersion, Permissions};
+use lopdf::encryption::crypt_filters::{Aes128CryptFilter, Aes256CryptFilter, CryptFilter};
+use lopdf::{Document, EncryptionState, EncryptionVersion, Object, Permissions, StringFormat};
 use rand::Rng;
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use std::sync::Arc;
 
+use crate::pdf::reader::{document_security, DocumentPermissions, EncryptionAlgorithm};
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProtectInput {
     pub input_path: String,
     pub output_path: String,
-    pub password: String,
-    pub require_password_to_open: bool,
-    // Mapped 1-to-1 to lopdf Permissions bitflags
-    pub allow_printing: bool,
-    pub allow_high_quality_printing: bool,
-    pub allow_modifying: bool,
-    pub allow_copying: bool,
-    pub allow_annotating: bool,
-    pub allow_form_filling: bool,
-    pub allow_assembly: bool,
+
+    /// Grants full access regardless of the permissions
+    pub owner_password: String,
+
+    /// Needed to open the document. Anyone can open it, with the permissions
+    /// below, when not given.
+    pub user_password: Option<String>,
+
+    #[serde(default)]
+    pub algorithm: EncryptionAlgorithm,
+
+    #[serde(flatten)]
+    pub permissions: DocumentPermissions,
 }
 
 pub fn protect_pdf(input: ProtectInput) -> Result<(), String> {
     let mut doc =
         Document::load(&input.input_path).map_err(|e| format!("Failed to load PDF: {e}"))?;
 
-    if doc.is_encrypted() {
-        return Err("Input PDF is already encrypted".into());
+    // Documents without an open password come back decrypted but still protected
+    if document_security(&doc).encrypted {
+        return Err(
+            "Input PDF is already encrypted. Change its security with the owner password instead."
+                .into(),
+        );
     }
 
-    // COPYABLE_FOR_ACCESSIBILITY is deprecated since PDF 2.0 but must always be set for
-    // backward compatibility with viewers following earlier specifications.
-    let mut permissions = Permissions::COPYABLE_FOR_ACCESSIBILITY;
+    encrypt_document(
+        &mut doc,
+        input.algorithm,
+        &input.owner_password,
+        input.user_password.as_deref().unwrap_or_default(),
+        input.permissions.to_permissions(),
+    )?;
 
-    if input.allow_printing {
-        permissions |= Permissions::PRINTABLE;
-    }
-    if input.allow_high_quality_printing {
-        permissions |= Permissions::PRINTABLE_IN_HIGH_QUALITY;
-    }
-    if input.allow_modifying {
-        permissions |= Permissions::MODIFIABLE;
-    }
-    if input.allow_copying {
-        permissions |= Permissions::COPYABLE;
-    }
-    if input.allow_annotating {
-        permissions |= Permissions::ANNOTABLE;
-    }
-    if input.allow_form_filling {
-        permissions |= Permissions::FILLABLE;
+    doc.save(&input.output_path)
+        .map_err(|e| format!("Failed to save PDF: {e}"))?;
+
+    Ok(())
+}
+
+/// Encrypts an unencrypted document with the standard security handler. An empty
+/// user password lets anyone open the document with the given permissions.
+pub fn encrypt_document(
+    doc: &mut Document,
+    algorithm: EncryptionAlgorithm,
+    owner_password: &str,
+    user_password: &str,
+    permissions: Permissions,
+) -> Result<(), String> {
+    // Without an owner password anyone could lift the permissions
+    if owner_password.is_empty() {
+        return Err("An owner password is required".into());
     }
-    if input.allow_assembly {
-        permissions |= Permissions::ASSEMBLABLE;
+
+    // RC4 and AES-128 keys are derived from the file identifier
+    if !doc.trailer.has(b"ID") {
+        let mut id = [0u8; 16];
+        rand::rng().fill(&mut id);
+        let id = Object::String(id.to_vec(), StringFormat::Hexadecimal);
+        doc.trailer.set("ID", vec![id.clone(), id]);
     }
 
-    let crypt_filter: Arc<dyn CryptFilter> = Arc::new(Aes128CryptFilter);
+    let filter =
+        |crypt_filter: Arc<dyn CryptFilter>| BTreeMap::from([(b"StdCF".to_vec(), crypt_filter)]);
     let mut file_encryption_key = [0u8; 32];
     rand::rng().fill(&mut file_encryption_key);
 
-    let crypt_filters: BTreeMap<Vec<u8>, Arc<dyn CryptFilter>> =
-        BTreeMap::from([(b"StdCF".to_vec(), crypt_filter)]);
-
-    let user_password = if input.require_password_to_open {
-        &input.password
-    } else {
-        ""
-    };
-
-    let encryption_version = EncryptionVersion::V4 {
-        document: &doc,
-        encrypt_metadata: true,
-        crypt_filters,
-        stream_filter: b"StdCF".to_vec(),
-        string_filter: b"StdCF".to_vec(),
-        owner_password: &input.password,
-        user_password: user_password,
-        permissions,
+    let encryption_version = match algorithm {
+        EncryptionAlgorithm::Rc4 => EncryptionVersion::V2 {
+            document: doc,
+            owner_password,
+            user_password,
+            key_length: 128,
+            permissions,
+        },
+        EncryptionAlgorithm::Aes128 => EncryptionVersion::V4 {
+            document: doc,
+            encrypt_metadata: true,
+            crypt_filters: filter(Arc::new(Aes128CryptFilter)),
+            stream_filter: b"StdCF".to_vec(),
+            string_filter: b"StdCF".to_vec(),
+            owner_password,
+            user_password,
+            permissions,
+        },
+        EncryptionAlgorithm::Aes256 => EncryptionVersion::V5 {
+            encrypt_metadata: true,
+            crypt_filters: filter(Arc::new(Aes256CryptFilter)),
+            file_encryption_key: &file_encryption_key,
+            stream_filter: b"StdCF".to_vec(),
+            string_filter: b"StdCF".to_vec(),
+            owner_password,
+            user_password,
+            permissions,
+        },
     };
 
     let encryption_state = EncryptionState::try_from(encryption_version)
@@ -86,8 +116,99 @@ pub fn protect_pdf(input: ProtectInput) -> Result<(), String> {
     doc.encrypt(&encryption_state)
         .map_err(|e| format!("Encryption failed: {e}"))?;
 
-    doc.save(&input.output_path)
-        .map_err(|e| format!("Failed to save PDF: {e}"))?;
+    // Oldest PDF version able to describe the algorithm
+    let min_version = match algorithm {
+        EncryptionAlgorithm::Rc4 => "1.4",
+        EncryptionAlgorithm::Aes128 => "1.6",
+        EncryptionAlgorithm::Aes256 => "2.0",
+    };
+    if doc.version.as_str() < min_version {
+        doc.version = min_version.to_string();
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, LoadOptions};
+
+    fn document() -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    #[test]
+    fn test_encrypt_document() {
+        let permissions = DocumentPermissions {
+            allow_printing: true,
+            ..Default::default()
+        };
+
+        for (algorithm, version, revision) in [
+            (EncryptionAlgorithm::Rc4, 2, 3),
+            (EncryptionAlgorithm::Aes128, 4, 4),
+            (EncryptionAlgorithm::Aes256, 5, 6),
+        ] {
+            let mut doc = document();
+            encrypt_document(
+                &mut doc,
+                algorithm,
+                "owner-secret",
+                "user-secret",
+                permissions.to_permissions(),
+            )
+            .unwrap();
+            let mut bytes = Vec::new();
+            doc.save_to(&mut bytes).unwrap();
+
+            let encrypted = Document::load_mem(&bytes).unwrap();
+            let encrypt = encrypted
+                .trailer
+                .get_deref(b"Encrypt", &encrypted)
+                .and_then(Object::as_dict)
+                .unwrap();
+            assert_eq!(encrypt.get(b"V").unwrap().as_i64().unwrap(), version);
+            assert_eq!(encrypt.get(b"R").unwrap().as_i64().unwrap(), revision);
+            assert!(encrypted.authenticate_user_password("wrong").is_err());
+
+            let opened =
+                Document::load_mem_with_options(&bytes, LoadOptions::with_password("user-secret"))
+                    .unwrap();
+            assert_eq!(opened.get_pages().len(), 1);
+            let opened =
+                Document::load_mem_with_options(&bytes, LoadOptions::with_password("owner-secret"))
+                    .unwrap();
+            assert_eq!(opened.get_pages().len(), 1);
+        }
+
+        assert!(encrypt_document(
+            &mut document(),
+            EncryptionAlgorithm::Aes256,
+            "",
+            "",
+            Permissions::empty()
+        )
+        .is_err());
+    }
+}
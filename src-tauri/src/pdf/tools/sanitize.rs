@@ -0,0 +1,931 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use lopdf::content::{Content, Operation};
+use lopdf::{decode_text_string, Dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::document_to_bytes;
+use crate::pdf::reader::redaction::content::{add_xobject, image_xobject, page_resources};
+use crate::utils::fs::write_atomic;
+
+/// Markup annotation subtypes (PDF 32000-1, 12.5.6.2), which hold comments.
+const MARKUP_SUBTYPES: [&[u8]; 17] = [
+    b"Text",
+    b"FreeText",
+    b"Line",
+    b"Square",
+    b"Circle",
+    b"Polygon",
+    b"PolyLine",
+    b"Highlight",
+    b"Underline",
+    b"Squiggly",
+    b"StrikeOut",
+    b"Stamp",
+    b"Caret",
+    b"Ink",
+    b"FileAttachment",
+    b"Sound",
+    b"Redact",
+];
+
+/// Name trees are shallow in practice; deeper ones are malformed or cyclic.
+const MAX_TREE_DEPTH: usize = 32;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SanitizeInput {
+    pub input_path: String,
+    pub output_path: String,
+
+    #[serde(default)]
+    pub options: SanitizeOptions,
+}
+
+/// What to remove. Everything is removed unless turned off.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SanitizeOptions {
+    /// Document information dictionary and XMP metadata streams
+    pub metadata: bool,
+
+    /// Document-level scripts and the JavaScript actions of pages, annotations and
+    /// form fields
+    pub javascript: bool,
+
+    /// Actions run on opening the document and additional actions (`/OpenAction`, `/AA`)
+    pub actions: bool,
+    pub embedded_files: bool,
+
+    /// Annotations other than comments and form fields, such as links
+    pub annotations: bool,
+
+    /// Markup annotations with their popups and replies
+    pub comments: bool,
+
+    /// Optional content groups that are off by default, with the content in them
+    pub hidden_layers: bool,
+    pub thumbnails: bool,
+
+    /// Data kept by the applications that edited the document (`/PieceInfo`)
+    pub private_data: bool,
+
+    /// Objects nothing in the document refers to, such as leftovers of earlier edits
+    pub unreferenced_objects: bool,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self {
+            metadata: true,
+            javascript: true,
+            actions: true,
+            embedded_files: true,
+            annotations: true,
+            comments: true,
+            hidden_layers: true,
+            thumbnails: true,
+            private_data: true,
+            unreferenced_objects: true,
+        }
+    }
+}
+
+/// What sanitizing removed from the document.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SanitizeReport {
+    /// Entries of the document information dictionary
+    pub info_keys: Vec<String>,
+    pub xmp_streams: usize,
+
+    /// Document-level scripts and JavaScript actions
+    pub scripts: usize,
+    pub actions: usize,
+
+    /// Names of the embedded files and file attachments
+    pub embedded_files: Vec<String>,
+    pub annotations: usize,
+    pub comments: usize,
+
+    /// Names of the hidden layers
+    pub hidden_layers: Vec<String>,
+    pub thumbnails: usize,
+    pub private_data: usize,
+    pub unreferenced_objects: usize,
+}
+
+/// Removes hidden information from a PDF before it is shared. The document is
+/// written out in full, so earlier revisions kept by incremental updates are gone
+/// as well.
+pub fn sanitize_pdf(input: SanitizeInput) -> Result<SanitizeReport, String> {
+    let mut document =
+        Document::load(&input.input_path).map_err(|e| format!("Failed to load PDF: {e}"))?;
+
+    // lopdf only decrypts documents without an open password
+    if document.is_encrypted() {
+        return Err("This PDF needs a password to open. Unlock it before sanitizing.".into());
+    }
+
+    let report = sanitize_document(&mut document, input.options)?;
+
+    write_atomic(
+        input.output_path.as_ref(),
+        &document_to_bytes(&mut document)?,
+    )?;
+
+    Ok(report)
+}
+
+pub fn sanitize_document(
+    document: &mut Document,
+    options: SanitizeOptions,
+) -> Result<SanitizeReport, String> {
+    let mut report = SanitizeReport::default();
+
+    // Unreachable before anything is removed, as opposed to what the steps below detach
+    let unreferenced = unreferenced_objects(document);
+
+    if options.javascript {
+        remove_javascript(document, &mut report);
+    }
+    if options.actions {
+        remove_actions(document, &mut report);
+    }
+    if options.embedded_files {
+        remove_embedded_files(document, &mut report);
+    }
+    if options.hidden_layers {
+        remove_hidden_layers(document, &mut report)?;
+    }
+    if options.annotations || options.comments || options.embedded_files {
+        remove_annotations(document, &options, &mut report);
+    }
+
+    for_each_dictionary(document, |dict| {
+        if options.metadata && dict.remove(b"Metadata").is_some() {
+            report.xmp_streams += 1;
+        }
+        if options.thumbnails && dict.remove(b"Thumb").is_some() {
+            report.thumbnails += 1;
+        }
+        if options.private_data && dict.remove(b"PieceInfo").is_some() {
+            dict.remove(b"LastModified");
+            report.private_data += 1;
+        }
+    });
+
+    if options.metadata {
+        if let Some(info) = document.trailer.remove(b"Info") {
+            if let Some(info) = resolve(document, &info).and_then(|info| info.as_dict().ok()) {
+                report.info_keys = info
+                    .iter()
+                    .map(|(key, _)| String::from_utf8_lossy(key).into_owned())
+                    .collect();
+            }
+        }
+    }
+
+    // Whatever the steps above detached is always dropped, so that it is not written
+    for id in unreferenced_objects(document) {
+        if options.unreferenced_objects || !unreferenced.contains(&id) {
+            document.objects.remove(&id);
+        }
+    }
+    if options.unreferenced_objects {
+        report.unreferenced_objects = unreferenced.len();
+    }
+
+    Ok(report)
+}
+
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Object> {
+    document.dereference(object).ok().map(|(_, object)| object)
+}
+
+fn unreferenced_objects(document: &mut Document) -> BTreeSet<ObjectId> {
+    let referenced = document
+        .traverse_objects(|_| {})
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    document
+        .objects
+        .keys()
+        .filter(|id| !referenced.contains(id))
+        .copied()
+        .collect()
+}
+
+/// Calls `visit` on every dictionary of every object, nested and stream
+/// dictionaries included. Nested dictionaries are visited before the ones
+/// holding them.
+fn for_each_dictionary(document: &mut Document, mut visit: impl FnMut(&mut Dictionary)) {
+    fn walk<F: FnMut(&mut Dictionary)>(object: &mut Object, visit: &mut F) {
+        let dict = match object {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &mut stream.dict,
+            Object::Array(items) => {
+                for item in items {
+                    walk(item, visit);
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        for (_, value) in dict.iter_mut() {
+            walk(value, visit);
+        }
+        visit(dict);
+    }
+
+    for object in document.objects.values_mut() {
+        walk(object, &mut visit);
+    }
+}
+
+/// Removes an entry of the catalog's `/Names` dictionary, returning the name tree.
+fn take_name_tree(document: &mut Document, key: &[u8]) -> Option<Object> {
+    let names_id = document
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Names"))
+        .and_then(Object::as_reference);
+
+    let names = match names_id {
+        Ok(names_id) => document.get_dictionary_mut(names_id).ok()?,
+        Err(_) => document
+            .catalog_mut()
+            .and_then(|catalog| catalog.get_mut(b"Names"))
+            .and_then(Object::as_dict_mut)
+            .ok()?,
+    };
+
+    names.remove(key)
+}
+
+/// Keys of a name tree, decoded as text.
+fn name_tree_keys(document: &Document, node: &Object, depth: usize, keys: &mut Vec<String>) {
+    let Some(node) = resolve(document, node).and_then(|node| node.as_dict().ok()) else {
+        return;
+    };
+    if depth > MAX_TREE_DEPTH {
+        return;
+    }
+
+    if let Ok(names) = node
+        .get_deref(b"Names", document)
+        .and_then(Object::as_array)
+    {
+        for name in names.iter().step_by(2) {
+            keys.push(decode_text_string(name).unwrap_or_default());
+        }
+    }
+    if let Ok(kids) = node.get_deref(b"Kids", document).and_then(Object::as_array) {
+        for kid in kids {
+            name_tree_keys(document, kid, depth + 1, keys);
+        }
+    }
+}
+
+fn remove_javascript(document: &mut Document, report: &mut SanitizeReport) {
+    // Document-level scripts, run when the document opens
+    if let Some(tree) = take_name_tree(document, b"JavaScript") {
+        let mut keys = Vec::new();
+        name_tree_keys(document, &tree, 0, &mut keys);
+        report.scripts += keys.len();
+    }
+
+    let is_javascript = |dict: &Dictionary| {
+        dict.get(b"S")
+            .and_then(Object::as_name)
+            .is_ok_and(|action| action == b"JavaScript")
+    };
+    let script_ids = document
+        .objects
+        .iter()
+        .filter(|(_, object)| object.as_dict().is_ok_and(is_javascript))
+        .map(|(id, _)| *id)
+        .collect::<HashSet<_>>();
+    let is_script = |object: &Object| match object {
+        Object::Reference(id) => script_ids.contains(id),
+        Object::Dictionary(dict) => is_javascript(dict),
+        _ => false,
+    };
+
+    // Any entry holding a JavaScript action: link and field actions (`/A`), the
+    // open action, chained actions (`/Next`) and additional-action triggers
+    let mut removed = 0;
+    for_each_dictionary(document, |dict| {
+        let keys = dict
+            .iter()
+            .filter(|(_, value)| is_script(value))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in keys {
+            dict.remove(&key);
+            removed += 1;
+        }
+
+        if let Ok(Object::Array(next)) = dict.get_mut(b"Next") {
+            let count = next.len();
+            next.retain(|action| !is_script(action));
+            removed += count - next.len();
+        }
+        if dict
+            .get(b"AA")
+            .and_then(Object::as_dict)
+            .is_ok_and(Dictionary::is_empty)
+        {
+            dict.remove(b"AA");
+        }
+    });
+
+    report.scripts += removed;
+}
+
+fn remove_actions(document: &mut Document, report: &mut SanitizeReport) {
+    if let Ok(catalog) = document.catalog_mut() {
+        if catalog.remove(b"OpenAction").is_some() {
+            report.actions += 1;
+        }
+    }
+
+    // Triggers of the document, pages, annotations and form fields
+    for_each_dictionary(document, |dict| {
+        if dict.remove(b"AA").is_some() {
+            report.actions += 1;
+        }
+    });
+}
+
+fn remove_embedded_files(document: &mut Document, report: &mut SanitizeReport) {
+    if let Some(tree) = take_name_tree(document, b"EmbeddedFiles") {
+        name_tree_keys(document, &tree, 0, &mut report.embedded_files);
+    }
+
+    // Associated files and portfolio layout; the files themselves are in the tree
+    if let Ok(catalog) = document.catalog_mut() {
+        catalog.remove(b"AF");
+        catalog.remove(b"Collection");
+    }
+}
+
+/// File name of a FileAttachment annotation.
+fn attachment_name(document: &Document, annotation: &Dictionary) -> String {
+    let Ok(spec) = annotation.get_deref(b"FS", document) else {
+        return String::new();
+    };
+
+    match spec {
+        Object::Dictionary(spec) => spec
+            .get(b"UF")
+            .or_else(|_| spec.get(b"F"))
+            .and_then(decode_text_string)
+            .unwrap_or_default(),
+        other => decode_text_string(other).unwrap_or_default(),
+    }
+}
+
+fn remove_annotations(
+    document: &mut Document,
+    options: &SanitizeOptions,
+    report: &mut SanitizeReport,
+) {
+    for page_id in document.get_pages().into_values() {
+        let Ok(annots) = document
+            .get_dictionary(page_id)
+            .and_then(|page| page.get_deref(b"Annots", document))
+            .and_then(Object::as_array)
+            .cloned()
+        else {
+            continue;
+        };
+
+        let dictionaries = annots
+            .iter()
+            .map(|item| resolve(document, item).and_then(|item| item.as_dict().ok()))
+            .collect::<Vec<_>>();
+        let subtype = |dict: &Dictionary| {
+            dict.get(b"Subtype")
+                .and_then(Object::as_name)
+                .map(<[u8]>::to_vec)
+                .unwrap_or_default()
+        };
+
+        let mut removed = vec![false; annots.len()];
+        for (index, dict) in dictionaries.iter().enumerate() {
+            let Some(dict) = dict else {
+                continue;
+            };
+
+            removed[index] = match subtype(dict).as_slice() {
+                b"Widget" | b"Popup" => false,
+                b"FileAttachment" if options.embedded_files => {
+                    report.embedded_files.push(attachment_name(document, dict));
+                    true
+                }
+                markup if MARKUP_SUBTYPES.contains(&markup) => {
+                    if options.comments {
+                        report.comments += 1;
+                    }
+                    options.comments
+                }
+                _ => {
+                    if options.annotations {
+                        report.annotations += 1;
+                    }
+                    options.annotations
+                }
+            };
+        }
+
+        // Popups go with the comments and with the annotation they belong to
+        let removed_ids = annots
+            .iter()
+            .zip(&removed)
+            .filter(|(_, removed)| **removed)
+            .filter_map(|(item, _)| item.as_reference().ok())
+            .collect::<HashSet<_>>();
+        for (index, dict) in dictionaries.iter().enumerate() {
+            let Some(dict) = dict else {
+                continue;
+            };
+            if subtype(dict) == b"Popup" {
+                let parent = dict.get(b"Parent").and_then(Object::as_reference);
+                removed[index] =
+                    options.comments || parent.is_ok_and(|parent| removed_ids.contains(&parent));
+            }
+        }
+
+        if !removed.contains(&true) {
+            continue;
+        }
+
+        let kept = annots
+            .into_iter()
+            .zip(removed)
+            .filter(|(_, removed)| !removed)
+            .map(|(item, _)| item)
+            .collect::<Vec<_>>();
+        if let Ok(page) = document.get_dictionary_mut(page_id) {
+            if kept.is_empty() {
+                page.remove(b"Annots");
+            } else {
+                page.set("Annots", kept);
+            }
+        }
+    }
+}
+
+/// Optional content groups that are off in the default configuration, by name.
+fn hidden_layers(document: &Document) -> BTreeMap<ObjectId, String> {
+    let Some(properties) = document
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get_deref(b"OCProperties", document).ok())
+        .and_then(|properties| properties.as_dict().ok())
+    else {
+        return BTreeMap::new();
+    };
+
+    let references = |dict: &Dictionary, key: &[u8]| {
+        dict.get_deref(key, document)
+            .and_then(Object::as_array)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| item.as_reference().ok())
+                    .collect::<HashSet<_>>()
+            })
+            .unwrap_or_default()
+    };
+
+    let config = properties
+        .get_deref(b"D", document)
+        .and_then(Object::as_dict)
+        .ok();
+    let (on, off, base_off) = match config {
+        Some(config) => (
+            references(config, b"ON"),
+            references(config, b"OFF"),
+            config
+                .get(b"BaseState")
+                .and_then(Object::as_name)
+                .is_ok_and(|state| state == b"OFF"),
+        ),
+        None => Default::default(),
+    };
+
+    references(properties, b"OCGs")
+        .into_iter()
+        .filter(|id| off.contains(id) || (base_off && !on.contains(id)))
+        .map(|id| {
+            let name = document
+                .get_dictionary(id)
+                .and_then(|ocg| ocg.get(b"Name"))
+                .and_then(decode_text_string)
+                .unwrap_or_default();
+            (id, name)
+        })
+        .collect()
+}
+
+fn remove_hidden_layers(
+    document: &mut Document,
+    report: &mut SanitizeReport,
+) -> Result<(), String> {
+    let layers = hidden_layers(document);
+    if layers.is_empty() {
+        return Ok(());
+    }
+
+    // Annotations and XObjects that belong to a hidden layer go with it
+    let mut removed = layers.keys().copied().collect::<HashSet<_>>();
+    for (id, object) in &document.objects {
+        let dict = match object {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &stream.dict,
+            _ => continue,
+        };
+        if dict
+            .get(b"OC")
+            .and_then(Object::as_reference)
+            .is_ok_and(|layer| layers.contains_key(&layer))
+        {
+            removed.insert(*id);
+        }
+    }
+
+    // Marked content of the layers in pages and forms. Nothing is changed unless
+    // every content stream can be read.
+    let mut pages = Vec::new();
+    for page_id in document.get_pages().into_values() {
+        let mut content = Vec::new();
+        for stream_id in document.get_page_contents(page_id) {
+            if let Ok(stream) = document.get_object(stream_id).and_then(Object::as_stream) {
+                content.extend(
+                    stream
+                        .get_plain_content()
+                        .unwrap_or_else(|_| stream.content.clone()),
+                );
+                content.push(b'\n');
+            }
+        }
+        let mut resources = page_resources(document, page_id);
+        if let Some(content) =
+            remove_layer_content(document, &content, &mut resources, &layers, &removed)?
+        {
+            pages.push((page_id, content, resources));
+        }
+    }
+
+    let form_ids = document
+        .objects
+        .iter()
+        .filter_map(|(id, object)| {
+            let stream = object.as_stream().ok()?;
+            let subtype = stream.dict.get(b"Subtype").and_then(Object::as_name).ok()?;
+            (subtype == b"Form").then_some(*id)
+        })
+        .collect::<Vec<_>>();
+    let mut forms = Vec::new();
+    for form_id in form_ids {
+        let Ok(form) = document.get_object(form_id).and_then(Object::as_stream) else {
+            continue;
+        };
+        let content = form
+            .get_plain_content()
+            .unwrap_or_else(|_| form.content.clone());
+        let mut resources = form
+            .dict
+            .get_deref(b"Resources", document)
+            .and_then(Object::as_dict)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(content) =
+            remove_layer_content(document, &content, &mut resources, &layers, &removed)?
+        {
+            forms.push((form_id, content, resources));
+        }
+    }
+
+    for (page_id, content, resources) in pages {
+        let mut stream = Stream::new(Dictionary::new(), content);
+        let _ = stream.compress();
+        let stream_id = document.add_object(stream);
+        if let Ok(page) = document.get_dictionary_mut(page_id) {
+            page.set("Contents", stream_id);
+            page.set("Resources", resources);
+        }
+    }
+    for (form_id, content, resources) in forms {
+        if let Ok(form) = document
+            .get_object_mut(form_id)
+            .and_then(Object::as_stream_mut)
+        {
+            form.set_plain_content(content);
+            form.dict.set("Resources", resources);
+            let _ = form.compress();
+        }
+    }
+
+    // Drop every reference to the layers and what belongs to them, from the
+    // configurations, membership dictionaries and resources alike
+    for object in document.objects.values_mut() {
+        strip_references(object, &removed);
+    }
+
+    report.hidden_layers = layers.into_values().collect();
+    Ok(())
+}
+
+/// Content without the marked content of hidden layers and the XObjects that
+/// belong to them. Returns `None` when nothing is removed.
+fn remove_layer_content(
+    document: &mut Document,
+    content: &[u8],
+    resources: &mut Dictionary,
+    layers: &BTreeMap<ObjectId, String>,
+    removed: &HashSet<ObjectId>,
+) -> Result<Option<Vec<u8>>, String> {
+    let names = |key: &[u8], hidden: &dyn Fn(ObjectId) -> bool| {
+        resources
+            .get_deref(key, document)
+            .and_then(Object::as_dict)
+            .map(|dict| {
+                dict.iter()
+                    .filter(|(_, value)| value.as_reference().is_ok_and(hidden))
+                    .map(|(name, _)| name.clone())
+                    .collect::<HashSet<_>>()
+            })
+            .unwrap_or_default()
+    };
+    let properties = names(b"Properties", &|id| layers.contains_key(&id));
+    let xobjects = names(b"XObject", &|id| removed.contains(&id));
+
+    if properties.is_empty() && xobjects.is_empty() {
+        return Ok(None);
+    }
+
+    let content =
+        Content::decode(content).map_err(|e| format!("Failed to parse content stream: {e}"))?;
+
+    let mut output = Vec::with_capacity(content.operations.len());
+    let mut depth = 0;
+    let mut changed = false;
+    for operation in content.operations {
+        let name = operation
+            .operands
+            .first()
+            .and_then(|name| name.as_name().ok());
+
+        if depth > 0 {
+            match operation.operator.as_str() {
+                "BDC" | "BMC" => depth += 1,
+                "EMC" => depth -= 1,
+                _ => {}
+            }
+            continue;
+        }
+
+        match operation.operator.as_str() {
+            "BDC"
+                if name == Some(b"OC".as_slice())
+                    && operation
+                        .operands
+                        .get(1)
+                        .and_then(|tag| tag.as_name().ok())
+                        .is_some_and(|tag| properties.contains(tag)) =>
+            {
+                depth = 1;
+                changed = true;
+            }
+            "Do" if name.is_some_and(|name| xobjects.contains(name)) => changed = true,
+            // `Content::encode` cannot write inline images back
+            "BI" => {
+                if let Some(Object::Stream(image)) = operation.operands.into_iter().next() {
+                    let image = image_xobject(document, resources, image);
+                    let image_id = document.add_object(image);
+                    let name = add_xobject(document, resources, image_id);
+                    output.push(Operation::new("Do", vec![Object::Name(name)]));
+                }
+            }
+            _ => output.push(operation),
+        }
+    }
+
+    if !changed {
+        return Ok(None);
+    }
+
+    Content { operations: output }
+        .encode()
+        .map(Some)
+        .map_err(|e| format!("Failed to write content stream: {e}"))
+}
+
+fn strip_references(object: &mut Object, removed: &HashSet<ObjectId>) {
+    let is_removed = |object: &Object| object.as_reference().is_ok_and(|id| removed.contains(&id));
+
+    match object {
+        Object::Array(items) => {
+            items.retain(|item| !is_removed(item));
+            for item in items {
+                strip_references(item, removed);
+            }
+        }
+        Object::Dictionary(dict) => strip_dictionary(dict, removed),
+        Object::Stream(stream) => strip_dictionary(&mut stream.dict, removed),
+        _ => {}
+    }
+
+    fn strip_dictionary(dict: &mut Dictionary, removed: &HashSet<ObjectId>) {
+        let keys = dict
+            .iter()
+            .filter(|(_, value)| value.as_reference().is_ok_and(|id| removed.contains(&id)))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in keys {
+            dict.remove(&key);
+        }
+        for (_, value) in dict.iter_mut() {
+            strip_references(value, removed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn document() -> Document {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+
+        let script_id = document.add_object(dictionary! {
+            "S" => "JavaScript",
+            "JS" => lopdf::text_string("app.alert('hi')"),
+        });
+        let layer_id = document.add_object(dictionary! {
+            "Type" => "OCG",
+            "Name" => lopdf::text_string("Draft notes"),
+        });
+        let content_id = document.add_object(Stream::new(
+            dictionary! {},
+            b"0 g 0 0 10 10 re f /OC /L0 BDC 1 0 0 rg 20 20 10 10 re f EMC".to_vec(),
+        ));
+        let note_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+            "Contents" => lopdf::text_string("Internal only"),
+        });
+        let popup_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Popup",
+            "Parent" => note_id,
+        });
+        let link_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "A" => script_id,
+        });
+        let widget_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "AA" => dictionary! { "K" => script_id },
+        });
+        let thumb_id = document.add_object(Stream::new(dictionary! {}, vec![0; 16]));
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 100.into(), 100.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! { "Properties" => dictionary! { "L0" => layer_id } },
+            "Annots" => vec![note_id.into(), popup_id.into(), link_id.into(), widget_id.into()],
+            "Thumb" => thumb_id,
+            "PieceInfo" => dictionary! { "Editor" => dictionary! { "Private" => "Data" } },
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+
+        let file_id = document.add_object(Stream::new(
+            dictionary! { "Type" => "EmbeddedFile" },
+            b"secret".to_vec(),
+        ));
+        let xmp_id = document.add_object(Stream::new(
+            dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+            b"<x:xmpmeta/>".to_vec(),
+        ));
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Metadata" => xmp_id,
+            "OpenAction" => dictionary! { "S" => "Launch", "F" => lopdf::text_string("calc.exe") },
+            "Names" => dictionary! {
+                "JavaScript" => dictionary! {
+                    "Names" => vec![lopdf::text_string("init"), script_id.into()],
+                },
+                "EmbeddedFiles" => dictionary! {
+                    "Names" => vec![
+                        lopdf::text_string("payload.exe"),
+                        dictionary! { "Type" => "Filespec", "EF" => dictionary! { "F" => file_id } }.into(),
+                    ],
+                },
+            },
+            "OCProperties" => dictionary! {
+                "OCGs" => vec![layer_id.into()],
+                "D" => dictionary! { "OFF" => vec![layer_id.into()], "Order" => vec![layer_id.into()] },
+            },
+        });
+        document.trailer.set("Root", catalog_id);
+        let info_id = document.add_object(dictionary! {
+            "Author" => lopdf::text_string("Jane"),
+            "Producer" => lopdf::text_string("Editor 1.0"),
+        });
+        document.trailer.set("Info", info_id);
+
+        // Left over from an earlier revision
+        document.add_object(dictionary! { "Type" => "Annot", "Subtype" => "Text" });
+        document
+    }
+
+    #[test]
+    fn test_sanitize_document() {
+        let mut document = document();
+        let report = sanitize_document(&mut document, SanitizeOptions::default()).unwrap();
+
+        assert_eq!(report.info_keys, vec!["Author", "Producer"]);
+        assert_eq!(report.xmp_streams, 1);
+        // The document-level script, the link action and the field trigger
+        assert_eq!(report.scripts, 3);
+        assert_eq!(report.actions, 1);
+        assert_eq!(report.embedded_files, vec!["payload.exe"]);
+        assert_eq!(report.comments, 1);
+        assert_eq!(report.annotations, 1);
+        assert_eq!(report.hidden_layers, vec!["Draft notes"]);
+        assert_eq!(report.thumbnails, 1);
+        assert_eq!(report.private_data, 1);
+        assert_eq!(report.unreferenced_objects, 1);
+
+        let page_id = *document.get_pages().get(&1).unwrap();
+        let page = document.get_dictionary(page_id).unwrap();
+        assert_eq!(page.get(b"Annots").unwrap().as_array().unwrap().len(), 1);
+        assert!(!page.has(b"Thumb") && !page.has(b"PieceInfo"));
+
+        let content = document.get_page_content(page_id).unwrap();
+        let content = String::from_utf8(content).unwrap();
+        assert!(content.contains("0 0 10 10 re"));
+        assert!(!content.contains("20 20 10 10 re"));
+
+        let catalog = document.catalog().unwrap();
+        assert!(!catalog.has(b"OpenAction") && !catalog.has(b"Metadata"));
+        let names = catalog.get(b"Names").unwrap().as_dict().unwrap();
+        assert!(names.is_empty());
+        let properties = catalog.get(b"OCProperties").unwrap().as_dict().unwrap();
+        assert!(properties
+            .get(b"OCGs")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .is_empty());
+
+        assert!(!document.trailer.has(b"Info"));
+        assert!(document.objects.values().all(|object| {
+            object
+                .as_stream()
+                .map_or(true, |stream| stream.content != b"secret")
+        }));
+    }
+
+    #[test]
+    fn test_sanitize_options() {
+        let mut document = document();
+        let options = SanitizeOptions {
+            comments: false,
+            unreferenced_objects: false,
+            ..Default::default()
+        };
+        let report = sanitize_document(&mut document, options).unwrap();
+
+        assert_eq!(report.comments, 0);
+        assert_eq!(report.unreferenced_objects, 0);
+
+        let page_id = *document.get_pages().get(&1).unwrap();
+        let page = document.get_dictionary(page_id).unwrap();
+        // The comment and its popup stay with the form field
+        assert_eq!(page.get(b"Annots").unwrap().as_array().unwrap().len(), 3);
+        assert_eq!(
+            document.objects.len(),
+            document.clone().traverse_objects(|_| {}).len() + 1
+        );
+    }
+}
@@ -0,0 +1,764 @@
+use std::path::Path;
+
+use cms::builder::{SignedDataBuilder, SignerInfoBuilder};
+use cms::cert::{CertificateChoices, IssuerAndSerialNumber};
+use cms::signed_data::{EncapsulatedContentInfo, SignerIdentifier};
+use const_oid::db::{rfc5911, rfc5912};
+use der::asn1::{Any, ObjectIdentifier, OctetString, SetOfVec};
+use der::{Encode, Tag};
+use lopdf::{
+    dictionary, Dictionary, Document, IncrementalDocument, Object, ObjectId, Stream, StringFormat,
+};
+use rsa::signature::{Keypair, Signer};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use x509_cert::attr::Attribute;
+use x509_cert::spki::{
+    AlgorithmIdentifierOwned, DynSignatureAlgorithmIdentifier, SignatureBitStringEncoding,
+};
+
+use crate::pdf::editor::page_height;
+use crate::pdf::reader::annotation::appearance::{num, win_ansi_hex, wrap_text};
+use crate::pdf::reader::annotation::create::pdf_date_now;
+use crate::pdf::reader::{
+    common_name, field_nodes, image_xobject, read_signatures, widget_rect, FormFieldType, PdfRect,
+    SignatureInfo,
+};
+use crate::utils::fs::write_atomic;
+use crate::utils::pkcs12::{load_pkcs12, SigningIdentity, SigningKey};
+
+const SUB_FILTER: &str = "ETSI.CAdES.detached";
+
+/// Written in place of the byte range and replaced by the real offsets once the
+/// file is laid out. lopdf writes integers as they are, so nothing moves.
+const PLACEHOLDER: i64 = 9_999_999_999;
+
+/// Space reserved for the signature value on top of the certificates it embeds
+const CONTENTS_RESERVE: usize = 8192;
+
+const SIGNING_CERTIFICATE_V2: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.2.47");
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignInput {
+    pub input_path: String,
+    pub output_path: String,
+
+    /// PKCS#12 file (.p12/.pfx) holding the private key and certificate
+    pub certificate_path: String,
+    pub password: String,
+
+    /// Unsigned signature field to sign. A new field is created when no field has
+    /// this name, named `Signature1`, `Signature2`... when not given.
+    pub field_name: Option<String>,
+
+    /// Visible signature. Signatures are invisible when not given.
+    pub appearance: Option<SignatureAppearance>,
+
+    pub reason: Option<String>,
+    pub location: Option<String>,
+    pub contact_info: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureAppearance {
+    /// Page and rectangle of a new field. Existing fields keep their own widget.
+    pub page_index: u16,
+    pub rect: PdfRect,
+
+    /// Image drawn in the signature, e.g. a scanned handwritten signature
+    pub image_path: Option<String>,
+
+    /// Draw the signer name, date, reason and location
+    pub show_text: bool,
+}
+
+/// Field receiving the signature.
+enum Target {
+    Existing {
+        field_id: ObjectId,
+        widget: Option<(ObjectId, [f32; 4])>,
+    },
+    New {
+        name: String,
+        page_id: ObjectId,
+        rect: [f32; 4],
+    },
+}
+
+/// Signs a PDF with a PKCS#12 certificate, without any network access.
+///
+/// The signature is appended as an incremental update, so earlier revisions and
+/// signatures stay intact, and is a PAdES baseline (B-B) CMS signature.
+pub fn sign_pdf(input: SignInput) -> Result<SignatureInfo, String> {
+    let bytes = std::fs::read(&input.input_path).map_err(|e| format!("Failed to read PDF: {e}"))?;
+    let certificate = std::fs::read(&input.certificate_path)
+        .map_err(|e| format!("Failed to read certificate file: {e}"))?;
+    let identity = load_pkcs12(&certificate, &input.password)?;
+
+    let (signed, field_name) = sign_document(&bytes, &identity, &input)?;
+    write_atomic(Path::new(&input.output_path), &signed)?;
+
+    read_signatures(&signed)?
+        .into_iter()
+        .find(|signature| signature.field_name == field_name)
+        .ok_or("The signed field could not be read back".into())
+}
+
+/// Signs the document bytes, returning the signed file and the name of the
+/// signed field.
+pub fn sign_document(
+    bytes: &[u8],
+    identity: &SigningIdentity,
+    input: &SignInput,
+) -> Result<(Vec<u8>, String), String> {
+    let mut incremental: IncrementalDocument = bytes
+        .try_into()
+        .map_err(|e| format!("Failed to load PDF: {e}"))?;
+
+    let prev = incremental.get_prev_documents();
+    if prev.trailer.has(b"Encrypt") {
+        return Err("Encrypted PDFs must be unlocked before signing".into());
+    }
+    let version = prev.version.clone();
+    let catalog_id = prev
+        .trailer
+        .get(b"Root")
+        .and_then(Object::as_reference)
+        .map_err(|e| format!("Failed to read document catalog: {e}"))?;
+
+    let (field_name, target) = signature_target(prev, input)?;
+
+    let signer_name = common_name(&identity.certificate)
+        .unwrap_or_else(|| identity.certificate.tbs_certificate.subject.to_string());
+    let date = pdf_date_now();
+
+    let reserve = CONTENTS_RESERVE
+        + std::iter::once(&identity.certificate)
+            .chain(&identity.chain)
+            .map(|certificate| certificate.to_der().map(|der| der.len()).unwrap_or(0))
+            .sum::<usize>();
+
+    let document = &mut incremental.new_document;
+    document.version = version;
+
+    let mut value = dictionary! {
+        "Type" => "Sig",
+        "Filter" => "Adobe.PPKLite",
+        "SubFilter" => SUB_FILTER,
+        "ByteRange" => vec![0.into(), PLACEHOLDER.into(), PLACEHOLDER.into(), PLACEHOLDER.into()],
+        "Contents" => Object::String(vec![0; reserve], StringFormat::Hexadecimal),
+        "M" => Object::string_literal(date.as_str()),
+        "Name" => lopdf::text_string(&signer_name),
+    };
+    for (key, text) in [
+        ("Reason", &input.reason),
+        ("Location", &input.location),
+        ("ContactInfo", &input.contact_info),
+    ] {
+        if let Some(text) = text.as_deref().filter(|text| !text.is_empty()) {
+            value.set(key, lopdf::text_string(text));
+        }
+    }
+    let value_id = document.add_object(value);
+
+    let rect = match &target {
+        Target::Existing { widget, .. } => widget.map(|(_, rect)| rect),
+        Target::New { rect, .. } => Some(*rect),
+    };
+    let appearance_id = match (&input.appearance, rect) {
+        (Some(appearance), Some([left, bottom, right, top])) if right > left && top > bottom => {
+            let mut lines = Vec::new();
+            if appearance.show_text {
+                lines.push(format!("Digitally signed by {signer_name}"));
+                lines.push(format!("Date: {}", display_date(&date)));
+                if let Some(reason) = input.reason.as_deref().filter(|text| !text.is_empty()) {
+                    lines.push(format!("Reason: {reason}"));
+                }
+                if let Some(location) = input.location.as_deref().filter(|text| !text.is_empty()) {
+                    lines.push(format!("Location: {location}"));
+                }
+            }
+            let image = appearance.image_path.as_deref().map(Path::new);
+            if image.is_none() && lines.is_empty() {
+                return Err("A visible signature needs an image or text".into());
+            }
+            Some(appearance_stream(
+                document,
+                right - left,
+                top - bottom,
+                image,
+                &lines,
+            )?)
+        }
+        _ => None,
+    };
+
+    match target {
+        Target::Existing { field_id, widget } => {
+            clone_object(&mut incremental, field_id)?;
+            dictionary_mut(&mut incremental.new_document, field_id)?.set("V", value_id);
+
+            if let (Some((widget_id, _)), Some(appearance_id)) = (widget, appearance_id) {
+                clone_object(&mut incremental, widget_id)?;
+                dictionary_mut(&mut incremental.new_document, widget_id)?
+                    .set("AP", dictionary! { "N" => appearance_id });
+            }
+        }
+        Target::New {
+            name,
+            page_id,
+            rect,
+        } => {
+            let mut widget = dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Widget",
+                "FT" => "Sig",
+                "T" => lopdf::text_string(&name),
+                "V" => value_id,
+                "P" => page_id,
+                "Rect" => rect.iter().map(|value| Object::Real(*value)).collect::<Vec<_>>(),
+                // Print and Locked
+                "F" => 132,
+            };
+            if let Some(appearance_id) = appearance_id {
+                widget.set("AP", dictionary! { "N" => appearance_id });
+            }
+            let widget_id = incremental.new_document.add_object(widget);
+
+            push_reference(&mut incremental, page_id, b"Annots", widget_id)?;
+            let acro_form_id = acro_form_id(&mut incremental, catalog_id)?;
+            push_reference(&mut incremental, acro_form_id, b"Fields", widget_id)?;
+        }
+    }
+
+    // Signatures must be preserved on save and opened in append mode
+    let acro_form_id = acro_form_id(&mut incremental, catalog_id)?;
+    dictionary_mut(&mut incremental.new_document, acro_form_id)?.set("SigFlags", 3);
+
+    let mut signed = Vec::new();
+    incremental
+        .save_to(&mut signed)
+        .map_err(|e| format!("Failed to save PDF: {e}"))?;
+
+    let placeholder = format!("[0 {PLACEHOLDER} {PLACEHOLDER} {PLACEHOLDER}]");
+    let range_at = find(&signed, bytes.len(), placeholder.as_bytes())
+        .ok_or("Failed to place the signature byte range")?;
+    let mut contents = vec![b'0'; reserve * 2 + 2];
+    contents[0] = b'<';
+    contents[reserve * 2 + 1] = b'>';
+    let contents_start =
+        find(&signed, bytes.len(), &contents).ok_or("Failed to place the signature value")?;
+    let contents_end = contents_start + contents.len();
+
+    let byte_range = format!(
+        "[0 {contents_start} {contents_end} {}]",
+        signed.len() - contents_end
+    );
+    signed[range_at..range_at + placeholder.len()]
+        .copy_from_slice(format!("{byte_range:<width$}", width = placeholder.len()).as_bytes());
+
+    let digest = Sha256::new()
+        .chain_update(&signed[..contents_start])
+        .chain_update(&signed[contents_end..])
+        .finalize();
+    let signature = match &identity.key {
+        SigningKey::Rsa(key) => signed_data::<_, rsa::pkcs1v15::Signature>(
+            &rsa::pkcs1v15::SigningKey::<Sha256>::new(key.as_ref().clone()),
+            identity,
+            &digest,
+        )?,
+        SigningKey::P256(key) => signed_data::<_, p256::ecdsa::DerSignature>(
+            &p256::ecdsa::SigningKey::from(key),
+            identity,
+            &digest,
+        )?,
+    };
+    if signature.len() > reserve {
+        return Err("The signature is larger than the space reserved for it".into());
+    }
+
+    let hex = signature
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<String>();
+    signed[contents_start + 1..contents_start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+
+    Ok((signed, field_name))
+}
+
+/// Finds the field to sign, checking that an existing field can take a signature.
+fn signature_target(document: &Document, input: &SignInput) -> Result<(String, Target), String> {
+    let nodes = field_nodes(document)?;
+
+    if let Some(node) = input
+        .field_name
+        .as_ref()
+        .and_then(|name| nodes.iter().find(|node| &node.name == name))
+    {
+        if node.field_type != FormFieldType::Signature {
+            return Err(format!("Field {} is not a signature field", node.name));
+        }
+        let field = document
+            .get_dictionary(node.id)
+            .map_err(|e| format!("Failed to read field {}: {e}", node.name))?;
+        if field.has(b"V") {
+            return Err(format!("Field {} is already signed", node.name));
+        }
+
+        let widget = node.widgets.first().and_then(|widget_id| {
+            let widget = document.get_dictionary(*widget_id).ok()?;
+            Some((*widget_id, widget_rect(document, widget)?))
+        });
+        return Ok((
+            node.name.clone(),
+            Target::Existing {
+                field_id: node.id,
+                widget,
+            },
+        ));
+    }
+
+    let name = match &input.field_name {
+        Some(name) if !name.is_empty() => name.clone(),
+        _ => (1..)
+            .map(|n| format!("Signature{n}"))
+            .find(|name| nodes.iter().all(|node| &node.name != name))
+            .unwrap_or_default(),
+    };
+    if name.contains('.') {
+        return Err("Signature field names cannot contain periods".into());
+    }
+
+    let pages = document.get_pages();
+    let (page_id, rect) = match &input.appearance {
+        Some(appearance) => {
+            let page_id = *pages
+                .get(&(appearance.page_index as u32 + 1))
+                .ok_or(format!("Page {} not found", appearance.page_index + 1))?;
+            let height = page_height(document, page_id);
+            let PdfRect {
+                left,
+                top,
+                right,
+                bottom,
+            } = appearance.rect;
+            let rect = [
+                left.min(right),
+                height - top.max(bottom),
+                left.max(right),
+                height - top.min(bottom),
+            ];
+            (page_id, rect)
+        }
+        // Invisible signatures have an empty widget on the first page
+        None => (
+            *pages.values().next().ok_or("The document has no pages")?,
+            [0.0; 4],
+        ),
+    };
+
+    Ok((
+        name.clone(),
+        Target::New {
+            name,
+            page_id,
+            rect,
+        },
+    ))
+}
+
+/// Form XObject showing the image on the left and the text on the right, or either
+/// alone across the whole rectangle.
+fn appearance_stream(
+    document: &mut Document,
+    width: f32,
+    height: f32,
+    image: Option<&Path>,
+    lines: &[String],
+) -> Result<ObjectId, String> {
+    let mut content = String::new();
+    let mut resources = Dictionary::new();
+
+    let text_left = match image {
+        Some(path) => {
+            let image_id = image_xobject(document, path)?;
+            let image = document
+                .get_dictionary(image_id)
+                .map_err(|e| format!("Failed to load image: {e}"))?;
+            let size = |key: &[u8]| image.get(key).and_then(Object::as_float).unwrap_or(1.0);
+            let (image_width, image_height) = (size(b"Width"), size(b"Height"));
+
+            let area = if lines.is_empty() { width } else { width / 2.0 };
+            let scale = (area / image_width).min(height / image_height);
+            let (drawn_width, drawn_height) = (image_width * scale, image_height * scale);
+            content.push_str(&format!(
+                "q {} 0 0 {} {} {} cm /Img Do Q\n",
+                num(drawn_width),
+                num(drawn_height),
+                num((area - drawn_width) / 2.0),
+                num((height - drawn_height) / 2.0)
+            ));
+            resources.set("XObject", dictionary! { "Img" => image_id });
+            area
+        }
+        None => 0.0,
+    };
+
+    if !lines.is_empty() {
+        let text_width = (width - text_left - 4.0).max(1.0);
+        let mut font_size: f32 = 10.0;
+        let wrapped = loop {
+            let max_chars = ((text_width / (font_size * 0.5)) as usize).max(1);
+            let wrapped = lines
+                .iter()
+                .flat_map(|line| wrap_text(line, max_chars))
+                .collect::<Vec<_>>();
+            if wrapped.len() as f32 * font_size * 1.2 <= height - 4.0 || font_size <= 4.0 {
+                break wrapped;
+            }
+            font_size -= 0.5;
+        };
+
+        content.push_str(&format!(
+            "BT /Helv {} Tf 0 g {} {} Td\n",
+            num(font_size),
+            num(text_left + 2.0),
+            num(height - 2.0 - font_size)
+        ));
+        for line in wrapped {
+            content.push_str(&format!(
+                "<{}> Tj 0 {} Td\n",
+                win_ansi_hex(&line),
+                num(-font_size * 1.2)
+            ));
+        }
+        content.push_str("ET\n");
+
+        resources.set(
+            "Font",
+            dictionary! {
+                "Helv" => dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => "Helvetica",
+                    "Encoding" => "WinAnsiEncoding",
+                },
+            },
+        );
+    }
+
+    let stream = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), Object::Real(width), Object::Real(height)],
+            "Resources" => resources,
+        },
+        content.into_bytes(),
+    );
+    Ok(document.add_object(stream))
+}
+
+/// CMS signed data over an external digest, with the signed attributes PAdES
+/// requires. The signing time is left to the `/M` entry as PAdES asks.
+fn signed_data<S, Signature>(
+    signer: &S,
+    identity: &SigningIdentity,
+    digest: &[u8],
+) -> Result<Vec<u8>, String>
+where
+    S: Keypair + DynSignatureAlgorithmIdentifier + Signer<Signature>,
+    Signature: SignatureBitStringEncoding,
+{
+    let failed = |e: &dyn std::fmt::Display| format!("Failed to create signature: {e}");
+
+    let certificate = &identity.certificate;
+    let content = EncapsulatedContentInfo {
+        econtent_type: rfc5911::ID_DATA,
+        econtent: None,
+    };
+    let digest_algorithm = AlgorithmIdentifierOwned {
+        oid: rfc5912::ID_SHA_256,
+        parameters: None,
+    };
+    let sid = SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+        issuer: certificate.tbs_certificate.issuer.clone(),
+        serial_number: certificate.tbs_certificate.serial_number.clone(),
+    });
+
+    let mut signer_info = SignerInfoBuilder::new(
+        signer,
+        sid,
+        digest_algorithm.clone(),
+        &content,
+        Some(digest),
+    )
+    .map_err(|e| failed(&e))?;
+    signer_info
+        .add_signed_attribute(signing_certificate(certificate)?)
+        .map_err(|e| failed(&e))?;
+
+    let mut builder = SignedDataBuilder::new(&content);
+    builder
+        .add_digest_algorithm(digest_algorithm)
+        .map_err(|e| failed(&e))?;
+    for certificate in std::iter::once(certificate).chain(&identity.chain) {
+        builder
+            .add_certificate(CertificateChoices::Certificate(certificate.clone()))
+            .map_err(|e| failed(&e))?;
+    }
+
+    builder
+        .add_signer_info::<_, Signature>(signer_info)
+        .map_err(|e| failed(&e))?
+        .build()
+        .map_err(|e| failed(&e))?
+        .to_der()
+        .map_err(|e| failed(&e))
+}
+
+/// `signing-certificate-v2` attribute binding the signer certificate by its
+/// SHA-256 hash (RFC 5035), with the default hash algorithm left out.
+fn signing_certificate(certificate: &x509_cert::Certificate) -> Result<Attribute, String> {
+    let failed = |e: der::Error| format!("Failed to create signature: {e}");
+
+    let der = certificate.to_der().map_err(failed)?;
+    let hash = OctetString::new(Sha256::digest(&der).to_vec()).map_err(failed)?;
+    let cert_id = Any::new(Tag::Sequence, hash.to_der().map_err(failed)?).map_err(failed)?;
+    let certs = Any::new(Tag::Sequence, cert_id.to_der().map_err(failed)?).map_err(failed)?;
+    let value = Any::new(Tag::Sequence, certs.to_der().map_err(failed)?).map_err(failed)?;
+
+    Ok(Attribute {
+        oid: SIGNING_CERTIFICATE_V2,
+        values: SetOfVec::try_from(vec![value]).map_err(failed)?,
+    })
+}
+
+/// The document's AcroForm as an indirect object of the update, creating it when
+/// the document has no form.
+fn acro_form_id(
+    incremental: &mut IncrementalDocument,
+    catalog_id: ObjectId,
+) -> Result<ObjectId, String> {
+    if let Ok(Object::Reference(id)) = incremental
+        .new_document
+        .get_dictionary(catalog_id)
+        .and_then(|catalog| catalog.get(b"AcroForm"))
+    {
+        return Ok(*id);
+    }
+
+    let acro_form = incremental
+        .get_prev_documents()
+        .get_dictionary(catalog_id)
+        .and_then(|catalog| catalog.get(b"AcroForm"))
+        .cloned();
+    let id = match acro_form {
+        Ok(Object::Reference(id)) => {
+            clone_object(incremental, id)?;
+            id
+        }
+        other => {
+            let acro_form = match other {
+                Ok(Object::Dictionary(acro_form)) => acro_form,
+                _ => dictionary! { "Fields" => Vec::<Object>::new() },
+            };
+            let id = incremental.new_document.add_object(acro_form);
+            clone_object(incremental, catalog_id)?;
+            dictionary_mut(&mut incremental.new_document, catalog_id)?.set("AcroForm", id);
+            id
+        }
+    };
+
+    Ok(id)
+}
+
+/// Appends a reference to an array entry of `owner_id`, copying into the update
+/// whichever object holds the array.
+fn push_reference(
+    incremental: &mut IncrementalDocument,
+    owner_id: ObjectId,
+    key: &[u8],
+    value: ObjectId,
+) -> Result<(), String> {
+    let array_id = incremental
+        .new_document
+        .get_dictionary(owner_id)
+        .or_else(|_| incremental.get_prev_documents().get_dictionary(owner_id))
+        .and_then(|owner| owner.get(key))
+        .and_then(Object::as_reference)
+        .ok();
+
+    match array_id {
+        Some(array_id) => {
+            clone_object(incremental, array_id)?;
+            incremental
+                .new_document
+                .get_object_mut(array_id)
+                .and_then(Object::as_array_mut)
+                .map_err(|e| format!("Failed to update document: {e}"))?
+                .push(value.into());
+        }
+        None => {
+            clone_object(incremental, owner_id)?;
+            let owner = dictionary_mut(&mut incremental.new_document, owner_id)?;
+            match owner.get_mut(key).and_then(Object::as_array_mut) {
+                Ok(items) => items.push(value.into()),
+                Err(_) => owner.set(key, vec![Object::from(value)]),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn clone_object(incremental: &mut IncrementalDocument, id: ObjectId) -> Result<(), String> {
+    incremental
+        .opt_clone_object_to_new_document(id)
+        .map_err(|e| format!("Failed to update document: {e}"))
+}
+
+fn dictionary_mut(document: &mut Document, id: ObjectId) -> Result<&mut Dictionary, String> {
+    document
+        .get_dictionary_mut(id)
+        .map_err(|e| format!("Failed to update document: {e}"))
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| from + position)
+}
+
+/// Formats a `D:YYYYMMDDHHmmSS` date for display, e.g. `2024-01-02 03:04:05 UTC`.
+fn display_date(date: &str) -> String {
+    let digits = date.trim_start_matches("D:");
+    match (digits.get(0..4), digits.get(4..6), digits.get(6..8)) {
+        (Some(year), Some(month), Some(day)) => {
+            let time = match (digits.get(8..10), digits.get(10..12), digits.get(12..14)) {
+                (Some(hour), Some(minute), Some(second)) => {
+                    format!(" {hour}:{minute}:{second} UTC")
+                }
+                _ => String::new(),
+            };
+            format!("{year}-{month}-{day}{time}")
+        }
+        _ => date.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::reader::SignatureStatus;
+    use crate::utils::pkcs12::tests::test_identity;
+
+    fn document() -> Vec<u8> {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn input(field_name: Option<&str>, appearance: Option<SignatureAppearance>) -> SignInput {
+        SignInput {
+            input_path: String::new(),
+            output_path: String::new(),
+            certificate_path: String::new(),
+            password: String::new(),
+            field_name: field_name.map(str::to_string),
+            appearance,
+            reason: Some("Approved".into()),
+            location: Some("Lisbon".into()),
+            contact_info: None,
+        }
+    }
+
+    #[test]
+    fn test_sign_incrementally() {
+        let identity = test_identity();
+        let original = document();
+
+        let (signed, name) = sign_document(&original, &identity, &input(None, None)).unwrap();
+        assert_eq!(name, "Signature1");
+        assert!(signed.starts_with(&original));
+
+        let signatures = read_signatures(&signed).unwrap();
+        assert_eq!(signatures.len(), 1);
+        let signature = &signatures[0];
+        assert_eq!(
+            signature.status,
+            SignatureStatus::Valid,
+            "{:?}",
+            signature.message
+        );
+        assert_eq!(signature.sub_filter.as_deref(), Some(SUB_FILTER));
+        assert_eq!(signature.signer_name.as_deref(), Some("Test Signer"));
+        assert_eq!(signature.reason.as_deref(), Some("Approved"));
+        assert!(signature.covers_revision);
+
+        let visible = SignatureAppearance {
+            page_index: 0,
+            rect: PdfRect {
+                left: 72.0,
+                top: 600.0,
+                right: 272.0,
+                bottom: 660.0,
+            },
+            image_path: None,
+            show_text: true,
+        };
+        let (countersigned, name) =
+            sign_document(&signed, &identity, &input(Some("Witness"), Some(visible))).unwrap();
+        assert_eq!(name, "Witness");
+
+        let signatures = read_signatures(&countersigned).unwrap();
+        assert_eq!(signatures.len(), 2);
+        assert_eq!(signatures[0].status, SignatureStatus::Valid);
+        assert!(signatures[0].modified_after_signing);
+        assert_eq!(signatures[1].status, SignatureStatus::Valid);
+        assert_eq!(signatures[1].page_index, Some(0));
+        assert!(signatures[1].covers_revision);
+
+        let document = Document::load_mem(&countersigned).unwrap();
+        let widget = field_nodes(&document).unwrap()[1].widgets[0];
+        let widget = document.get_dictionary(widget).unwrap();
+        assert_eq!(
+            widget_rect(&document, widget),
+            Some([72.0, 132.0, 272.0, 192.0])
+        );
+        assert!(widget.has(b"AP"));
+
+        assert_eq!(
+            sign_document(&countersigned, &identity, &input(Some("Witness"), None))
+                .err()
+                .as_deref(),
+            Some("Field Witness is already signed")
+        );
+    }
+}
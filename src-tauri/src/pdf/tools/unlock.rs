@@ -1,24 +1,158 @@
-use lopdf::Document;
-use serde::Deserialize;
+use lopdf::{Document, LoadOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::reader::{document_security, DocumentPermissions, EncryptionAlgorithm};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UnlockInput {
     pub input_path: String,
     pub output_path: String,
-    pub password: String,
+
+    /// User or owner password. Not needed when the PDF opens without a password.
+    #[serde(default)]
+    pub password: Option<String>,
 }
 
-pub fn unlock_pdf(input: UnlockInput) -> Result<(), String> {
-    let mut doc = Document::load_with_password(&input.input_path, &input.password)
-        .map_err(|e| format!("Failed to load PDF: {e}"))?;
+/// Kind of protection removed from a document.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovedProtection {
+    /// The document opened without a password and only restricted its use
+    Restrictions,
 
-    if doc.is_encrypted() {
-        return Err("Failed to remove encryption from the PDF. The file may be corrupted.".into());
-    }
+    /// The document needed a password to open
+    OpenPassword,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockReport {
+    pub removed: RemovedProtection,
+    pub algorithm: Option<EncryptionAlgorithm>,
+
+    /// Permissions the document granted before it was unlocked
+    pub permissions: DocumentPermissions,
+}
+
+pub fn unlock_pdf(input: UnlockInput) -> Result<UnlockReport, String> {
+    let bytes = std::fs::read(&input.input_path).map_err(|e| format!("Failed to read PDF: {e}"))?;
+
+    let (mut doc, report) = unlock_document(&bytes, input.password.as_deref())?;
 
     doc.save(&input.output_path)
         .map_err(|e| format!("Failed to save PDF: {e}"))?;
 
-    Ok(())
+    Ok(report)
+}
+
+/// Loads an encrypted document and drops its encryption. Documents without an
+/// open password are decrypted on load and need no password.
+pub fn unlock_document(
+    bytes: &[u8],
+    password: Option<&str>,
+) -> Result<(Document, UnlockReport), String> {
+    let doc = Document::load_mem(bytes).map_err(|e| format!("Failed to load PDF: {e}"))?;
+    let security = document_security(&doc);
+
+    if !security.encrypted {
+        return Err("Input PDF is not encrypted".into());
+    }
+
+    let (mut doc, removed) = if security.requires_password {
+        let password = password
+            .filter(|password| !password.is_empty())
+            .ok_or("This PDF needs a password to open. Enter its user or owner password.")?;
+
+        let doc = Document::load_mem_with_options(bytes, LoadOptions::with_password(password))
+            .map_err(|_| "Incorrect password".to_string())?;
+        if doc.is_encrypted() {
+            return Err("Incorrect password".into());
+        }
+        (doc, RemovedProtection::OpenPassword)
+    } else {
+        (doc, RemovedProtection::Restrictions)
+    };
+
+    // Saving would otherwise encrypt the document again
+    doc.encryption_state = None;
+
+    Ok((
+        doc,
+        UnlockReport {
+            removed,
+            algorithm: security.algorithm,
+            permissions: security.permissions,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::tools::encrypt_document;
+    use lopdf::{dictionary, Object, Permissions};
+
+    fn encrypted(user_password: &str) -> Vec<u8> {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => Vec::<Object>::new(),
+                "Count" => 0,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+
+        encrypt_document(
+            &mut doc,
+            EncryptionAlgorithm::Aes128,
+            "owner-secret",
+            user_password,
+            Permissions::PRINTABLE,
+        )
+        .unwrap();
+
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn saved(mut doc: Document) -> Document {
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        Document::load_mem(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_unlock_restrictions() {
+        let (doc, report) = unlock_document(&encrypted(""), None).unwrap();
+        assert_eq!(report.removed, RemovedProtection::Restrictions);
+        assert_eq!(report.algorithm, Some(EncryptionAlgorithm::Aes128));
+        assert!(report.permissions.allow_printing);
+        assert!(!report.permissions.allow_copying);
+
+        let doc = saved(doc);
+        assert!(!doc.trailer.has(b"Encrypt"));
+        assert!(!document_security(&doc).encrypted);
+    }
+
+    #[test]
+    fn test_unlock_open_password() {
+        let bytes = encrypted("user-secret");
+        assert!(unlock_document(&bytes, None).is_err());
+        assert!(unlock_document(&bytes, Some("wrong")).is_err());
+
+        for password in ["user-secret", "owner-secret"] {
+            let (doc, report) = unlock_document(&bytes, Some(password)).unwrap();
+            assert_eq!(report.removed, RemovedProtection::OpenPassword);
+            assert!(!saved(doc).trailer.has(b"Encrypt"));
+        }
+    }
 }
@@ -2,9 +2,16 @@ use std::path::PathBuf;
 
 use flume::Sender;
 
-use crate::pdf::reader::{Annotation, RenderedTile};
-use crate::pdf::reader::{PageText, RenderedPage, SearchHit};
-use crate::pdf::tools::{ImageToPdfOptions, PageSelectionInput, ProtectInput, UnlockInput};
+use crate::pdf::reader::{
+    Annotation, AnnotationExchangeFormat, AnnotationImportSummary, AnnotationQuery,
+    AnnotationQueryResult, AnnotationStorage, DocumentSecurity, FormField, FormFieldUpdate,
+    HighlightGrouping, HighlightSummaryFormat, RedactionPattern, RedactionSummary, RenderedTile,
+    RestrictedAction, ReviewStatus, SignatureInfo,
+};
+use crate::pdf::reader::{PageText, RenderOptions, RenderedPage, SearchHit};
+use crate::pdf::tools::{
+    ImageToPdfOptions, PageSelectionInput, ProtectInput, UnlockInput, UnlockReport,
+};
 use crate::pdf::{Bookmarks, DocumentId, PdfInfo};
 
 pub enum PdfEvent {
@@ -17,6 +24,7 @@ pub enum PdfEvent {
         id: DocumentId,
         page_index: u16,
         target_width: i32,
+        options: RenderOptions,
         reply: Sender<Result<RenderedPage, String>>,
     },
     RenderTile {
@@ -27,6 +35,7 @@ pub enum PdfEvent {
         tile_y: i32,
         tile_width: i32,
         tile_height: i32,
+        options: RenderOptions,
         reply: Sender<Result<RenderedTile, String>>,
     },
     Info {
@@ -62,17 +71,130 @@ pub enum PdfEvent {
     },
     GetAnnotations {
         id: DocumentId,
+        sidecar_dir: PathBuf,
         reply: Sender<Result<Vec<Annotation>, String>>,
     },
+    QueryAnnotations {
+        id: DocumentId,
+        query: AnnotationQuery,
+        sidecar_dir: PathBuf,
+        reply: Sender<Result<AnnotationQueryResult, String>>,
+    },
     AddAnnotation {
         id: DocumentId,
         annotation: Annotation,
+        sidecar_dir: PathBuf,
+        reply: Sender<Result<(), String>>,
+    },
+    AddStamp {
+        id: DocumentId,
+        annotation: Annotation,
+        stamp_dir: PathBuf,
+        sidecar_dir: PathBuf,
         reply: Sender<Result<(), String>>,
     },
     RemoveAnnotation {
         id: DocumentId,
         page_index: u16,
         annotation_id: String,
+        sidecar_dir: PathBuf,
+        reply: Sender<Result<(), String>>,
+    },
+    SetReviewState {
+        id: DocumentId,
+        page_index: u16,
+        annotation_id: String,
+        status: ReviewStatus,
+        sidecar_dir: PathBuf,
+        reply: Sender<Result<(), String>>,
+    },
+    GetAnnotationStorage {
+        id: DocumentId,
+        sidecar_dir: PathBuf,
+        reply: Sender<Result<AnnotationStorage, String>>,
+    },
+    UseSidecarAnnotations {
+        id: DocumentId,
+        sidecar_dir: PathBuf,
+        reply: Sender<Result<(), String>>,
+    },
+    BakeSidecarAnnotations {
+        id: DocumentId,
+        stamp_dir: PathBuf,
+        sidecar_dir: PathBuf,
+        dest: Option<PathBuf>,
+        reply: Sender<Result<AnnotationImportSummary, String>>,
+    },
+    ExportAnnotations {
+        id: DocumentId,
+        format: AnnotationExchangeFormat,
+        dest: PathBuf,
+        reply: Sender<Result<(), String>>,
+    },
+    ImportAnnotations {
+        id: DocumentId,
+        source: PathBuf,
+        stamp_dir: PathBuf,
+        sidecar_dir: PathBuf,
+        reply: Sender<Result<AnnotationImportSummary, String>>,
+    },
+    ExportHighlightSummary {
+        id: DocumentId,
+        format: HighlightSummaryFormat,
+        group_by: HighlightGrouping,
+        dest: PathBuf,
+        sidecar_dir: PathBuf,
+        reply: Sender<Result<(), String>>,
+    },
+    GetFormFields {
+        id: DocumentId,
+        reply: Sender<Result<Vec<FormField>, String>>,
+    },
+    FillFormFields {
+        id: DocumentId,
+        updates: Vec<FormFieldUpdate>,
+        reply: Sender<Result<(), String>>,
+    },
+    GetSignatures {
+        id: DocumentId,
+        reply: Sender<Result<Vec<SignatureInfo>, String>>,
+    },
+    /// Checks `owner_password` when given and lifts the permissions of the document
+    GetSecurity {
+        id: DocumentId,
+        owner_password: Option<String>,
+        reply: Sender<Result<DocumentSecurity, String>>,
+    },
+    /// Refuses an action the permissions of the document forbid
+    EnsureAllowed {
+        id: DocumentId,
+        action: RestrictedAction,
+        reply: Sender<Result<(), String>>,
+    },
+    /// Marks matches of the patterns with Redact annotations
+    MarkRedactions {
+        id: DocumentId,
+        patterns: Vec<RedactionPattern>,
+        sidecar_dir: PathBuf,
+        reply: Sender<Result<Vec<Annotation>, String>>,
+    },
+    /// Applies the Redact annotations, in place or to a copy at `dest`
+    ApplyRedactions {
+        id: DocumentId,
+        sidecar_dir: PathBuf,
+        dest: Option<PathBuf>,
+        reply: Sender<Result<RedactionSummary, String>>,
+    },
+    /// Checks a file for risky constructs before it is opened
+    ScanDocument {
+        path: PathBuf,
+        reply: Sender<Result<crate::pdf::reader::RiskReport, String>>,
+    },
+    /// Writes serialized bytes of an open document to `dest` and reloads it from there
+    Save {
+        id: DocumentId,
+        dest: PathBuf,
+        bytes: Vec<u8>,
         reply: Sender<Result<(), String>>,
     },
     Merge {
@@ -119,12 +241,48 @@ pub enum PdfEvent {
         input: ProtectInput,
         reply: Sender<Result<(), String>>,
     },
+    ChangeSecurity {
+        input: crate::pdf::tools::ChangeSecurityInput,
+        reply: Sender<Result<(), String>>,
+    },
     Unlock {
         input: UnlockInput,
-        reply: Sender<Result<(), String>>,
+        reply: Sender<Result<UnlockReport, String>>,
     },
     Watermark {
         input: crate::pdf::tools::WatermarkInput,
         reply: Sender<Result<(), String>>,
     },
+    Flatten {
+        input: crate::pdf::tools::FlattenInput,
+        reply: Sender<Result<crate::pdf::tools::FlattenSummary, String>>,
+    },
+    MergeAnnotations {
+        input: crate::pdf::tools::AnnotationMergeInput,
+        reply: Sender<Result<crate::pdf::tools::AnnotationMergeReport, String>>,
+    },
+    ExportFormData {
+        input: crate::pdf::tools::FormExportInput,
+        reply: Sender<Result<usize, String>>,
+    },
+    ImportFormData {
+        input: crate::pdf::tools::FormImportInput,
+        reply: Sender<Result<crate::pdf::reader::FormImportSummary, String>>,
+    },
+    FillFormBatch {
+        input: crate::pdf::tools::FormBatchInput,
+        reply: Sender<Result<crate::pdf::tools::FormBatchSummary, String>>,
+    },
+    FlattenForm {
+        input: crate::pdf::tools::FlattenFormInput,
+        reply: Sender<Result<crate::pdf::tools::FlattenSummary, String>>,
+    },
+    Sign {
+        input: crate::pdf::tools::SignInput,
+        reply: Sender<Result<SignatureInfo, String>>,
+    },
+    Sanitize {
+        input: crate::pdf::tools::SanitizeInput,
+        reply: Sender<Result<crate::pdf::tools::SanitizeReport, String>>,
+    },
 }
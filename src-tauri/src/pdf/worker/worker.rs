@@ -1,8 +1,9 @@
 use flume::{Receiver, Sender};
+use parking_lot::RwLock;
 use pdfium_render::prelude::{PdfDocument, Pdfium};
-use std::{collections::HashMap, thread};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, thread};
 
-use crate::pdf::{reader, tools, worker::PdfEvent, DocumentId};
+use crate::pdf::{editor, reader, tools, worker::PdfEvent, DocumentId};
 
 pub struct PdfWorker {
     sender: Sender<PdfEvent>,
@@ -11,11 +12,13 @@ pub struct PdfWorker {
 impl PdfWorker {
     pub fn spawn() -> Self {
         let (tx, rx) = flume::unbounded::<PdfEvent>();
+        let registry = DocumentRegistry::default();
 
         for _ in 0..4 {
             let rx = rx.clone();
+            let registry = registry.clone();
             thread::spawn(move || {
-                worker_loop(rx);
+                worker_loop(rx, registry);
             });
         }
 
@@ -27,17 +30,170 @@ impl PdfWorker {
     }
 }
 
-fn worker_loop(rx: Receiver<PdfEvent>) {
+/// Files of the open documents, shared by all worker threads.
+///
+/// Each thread loads documents into its own pdfium instance on first use. The
+/// revision of a document changes whenever its file is rewritten, telling the
+/// other threads to drop their copy and load it again.
+#[derive(Clone, Default)]
+struct DocumentRegistry {
+    entries: Arc<RwLock<HashMap<DocumentId, RegisteredDocument>>>,
+}
+
+struct RegisteredDocument {
+    path: PathBuf,
+    revision: u64,
+
+    /// The owner password was given for this document
+    owner_unlocked: bool,
+
+    /// Read from the file on first use and dropped when the file changes
+    security: Option<reader::DocumentSecurity>,
+
+    /// Fingerprint and parsed objects of the file, kept like `security`
+    source: Option<Arc<reader::SourceFile>>,
+}
+
+impl DocumentRegistry {
+    fn insert(&self, id: DocumentId, path: PathBuf) {
+        self.entries.write().insert(
+            id,
+            RegisteredDocument {
+                path,
+                revision: 0,
+                owner_unlocked: false,
+                security: None,
+                source: None,
+            },
+        );
+    }
+
+    fn remove(&self, id: &DocumentId) {
+        self.entries.write().remove(id);
+    }
+
+    /// Marks the file of a document as rewritten, optionally at a new path.
+    fn touch(&self, id: &DocumentId, path: Option<PathBuf>) {
+        if let Some(entry) = self.entries.write().get_mut(id) {
+            entry.revision += 1;
+            entry.security = None;
+            entry.source = None;
+            if let Some(path) = path {
+                entry.path = path;
+            }
+        }
+    }
+
+    /// Encryption and permissions of a document.
+    fn security(&self, id: &DocumentId) -> Result<reader::DocumentSecurity, String> {
+        let path = {
+            let entries = self.entries.read();
+            let entry = entries.get(id).ok_or("Document not found")?;
+            if let Some(security) = &entry.security {
+                return Ok(security.clone());
+            }
+            entry.path.clone()
+        };
+
+        let mut security = reader::read_security(&path)?;
+        if let Some(entry) = self.entries.write().get_mut(id) {
+            security.owner_unlocked = entry.owner_unlocked;
+            entry.security = Some(security.clone());
+        }
+        Ok(security)
+    }
+
+    /// File of a document, hashed and parsed once per revision.
+    fn source(&self, id: &DocumentId) -> Result<Arc<reader::SourceFile>, String> {
+        let (path, revision) = {
+            let entries = self.entries.read();
+            let entry = entries.get(id).ok_or("Document not found")?;
+            if let Some(source) = &entry.source {
+                return Ok(source.clone());
+            }
+            (entry.path.clone(), entry.revision)
+        };
+
+        let source = Arc::new(reader::SourceFile::load(&path)?);
+        if let Some(entry) = self.entries.write().get_mut(id) {
+            // The file may have been rewritten while it was read
+            if entry.revision == revision {
+                entry.source = Some(source.clone());
+            }
+        }
+        Ok(source)
+    }
+
+    /// Lifts the permissions of a document after its owner password was checked.
+    fn unlock_owner(&self, id: &DocumentId) {
+        if let Some(entry) = self.entries.write().get_mut(id) {
+            entry.owner_unlocked = true;
+            entry.security = None;
+        }
+    }
+
+    fn ensure_allowed(
+        &self,
+        id: &DocumentId,
+        action: reader::RestrictedAction,
+    ) -> Result<(), String> {
+        reader::ensure_allowed(&self.security(id)?, action)
+    }
+
+    /// Refuses writing annotations into a document that does not permit it.
+    /// Annotations kept in a sidecar file leave the document untouched.
+    fn ensure_annotatable(
+        &self,
+        file: &reader::SourceFile,
+        id: &DocumentId,
+        store: Option<&reader::SidecarStore>,
+    ) -> Result<(), String> {
+        if let Some(store) = store {
+            if reader::get_annotation_storage(file, store)? == reader::AnnotationStorage::Sidecar {
+                return Ok(());
+            }
+        }
+        self.ensure_allowed(id, reader::RestrictedAction::Annotate)
+    }
+
+    /// Brings a thread's paths up to date and drops documents whose file changed.
+    fn sync(
+        &self,
+        paths: &mut HashMap<DocumentId, PathBuf>,
+        revisions: &mut HashMap<DocumentId, u64>,
+        documents: &mut HashMap<DocumentId, PdfDocument>,
+    ) {
+        let entries = self.entries.read();
+
+        paths.retain(|id, _| entries.contains_key(id));
+        revisions.retain(|id, _| entries.contains_key(id));
+        documents.retain(|id, _| entries.contains_key(id));
+
+        for (id, entry) in entries.iter() {
+            if revisions.get(id) != Some(&entry.revision) {
+                documents.remove(id);
+                revisions.insert(id.clone(), entry.revision);
+            }
+            paths.insert(id.clone(), entry.path.clone());
+        }
+    }
+}
+
+fn worker_loop(rx: Receiver<PdfEvent>, registry: DocumentRegistry) {
     let pdfium = Pdfium::default();
     let mut documents: HashMap<DocumentId, PdfDocument> = HashMap::new();
-    let mut paths: HashMap<DocumentId, std::path::PathBuf> = HashMap::new();
+    let mut paths: HashMap<DocumentId, PathBuf> = HashMap::new();
+    let mut revisions: HashMap<DocumentId, u64> = HashMap::new();
 
     while let Ok(cmd) = rx.recv() {
+        registry.sync(&mut paths, &mut revisions, &mut documents);
+
         match cmd {
             PdfEvent::Open { id, path, reply } => {
                 // Just store the path and verify it exists
                 if path.exists() {
-                    paths.insert(id, path);
+                    paths.insert(id.clone(), path.clone());
+                    registry.insert(id, path);
                     let _ = reply.send(Ok(()));
                 } else {
                     let _ = reply.send(Err("File not found".to_string()));
@@ -47,10 +203,13 @@ fn worker_loop(rx: Receiver<PdfEvent>) {
                 id,
                 page_index,
                 target_width,
+                options,
                 reply,
             } => {
                 let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
-                    Ok(_) => reader::render_page(&documents, &id, page_index, target_width),
+                    Ok(_) => {
+                        reader::render_page(&documents, &id, page_index, target_width, &options)
+                    }
                     Err(e) => Err(e),
                 };
                 let _ = reply.send(result);
@@ -63,6 +222,7 @@ fn worker_loop(rx: Receiver<PdfEvent>) {
                 tile_y,
                 tile_width,
                 tile_height,
+                options,
                 reply,
             } => {
                 let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
@@ -75,6 +235,7 @@ fn worker_loop(rx: Receiver<PdfEvent>) {
                         tile_y,
                         tile_width,
                         tile_height,
+                        &options,
                     ),
                     Err(e) => Err(e),
                 };
@@ -94,8 +255,21 @@ fn worker_loop(rx: Receiver<PdfEvent>) {
             PdfEvent::Close { id, reply } => {
                 documents.remove(&id);
                 paths.remove(&id);
+                registry.remove(&id);
                 let _ = reply.send(Ok(()));
             }
+            PdfEvent::Save {
+                id,
+                dest,
+                bytes,
+                reply,
+            } => {
+                let result = editor::write_document(&mut documents, &id, &dest, &bytes);
+                if result.is_ok() {
+                    registry.touch(&id, Some(dest));
+                }
+                let _ = reply.send(result);
+            }
             PdfEvent::Bookmarks { id, reply } => {
                 let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
                     Ok(_) => reader::get_bookmarks(&documents, &id),
@@ -109,7 +283,9 @@ fn worker_loop(rx: Receiver<PdfEvent>) {
                 reply,
             } => {
                 let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
-                    Ok(_) => reader::get_text_by_page(&documents, &id, page_index),
+                    Ok(_) => registry
+                        .ensure_allowed(&id, reader::RestrictedAction::CopyText)
+                        .and_then(|_| reader::get_text_by_page(&documents, &id, page_index)),
                     Err(e) => Err(e),
                 };
                 let _ = reply.send(result);
@@ -132,9 +308,31 @@ fn worker_loop(rx: Receiver<PdfEvent>) {
                 };
                 let _ = reply.send(result);
             }
-            PdfEvent::GetAnnotations { id, reply } => {
+            PdfEvent::GetAnnotations {
+                id,
+                sidecar_dir,
+                reply,
+            } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
                 let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
-                    Ok(_) => reader::get_annotations(&documents, &id),
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        reader::get_annotations(&documents, &file, &id, Some(&store))
+                    }),
+                    Err(e) => Err(e),
+                };
+                let _ = reply.send(result);
+            }
+            PdfEvent::QueryAnnotations {
+                id,
+                query,
+                sidecar_dir,
+                reply,
+            } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        reader::query_annotations(&documents, &file, &id, &query, Some(&store))
+                    }),
                     Err(e) => Err(e),
                 };
                 let _ = reply.send(result);
@@ -142,24 +340,321 @@ fn worker_loop(rx: Receiver<PdfEvent>) {
             PdfEvent::AddAnnotation {
                 id,
                 annotation,
+                sidecar_dir,
+                reply,
+            } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        registry.ensure_annotatable(&file, &id, Some(&store))?;
+                        reader::add_annotation(&mut documents, &file, &id, annotation, Some(&store))
+                    }),
+                    Err(e) => Err(e),
+                };
+                if result.is_ok() {
+                    registry.touch(&id, None);
+                }
+                let _ = reply.send(result);
+            }
+            PdfEvent::AddStamp {
+                id,
+                annotation,
+                stamp_dir,
+                sidecar_dir,
                 reply,
             } => {
+                let library = reader::StampLibrary::new(stamp_dir);
+                let store = reader::SidecarStore::new(sidecar_dir);
                 let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
-                    Ok(_) => reader::add_annotation(&documents, &id, annotation),
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        registry.ensure_annotatable(&file, &id, Some(&store))?;
+                        reader::add_stamp(
+                            &mut documents,
+                            &file,
+                            &id,
+                            annotation,
+                            &library,
+                            Some(&store),
+                        )
+                    }),
                     Err(e) => Err(e),
                 };
+                if result.is_ok() {
+                    registry.touch(&id, None);
+                }
                 let _ = reply.send(result);
             }
             PdfEvent::RemoveAnnotation {
                 id,
                 page_index,
                 annotation_id,
+                sidecar_dir,
                 reply,
             } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
+                // Annotations kept in the PDF need the annotate permission even
+                // when new ones go to a sidecar
                 let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
-                    Ok(_) => reader::delete_annotation(&documents, &id, page_index, annotation_id),
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        if reader::delete_sidecar_annotation(&file, &annotation_id, &store)? {
+                            return Ok(());
+                        }
+                        registry.ensure_allowed(&id, reader::RestrictedAction::Annotate)?;
+                        reader::delete_annotation(
+                            &mut documents,
+                            &file,
+                            &id,
+                            page_index,
+                            &annotation_id,
+                        )
+                    }),
                     Err(e) => Err(e),
                 };
+                if result.is_ok() {
+                    registry.touch(&id, None);
+                }
+                let _ = reply.send(result);
+            }
+            PdfEvent::SetReviewState {
+                id,
+                page_index,
+                annotation_id,
+                status,
+                sidecar_dir,
+                reply,
+            } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        registry.ensure_annotatable(&file, &id, Some(&store))?;
+                        reader::set_review_state(
+                            &mut documents,
+                            &file,
+                            &id,
+                            page_index,
+                            &annotation_id,
+                            &status,
+                            Some(&store),
+                        )
+                    }),
+                    Err(e) => Err(e),
+                };
+                if result.is_ok() {
+                    registry.touch(&id, None);
+                }
+                let _ = reply.send(result);
+            }
+            PdfEvent::GetAnnotationStorage {
+                id,
+                sidecar_dir,
+                reply,
+            } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = registry
+                    .source(&id)
+                    .and_then(|file| reader::get_annotation_storage(&file, &store));
+                let _ = reply.send(result);
+            }
+            PdfEvent::UseSidecarAnnotations {
+                id,
+                sidecar_dir,
+                reply,
+            } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = registry
+                    .source(&id)
+                    .and_then(|file| reader::use_sidecar_annotations(&file, &store));
+                let _ = reply.send(result);
+            }
+            PdfEvent::BakeSidecarAnnotations {
+                id,
+                stamp_dir,
+                sidecar_dir,
+                dest,
+                reply,
+            } => {
+                let library = reader::StampLibrary::new(stamp_dir);
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        registry.ensure_annotatable(&file, &id, None)?;
+                        reader::bake_sidecar_annotations(
+                            &mut documents,
+                            &file,
+                            &id,
+                            &store,
+                            &library,
+                            dest.as_deref(),
+                        )
+                    }),
+                    Err(e) => Err(e),
+                };
+                if result.is_ok() {
+                    registry.touch(&id, None);
+                }
+                let _ = reply.send(result);
+            }
+            PdfEvent::ExportAnnotations {
+                id,
+                format,
+                dest,
+                reply,
+            } => {
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        reader::export_annotations(&documents, &file, &id, format, &dest)
+                    }),
+                    Err(e) => Err(e),
+                };
+                let _ = reply.send(result);
+            }
+            PdfEvent::ImportAnnotations {
+                id,
+                source,
+                stamp_dir,
+                sidecar_dir,
+                reply,
+            } => {
+                let library = reader::StampLibrary::new(stamp_dir);
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        registry.ensure_annotatable(&file, &id, Some(&store))?;
+                        reader::import_annotations(
+                            &mut documents,
+                            &file,
+                            &id,
+                            &source,
+                            &library,
+                            Some(&store),
+                        )
+                    }),
+                    Err(e) => Err(e),
+                };
+                if result.is_ok() {
+                    registry.touch(&id, None);
+                }
+                let _ = reply.send(result);
+            }
+            PdfEvent::ExportHighlightSummary {
+                id,
+                format,
+                group_by,
+                dest,
+                sidecar_dir,
+                reply,
+            } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        registry.ensure_allowed(&id, reader::RestrictedAction::CopyText)?;
+                        reader::export_highlight_summary(
+                            &documents,
+                            &file,
+                            &id,
+                            format,
+                            group_by,
+                            &dest,
+                            Some(&store),
+                        )
+                    }),
+                    Err(e) => Err(e),
+                };
+                let _ = reply.send(result);
+            }
+            PdfEvent::GetFormFields { id, reply } => {
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => reader::get_form_fields(&paths, &id),
+                    Err(e) => Err(e),
+                };
+                let _ = reply.send(result);
+            }
+            PdfEvent::FillFormFields { id, updates, reply } => {
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        registry.ensure_allowed(&id, reader::RestrictedAction::FillForm)?;
+                        reader::fill_form_fields(&mut documents, &file, &id, &updates)
+                    }),
+                    Err(e) => Err(e),
+                };
+                if result.is_ok() {
+                    registry.touch(&id, None);
+                }
+                let _ = reply.send(result);
+            }
+            PdfEvent::GetSignatures { id, reply } => {
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => reader::get_signatures(&paths, &id),
+                    Err(e) => Err(e),
+                };
+                let _ = reply.send(result);
+            }
+            PdfEvent::GetSecurity {
+                id,
+                owner_password,
+                reply,
+            } => {
+                let result = match (owner_password, paths.get(&id)) {
+                    (Some(password), Some(path)) => reader::verify_owner_password(path, &password)
+                        .map(|_| {
+                            registry.unlock_owner(&id);
+                        }),
+                    (_, None) => Err("Document not found".to_string()),
+                    (None, _) => Ok(()),
+                }
+                .and_then(|_| registry.security(&id));
+                let _ = reply.send(result);
+            }
+            PdfEvent::EnsureAllowed { id, action, reply } => {
+                let _ = reply.send(registry.ensure_allowed(&id, action));
+            }
+            PdfEvent::MarkRedactions {
+                id,
+                patterns,
+                sidecar_dir,
+                reply,
+            } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        registry.ensure_annotatable(&file, &id, Some(&store))?;
+                        reader::mark_redactions(&mut documents, &file, &id, &patterns, Some(&store))
+                    }),
+                    Err(e) => Err(e),
+                };
+                if result.is_ok() {
+                    registry.touch(&id, None);
+                }
+                let _ = reply.send(result);
+            }
+            PdfEvent::ApplyRedactions {
+                id,
+                sidecar_dir,
+                dest,
+                reply,
+            } => {
+                let store = reader::SidecarStore::new(sidecar_dir);
+                let result = match ensure_doc(&pdfium, &mut documents, &paths, &id) {
+                    Ok(_) => registry.source(&id).and_then(|file| {
+                        registry.ensure_annotatable(&file, &id, Some(&store))?;
+                        registry.ensure_allowed(&id, reader::RestrictedAction::Modify)?;
+                        reader::apply_redactions(
+                            &mut documents,
+                            &file,
+                            &id,
+                            Some(&store),
+                            dest.as_deref(),
+                        )
+                    }),
+                    Err(e) => Err(e),
+                };
+                if result.is_ok() {
+                    registry.touch(&id, None);
+                }
+                let _ = reply.send(result);
+            }
+            PdfEvent::ScanDocument { path, reply } => {
+                let result = reader::scan_document(&path);
                 let _ = reply.send(result);
             }
             PdfEvent::Merge {
@@ -223,6 +718,10 @@ fn worker_loop(rx: Receiver<PdfEvent>) {
                 let result = tools::protect_pdf(input);
                 let _ = reply.send(result);
             }
+            PdfEvent::ChangeSecurity { input, reply } => {
+                let result = tools::change_security_pdf(input);
+                let _ = reply.send(result);
+            }
             PdfEvent::Unlock { input, reply } => {
                 let result = tools::unlock_pdf(input);
                 let _ = reply.send(result);
@@ -231,6 +730,38 @@ fn worker_loop(rx: Receiver<PdfEvent>) {
                 let result = tools::watermark_pdf(input);
                 let _ = reply.send(result);
             }
+            PdfEvent::Flatten { input, reply } => {
+                let result = tools::flatten_pdf(input);
+                let _ = reply.send(result);
+            }
+            PdfEvent::MergeAnnotations { input, reply } => {
+                let result = tools::merge_annotation_copies(input);
+                let _ = reply.send(result);
+            }
+            PdfEvent::ExportFormData { input, reply } => {
+                let result = tools::export_form_data(input);
+                let _ = reply.send(result);
+            }
+            PdfEvent::ImportFormData { input, reply } => {
+                let result = tools::import_form_data(input);
+                let _ = reply.send(result);
+            }
+            PdfEvent::FillFormBatch { input, reply } => {
+                let result = tools::fill_form_batch(input);
+                let _ = reply.send(result);
+            }
+            PdfEvent::FlattenForm { input, reply } => {
+                let result = tools::flatten_form_pdf(input);
+                let _ = reply.send(result);
+            }
+            PdfEvent::Sign { input, reply } => {
+                let result = tools::sign_pdf(input);
+                let _ = reply.send(result);
+            }
+            PdfEvent::Sanitize { input, reply } => {
+                let result = tools::sanitize_pdf(input);
+                let _ = reply.send(result);
+            }
         }
     }
 }
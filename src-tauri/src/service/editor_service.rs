@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use flume::bounded;
+
+use crate::{
+    pdf::{
+        editor::{
+            serialize_edits, DocumentMetadata, EditHistory, EditOperation, SaveMode, SaveOutput,
+        },
+        reader::StampLibrary,
+        worker::PdfEvent,
+    },
+    state::AppState,
+};
+
+pub fn apply_edit(
+    state: &AppState,
+    id: String,
+    operation: EditOperation,
+    stamp_dir: PathBuf,
+) -> Result<EditHistory, String> {
+    let mut manager = state.manager.write();
+    let (tx, rx) = bounded(1);
+
+    manager
+        .worker()
+        .sender()
+        .send(PdfEvent::EnsureAllowed {
+            id: id.clone(),
+            action: operation.restricted_action(),
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending permission check: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving permission check: {e}"))??;
+
+    let session = manager.session(&id)?;
+    session.apply(operation, Some(&StampLibrary::new(stamp_dir)))?;
+
+    Ok(session.history())
+}
+
+pub fn undo_edit(state: &AppState, id: String) -> Result<EditHistory, String> {
+    let mut manager = state.manager.write();
+    let session = manager.session(&id)?;
+
+    session.undo()?;
+
+    Ok(session.history())
+}
+
+pub fn redo_edit(state: &AppState, id: String) -> Result<EditHistory, String> {
+    let mut manager = state.manager.write();
+    let session = manager.session(&id)?;
+
+    session.redo()?;
+
+    Ok(session.history())
+}
+
+pub fn get_edit_history(state: &AppState, id: String) -> Result<EditHistory, String> {
+    let manager = state.manager.read();
+
+    Ok(manager
+        .existing_session(&id)
+        .map(|session| session.history())
+        .unwrap_or_default())
+}
+
+/// Saves the open document to its file or, with `dest`, to a new file that the
+/// document is then read from.
+pub fn save_pdf(state: &AppState, id: String, dest: Option<String>) -> Result<SaveMode, String> {
+    let mut manager = state.manager.write();
+    let path = manager.path(&id).ok_or("Document not found")?.clone();
+    let dest = dest.map(PathBuf::from).unwrap_or_else(|| path.clone());
+
+    let output = match manager.existing_session(&id) {
+        Some(session) => {
+            if session.is_stale() {
+                return Err(
+                    "The file was changed by another program; reopen it before saving".to_string(),
+                );
+            }
+
+            let original =
+                std::fs::read(session.path()).map_err(|e| format!("Failed to read PDF: {e}"))?;
+            serialize_edits(original, session.document())?
+        }
+        // Without edits there is nothing to save; Save As copies the file.
+        None if dest == path => return Ok(SaveMode::Unchanged),
+        None => SaveOutput {
+            bytes: std::fs::read(&path).map_err(|e| format!("Failed to read PDF: {e}"))?,
+            mode: SaveMode::Unchanged,
+        },
+    };
+
+    let mode = output.mode;
+    if mode != SaveMode::Unchanged || dest != path {
+        let (tx, rx) = bounded(1);
+
+        manager
+            .worker()
+            .sender()
+            .send(PdfEvent::Save {
+                id: id.clone(),
+                dest: dest.clone(),
+                bytes: output.bytes,
+                reply: tx,
+            })
+            .map_err(|e| format!("Error sending save command: {e}"))?;
+
+        rx.recv()
+            .map_err(|e| format!("Error receiving save result: {e}"))??;
+    }
+
+    manager.set_path(&id, dest.clone());
+    if let Some(session) = manager.existing_session_mut(&id) {
+        session.mark_saved(dest);
+    }
+
+    Ok(mode)
+}
+
+pub fn discard_edits(state: &AppState, id: String) -> Result<(), String> {
+    state.manager.write().discard_session(&id);
+
+    Ok(())
+}
+
+pub fn get_document_metadata(state: &AppState, id: String) -> Result<DocumentMetadata, String> {
+    let mut manager = state.manager.write();
+    let session = manager.session(&id)?;
+
+    Ok(session.metadata())
+}
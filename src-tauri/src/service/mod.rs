@@ -1,2 +1,3 @@
+pub mod editor_service;
 pub mod reader_service;
 pub mod tools_service;
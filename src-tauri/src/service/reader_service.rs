@@ -1,10 +1,16 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{
     pdf::{
-        reader::{Annotation, PageText, RenderedPage, RenderedTile, SearchHit},
+        reader::{
+            Annotation, AnnotationExchangeFormat, AnnotationImportSummary, AnnotationQuery,
+            AnnotationQueryResult, AnnotationStorage, DocumentSecurity, FormField, FormFieldUpdate,
+            HighlightGrouping, HighlightSummaryFormat, PageText, RedactionPattern,
+            RedactionSummary, RenderOptions, RenderedPage, RenderedTile, ReviewStatus, RiskReport,
+            SearchHit, SignatureInfo, StampDefinition, StampLibrary,
+        },
         worker::PdfEvent,
-        Bookmarks, PdfInfo,
+        Bookmarks, DocumentId, PdfInfo,
     },
     state::AppState,
 };
@@ -36,6 +42,7 @@ pub fn render_page(
     id: String,
     page_index: u16,
     target_width: i32,
+    options: RenderOptions,
 ) -> Result<RenderedPage, String> {
     let manager = state.manager.read();
     let worker = manager.worker();
@@ -48,6 +55,7 @@ pub fn render_page(
             id,
             page_index,
             target_width,
+            options,
             reply: tx,
         })
         .map_err(|e| format!("Error sending render command: {e}"))?;
@@ -65,6 +73,7 @@ pub fn render_tile(
     tile_y: i32,
     tile_width: i32,
     tile_height: i32,
+    options: RenderOptions,
 ) -> Result<RenderedTile, String> {
     let manager = state.manager.read();
     let worker = manager.worker();
@@ -81,6 +90,7 @@ pub fn render_tile(
             tile_y,
             tile_width,
             tile_height,
+            options,
             reply: tx,
         })
         .map_err(|e| format!("Error sending render tile command: {e}"))?;
@@ -105,7 +115,8 @@ pub fn get_pdf_info(state: &AppState, id: String) -> Result<PdfInfo, String> {
 }
 
 pub fn close_pdf(state: &AppState, id: String) -> Result<(), String> {
-    let manager = state.manager.read();
+    let mut manager = state.manager.write();
+    manager.close(&id);
     let worker = manager.worker();
 
     let (tx, rx) = bounded(1);
@@ -199,7 +210,11 @@ pub fn generate_preview(
         .map_err(|e| format!("Error receiving preview result: {e}"))?
 }
 
-pub fn get_annotations(state: &AppState, id: String) -> Result<Vec<Annotation>, String> {
+pub fn get_annotations(
+    state: &AppState,
+    id: String,
+    sidecar_dir: PathBuf,
+) -> Result<Vec<Annotation>, String> {
     let manager = state.manager.read();
     let worker = manager.worker();
 
@@ -207,14 +222,23 @@ pub fn get_annotations(state: &AppState, id: String) -> Result<Vec<Annotation>,
 
     worker
         .sender()
-        .send(PdfEvent::GetAnnotations { id, reply: tx })
+        .send(PdfEvent::GetAnnotations {
+            id,
+            sidecar_dir,
+            reply: tx,
+        })
         .map_err(|e| format!("Error sending get annotations command: {e}"))?;
 
     rx.recv()
         .map_err(|e| format!("Error receiving get annotations result: {e}"))?
 }
 
-pub fn add_annotation(state: &AppState, id: String, annotation: Annotation) -> Result<(), String> {
+pub fn query_annotations(
+    state: &AppState,
+    id: String,
+    query: AnnotationQuery,
+    sidecar_dir: PathBuf,
+) -> Result<AnnotationQueryResult, String> {
     let manager = state.manager.read();
     let worker = manager.worker();
 
@@ -222,15 +246,92 @@ pub fn add_annotation(state: &AppState, id: String, annotation: Annotation) -> R
 
     worker
         .sender()
-        .send(PdfEvent::AddAnnotation {
+        .send(PdfEvent::QueryAnnotations {
             id,
+            query,
+            sidecar_dir,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending query annotations command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving query annotations result: {e}"))?
+}
+
+pub fn add_annotation(
+    state: &AppState,
+    id: String,
+    annotation: Annotation,
+    stamp_dir: PathBuf,
+    sidecar_dir: PathBuf,
+) -> Result<(), String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::AddAnnotation {
+            id: id.clone(),
             annotation,
+            sidecar_dir,
             reply: tx,
         })
         .map_err(|e| format!("Error sending add annotation command: {e}"))?;
 
     rx.recv()
-        .map_err(|e| format!("Error receiving add annotation result: {e}"))?
+        .map_err(|e| format!("Error receiving add annotation result: {e}"))??;
+
+    drop(manager);
+    rebase_session(state, &id, stamp_dir)
+}
+
+pub fn add_stamp(
+    state: &AppState,
+    id: String,
+    annotation: Annotation,
+    stamp_dir: PathBuf,
+    sidecar_dir: PathBuf,
+) -> Result<(), String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::AddStamp {
+            id: id.clone(),
+            annotation,
+            stamp_dir: stamp_dir.clone(),
+            sidecar_dir,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending add stamp command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving add stamp result: {e}"))??;
+
+    drop(manager);
+    rebase_session(state, &id, stamp_dir)
+}
+
+pub fn list_stamps(stamp_dir: PathBuf) -> Result<Vec<StampDefinition>, String> {
+    StampLibrary::new(stamp_dir).list()
+}
+
+pub fn add_custom_stamp(
+    stamp_dir: PathBuf,
+    path: String,
+    name: String,
+    page_index: Option<u16>,
+) -> Result<StampDefinition, String> {
+    StampLibrary::new(stamp_dir).add(Path::new(&path), &name, page_index)
+}
+
+pub fn remove_custom_stamp(stamp_dir: PathBuf, stamp_id: String) -> Result<(), String> {
+    StampLibrary::new(stamp_dir).remove(&stamp_id)
 }
 
 pub fn remove_annotation(
@@ -238,6 +339,8 @@ pub fn remove_annotation(
     id: String,
     page_index: u16,
     annotation_id: String,
+    stamp_dir: PathBuf,
+    sidecar_dir: PathBuf,
 ) -> Result<(), String> {
     let manager = state.manager.read();
     let worker = manager.worker();
@@ -247,13 +350,387 @@ pub fn remove_annotation(
     worker
         .sender()
         .send(PdfEvent::RemoveAnnotation {
-            id,
+            id: id.clone(),
             page_index,
             annotation_id,
+            sidecar_dir,
             reply: tx,
         })
         .map_err(|e| format!("Error sending remove annotation command: {e}"))?;
 
     rx.recv()
-        .map_err(|e| format!("Error receiving remove annotation result: {e}"))?
+        .map_err(|e| format!("Error receiving remove annotation result: {e}"))??;
+
+    drop(manager);
+    rebase_session(state, &id, stamp_dir)
+}
+
+pub fn set_review_state(
+    state: &AppState,
+    id: String,
+    page_index: u16,
+    annotation_id: String,
+    status: ReviewStatus,
+    stamp_dir: PathBuf,
+    sidecar_dir: PathBuf,
+) -> Result<(), String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::SetReviewState {
+            id: id.clone(),
+            page_index,
+            annotation_id,
+            status,
+            sidecar_dir,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending set review state command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving set review state result: {e}"))??;
+
+    drop(manager);
+    rebase_session(state, &id, stamp_dir)
+}
+
+pub fn get_annotation_storage(
+    state: &AppState,
+    id: String,
+    sidecar_dir: PathBuf,
+) -> Result<AnnotationStorage, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::GetAnnotationStorage {
+            id,
+            sidecar_dir,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending get annotation storage command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving get annotation storage result: {e}"))?
+}
+
+pub fn use_sidecar_annotations(
+    state: &AppState,
+    id: String,
+    sidecar_dir: PathBuf,
+) -> Result<(), String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::UseSidecarAnnotations {
+            id,
+            sidecar_dir,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending use sidecar annotations command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving use sidecar annotations result: {e}"))?
+}
+
+pub fn bake_sidecar_annotations(
+    state: &AppState,
+    id: String,
+    stamp_dir: PathBuf,
+    sidecar_dir: PathBuf,
+    dest: Option<String>,
+) -> Result<AnnotationImportSummary, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::BakeSidecarAnnotations {
+            id: id.clone(),
+            stamp_dir: stamp_dir.clone(),
+            sidecar_dir,
+            dest: dest.map(PathBuf::from),
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending bake sidecar annotations command: {e}"))?;
+
+    let result = rx
+        .recv()
+        .map_err(|e| format!("Error receiving bake sidecar annotations result: {e}"))??;
+
+    drop(manager);
+    rebase_session(state, &id, stamp_dir)?;
+
+    Ok(result)
+}
+
+pub fn export_annotations(
+    state: &AppState,
+    id: String,
+    format: AnnotationExchangeFormat,
+    dest: String,
+) -> Result<(), String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::ExportAnnotations {
+            id,
+            format,
+            dest: PathBuf::from(dest),
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending export annotations command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving export annotations result: {e}"))?
+}
+
+pub fn import_annotations(
+    state: &AppState,
+    id: String,
+    source: String,
+    stamp_dir: PathBuf,
+    sidecar_dir: PathBuf,
+) -> Result<AnnotationImportSummary, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::ImportAnnotations {
+            id: id.clone(),
+            source: PathBuf::from(source),
+            stamp_dir: stamp_dir.clone(),
+            sidecar_dir,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending import annotations command: {e}"))?;
+
+    let result = rx
+        .recv()
+        .map_err(|e| format!("Error receiving import annotations result: {e}"))??;
+
+    drop(manager);
+    rebase_session(state, &id, stamp_dir)?;
+
+    Ok(result)
+}
+
+pub fn export_highlight_summary(
+    state: &AppState,
+    id: String,
+    format: HighlightSummaryFormat,
+    group_by: HighlightGrouping,
+    dest: String,
+    sidecar_dir: PathBuf,
+) -> Result<(), String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::ExportHighlightSummary {
+            id,
+            format,
+            group_by,
+            dest: PathBuf::from(dest),
+            sidecar_dir,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending export highlight summary command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving export highlight summary result: {e}"))?
+}
+
+pub fn get_form_fields(state: &AppState, id: String) -> Result<Vec<FormField>, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::GetFormFields { id, reply: tx })
+        .map_err(|e| format!("Error sending get form fields command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving get form fields result: {e}"))?
+}
+
+pub fn fill_form_fields(
+    state: &AppState,
+    id: String,
+    updates: Vec<FormFieldUpdate>,
+    stamp_dir: PathBuf,
+) -> Result<(), String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::FillFormFields {
+            id: id.clone(),
+            updates,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending fill form fields command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving fill form fields result: {e}"))??;
+
+    drop(manager);
+    rebase_session(state, &id, stamp_dir)
+}
+
+pub fn get_signatures(state: &AppState, id: String) -> Result<Vec<SignatureInfo>, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::GetSignatures { id, reply: tx })
+        .map_err(|e| format!("Error sending get signatures command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving get signatures result: {e}"))?
+}
+
+pub fn get_document_security(
+    state: &AppState,
+    id: String,
+    owner_password: Option<String>,
+) -> Result<DocumentSecurity, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::GetSecurity {
+            id,
+            owner_password,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending get security command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving get security result: {e}"))?
+}
+
+pub fn mark_redactions(
+    state: &AppState,
+    id: String,
+    patterns: Vec<RedactionPattern>,
+    stamp_dir: PathBuf,
+    sidecar_dir: PathBuf,
+) -> Result<Vec<Annotation>, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::MarkRedactions {
+            id: id.clone(),
+            patterns,
+            sidecar_dir,
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending mark redactions command: {e}"))?;
+
+    let result = rx
+        .recv()
+        .map_err(|e| format!("Error receiving mark redactions result: {e}"))??;
+
+    drop(manager);
+    rebase_session(state, &id, stamp_dir)?;
+
+    Ok(result)
+}
+
+pub fn apply_redactions(
+    state: &AppState,
+    id: String,
+    stamp_dir: PathBuf,
+    sidecar_dir: PathBuf,
+    dest: Option<String>,
+) -> Result<RedactionSummary, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::ApplyRedactions {
+            id: id.clone(),
+            sidecar_dir,
+            dest: dest.map(PathBuf::from),
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending apply redactions command: {e}"))?;
+
+    let result = rx
+        .recv()
+        .map_err(|e| format!("Error receiving apply redactions result: {e}"))??;
+
+    drop(manager);
+    rebase_session(state, &id, stamp_dir)?;
+
+    Ok(result)
+}
+
+/// Replays the unsaved edits of the document's edit session on top of its file after
+/// the worker rewrote it, so the session is not left editing an outdated copy.
+fn rebase_session(state: &AppState, id: &DocumentId, stamp_dir: PathBuf) -> Result<(), String> {
+    match state.manager.write().existing_session_mut(id) {
+        Some(session) => session.rebase(Some(&StampLibrary::new(stamp_dir))),
+        None => Ok(()),
+    }
+}
+
+pub fn scan_document(state: &AppState, path: String) -> Result<RiskReport, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::ScanDocument {
+            path: path.into(),
+            reply: tx,
+        })
+        .map_err(|e| format!("Error sending scan document command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving scan document result: {e}"))?
 }
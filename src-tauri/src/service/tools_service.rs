@@ -202,7 +202,28 @@ pub async fn protect_pdf(state: &AppState, input: tools::ProtectInput) -> Result
         .map_err(|e| format!("Error receiving protect result: {e}"))?
 }
 
-pub async fn unlock_pdf(state: &AppState, input: tools::UnlockInput) -> Result<(), String> {
+pub async fn change_security_pdf(
+    state: &AppState,
+    input: tools::ChangeSecurityInput,
+) -> Result<(), String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = flume::bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::ChangeSecurity { input, reply: tx })
+        .map_err(|e| format!("Error sending change security command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving change security result: {e}"))?
+}
+
+pub async fn unlock_pdf(
+    state: &AppState,
+    input: tools::UnlockInput,
+) -> Result<tools::UnlockReport, String> {
     let manager = state.manager.read();
     let worker = manager.worker();
 
@@ -231,3 +252,147 @@ pub async fn watermark_pdf(state: &AppState, input: tools::WatermarkInput) -> Re
     rx.recv()
         .map_err(|e| format!("Error receiving watermark result: {e}"))?
 }
+
+pub async fn flatten_pdf(
+    state: &AppState,
+    input: tools::FlattenInput,
+) -> Result<tools::FlattenSummary, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = flume::bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::Flatten { input, reply: tx })
+        .map_err(|e| format!("Error sending flatten command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving flatten result: {e}"))?
+}
+
+pub async fn merge_annotations(
+    state: &AppState,
+    input: tools::AnnotationMergeInput,
+) -> Result<tools::AnnotationMergeReport, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = flume::bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::MergeAnnotations { input, reply: tx })
+        .map_err(|e| format!("Error sending merge annotations command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving merge annotations result: {e}"))?
+}
+
+pub async fn export_form_data(
+    state: &AppState,
+    input: tools::FormExportInput,
+) -> Result<usize, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = flume::bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::ExportFormData { input, reply: tx })
+        .map_err(|e| format!("Error sending export form data command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving export form data result: {e}"))?
+}
+
+pub async fn import_form_data(
+    state: &AppState,
+    input: tools::FormImportInput,
+) -> Result<crate::pdf::reader::FormImportSummary, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = flume::bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::ImportFormData { input, reply: tx })
+        .map_err(|e| format!("Error sending import form data command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving import form data result: {e}"))?
+}
+
+pub async fn fill_form_batch(
+    state: &AppState,
+    input: tools::FormBatchInput,
+) -> Result<tools::FormBatchSummary, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = flume::bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::FillFormBatch { input, reply: tx })
+        .map_err(|e| format!("Error sending fill form batch command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving fill form batch result: {e}"))?
+}
+
+pub async fn flatten_form_pdf(
+    state: &AppState,
+    input: tools::FlattenFormInput,
+) -> Result<tools::FlattenSummary, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = flume::bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::FlattenForm { input, reply: tx })
+        .map_err(|e| format!("Error sending flatten form command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving flatten form result: {e}"))?
+}
+
+pub async fn sign_pdf(
+    state: &AppState,
+    input: tools::SignInput,
+) -> Result<crate::pdf::reader::SignatureInfo, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = flume::bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::Sign { input, reply: tx })
+        .map_err(|e| format!("Error sending sign command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving sign result: {e}"))?
+}
+
+pub async fn sanitize_pdf(
+    state: &AppState,
+    input: tools::SanitizeInput,
+) -> Result<tools::SanitizeReport, String> {
+    let manager = state.manager.read();
+    let worker = manager.worker();
+
+    let (tx, rx) = flume::bounded(1);
+
+    worker
+        .sender()
+        .send(PdfEvent::Sanitize { input, reply: tx })
+        .map_err(|e| format!("Error sending sanitize command: {e}"))?;
+
+    rx.recv()
+        .map_err(|e| format!("Error receiving sanitize result: {e}"))?
+}
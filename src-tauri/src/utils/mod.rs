@@ -1 +1,4 @@
+pub mod fs;
+pub mod objects;
 pub mod page_selection;
+pub mod pkcs12;
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// Deep-copies an object from `source`, adding the objects it references to
+/// `document`. `ids` maps source object ids to ids that were already copied.
+pub fn copy_object(
+    document: &mut Document,
+    source: &Document,
+    object: &Object,
+    ids: &mut HashMap<ObjectId, ObjectId>,
+) -> Object {
+    match object {
+        Object::Reference(source_id) => {
+            if let Some(target_id) = ids.get(source_id) {
+                return Object::Reference(*target_id);
+            }
+
+            let target_id = document.new_object_id();
+            ids.insert(*source_id, target_id);
+            let copied = match source.get_object(*source_id) {
+                Ok(object) => copy_object(document, source, object, ids),
+                Err(_) => Object::Null,
+            };
+            document.objects.insert(target_id, copied);
+            Object::Reference(target_id)
+        }
+        Object::Array(items) => Object::Array(
+            items
+                .iter()
+                .map(|item| copy_object(document, source, item, ids))
+                .collect(),
+        ),
+        Object::Dictionary(dict) => {
+            Object::Dictionary(copy_dictionary(document, source, dict, ids))
+        }
+        Object::Stream(stream) => {
+            let mut stream = stream.clone();
+            stream.dict = copy_dictionary(document, source, &stream.dict, ids);
+            Object::Stream(stream)
+        }
+        other => other.clone(),
+    }
+}
+
+pub fn copy_dictionary(
+    document: &mut Document,
+    source: &Document,
+    dict: &Dictionary,
+    ids: &mut HashMap<ObjectId, ObjectId>,
+) -> Dictionary {
+    dict.iter()
+        .map(|(key, value)| (key.clone(), copy_object(document, source, value, ids)))
+        .collect()
+}
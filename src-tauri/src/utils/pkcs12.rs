@@ -0,0 +1,362 @@
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, InnerIvInit, KeyInit, KeyIvInit};
+use cms::content_info::ContentInfo;
+use cms::encrypted_data::EncryptedData;
+use const_oid::db::{rfc5911, rfc5912};
+use der::asn1::{AnyRef, OctetString};
+use der::{Decode, Encode};
+use hmac::{Mac, SimpleHmac};
+use pkcs12::cert_type::CertBag;
+use pkcs12::kdf::{derive_key_utf8, Pkcs12KeyType};
+use pkcs12::pbe_params::{EncryptedPrivateKeyInfo, Pkcs12PbeParams};
+use pkcs12::pfx::Pfx;
+use pkcs12::safe_bag::SafeContents;
+use rsa::pkcs8::DecodePrivateKey;
+use x509_cert::spki::{AlgorithmIdentifierOwned, EncodePublicKey};
+use x509_cert::Certificate;
+
+const PBES2: der::asn1::ObjectIdentifier =
+    der::asn1::ObjectIdentifier::new_unwrap("1.2.840.113549.1.5.13");
+
+/// Private key of a signing certificate.
+pub enum SigningKey {
+    Rsa(Box<rsa::RsaPrivateKey>),
+    P256(p256::SecretKey),
+}
+
+/// Private key and certificates read from a PKCS#12 (.p12/.pfx) file.
+pub struct SigningIdentity {
+    pub key: SigningKey,
+
+    /// Certificate of the private key
+    pub certificate: Certificate,
+
+    /// Other certificates in the file, usually the issuing chain
+    pub chain: Vec<Certificate>,
+}
+
+/// Reads the private key and certificates of a PKCS#12 file.
+///
+/// Supports files encrypted with PBES2 (AES) as written by current tools, and the
+/// legacy triple DES and RC2 schemes still used by older exports.
+pub fn load_pkcs12(bytes: &[u8], password: &str) -> Result<SigningIdentity, String> {
+    let pfx = Pfx::from_der(bytes).map_err(|e| format!("Failed to read certificate file: {e}"))?;
+    if pfx.auth_safe.content_type != rfc5911::ID_DATA {
+        return Err("Public-key protected certificate files are not supported".into());
+    }
+
+    let auth_safe = pfx
+        .auth_safe
+        .content
+        .decode_as::<OctetString>()
+        .map_err(|e| format!("Failed to read certificate file: {e}"))?;
+    if let Some(mac_data) = &pfx.mac_data {
+        verify_mac(mac_data, password, auth_safe.as_bytes())?;
+    }
+
+    let contents = Vec::<ContentInfo>::from_der(auth_safe.as_bytes())
+        .map_err(|e| format!("Failed to read certificate file: {e}"))?;
+
+    let mut keys = Vec::new();
+    let mut certificates = Vec::new();
+
+    for content in contents {
+        let safe_contents = match content.content_type {
+            rfc5911::ID_DATA => content
+                .content
+                .decode_as::<OctetString>()
+                .map(|data| data.as_bytes().to_vec())
+                .map_err(|e| format!("Failed to read certificate file: {e}"))?,
+            rfc5911::ID_ENCRYPTED_DATA => {
+                let encrypted = content
+                    .content
+                    .decode_as::<EncryptedData>()
+                    .map_err(|e| format!("Failed to read certificate file: {e}"))?;
+                let info = encrypted.enc_content_info;
+                let data = info
+                    .encrypted_content
+                    .ok_or("Encrypted certificate data is empty")?;
+                decrypt(&info.content_enc_alg, password, data.as_bytes())?
+            }
+            _ => continue,
+        };
+
+        let bags = SafeContents::from_der(&safe_contents)
+            .map_err(|e| format!("Failed to read certificate file: {e}"))?;
+        for bag in bags {
+            // The bag value is kept with its [0] wrapper
+            let value = AnyRef::from_der(&bag.bag_value)
+                .map_err(|e| format!("Failed to read certificate file: {e}"))?
+                .value()
+                .to_vec();
+
+            match bag.bag_id {
+                pkcs12::PKCS_12_PKCS8_KEY_BAG_OID => {
+                    let encrypted = EncryptedPrivateKeyInfo::from_der(&value)
+                        .map_err(|e| format!("Failed to read private key: {e}"))?;
+                    keys.push(decrypt(
+                        &encrypted.encryption_algorithm,
+                        password,
+                        encrypted.encrypted_data.as_bytes(),
+                    )?);
+                }
+                pkcs12::PKCS_12_KEY_BAG_OID => keys.push(value),
+                pkcs12::PKCS_12_CERT_BAG_OID => {
+                    let bag = CertBag::from_der(&value)
+                        .map_err(|e| format!("Failed to read certificate: {e}"))?;
+                    if bag.cert_id == pkcs12::PKCS_12_X509_CERT_OID {
+                        certificates.push(
+                            Certificate::from_der(bag.cert_value.as_bytes())
+                                .map_err(|e| format!("Failed to read certificate: {e}"))?,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let key = keys
+        .first()
+        .ok_or("The certificate file has no private key".to_string())
+        .and_then(|key| private_key(key))?;
+
+    // The signing certificate is the one holding the public half of the key
+    let public_key = match &key {
+        SigningKey::Rsa(key) => key.to_public_key().to_public_key_der(),
+        SigningKey::P256(key) => key.public_key().to_public_key_der(),
+    }
+    .map_err(|e| format!("Failed to read private key: {e}"))?;
+    let index = certificates
+        .iter()
+        .position(|certificate| {
+            certificate
+                .tbs_certificate
+                .subject_public_key_info
+                .to_der()
+                .is_ok_and(|der| der == public_key.as_bytes())
+        })
+        .ok_or("The certificate file has no certificate for its private key")?;
+    let certificate = certificates.remove(index);
+
+    Ok(SigningIdentity {
+        key,
+        certificate,
+        chain: certificates,
+    })
+}
+
+fn private_key(der: &[u8]) -> Result<SigningKey, String> {
+    if let Ok(key) = rsa::RsaPrivateKey::from_pkcs8_der(der) {
+        return Ok(SigningKey::Rsa(Box::new(key)));
+    }
+    if let Ok(key) = p256::SecretKey::from_pkcs8_der(der) {
+        return Ok(SigningKey::P256(key));
+    }
+    Err("Only RSA and P-256 elliptic curve keys are supported".into())
+}
+
+/// Checks the password against the integrity MAC, so a wrong password is reported
+/// as such rather than as corrupt data.
+fn verify_mac(
+    mac_data: &pkcs12::mac_data::MacData,
+    password: &str,
+    data: &[u8],
+) -> Result<(), String> {
+    let salt = mac_data.mac_salt.as_bytes();
+    let iterations = mac_data.iterations;
+    let expected = match mac_data.mac.algorithm.oid {
+        rfc5912::ID_SHA_1 => mac::<sha1::Sha1>(password, salt, iterations, data)?,
+        rfc5912::ID_SHA_256 => mac::<sha2::Sha256>(password, salt, iterations, data)?,
+        rfc5912::ID_SHA_384 => mac::<sha2::Sha384>(password, salt, iterations, data)?,
+        rfc5912::ID_SHA_512 => mac::<sha2::Sha512>(password, salt, iterations, data)?,
+        oid => return Err(format!("Unsupported certificate file MAC algorithm {oid}")),
+    };
+
+    if expected != mac_data.mac.digest.as_bytes() {
+        return Err("Wrong certificate password".into());
+    }
+    Ok(())
+}
+
+fn mac<D>(password: &str, salt: &[u8], iterations: i32, data: &[u8]) -> Result<Vec<u8>, String>
+where
+    D: sha2::Digest + sha2::digest::FixedOutputReset + sha2::digest::core_api::BlockSizeUser,
+{
+    let key = derive_key_utf8::<D>(
+        password,
+        salt,
+        Pkcs12KeyType::Mac,
+        iterations,
+        <D as sha2::Digest>::output_size(),
+    )
+    .map_err(|e| format!("Invalid password: {e}"))?;
+    let mut mac = <SimpleHmac<D> as KeyInit>::new_from_slice(&key)
+        .map_err(|e| format!("Failed to check password: {e}"))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn decrypt(
+    algorithm: &AlgorithmIdentifierOwned,
+    password: &str,
+    data: &[u8],
+) -> Result<Vec<u8>, String> {
+    let failed = |e: &dyn std::fmt::Display| format!("Failed to decrypt certificate file: {e}");
+
+    if algorithm.oid == PBES2 {
+        let der = algorithm.to_der().map_err(|e| failed(&e))?;
+        let scheme = pkcs5::EncryptionScheme::from_der(&der).map_err(|e| failed(&e))?;
+        return scheme
+            .decrypt(password, data)
+            .map_err(|_| "Wrong certificate password".to_string());
+    }
+
+    let params = algorithm
+        .parameters
+        .as_ref()
+        .ok_or("Missing encryption parameters")?
+        .decode_as::<Pkcs12PbeParams>()
+        .map_err(|e| failed(&e))?;
+    let derive = |id, len| {
+        derive_key_utf8::<sha1::Sha1>(password, params.salt.as_bytes(), id, params.iterations, len)
+            .map_err(|e| failed(&e))
+    };
+
+    let decrypted = match algorithm.oid {
+        pkcs12::PKCS_12_PBE_WITH_SHAAND3_KEY_TRIPLE_DES_CBC => {
+            let key = derive(Pkcs12KeyType::EncryptionKey, 24)?;
+            let iv = derive(Pkcs12KeyType::Iv, 8)?;
+            cbc::Decryptor::<des::TdesEde3>::new_from_slices(&key, &iv)
+                .map_err(|e| failed(&e))?
+                .decrypt_padded_vec_mut::<Pkcs7>(data)
+        }
+        pkcs12::PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC
+        | pkcs12::PKCS_12_PBE_WITH_SHAAND128_BIT_RC2_CBC => {
+            let key_length = if algorithm.oid == pkcs12::PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC {
+                5
+            } else {
+                16
+            };
+            let key = derive(Pkcs12KeyType::EncryptionKey, key_length)?;
+            let iv = derive(Pkcs12KeyType::Iv, 8)?;
+            let cipher = rc2::Rc2::new_with_eff_key_len(&key, key_length * 8);
+            cbc::Decryptor::<rc2::Rc2>::inner_iv_slice_init(cipher, &iv)
+                .map_err(|e| failed(&e))?
+                .decrypt_padded_vec_mut::<Pkcs7>(data)
+        }
+        oid => return Err(format!("Unsupported certificate file encryption {oid}")),
+    };
+
+    decrypted.map_err(|_| "Wrong certificate password".to_string())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use der::asn1::Any;
+    use p256::ecdsa::DerSignature;
+    use pkcs12::digest_info::DigestInfo;
+    use pkcs12::mac_data::MacData;
+    use pkcs12::pfx::Version;
+    use pkcs12::safe_bag::SafeBag;
+    use rsa::pkcs8::EncodePrivateKey;
+    use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+    use x509_cert::name::Name;
+    use x509_cert::serial_number::SerialNumber;
+    use x509_cert::spki::SubjectPublicKeyInfoOwned;
+    use x509_cert::time::Validity;
+
+    /// Self-signed P-256 certificate with its key, as found in a PKCS#12 file.
+    pub fn test_identity() -> SigningIdentity {
+        let key = p256::SecretKey::from_bytes(&[7u8; 32].into()).unwrap();
+        let signer = p256::ecdsa::SigningKey::from(&key);
+        let public_key = SubjectPublicKeyInfoOwned::from_key(*signer.verifying_key()).unwrap();
+        let certificate = CertificateBuilder::new(
+            Profile::Root,
+            SerialNumber::from(42u32),
+            Validity::from_now(Duration::from_secs(3_600)).unwrap(),
+            Name::from_str("CN=Test Signer,O=Velin").unwrap(),
+            public_key,
+            &signer,
+        )
+        .unwrap()
+        .build::<DerSignature>()
+        .unwrap();
+
+        SigningIdentity {
+            key: SigningKey::P256(key),
+            certificate,
+            chain: Vec::new(),
+        }
+    }
+
+    /// PKCS#12 file with the key and certificate in plain bags, protected by a
+    /// SHA-256 MAC.
+    fn pfx(identity: &SigningIdentity, password: &str) -> Vec<u8> {
+        let SigningKey::P256(key) = &identity.key else {
+            unreachable!()
+        };
+        let key_bag = SafeBag {
+            bag_id: pkcs12::PKCS_12_KEY_BAG_OID,
+            bag_value: key.to_pkcs8_der().unwrap().as_bytes().to_vec(),
+            bag_attributes: None,
+        };
+        let cert_bag = SafeBag {
+            bag_id: pkcs12::PKCS_12_CERT_BAG_OID,
+            bag_value: CertBag {
+                cert_id: pkcs12::PKCS_12_X509_CERT_OID,
+                cert_value: OctetString::new(identity.certificate.to_der().unwrap()).unwrap(),
+            }
+            .to_der()
+            .unwrap(),
+            bag_attributes: None,
+        };
+
+        let data = |bytes: Vec<u8>| ContentInfo {
+            content_type: rfc5911::ID_DATA,
+            content: Any::encode_from(&OctetString::new(bytes).unwrap()).unwrap(),
+        };
+        let safe_contents = vec![key_bag, cert_bag].to_der().unwrap();
+        let auth_safe = vec![data(safe_contents)].to_der().unwrap();
+
+        let salt = [1u8; 8];
+        let digest = mac::<sha2::Sha256>(password, &salt, 2048, &auth_safe).unwrap();
+        Pfx {
+            version: Version::V3,
+            auth_safe: data(auth_safe),
+            mac_data: Some(MacData {
+                mac: DigestInfo {
+                    algorithm: AlgorithmIdentifierOwned {
+                        oid: rfc5912::ID_SHA_256,
+                        parameters: None,
+                    },
+                    digest: OctetString::new(digest).unwrap(),
+                },
+                mac_salt: OctetString::new(salt.to_vec()).unwrap(),
+                iterations: 2048,
+            }),
+        }
+        .to_der()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_load_pkcs12() {
+        let identity = test_identity();
+        let bytes = pfx(&identity, "correct horse");
+
+        let loaded = load_pkcs12(&bytes, "correct horse").unwrap();
+        assert!(matches!(loaded.key, SigningKey::P256(_)));
+        assert_eq!(loaded.certificate, identity.certificate);
+        assert!(loaded.chain.is_empty());
+
+        assert_eq!(
+            load_pkcs12(&bytes, "wrong").err().as_deref(),
+            Some("Wrong certificate password")
+        );
+    }
+}
@@ -56,19 +56,65 @@ export enum AnnotationType {
   Redacted = "redacted",
 }
 
+export type LineEnding =
+  | "none"
+  | "square"
+  | "circle"
+  | "diamond"
+  | "openarrow"
+  | "closedarrow"
+  | "butt"
+  | "ropenarrow"
+  | "rclosedarrow"
+  | "slash";
+
+export type LineEndings = {
+  start: LineEnding;
+  end: LineEnding;
+};
+
+export type BorderStyle = "solid" | "dashed" | "beveled" | "inset" | "underline";
+
 export type AnnotationAppearance = {
   color: string;
+  interior_color?: string;
   opacity: number;
   border_width?: number;
+  border_style?: BorderStyle;
+  dash_pattern?: number[];
+  line_endings?: LineEndings;
+  font_size?: number;
+  icon?: string;
 };
 
 export type AnnotationMetadata = {
   author?: string;
   contents?: string;
+  rich_contents?: string;
   creation_date?: string;
   modified_date?: string;
 };
 
+export type ReviewState =
+  | "none"
+  | "accepted"
+  | "rejected"
+  | "cancelled"
+  | "completed"
+  | "marked"
+  | "unmarked";
+
+export type ReviewStatus = {
+  state: ReviewState;
+  author?: string;
+  date?: string;
+};
+
+export type AnnotationPopup = {
+  rect: PdfRect;
+  open: boolean;
+};
+
 export type AnnotationFlags = {
   hidden: boolean;
   locked: boolean;
@@ -85,4 +131,58 @@ export type Annotation = {
   appearance: AnnotationAppearance;
   metadata: AnnotationMetadata;
   flags: AnnotationFlags;
+  in_reply_to?: string;
+  replies: Annotation[];
+  review_states: ReviewStatus[];
+  popup?: AnnotationPopup;
+};
+
+export type AnnotationExchangeFormat = "xfdf" | "fdf";
+
+export type AnnotationImportSummary = {
+  added: number;
+  updated: number;
+  skipped: number;
+  review_states: number;
+};
+
+export type HighlightSummaryFormat = "markdown" | "html" | "json";
+
+export type HighlightGrouping = "page" | "color";
+
+export type AnnotationStorage = "document" | "sidecar";
+
+export type AnnotationSortKey = "page" | "date" | "author" | "type" | "color";
+
+export type AnnotationQuery = {
+  authors?: string[];
+  subtypes?: AnnotationType[];
+  colors?: string[];
+  from?: string;
+  to?: string;
+  text?: string;
+  pages?: string;
+  sort_by?: AnnotationSortKey;
+  descending?: boolean;
+  offset?: number;
+  limit?: number;
+};
+
+export type AnnotationQueryResult = {
+  total: number;
+  annotations: Annotation[];
+};
+
+export type StampKind = "builtin" | "image" | "page";
+
+export type StampDefinition = {
+  id: string;
+  name: string;
+  kind: StampKind;
+  dynamic: boolean;
+  color?: string;
+  file?: string;
+  page_index?: number;
+  width: number;
+  height: number;
 };
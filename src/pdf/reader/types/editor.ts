@@ -0,0 +1,38 @@
+import type { Annotation } from "./annotation";
+
+export type DocumentMetadata = {
+  title?: string;
+  author?: string;
+  subject?: string;
+  keywords?: string;
+  creator?: string;
+  producer?: string;
+};
+
+export type EditOperation =
+  | { type: "add_annotation"; data: Annotation }
+  | { type: "update_annotation"; data: Annotation }
+  | {
+      type: "remove_annotation";
+      data: { page_index: number; annotation_id: string };
+    }
+  | { type: "rotate_pages"; data: { pages: number[]; angle: number } }
+  | { type: "delete_pages"; data: { pages: number[] } }
+  | { type: "reorder_pages"; data: { order: number[] } }
+  | { type: "set_metadata"; data: DocumentMetadata };
+
+export type EditHistoryEntry = {
+  revision: number;
+  label: string;
+  applied: boolean;
+};
+
+export type EditHistory = {
+  entries: EditHistoryEntry[];
+  can_undo: boolean;
+  can_redo: boolean;
+  dirty: boolean;
+  dropped: string[];
+};
+
+export type SaveMode = "unchanged" | "incremental" | "full";
@@ -0,0 +1,61 @@
+import type { PdfRect } from "./annotation";
+
+export type FormFieldType =
+  | "text"
+  | "checkbox"
+  | "radio"
+  | "button"
+  | "combo"
+  | "list"
+  | "signature"
+  | "unknown";
+
+export type FormFieldValue =
+  | { type: "text"; data: string }
+  | { type: "checked"; data: boolean }
+  | { type: "selected"; data: string[] };
+
+export type FormFieldFlags = {
+  read_only: boolean;
+  required: boolean;
+  no_export: boolean;
+  multiline: boolean;
+  password: boolean;
+  comb: boolean;
+  editable: boolean;
+  multi_select: boolean;
+};
+
+export type FormFieldOption = {
+  value: string;
+  label: string;
+};
+
+export type FormWidget = {
+  page_index: number;
+  rect: PdfRect;
+  on_state?: string;
+};
+
+export type FormField = {
+  name: string;
+  label?: string;
+  field_type: FormFieldType;
+  value?: FormFieldValue;
+  options: FormFieldOption[];
+  flags: FormFieldFlags;
+  max_length?: number;
+  widgets: FormWidget[];
+};
+
+export type FormFieldUpdate = {
+  name: string;
+  value: FormFieldValue;
+};
+
+export type FormDataFormat = "json" | "fdf" | "xfdf";
+
+export type FormImportSummary = {
+  filled: number;
+  skipped: string[];
+};
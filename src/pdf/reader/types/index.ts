@@ -1,2 +1,8 @@
 export * from "./annotation";
+export * from "./editor";
+export * from "./form";
+export * from "./redaction";
 export * from "./render";
+export * from "./scan";
+export * from "./security";
+export * from "./signature";
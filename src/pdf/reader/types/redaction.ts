@@ -0,0 +1,14 @@
+export type RedactionPattern =
+  | { type: "text"; data: string }
+  | { type: "regex"; data: string }
+  | { type: "email" }
+  | { type: "phone" };
+
+export type RedactionSummary = {
+  pages: number;
+  glyphs: number;
+  images: number;
+  paths: number;
+  annotations: number;
+  metadata: number;
+};
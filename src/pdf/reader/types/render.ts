@@ -11,3 +11,8 @@ export type RenderedTile = {
   height: number;
   pixels: Uint8Array;
 };
+
+export type RenderOptions = {
+  forms?: boolean;
+  highlight_fields?: boolean;
+};
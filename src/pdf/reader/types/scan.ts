@@ -0,0 +1,33 @@
+export type RiskSeverity = "info" | "low" | "medium" | "high";
+
+export type RiskKind =
+  | "javaScript"
+  | "launchAction"
+  | "uriAction"
+  | "submitFormAction"
+  | "importDataAction"
+  | "embeddedFile"
+  | "embeddedExecutable"
+  | "externalReference"
+  | "malformedXref"
+  | "incrementalUpdate"
+  | "hiddenObjects"
+  | "changedAfterSigning"
+  | "encrypted"
+  | "unreadable";
+
+export type RiskFinding = {
+  kind: RiskKind;
+  severity: RiskSeverity;
+  message: string;
+  objectId: number | null;
+  pageIndex: number | null;
+  target: string | null;
+  automatic: boolean;
+};
+
+export type RiskReport = {
+  severity: RiskSeverity | null;
+  findings: RiskFinding[];
+  revisions: number;
+};
@@ -0,0 +1,22 @@
+import { EncryptionAlgorithm } from "@/pdf/tools";
+
+export type DocumentPermissions = {
+  allowPrinting: boolean;
+  allowHighQualityPrinting: boolean;
+  allowModifying: boolean;
+  allowCopying: boolean;
+  allowAnnotating: boolean;
+  allowFormFilling: boolean;
+  allowAssembly: boolean;
+};
+
+export type DocumentSecurity = {
+  encrypted: boolean;
+  algorithm: EncryptionAlgorithm | null;
+  keyLength: number | null;
+  version: number | null;
+  revision: number | null;
+  requiresPassword: boolean;
+  ownerUnlocked: boolean;
+  permissions: DocumentPermissions;
+};
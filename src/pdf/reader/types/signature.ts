@@ -0,0 +1,38 @@
+export type SignatureStatus =
+  | "valid"
+  | "altered"
+  | "invalid"
+  | "unverified"
+  | "malformed"
+  | "unsigned";
+
+export type SignedRange = {
+  offset: number;
+  length: number;
+};
+
+export type SignerCertificate = {
+  subject: string;
+  issuer: string;
+  serial_number: string;
+  not_before: string;
+  not_after: string;
+};
+
+export type SignatureInfo = {
+  field_name: string;
+  page_index: number | null;
+  status: SignatureStatus;
+  message: string | null;
+  signer_name: string | null;
+  certificate: SignerCertificate | null;
+  signing_time: string | null;
+  reason: string | null;
+  location: string | null;
+  contact_info: string | null;
+  sub_filter: string | null;
+  digest_algorithm: string | null;
+  byte_range: SignedRange[];
+  covers_revision: boolean;
+  modified_after_signing: boolean;
+};
@@ -5,6 +5,7 @@ import {
   Group,
   Paper,
   PasswordInput,
+  Select,
   Stack,
   Text,
   Tooltip,
@@ -17,7 +18,7 @@ import { Shield, Save, Pencil } from "lucide-react";
 import { JSX } from "react";
 import { useTranslation } from "react-i18next";
 import { FileSelection } from "../components";
-import { ToolPreferencesProps, TOOLS } from "../types";
+import { EncryptionAlgorithm, ToolPreferencesProps, TOOLS } from "../types";
 import { ToolDetailShell } from "../components";
 import { useProtectStore } from "./protect.store";
 import { pickPdfFile, savePdfFile } from "@/services/file";
@@ -27,16 +28,26 @@ export function Protect({ onBackPressed }: ToolPreferencesProps): JSX.Element {
   const {
     file,
     destinationPath,
+    alreadyProtected,
+    currentPassword,
     password,
     confirmPassword,
     requirePasswordToOpen,
+    userPassword,
+    confirmUserPassword,
+    algorithm,
     permissions,
     isLoading,
     setFile,
     setDestinationPath,
+    setAlreadyProtected,
+    setCurrentPassword,
     setPassword,
     setConfirmPassword,
     setRequirePasswordToOpen,
+    setUserPassword,
+    setConfirmUserPassword,
+    setAlgorithm,
     togglePermission,
     runProtect,
   } = useProtectStore();
@@ -45,6 +56,10 @@ export function Protect({ onBackPressed }: ToolPreferencesProps): JSX.Element {
   const MIN_PASSWORD_LENGTH = 8;
   const passwordValid = password.length >= MIN_PASSWORD_LENGTH;
   const passwordsMatch = password === confirmPassword;
+  const currentPasswordValid = !alreadyProtected || currentPassword.length > 0;
+  const userPasswordValid =
+    !requirePasswordToOpen ||
+    (userPassword.length > 0 && userPassword === confirmUserPassword);
 
   const getDefaultDestination = (sourceFile: string = "image") => {
     const filename = sourceFile.split(/[/\\]/).pop() || "";
@@ -78,7 +93,14 @@ export function Protect({ onBackPressed }: ToolPreferencesProps): JSX.Element {
       actionLabel={t("tools.protect.action", { defaultValue: "Protect PDF" })}
       onAction={runProtect}
       onBackClick={onBackPressed}
-      isValid={hasFile && passwordValid && !isLoading && passwordsMatch}
+      isValid={
+        hasFile &&
+        passwordValid &&
+        !isLoading &&
+        passwordsMatch &&
+        currentPasswordValid &&
+        userPasswordValid
+      }
     >
       <Stack gap="lg" pos="relative">
         <LoadingOverlay visible={isLoading} zIndex={1000} />
@@ -93,11 +115,52 @@ export function Protect({ onBackPressed }: ToolPreferencesProps): JSX.Element {
           <>
             <Paper withBorder p="md" radius="md">
               <Stack gap="md">
-                <Text size="sm" fw={600}>
-                  {t("tools.protect.password_label", {
-                    defaultValue: "Password",
+                <Checkbox
+                  label={t("tools.protect.already_protected", {
+                    defaultValue: "File is already protected",
                   })}
-                </Text>
+                  description={t("tools.protect.already_protected_desc", {
+                    defaultValue:
+                      "Replace its passwords, permissions and encryption in one step.",
+                  })}
+                  checked={alreadyProtected}
+                  onChange={(e) => setAlreadyProtected(e.currentTarget.checked)}
+                  size="sm"
+                />
+                {alreadyProtected && (
+                  <>
+                    <Text size="sm" fw={600}>
+                      {t("tools.protect.current_password_label", {
+                        defaultValue: "Current Owner Password",
+                      })}
+                    </Text>
+                    <PasswordInput
+                      placeholder={t(
+                        "tools.protect.current_password_placeholder",
+                        {
+                          defaultValue:
+                            "Owner password the file is protected with",
+                        },
+                      )}
+                      value={currentPassword}
+                      onChange={(e) => setCurrentPassword(e.currentTarget.value)}
+                    />
+                    <Divider />
+                  </>
+                )}
+                <Box>
+                  <Text size="sm" fw={600}>
+                    {t("tools.protect.password_label", {
+                      defaultValue: "Owner Password",
+                    })}
+                  </Text>
+                  <Text size="xs" c="dimmed">
+                    {t("tools.protect.password_desc", {
+                      defaultValue:
+                        "Needed to change permissions or remove protection.",
+                    })}
+                  </Text>
+                </Box>
                 <PasswordInput
                   placeholder={t("tools.protect.password_placeholder", {
                     defaultValue: "Enter password",
@@ -154,6 +217,41 @@ export function Protect({ onBackPressed }: ToolPreferencesProps): JSX.Element {
                   }
                   size="sm"
                 />
+                {requirePasswordToOpen && (
+                  <>
+                    <Text size="sm" fw={600}>
+                      {t("tools.protect.user_password_label", {
+                        defaultValue: "Open Password",
+                      })}
+                    </Text>
+                    <PasswordInput
+                      placeholder={t("tools.protect.user_password_placeholder", {
+                        defaultValue: "Password needed to open the document",
+                      })}
+                      value={userPassword}
+                      onChange={(e) => setUserPassword(e.currentTarget.value)}
+                    />
+                    <PasswordInput
+                      placeholder={t("tools.protect.confirm_password", {
+                        defaultValue: "Confirm Password",
+                      })}
+                      value={confirmUserPassword}
+                      onChange={(e) =>
+                        setConfirmUserPassword(e.currentTarget.value)
+                      }
+                    />
+                    {userPassword !== confirmUserPassword &&
+                      confirmUserPassword.length > 0 && (
+                        <Alert
+                          color="red"
+                          icon={<Shield size={16} />}
+                          title={t("tools.protect.password_mismatch", {
+                            defaultValue: "Password do not match",
+                          })}
+                        />
+                      )}
+                  </>
+                )}
                 <Divider
                   label={t("tools.protect.permissions", {
                     defaultValue: "Permissions",
@@ -211,18 +309,37 @@ export function Protect({ onBackPressed }: ToolPreferencesProps): JSX.Element {
                     onChange={() => togglePermission("assembly")}
                   />
                 </Stack>
+                <Select
+                  label={t("tools.protect.encryption_level", {
+                    defaultValue: "Encryption Level",
+                  })}
+                  value={algorithm}
+                  onChange={(value) =>
+                    value && setAlgorithm(value as EncryptionAlgorithm)
+                  }
+                  data={[
+                    {
+                      label: t("tools.protect.algorithm.aes256"),
+                      value: "aes256",
+                    },
+                    {
+                      label: t("tools.protect.algorithm.aes128"),
+                      value: "aes128",
+                    },
+                    { label: t("tools.protect.algorithm.rc4"), value: "rc4" },
+                  ]}
+                  radius="md"
+                  allowDeselect={false}
+                />
                 <Alert
                   icon={<Shield size={16} />}
                   title={t("tools.protect.encryption_level", {
                     defaultValue: "Encryption Level",
                   })}
-                  color="green"
+                  color={algorithm === "rc4" ? "yellow" : "green"}
                   variant="light"
                 >
-                  {t("tools.protect.encryption_desc", {
-                    defaultValue:
-                      "Document will be encrypted with AES-256 bit security standard.",
-                  })}
+                  {t(`tools.protect.encryption_desc.${algorithm}`)}
                 </Alert>
               </Stack>
             </Paper>
@@ -5,7 +5,8 @@ import {
 } from "@/services/notifications";
 import i18next from "@/services/i18n/i18n";
 import { create } from "zustand";
-import { protectPdf } from "@/services/tauri";
+import { changeSecurityPdf, protectPdf } from "@/services/tauri";
+import { EncryptionAlgorithm } from "../types";
 
 interface Permissions {
   printing: boolean;
@@ -30,16 +31,26 @@ const DEFAULT_PERMISSIONS: Permissions = {
 interface ProtectState {
   file: string;
   destinationPath: string;
+  alreadyProtected: boolean;
+  currentPassword: string;
   password: string;
   confirmPassword: string;
   requirePasswordToOpen: boolean;
+  userPassword: string;
+  confirmUserPassword: string;
+  algorithm: EncryptionAlgorithm;
   permissions: Permissions;
   isLoading: boolean;
   setFile: (file: string) => void;
   setDestinationPath: (path: string) => void;
+  setAlreadyProtected: (value: boolean) => void;
+  setCurrentPassword: (pw: string) => void;
   setPassword: (pw: string) => void;
   setConfirmPassword: (pw: string) => void;
   setRequirePasswordToOpen: (value: boolean) => void;
+  setUserPassword: (pw: string) => void;
+  setConfirmUserPassword: (pw: string) => void;
+  setAlgorithm: (algorithm: EncryptionAlgorithm) => void;
   togglePermission: (key: keyof Permissions) => void;
   setIsLoading: (loading: boolean) => void;
   runProtect: () => Promise<void>;
@@ -49,17 +60,27 @@ interface ProtectState {
 export const useProtectStore = create<ProtectState>((set, get) => ({
   file: "",
   destinationPath: "",
+  alreadyProtected: false,
+  currentPassword: "",
   password: "",
   confirmPassword: "",
   requirePasswordToOpen: false,
+  userPassword: "",
+  confirmUserPassword: "",
+  algorithm: "aes256",
   permissions: { ...DEFAULT_PERMISSIONS },
   isLoading: false,
 
   setFile: (file) => set({ file }),
   setDestinationPath: (path) => set({ destinationPath: path }),
+  setAlreadyProtected: (value) => set({ alreadyProtected: value }),
+  setCurrentPassword: (pw) => set({ currentPassword: pw }),
   setPassword: (pw) => set({ password: pw }),
   setConfirmPassword: (pw) => set({ confirmPassword: pw }),
   setRequirePasswordToOpen: (value) => set({ requirePasswordToOpen: value }),
+  setUserPassword: (pw) => set({ userPassword: pw }),
+  setConfirmUserPassword: (pw) => set({ confirmUserPassword: pw }),
+  setAlgorithm: (algorithm) => set({ algorithm }),
   togglePermission: (key) =>
     set((state) => ({
       permissions: { ...state.permissions, [key]: !state.permissions[key] },
@@ -69,9 +90,14 @@ export const useProtectStore = create<ProtectState>((set, get) => ({
     set({
       file: "",
       destinationPath: "",
+      alreadyProtected: false,
+      currentPassword: "",
       password: "",
       confirmPassword: "",
       requirePasswordToOpen: false,
+      userPassword: "",
+      confirmUserPassword: "",
+      algorithm: "aes256",
       permissions: { ...DEFAULT_PERMISSIONS },
       isLoading: false,
     }),
@@ -80,8 +106,12 @@ export const useProtectStore = create<ProtectState>((set, get) => ({
     const {
       file,
       destinationPath,
+      alreadyProtected,
+      currentPassword,
       password,
       requirePasswordToOpen,
+      userPassword,
+      algorithm,
       permissions,
       isLoading,
       setIsLoading,
@@ -94,20 +124,35 @@ export const useProtectStore = create<ProtectState>((set, get) => ({
       );
       return;
     }
+    if (alreadyProtected && !currentPassword) {
+      notifyWarning(
+        i18next.t(
+          "tools:tools.protect.notifications.warning.no_current_password",
+        ),
+      );
+      return;
+    }
     if (!password) {
       notifyWarning(
         i18next.t("tools:tools.protect.notifications.warning.no_password"),
       );
       return;
     }
+    if (requirePasswordToOpen && !userPassword) {
+      notifyWarning(
+        i18next.t("tools:tools.protect.notifications.warning.no_user_password"),
+      );
+      return;
+    }
     if (isLoading) return;
 
     setIsLoading(true);
-    const result = await protectPdf({
+    const input = {
       inputPath: file,
       outputPath: destinationPath || "",
-      password,
-      requirePasswordToOpen,
+      ownerPassword: password,
+      userPassword: requirePasswordToOpen ? userPassword : null,
+      algorithm,
       allowPrinting: permissions.printing,
       allowHighQualityPrinting: permissions.highQualityPrinting,
       allowModifying: permissions.modifying,
@@ -115,9 +160,18 @@ export const useProtectStore = create<ProtectState>((set, get) => ({
       allowAnnotating: permissions.annotating,
       allowFormFilling: permissions.formFilling,
       allowAssembly: permissions.assembly,
-    });
+    };
+    const result = alreadyProtected
+      ? await changeSecurityPdf({ ...input, currentPassword })
+      : await protectPdf(input);
     if (result.ok) {
-      notifySuccess(i18next.t("tools:tools.protect.notifications.success"));
+      notifySuccess(
+        i18next.t(
+          alreadyProtected
+            ? "tools:tools.protect.notifications.security_changed"
+            : "tools:tools.protect.notifications.success",
+        ),
+      );
       removeFile();
     } else {
       notifyError(
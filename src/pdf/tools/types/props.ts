@@ -1,3 +1,5 @@
+import { DocumentPermissions } from "@/pdf/reader";
+
 export type ToolPreferencesProps = {
   onBackPressed: () => void;
 };
@@ -7,11 +9,14 @@ export type PageSelectionInput = {
   selection: string | null;
 };
 
+export type EncryptionAlgorithm = "aes256" | "aes128" | "rc4";
+
 export type ProtectInput = {
   inputPath: string;
   outputPath: string;
-  password: string;
-  requirePasswordToOpen: boolean;
+  ownerPassword: string;
+  userPassword: string | null;
+  algorithm: EncryptionAlgorithm;
   allowPrinting: boolean;
   allowHighQualityPrinting: boolean;
   allowModifying: boolean;
@@ -21,8 +26,53 @@ export type ProtectInput = {
   allowAssembly: boolean;
 };
 
+export type ChangeSecurityInput = ProtectInput & {
+  currentPassword: string;
+};
+
 export type UnlockInput = {
   inputPath: string;
   outputPath: string;
-  password: string;
+  password: string | null;
+};
+
+export type RemovedProtection = "restrictions" | "open_password";
+
+export type UnlockReport = {
+  removed: RemovedProtection;
+  algorithm: EncryptionAlgorithm | null;
+  permissions: DocumentPermissions;
+};
+
+export type SanitizeOptions = {
+  metadata: boolean;
+  javascript: boolean;
+  actions: boolean;
+  embeddedFiles: boolean;
+  annotations: boolean;
+  comments: boolean;
+  hiddenLayers: boolean;
+  thumbnails: boolean;
+  privateData: boolean;
+  unreferencedObjects: boolean;
+};
+
+export type SanitizeInput = {
+  inputPath: string;
+  outputPath: string;
+  options?: Partial<SanitizeOptions>;
+};
+
+export type SanitizeReport = {
+  infoKeys: string[];
+  xmpStreams: number;
+  scripts: number;
+  actions: number;
+  embeddedFiles: string[];
+  annotations: number;
+  comments: number;
+  hiddenLayers: string[];
+  thumbnails: number;
+  privateData: number;
+  unreferencedObjects: number;
 };
@@ -69,7 +69,7 @@ export function Unlock({ onBackPressed }: ToolPreferencesProps): JSX.Element {
       actionLabel={t("tools.unlock.action", { defaultValue: "Unlock PDF" })}
       onAction={runUnlock}
       onBackClick={onBackPressed}
-      isValid={hasFile && !!password && !isLoading}
+      isValid={hasFile && !isLoading}
     >
       <Stack gap="lg" pos="relative">
         <LoadingOverlay visible={isLoading} zIndex={1000} />
@@ -101,7 +101,7 @@ export function Unlock({ onBackPressed }: ToolPreferencesProps): JSX.Element {
                       <Text size="xs" span>
                         {t("tools.unlock.password_hint", {
                           defaultValue:
-                            "Enter the password used to restrict or open this PDF.",
+                            "Leave empty if the PDF opens without asking for a password.",
                         })}
                       </Text>
                     </Group>
@@ -145,7 +145,7 @@ export function Unlock({ onBackPressed }: ToolPreferencesProps): JSX.Element {
                       <Text size="sm" c="dimmed">
                         {t("tools.unlock.password_guide", {
                           defaultValue:
-                            "If the PDF asks for a password when you open it, enter that password. If it opens without asking but has restricted features (like printing or copying), enter the password that was used to set those restrictions (the owner password).",
+                            "If the PDF asks for a password when you open it, enter that password. If it opens without asking but has restricted features (like printing or copying), no password is needed and only the restrictions are removed.",
                         })}
                       </Text>
                     </Collapse>
@@ -54,22 +54,20 @@ export const useUnlockStore = create<UnlockState>((set, get) => ({
       );
       return;
     }
-    if (!password) {
-      notifyWarning(
-        i18next.t("tools:tools.unlock.notifications.warning.no_password"),
-      );
-      return;
-    }
     if (isLoading) return;
 
     setIsLoading(true);
     const result = await unlockPdf({
       inputPath: file,
       outputPath: destinationPath || "",
-      password,
+      password: password || null,
     });
     if (result.ok) {
-      notifySuccess(i18next.t("tools:tools.unlock.notifications.success"));
+      notifySuccess(
+        i18next.t(
+          `tools:tools.unlock.notifications.success.${result.data.removed}`,
+        ),
+      );
       removeFile();
     } else {
       notifyError(
@@ -10,6 +10,11 @@ import {
   fetchTextByPage,
   generatePreview,
   fetchAnnotations,
+  fetchSignatures,
+  fetchDocumentSecurity,
+  markRedactions,
+  applyRedactions,
+  scanDocument,
 } from "./reader";
 import { safeInvoke } from "@/services/tauri";
 
@@ -188,5 +193,48 @@ describe("reader tauri service", () => {
         id: "doc-id",
       });
     });
+
+    it("fetchSignatures should call safeInvoke", async () => {
+      vi.mocked(safeInvoke).mockResolvedValue({ ok: true, data: [] });
+      await fetchSignatures("doc-id");
+      expect(safeInvoke).toHaveBeenCalledWith("get_signatures", {
+        id: "doc-id",
+      });
+    });
+
+    it("fetchDocumentSecurity should pass the owner password", async () => {
+      vi.mocked(safeInvoke).mockResolvedValue({ ok: true, data: {} });
+      await fetchDocumentSecurity("doc-id", "secret");
+      expect(safeInvoke).toHaveBeenCalledWith("get_document_security", {
+        id: "doc-id",
+        ownerPassword: "secret",
+      });
+    });
+
+    it("markRedactions should pass the patterns", async () => {
+      vi.mocked(safeInvoke).mockResolvedValue({ ok: true, data: [] });
+      await markRedactions("doc-id", [{ type: "email" }]);
+      expect(safeInvoke).toHaveBeenCalledWith("mark_redactions", {
+        id: "doc-id",
+        patterns: [{ type: "email" }],
+      });
+    });
+
+    it("applyRedactions should redact in place without a destination", async () => {
+      vi.mocked(safeInvoke).mockResolvedValue({ ok: true, data: {} });
+      await applyRedactions("doc-id");
+      expect(safeInvoke).toHaveBeenCalledWith("apply_redactions", {
+        id: "doc-id",
+        dest: null,
+      });
+    });
+
+    it("scanDocument should pass the file path", async () => {
+      vi.mocked(safeInvoke).mockResolvedValue({ ok: true, data: {} });
+      await scanDocument("/path/to/file.pdf");
+      expect(safeInvoke).toHaveBeenCalledWith("scan_document", {
+        path: "/path/to/file.pdf",
+      });
+    });
   });
 });
@@ -1,5 +1,15 @@
 import { Bookmarks, PageText, PdfInfo } from "@/shared/types";
-import { Annotation, RenderedPage, RenderedTile } from "@/pdf/reader";
+import {
+  Annotation,
+  DocumentSecurity,
+  RedactionPattern,
+  RedactionSummary,
+  RenderedPage,
+  RenderedTile,
+  RenderOptions,
+  RiskReport,
+  SignatureInfo,
+} from "@/pdf/reader";
 import { InvokeResult, safeInvoke } from "@/services/tauri";
 
 export const openPdf = async (path: string): Promise<InvokeResult<string>> => {
@@ -16,11 +26,13 @@ export const renderPage = async (
   id: string,
   pageIndex: number,
   targetWidth: number,
+  options?: RenderOptions,
 ): Promise<InvokeResult<RenderedPage>> => {
   const data = await safeInvoke<Uint8Array>("render_page", {
     id,
     pageIndex,
     targetWidth,
+    options,
   });
 
   if (!data.ok) return { ok: false, error: data.error };
@@ -69,6 +81,7 @@ export const renderTile = async (
   tileY: number,
   tileWidth: number,
   tileHeight: number,
+  options?: RenderOptions,
 ): Promise<InvokeResult<RenderedTile>> => {
   const data = await safeInvoke<Uint8Array>("render_tile", {
     id,
@@ -78,6 +91,7 @@ export const renderTile = async (
     tileY,
     tileWidth,
     tileHeight,
+    options,
   });
 
   if (!data.ok) return { ok: false, error: data.error };
@@ -148,3 +162,42 @@ export const fetchAnnotations = async (
 ): Promise<InvokeResult<Annotation[]>> => {
   return safeInvoke<Annotation[]>("get_annotations", { id });
 };
+
+export const fetchSignatures = async (
+  id: string,
+): Promise<InvokeResult<SignatureInfo[]>> => {
+  return safeInvoke<SignatureInfo[]>("get_signatures", { id });
+};
+
+export const fetchDocumentSecurity = async (
+  id: string,
+  ownerPassword?: string,
+): Promise<InvokeResult<DocumentSecurity>> => {
+  return safeInvoke<DocumentSecurity>("get_document_security", {
+    id,
+    ownerPassword: ownerPassword ?? null,
+  });
+};
+
+export const markRedactions = async (
+  id: string,
+  patterns: RedactionPattern[],
+): Promise<InvokeResult<Annotation[]>> => {
+  return safeInvoke<Annotation[]>("mark_redactions", { id, patterns });
+};
+
+export const applyRedactions = async (
+  id: string,
+  dest?: string,
+): Promise<InvokeResult<RedactionSummary>> => {
+  return safeInvoke<RedactionSummary>("apply_redactions", {
+    id,
+    dest: dest ?? null,
+  });
+};
+
+export const scanDocument = async (
+  path: string,
+): Promise<InvokeResult<RiskReport>> => {
+  return safeInvoke<RiskReport>("scan_document", { path });
+};
@@ -5,6 +5,7 @@ import {
   extractPdf,
   pdfToImg,
   compressPdf,
+  sanitizePdf,
 } from "./tools";
 import { safeInvoke } from "./invokeResult";
 import { PageSelectionInput } from "@/pdf/tools";
@@ -103,4 +104,19 @@ describe("tools service", () => {
       });
     });
   });
+
+  describe("sanitizePdf", () => {
+    it("should call safeInvoke with correct arguments", async () => {
+      const input = {
+        inputPath: "/path/to/input.pdf",
+        outputPath: "/path/to/output.pdf",
+        options: { comments: false },
+      };
+      vi.mocked(safeInvoke).mockResolvedValue({ ok: true, data: undefined });
+
+      await sanitizePdf(input);
+
+      expect(safeInvoke).toHaveBeenCalledWith("sanitize_pdf", { input });
+    });
+  });
 });
@@ -1,5 +1,13 @@
 import { InvokeResult, safeInvoke } from "./invokeResult";
-import { PageSelectionInput, ProtectInput, UnlockInput } from "@/pdf/tools";
+import {
+  ChangeSecurityInput,
+  PageSelectionInput,
+  ProtectInput,
+  SanitizeInput,
+  SanitizeReport,
+  UnlockInput,
+  UnlockReport,
+} from "@/pdf/tools";
 
 export const mergePdfs = async (
   inputs: PageSelectionInput[],
@@ -72,10 +80,22 @@ export const protectPdf = async (
   return safeInvoke("protect_pdf", { input });
 };
 
+export const changeSecurityPdf = async (
+  input: ChangeSecurityInput,
+): Promise<InvokeResult<void>> => {
+  return safeInvoke("change_security_pdf", { input });
+};
+
 export const unlockPdf = async (
   input: UnlockInput,
-): Promise<InvokeResult<void>> => {
-  return safeInvoke("unlock_pdf", { input });
+): Promise<InvokeResult<UnlockReport>> => {
+  return safeInvoke<UnlockReport>("unlock_pdf", { input });
+};
+
+export const sanitizePdf = async (
+  input: SanitizeInput,
+): Promise<InvokeResult<SanitizeReport>> => {
+  return safeInvoke<SanitizeReport>("sanitize_pdf", { input });
 };
 
 export interface WatermarkInput {